@@ -0,0 +1,20 @@
+pub mod bottlenecks;
+pub mod clustering;
+pub mod condensation;
+pub mod correlation;
+pub mod coupling;
+pub mod cross_validation;
+pub mod diagnostics;
+pub mod first_passage;
+pub mod fixed_points;
+pub mod occupation;
+pub mod pareto;
+pub mod phase_type;
+pub mod projection;
+pub mod quasi_stationary;
+pub mod random_walk;
+pub mod rule_dependencies;
+pub mod sensitivity;
+pub mod stationary;
+mod transition_matrix;
+pub mod truncation;
@@ -0,0 +1,260 @@
+use std::{fmt::Debug, hash::Hash};
+
+use crate::prelude::*;
+
+use super::transition_matrix::TransitionMatrix;
+
+// A split of the known states into two regions. The flow is the probability moving from the
+// inside to the outside in one step, the conductance relates it to the mass of the smaller region.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cut<S> {
+    inside: Vec<S>,
+    outside: Vec<S>,
+    flow: Probability,
+    conductance: f64,
+}
+
+impl<S> Cut<S> {
+    pub fn inside(&self) -> &Vec<S> {
+        &self.inside
+    }
+
+    pub fn outside(&self) -> &Vec<S> {
+        &self.outside
+    }
+
+    pub fn flow(&self) -> Probability {
+        self.flow
+    }
+
+    pub fn conductance(&self) -> f64 {
+        self.conductance
+    }
+}
+
+fn conductance(flow: Probability, inside_mass: Probability, outside_mass: Probability) -> f64 {
+    let smaller_mass = inside_mass.min(outside_mass);
+    if smaller_mass > 0. {
+        flow / smaller_mass
+    } else {
+        f64::INFINITY
+    }
+}
+
+// Orders the states along the second eigenvector of the symmetrized chain, states on the same side
+// of a bottleneck end up next to each other
+fn spectral_order<S>(matrix: &TransitionMatrix<'_, S>, weights: &[Probability]) -> Vec<usize> {
+    let mut symmetrized: Vec<Vec<(usize, f64)>> = vec![Vec::new(); matrix.len()];
+    (0..matrix.len()).for_each(|source| {
+        matrix
+            .row(source)
+            .into_iter()
+            .flatten()
+            .filter(|(target, _)| *target != source)
+            .for_each(|(target, probability)| {
+                let flow = weights[source] * probability / 2.;
+                symmetrized[source].push((*target, flow));
+                symmetrized[*target].push((source, flow));
+            });
+    });
+    let degrees = symmetrized
+        .iter()
+        .map(|neighbors| neighbors.iter().map(|(_, flow)| flow).sum::<f64>())
+        .collect::<Vec<_>>();
+    let total_degree = degrees.iter().sum::<f64>();
+    if total_degree == 0. {
+        return (0..matrix.len()).collect();
+    }
+
+    let mut vector = (0..matrix.len())
+        .map(|index| index as f64)
+        .collect::<Vec<_>>();
+    for _ in 0..10_000 {
+        // Lazy walk, so the iteration doesn't oscillate on periodic chains
+        let mut next = (0..matrix.len())
+            .map(|index| {
+                if degrees[index] == 0. {
+                    return vector[index];
+                }
+                let neighbors = symmetrized[index]
+                    .iter()
+                    .map(|(neighbor, flow)| flow * vector[*neighbor])
+                    .sum::<f64>();
+                (vector[index] + neighbors / degrees[index]) / 2.
+            })
+            .collect::<Vec<_>>();
+        let mean = next
+            .iter()
+            .zip(&degrees)
+            .map(|(value, degree)| value * degree)
+            .sum::<f64>()
+            / total_degree;
+        next.iter_mut().for_each(|value| *value -= mean);
+        let norm = next.iter().map(|value| value * value).sum::<f64>().sqrt();
+        if norm == 0. {
+            break;
+        }
+        next.iter_mut().for_each(|value| *value /= norm);
+        let change = next
+            .iter()
+            .zip(&vector)
+            .map(|(new, old)| (new - old).abs())
+            .fold(0., f64::max);
+        vector = next;
+        if change < 1e-12 {
+            break;
+        }
+    }
+    let mut order = (0..matrix.len()).collect::<Vec<_>>();
+    order.sort_by(|first, second| vector[*first].total_cmp(&vector[*second]));
+    order
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Probability flows are weighted by the distribution at the given time, states whose
+    // transitions are not cached yet don't contribute any flow
+    pub fn cut(&self, time: Time, inside: impl Fn(&S) -> bool) -> Cut<S> {
+        let matrix = self.transition_matrix();
+        let weights = (0..matrix.len())
+            .map(|index| self.probability_of(matrix.state(index), time))
+            .collect::<Vec<_>>();
+        let is_inside = (0..matrix.len())
+            .map(|index| inside(matrix.state(index)))
+            .collect::<Vec<_>>();
+        let flow = (0..matrix.len())
+            .filter(|source| is_inside[*source])
+            .map(|source| {
+                matrix
+                    .row(source)
+                    .into_iter()
+                    .flatten()
+                    .filter(|(target, _)| !is_inside[*target])
+                    .map(|(_, probability)| weights[source] * probability)
+                    .sum::<Probability>()
+            })
+            .sum::<Probability>();
+        let inside_mass = (0..matrix.len())
+            .filter(|index| is_inside[*index])
+            .map(|index| weights[index])
+            .sum::<Probability>();
+        let outside_mass = weights.iter().sum::<Probability>() - inside_mass;
+        let (inside, outside) = (0..matrix.len())
+            .map(|index| (matrix.state(index).clone(), is_inside[index]))
+            .partition::<Vec<_>, _>(|(_, is_inside)| *is_inside);
+        Cut {
+            inside: inside.into_iter().map(|(state, _)| state).collect(),
+            outside: outside.into_iter().map(|(state, _)| state).collect(),
+            flow,
+            conductance: conductance(flow, inside_mass, outside_mass),
+        }
+    }
+
+    // The cut with the lowest conductance found by sweeping along the spectral order of the states.
+    // This is a heuristic, finding the optimal cut is NP-hard. None if there is no cut with
+    // probability on both sides.
+    pub fn bottleneck(&self, time: Time) -> Option<Cut<S>> {
+        let matrix = self.transition_matrix();
+        let weights = (0..matrix.len())
+            .map(|index| self.probability_of(matrix.state(index), time))
+            .collect::<Vec<_>>();
+        let mut incoming: Vec<Vec<(usize, Probability)>> = vec![Vec::new(); matrix.len()];
+        (0..matrix.len()).for_each(|source| {
+            matrix
+                .row(source)
+                .into_iter()
+                .flatten()
+                .for_each(|(target, probability)| {
+                    incoming[*target].push((source, *probability));
+                });
+        });
+        let order = spectral_order(&matrix, &weights);
+        let total_mass = weights.iter().sum::<Probability>();
+
+        let mut is_inside = vec![false; matrix.len()];
+        let mut flow = 0.;
+        let mut inside_mass = 0.;
+        let mut best: Option<(usize, Probability, f64)> = None;
+        for (position, index) in order
+            .iter()
+            .enumerate()
+            .take(matrix.len().saturating_sub(1))
+        {
+            is_inside[*index] = true;
+            inside_mass += weights[*index];
+            flow += matrix
+                .row(*index)
+                .into_iter()
+                .flatten()
+                .filter(|(target, _)| !is_inside[*target])
+                .map(|(_, probability)| weights[*index] * probability)
+                .sum::<Probability>();
+            flow -= incoming[*index]
+                .iter()
+                .filter(|(source, _)| is_inside[*source] && source != index)
+                .map(|(source, probability)| weights[*source] * probability)
+                .sum::<Probability>();
+            let conductance = conductance(flow.max(0.), inside_mass, total_mass - inside_mass);
+            if conductance.is_finite() && best.is_none_or(|(_, _, lowest)| conductance < lowest) {
+                best = Some((position, flow.max(0.), conductance));
+            }
+        }
+        best.map(|(position, flow, conductance)| {
+            let state = |index: &usize| matrix.state(*index).clone();
+            Cut {
+                inside: order[..=position].iter().map(state).collect(),
+                outside: order[position + 1..].iter().map(state).collect(),
+                flow,
+                conductance,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use hashbrown::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn bottleneck() {
+        // Two triangles which are only connected through the rare edge between 0 and 3
+        let state_transition_generator = Arc::new(|state: u8| {
+            let group = state / 3 * 3;
+            let mut transitions = (group..group + 3)
+                .filter(|other| *other != state)
+                .map(|other| (other, "move", 0.495))
+                .collect::<Vec<_>>();
+            match state {
+                0 => transitions.push((3, "cross", 0.01)),
+                3 => transitions.push((0, "cross", 0.01)),
+                _ => transitions.push((state, "stay", 0.01)),
+            }
+            transitions
+        });
+        let uniform = (0..6)
+            .map(|state| (state, 1. / 6.))
+            .collect::<HashMap<_, _>>();
+        let mut simulation = Simulation::new_with_distribution(uniform, state_transition_generator);
+        simulation.explore();
+
+        let cut = simulation.bottleneck(0).unwrap();
+        let mut inside = cut.inside().clone();
+        inside.sort();
+        assert!(inside == vec![0, 1, 2] || inside == vec![3, 4, 5]);
+        assert!(cut.flow().approx_eq(&(0.01 / 6.), Tolerance::stored()));
+        assert!(cut
+            .conductance()
+            .approx_eq(&(0.01 / 3.), Tolerance::stored()));
+
+        let other = simulation.cut(0, |state| *state % 2 == 0);
+        assert!(other.conductance() > cut.conductance());
+        assert_eq!(other.inside().len(), 3);
+    }
+}
@@ -0,0 +1,271 @@
+use std::{fmt::Debug, hash::Hash};
+
+use hashbrown::HashMap;
+
+use crate::prelude::*;
+
+pub type ClusterIndex = usize;
+
+// A partition of the explored states. Unexplored states are never clustered, their dynamics are
+// not known yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateClustering<S>
+where
+    S: Hash + Eq,
+{
+    clusters: Vec<Vec<S>>,
+    cluster_indices: HashMap<S, ClusterIndex>,
+}
+
+impl<S> StateClustering<S>
+where
+    S: Hash + Clone + Eq,
+{
+    fn new(clusters: Vec<Vec<S>>) -> Self {
+        let cluster_indices = clusters
+            .iter()
+            .enumerate()
+            .flat_map(|(index, cluster)| cluster.iter().map(move |state| (state.clone(), index)))
+            .collect();
+        Self {
+            clusters,
+            cluster_indices,
+        }
+    }
+
+    pub fn clusters(&self) -> &Vec<Vec<S>> {
+        &self.clusters
+    }
+
+    pub fn cluster_of(&self, state: &S) -> Option<ClusterIndex> {
+        self.cluster_indices.get(state).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.clusters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clusters.is_empty()
+    }
+
+    // Clusters with more than one state, i.e. where the model could be simplified
+    pub fn merged_clusters(&self) -> Vec<&Vec<S>> {
+        self.clusters
+            .iter()
+            .filter(|cluster| cluster.len() > 1)
+            .collect()
+    }
+}
+
+fn total_variation(
+    first: &HashMap<usize, Probability>,
+    second: &HashMap<usize, Probability>,
+) -> Probability {
+    let first_only = first
+        .iter()
+        .map(|(block, probability)| (probability - second.get(block).unwrap_or(&0.)).abs())
+        .sum::<Probability>();
+    let second_only = second
+        .iter()
+        .filter(|(block, _)| !first.contains_key(*block))
+        .map(|(_, probability)| probability)
+        .sum::<Probability>();
+    (first_only + second_only) / 2.
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // The distribution over blocks after one step from every explored state
+    fn block_distributions(
+        &self,
+        states: &[&S],
+        block_of: &HashMap<u64, usize>,
+    ) -> Vec<HashMap<usize, Probability>> {
+        states
+            .iter()
+            .map(|state| {
+                let mut distribution = HashMap::new();
+                self.cached_outgoing_transitions(state)
+                    .unwrap()
+                    .iter()
+                    .for_each(|(next_state, _, probability)| {
+                        *distribution
+                            .entry(block_of[&hash(next_state)])
+                            .or_insert(0.) += probability
+                    });
+                distribution
+            })
+            .collect()
+    }
+
+    fn explored_states(&self) -> Vec<&S> {
+        self.states_in_graph_order()
+            .into_iter()
+            .filter(|state| self.cached_outgoing_transitions(state).is_some())
+            .collect()
+    }
+
+    // Every state joins the first cluster whose first state has an outgoing transition
+    // distribution within the given total variation distance, so states in a cluster move to
+    // the same successors with similar probabilities
+    pub fn cluster_states(&self, epsilon: Probability) -> StateClustering<S> {
+        let states = self.explored_states();
+        let block_of = self
+            .states_in_graph_order()
+            .into_iter()
+            .enumerate()
+            .map(|(index, state)| (hash(state), index))
+            .collect();
+        let distributions = self.block_distributions(&states, &block_of);
+        let clusters = greedy_clusters(0..states.len(), &distributions, epsilon);
+        StateClustering::new(
+            clusters
+                .into_iter()
+                .map(|cluster| {
+                    cluster
+                        .into_iter()
+                        .map(|index| states[index].clone())
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    // Partition refinement starting from the states grouped by the observable, which is kept
+    // by the lumping, until the states of every cluster move to every cluster with probabilities
+    // within the given distance. With a distance of zero this is the coarsest ordinary lumping
+    // of the explored states which keeps the observable, otherwise an approximate one.
+    pub fn suggest_lumping<P>(
+        &self,
+        epsilon: Probability,
+        observable: impl Fn(&S) -> P,
+    ) -> StateClustering<S>
+    where
+        P: Hash + Eq,
+    {
+        let states = self.explored_states();
+        // Unexplored states are blocks of their own
+        let mut block_of: HashMap<u64, usize> = self
+            .states_in_graph_order()
+            .into_iter()
+            .filter(|state| self.cached_outgoing_transitions(state).is_none())
+            .enumerate()
+            .map(|(index, state)| (hash(state), index))
+            .collect();
+        let mut clusters: Vec<Vec<usize>> = Vec::new();
+        let mut values: HashMap<P, usize> = HashMap::new();
+        states.iter().enumerate().for_each(|(index, state)| {
+            let cluster = *values.entry(observable(state)).or_insert_with(|| {
+                clusters.push(Vec::new());
+                clusters.len() - 1
+            });
+            clusters[cluster].push(index);
+        });
+        let mut offset = block_of.len();
+        clusters.iter().enumerate().for_each(|(index, cluster)| {
+            cluster.iter().for_each(|state| {
+                block_of.insert(hash(states[*state]), offset + index);
+            })
+        });
+        loop {
+            let distributions = self.block_distributions(&states, &block_of);
+            let refined = clusters
+                .iter()
+                .flat_map(|cluster| {
+                    greedy_clusters(cluster.iter().copied(), &distributions, epsilon)
+                })
+                .collect::<Vec<_>>();
+            if refined.len() == clusters.len() {
+                break;
+            }
+            offset += clusters.len();
+            refined.iter().enumerate().for_each(|(index, cluster)| {
+                cluster.iter().for_each(|state| {
+                    block_of.insert(hash(states[*state]), offset + index);
+                })
+            });
+            clusters = refined;
+        }
+        StateClustering::new(
+            clusters
+                .into_iter()
+                .map(|cluster| {
+                    cluster
+                        .into_iter()
+                        .map(|index| states[index].clone())
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+}
+
+fn greedy_clusters(
+    members: impl Iterator<Item = usize>,
+    distributions: &[HashMap<usize, Probability>],
+    epsilon: Probability,
+) -> Vec<Vec<usize>> {
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    members.for_each(|member| {
+        match clusters.iter_mut().find(|cluster| {
+            total_variation(&distributions[cluster[0]], &distributions[member]) <= epsilon
+        }) {
+            Some(cluster) => cluster.push(member),
+            None => clusters.push(vec![member]),
+        }
+    });
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn clustering() {
+        // Two copies of the same sticky coin, the copy is irrelevant for the dynamics
+        let state_transition_generator = Arc::new(|(copy, heads): (bool, bool)| {
+            vec![
+                ((copy, heads), "stay", 0.8),
+                ((!copy, !heads), "flip", 0.15),
+                ((copy, !heads), "flip", 0.05),
+            ]
+        });
+        let mut simulation = Simulation::new((false, false), state_transition_generator);
+        simulation.explore();
+
+        let clustering = simulation.cluster_states(0.);
+        assert_eq!(clustering.len(), 4);
+        assert!(clustering.merged_clusters().is_empty());
+
+        let lumping = simulation.suggest_lumping(0., |(_, heads)| *heads);
+        assert_eq!(lumping.len(), 2);
+        assert_eq!(
+            lumping.cluster_of(&(false, true)),
+            lumping.cluster_of(&(true, true))
+        );
+        assert_ne!(
+            lumping.cluster_of(&(false, true)),
+            lumping.cluster_of(&(false, false))
+        );
+
+        // Staying is likely, so states are only similar for a large distance, and a state is more
+        // similar to the state it flips to with both coins
+        assert_eq!(simulation.cluster_states(0.95).len(), 1);
+        let clustering = simulation.cluster_states(0.75);
+        assert_eq!(clustering.len(), 2);
+        assert_eq!(
+            clustering.cluster_of(&(false, false)),
+            clustering.cluster_of(&(true, true))
+        );
+        assert_eq!(simulation.cluster_states(0.5).len(), 4);
+        // Remembering the copy prevents the lumping
+        assert_eq!(simulation.suggest_lumping(0., |state| *state).len(), 4);
+    }
+}
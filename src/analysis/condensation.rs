@@ -0,0 +1,280 @@
+use std::{fmt::Debug, hash::Hash, sync::Arc};
+
+use hashbrown::HashMap;
+use petgraph::Graph;
+
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommunicatingClass<S> {
+    states: Vec<S>,
+    closed: bool,
+    explored: bool,
+}
+
+impl<S> CommunicatingClass<S> {
+    pub fn states(&self) -> &Vec<S> {
+        &self.states
+    }
+
+    // No probability ever leaves a closed class
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    // Unexplored states are put into classes of their own, their dynamics are not known yet
+    pub fn is_explored(&self) -> bool {
+        self.explored
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+}
+
+pub type ClassIndex = usize;
+
+// The chain over communicating classes. Transitions between classes are averaged over the states
+// of the source class, weighted by a distribution or uniformly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condensation<S>
+where
+    S: Hash + Eq,
+{
+    classes: Vec<CommunicatingClass<S>>,
+    class_indices: HashMap<S, ClassIndex>,
+    transitions: Vec<Vec<(ClassIndex, Probability)>>,
+}
+
+impl<S> Condensation<S>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + 'static,
+{
+    // Classes are ordered such that probability only flows to classes with a higher index
+    pub fn classes(&self) -> &Vec<CommunicatingClass<S>> {
+        &self.classes
+    }
+
+    pub fn class_of(&self, state: &S) -> Option<ClassIndex> {
+        self.class_indices.get(state).copied()
+    }
+
+    pub fn transitions(&self, class: ClassIndex) -> &Vec<(ClassIndex, Probability)> {
+        &self.transitions[class]
+    }
+
+    pub fn closed_classes(&self) -> Vec<ClassIndex> {
+        (0..self.classes.len())
+            .filter(|class| self.classes[*class].closed)
+            .collect()
+    }
+
+    pub fn graph(&self) -> Graph<ClassIndex, Probability> {
+        let mut graph = Graph::new();
+        let nodes = (0..self.classes.len())
+            .map(|class| graph.add_node(class))
+            .collect::<Vec<_>>();
+        self.transitions
+            .iter()
+            .enumerate()
+            .for_each(|(source, transitions)| {
+                transitions.iter().for_each(|(target, probability)| {
+                    graph.add_edge(nodes[source], nodes[*target], *probability);
+                });
+            });
+        graph
+    }
+
+    pub fn class_distribution(
+        &self,
+        distribution: &StateProbabilityDistribution<S>,
+    ) -> StateProbabilityDistribution<ClassIndex> {
+        let mut class_distribution = HashMap::new();
+        distribution.iter().for_each(|(state, probability)| {
+            let class = self
+                .class_of(state)
+                .expect("State of the distribution is not known to the condensation");
+            *class_distribution.entry(class).or_insert(0.) += probability;
+        });
+        class_distribution
+    }
+
+    // The meta-chain as a simulation of its own, transitions are labeled with the target class
+    pub fn to_simulation(
+        &self,
+        initial_distribution: StateProbabilityDistribution<ClassIndex>,
+    ) -> Simulation<ClassIndex, ClassIndex> {
+        let transitions = Arc::new(self.transitions.clone());
+        Simulation::new_with_distribution(
+            initial_distribution,
+            Arc::new(move |class: ClassIndex| {
+                transitions[class]
+                    .iter()
+                    .map(|(target, probability)| (*target, *target, *probability))
+                    .collect()
+            }),
+        )
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + 'static,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    pub fn communicating_classes(&self) -> Vec<CommunicatingClass<S>> {
+        self.condensation().classes
+    }
+
+    pub fn condensation(&self) -> Condensation<S> {
+        self.condensation_with_weights(|_| 1.)
+    }
+
+    // States without probability at the given time are ignored, unless the whole class has none
+    pub fn condensation_weighted_by(&self, time: Time) -> Condensation<S> {
+        self.condensation_with_weights(|state| self.probability_of(state, time))
+    }
+
+    fn condensation_with_weights(&self, weight: impl Fn(&S) -> Probability) -> Condensation<S> {
+        let matrix = self.transition_matrix();
+        let components = matrix.components();
+        let mut component_of = vec![0; matrix.len()];
+        components
+            .iter()
+            .enumerate()
+            .for_each(|(class, component)| {
+                component
+                    .iter()
+                    .for_each(|index| component_of[*index] = class);
+            });
+
+        let classes = components
+            .iter()
+            .enumerate()
+            .map(|(class, component)| {
+                let explored = component.iter().all(|index| matrix.is_explored(*index));
+                let leaves = component.iter().any(|index| {
+                    matrix
+                        .row(*index)
+                        .into_iter()
+                        .flatten()
+                        .any(|(target, probability)| {
+                            *probability > 0. && component_of[*target] != class
+                        })
+                });
+                CommunicatingClass {
+                    states: component
+                        .iter()
+                        .map(|index| matrix.state(*index).clone())
+                        .collect(),
+                    closed: explored && !leaves,
+                    explored,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let transitions = components
+            .iter()
+            .map(|component| {
+                if !component.iter().all(|index| matrix.is_explored(*index)) {
+                    return vec![(component_of[component[0]], 1.)];
+                }
+                let weights = component
+                    .iter()
+                    .map(|index| weight(matrix.state(*index)))
+                    .collect::<Vec<_>>();
+                let weight_sum = weights.iter().sum::<Probability>();
+                let weights = if weight_sum > 0. {
+                    weights.iter().map(|weight| weight / weight_sum).collect()
+                } else {
+                    vec![1. / component.len() as Probability; component.len()]
+                };
+                let mut transitions: Vec<(ClassIndex, Probability)> = Vec::new();
+                component.iter().zip(weights).for_each(|(index, weight)| {
+                    matrix
+                        .row(*index)
+                        .unwrap()
+                        .iter()
+                        .for_each(|(target, probability)| {
+                            let target = component_of[*target];
+                            match transitions.iter_mut().find(|(class, _)| *class == target) {
+                                Some((_, merged)) => *merged += weight * probability,
+                                None => transitions.push((target, weight * probability)),
+                            }
+                        });
+                });
+                transitions.retain(|(_, probability)| *probability > 0.);
+                transitions
+            })
+            .collect();
+
+        let class_indices = (0..matrix.len())
+            .map(|index| (matrix.state(index).clone(), component_of[index]))
+            .collect();
+
+        Condensation {
+            classes,
+            class_indices,
+            transitions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn condensation() {
+        // 0 <-> 1 leave to either 2 <-> 3 or the absorbing state 4
+        let state_transition_generator = Arc::new(|state: i32| match state {
+            0 => vec![(1, "swap", 0.8), (2, "left", 0.2)],
+            1 => vec![(0, "swap", 0.6), (4, "right", 0.4)],
+            2 => vec![(3, "swap", 1.)],
+            3 => vec![(2, "swap", 1.)],
+            _ => vec![(state, "stay", 1.)],
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.explore();
+
+        let condensation = simulation.condensation();
+        assert_eq!(condensation.classes().len(), 3);
+        let transient = condensation.class_of(&0).unwrap();
+        assert_eq!(condensation.class_of(&1), Some(transient));
+        assert_eq!(transient, 0);
+        assert!(!condensation.classes()[transient].is_closed());
+        assert_eq!(condensation.closed_classes().len(), 2);
+
+        let cycle = condensation.class_of(&2).unwrap();
+        let absorbing = condensation.class_of(&4).unwrap();
+        let probability = |target| {
+            condensation
+                .transitions(transient)
+                .iter()
+                .find(|(class, _)| *class == target)
+                .unwrap()
+                .1
+        };
+        assert!((probability(transient) - 0.7).abs() < 1e-12);
+        assert!((probability(cycle) - 0.1).abs() < 1e-12);
+        assert!((probability(absorbing) - 0.2).abs() < 1e-12);
+        assert_eq!(condensation.graph().edge_count(), 5);
+
+        let initial = condensation.class_distribution(&simulation.initial_distribution());
+        let mut meta_chain = condensation.to_simulation(initial);
+        meta_chain.next_step();
+        assert!(meta_chain
+            .probability_of(&absorbing, 1)
+            .approx_eq(&0.2, Tolerance::stored()));
+
+        let weighted = simulation.condensation_weighted_by(0);
+        assert_eq!(
+            weighted.transitions(transient),
+            &vec![(transient, 0.8), (cycle, 0.2)]
+        );
+    }
+}
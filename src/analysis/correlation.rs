@@ -0,0 +1,177 @@
+use std::{collections::BTreeSet, fmt::Debug, hash::Hash};
+
+use crate::models::entities::*;
+use crate::prelude::*;
+
+pub type ParameterKey = (EntityName, ParameterName);
+
+// Covariances between numeric parameters at one time. Each pair is computed over the states in
+// which both parameters exist, conditioned on that.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorrelationMatrix {
+    parameters: Vec<ParameterKey>,
+    covariances: Vec<Vec<Option<f64>>>,
+}
+
+impl CorrelationMatrix {
+    // Sorted by entity and parameter name
+    pub fn parameters(&self) -> &Vec<ParameterKey> {
+        &self.parameters
+    }
+
+    pub fn index_of(&self, entity: &str, parameter: &str) -> Option<usize> {
+        self.parameters
+            .iter()
+            .position(|(other_entity, other_parameter)| {
+                other_entity == entity && other_parameter == parameter
+            })
+    }
+
+    pub fn covariance(&self, first: usize, second: usize) -> Option<f64> {
+        self.covariances[first][second]
+    }
+
+    // None if the parameters never exist together or one of them is constant
+    pub fn correlation(&self, first: usize, second: usize) -> Option<f64> {
+        let variances = self.covariance(first, first)? * self.covariance(second, second)?;
+        if variances <= 0. {
+            return None;
+        }
+        Some((self.covariance(first, second)? / variances.sqrt()).clamp(-1., 1.))
+    }
+}
+
+fn covariance_of(
+    rows: &[(Vec<Option<f64>>, Probability)],
+    first: usize,
+    second: usize,
+) -> Option<f64> {
+    let (mut mass, mut first_sum, mut second_sum, mut product_sum) = (0., 0., 0., 0.);
+    rows.iter().for_each(|(values, probability)| {
+        if let (Some(first), Some(second)) = (values[first], values[second]) {
+            mass += probability;
+            first_sum += probability * first;
+            second_sum += probability * second;
+            product_sum += probability * first * second;
+        }
+    });
+    if mass <= 0. {
+        return None;
+    }
+    Some(product_sum / mass - first_sum * second_sum / (mass * mass))
+}
+
+impl<V, T> Simulation<State<V>, T>
+where
+    V: Numeric + Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    pub fn covariance(&self, time: Time, first: (&str, &str), second: (&str, &str)) -> Option<f64> {
+        let rows = self.parameter_rows(time, &[first, second]);
+        covariance_of(&rows, 0, 1)
+    }
+
+    pub fn correlation_matrix(&self, time: Time) -> CorrelationMatrix {
+        let parameters = self
+            .iter_probability_distribution(time)
+            .expect("No probability distribution found for given time")
+            .flat_map(|(state, _)| {
+                state.entities().flat_map(|(entity_name, entity)| {
+                    entity
+                        .parameters()
+                        .map(|(parameter, _)| (entity_name.clone(), parameter.clone()))
+                })
+            })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let rows = self.parameter_rows(
+            time,
+            &parameters
+                .iter()
+                .map(|(entity, parameter)| (entity.as_str(), parameter.as_str()))
+                .collect::<Vec<_>>(),
+        );
+        let covariances = (0..parameters.len())
+            .map(|first| {
+                (0..parameters.len())
+                    .map(|second| covariance_of(&rows, first, second))
+                    .collect()
+            })
+            .collect();
+        CorrelationMatrix {
+            parameters,
+            covariances,
+        }
+    }
+
+    fn parameter_rows(
+        &self,
+        time: Time,
+        parameters: &[(&str, &str)],
+    ) -> Vec<(Vec<Option<f64>>, Probability)> {
+        self.iter_probability_distribution(time)
+            .expect("No probability distribution found for given time")
+            .map(|(state, probability)| {
+                let values = parameters
+                    .iter()
+                    .map(|(entity, parameter)| {
+                        state.parameter(entity, parameter).map(Numeric::to_f64)
+                    })
+                    .collect();
+                (values, probability)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn correlation_matrix() {
+        // A coin which is copied to a mirror or turned over, and a counter which is incremented
+        let state_transition_generator = Arc::new(|state: State<i32>| {
+            let coin = *state.parameter("coin", "heads").unwrap();
+            let mut copy = state.clone();
+            copy.set_parameter("mirror", "heads", coin);
+            let mut flip = state.clone();
+            flip.set_parameter("coin", "heads", 1 - coin);
+            flip.set_parameter("mirror", "heads", 1 - coin);
+            let mut count = state.clone();
+            count.set_parameter("counter", "value", 1);
+            vec![
+                (copy, "copy", 0.25),
+                (flip, "flip", 0.25),
+                (count, "count", 0.5),
+            ]
+        });
+        let initial_state = State::new()
+            .with_entity("coin", StateEntity::new().with_parameter("heads", 0))
+            .with_entity("mirror", StateEntity::new().with_parameter("heads", 0))
+            .with_entity("counter", StateEntity::new().with_parameter("value", 0));
+        let mut simulation = Simulation::new(initial_state, state_transition_generator);
+        simulation.next_step();
+
+        assert_eq!(
+            simulation.covariance(1, ("coin", "heads"), ("mirror", "heads")),
+            Some(0.1875)
+        );
+        assert_eq!(
+            simulation.covariance(1, ("coin", "heads"), ("missing", "heads")),
+            None
+        );
+
+        let matrix = simulation.correlation_matrix(1);
+        assert_eq!(matrix.parameters().len(), 3);
+        let coin = matrix.index_of("coin", "heads").unwrap();
+        let mirror = matrix.index_of("mirror", "heads").unwrap();
+        let counter = matrix.index_of("counter", "value").unwrap();
+        assert!((matrix.correlation(coin, mirror).unwrap() - 1.).abs() < 1e-12);
+        assert!((matrix.correlation(coin, counter).unwrap() + 1. / 3f64.sqrt()).abs() < 1e-12);
+        assert_eq!(matrix.covariance(counter, counter), Some(0.25));
+    }
+}
@@ -0,0 +1,131 @@
+use std::{cmp::Ordering, fmt::Debug, hash::Hash};
+
+use crate::prelude::*;
+use crate::random::*;
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Chooses the successor by inverting the cumulative distribution of the successors sorted by
+    // the order, so chains driven by the same random number stay ordered in a monotone model
+    fn monotone_update(
+        &mut self,
+        state: &S,
+        random: Probability,
+        order: &impl Fn(&S, &S) -> Ordering,
+    ) -> S {
+        if self.cached_outgoing_transitions(state).is_none() {
+            self.explore_frontier(vec![state.clone()]);
+        }
+        let mut transitions = self
+            .cached_outgoing_transitions(state)
+            .unwrap()
+            .iter()
+            .map(|(next_state, _, probability)| (next_state, *probability))
+            .collect::<Vec<_>>();
+        assert!(
+            !transitions.is_empty(),
+            "State {state:?} has no successors, so there is no stationary distribution"
+        );
+        transitions.sort_by(|(first, _), (second, _)| {
+            order(first, second).then_with(|| hash(*first).cmp(&hash(*second)))
+        });
+        let mut remaining = random;
+        for (next_state, probability) in &transitions {
+            if remaining < *probability {
+                return (*next_state).clone();
+            }
+            remaining -= probability;
+        }
+        // Rounding errors may leave a tiny remainder
+        transitions.last().unwrap().0.clone()
+    }
+
+    // Coupling from the past for monotone models, an exact sample of the stationary distribution.
+    // The order is a linear extension of a partial order in which the model is monotone, i.e.
+    // ordered states have stochastically ordered successors, and bottom and top are its least and
+    // greatest states. None if the chains from bottom and top did not coalesce within the maximal
+    // horizon.
+    pub fn sample_stationary_exact(
+        &mut self,
+        bottom: S,
+        top: S,
+        order: impl Fn(&S, &S) -> Ordering,
+        max_horizon: Time,
+        seed: u64,
+    ) -> Option<S> {
+        assert!(
+            !self.is_sub_stochastic(),
+            "Exact stationary sampling is not possible when probability is killed"
+        );
+        let mut rng = SeededRng::new(seed);
+        // The random number used for the step ending at time -index
+        let mut randoms: Vec<Probability> = Vec::new();
+        let mut horizon = 1;
+        loop {
+            while randoms.len() < horizon as usize {
+                randoms.push(rng.next_probability());
+            }
+            let mut lower = bottom.clone();
+            let mut upper = top.clone();
+            for random in randoms.iter().rev() {
+                lower = self.monotone_update(&lower, *random, &order);
+                upper = self.monotone_update(&upper, *random, &order);
+            }
+            if lower == upper {
+                return Some(lower);
+            }
+            if horizon >= max_horizon {
+                return None;
+            }
+            horizon = (2 * horizon).min(max_horizon);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use hashbrown::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn coupling_from_the_past() {
+        // A lazy birth death chain on 0 to 4, its stationary distribution is uniform
+        let state_transition_generator = Arc::new(|level: u8| {
+            vec![
+                (level.saturating_add(1).min(4), "up", 0.3),
+                (level.saturating_sub(1), "down", 0.3),
+                (level, "stay", 0.4),
+            ]
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        let samples = (0..2000)
+            .map(|seed| {
+                simulation
+                    .sample_stationary_exact(0, 4, u8::cmp, 1 << 12, seed)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let mut counts: HashMap<u8, usize> = HashMap::new();
+        samples
+            .iter()
+            .for_each(|level| *counts.entry(*level).or_insert(0) += 1);
+        assert_eq!(counts.len(), 5);
+        assert!(counts
+            .values()
+            .all(|count| (*count as f64 / 2000. - 0.2).abs() < 0.03));
+        assert_eq!(
+            simulation.sample_stationary_exact(0, 4, u8::cmp, 1 << 12, 7),
+            Some(samples[7])
+        );
+        assert_eq!(
+            simulation.sample_stationary_exact(0, 4, u8::cmp, 1, 1),
+            None
+        );
+    }
+}
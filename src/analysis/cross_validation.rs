@@ -0,0 +1,191 @@
+use std::{collections::BTreeMap, fmt::Debug, hash::Hash};
+
+use crate::prelude::*;
+
+pub type ValuedObservable<S> = dyn Fn(&S) -> f64 + Sync;
+
+// The expectation of an observable at every time up to the horizon, from the exact propagation
+// and from the mean over the sampled trajectories
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObservableDiscrepancy {
+    exact: Vec<f64>,
+    sampled: Vec<f64>,
+    standard_errors: Vec<f64>,
+}
+
+impl ObservableDiscrepancy {
+    pub fn exact(&self) -> &Vec<f64> {
+        &self.exact
+    }
+
+    pub fn sampled(&self) -> &Vec<f64> {
+        &self.sampled
+    }
+
+    // Of the sampled means
+    pub fn standard_errors(&self) -> &Vec<f64> {
+        &self.standard_errors
+    }
+
+    pub fn errors(&self) -> Vec<f64> {
+        self.exact
+            .iter()
+            .zip(&self.sampled)
+            .map(|(exact, sampled)| (exact - sampled).abs())
+            .collect()
+    }
+
+    pub fn max_error(&self) -> f64 {
+        self.errors().into_iter().fold(0., f64::max)
+    }
+
+    // The largest error in units of the standard error. Errors far above a few standard errors
+    // point to a bug rather than too few samples.
+    pub fn max_z_score(&self) -> f64 {
+        self.errors()
+            .into_iter()
+            .zip(&self.standard_errors)
+            .map(|(error, standard_error)| {
+                if error == 0. {
+                    0.
+                } else if *standard_error == 0. {
+                    f64::INFINITY
+                } else {
+                    error / standard_error
+                }
+            })
+            .fold(0., f64::max)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossValidation {
+    samples: usize,
+    discrepancies: BTreeMap<String, ObservableDiscrepancy>,
+}
+
+impl CrossValidation {
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    // Sorted by the name of the observable
+    pub fn discrepancies(&self) -> &BTreeMap<String, ObservableDiscrepancy> {
+        &self.discrepancies
+    }
+
+    pub fn discrepancy(&self, name: &str) -> Option<&ObservableDiscrepancy> {
+        self.discrepancies.get(name)
+    }
+
+    pub fn max_error(&self) -> f64 {
+        self.discrepancies
+            .values()
+            .map(ObservableDiscrepancy::max_error)
+            .fold(0., f64::max)
+    }
+
+    // Whether the sampler is accurate enough to replace the exact propagation
+    pub fn is_accurate(&self, tolerance: f64) -> bool {
+        self.max_error() <= tolerance
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Propagates exactly up to the horizon if necessary and samples an ensemble of the same
+    // length. Trajectories whose mass was killed contribute zero, like the killed mass does to
+    // the exact expectation.
+    pub fn cross_validate(
+        &mut self,
+        steps: usize,
+        samples: usize,
+        seed: u64,
+        observables: &[(&str, &ValuedObservable<S>)],
+    ) -> CrossValidation {
+        assert!(samples > 0, "Cross validation needs at least one sample");
+        while self.time() < steps as Time {
+            self.advance();
+        }
+        let ensemble = self.sample_ensemble(samples, steps, seed);
+        let discrepancies = observables
+            .iter()
+            .map(|(name, observable)| {
+                let exact = (0..=steps)
+                    .map(|time| self.expectation(time as Time, observable))
+                    .collect();
+                let (sampled, standard_errors) = (0..=steps)
+                    .map(|time| {
+                        let values = ensemble
+                            .trajectories()
+                            .iter()
+                            .map(|trajectory| trajectory.states().get(time).map_or(0., observable))
+                            .collect::<Vec<_>>();
+                        let mean = values.iter().sum::<f64>() / samples as f64;
+                        let variance = values
+                            .iter()
+                            .map(|value| (value - mean).powi(2))
+                            .sum::<f64>()
+                            / samples as f64;
+                        (mean, (variance / samples as f64).sqrt())
+                    })
+                    .unzip();
+                (
+                    name.to_string(),
+                    ObservableDiscrepancy {
+                        exact,
+                        sampled,
+                        standard_errors,
+                    },
+                )
+            })
+            .collect();
+        CrossValidation {
+            samples,
+            discrepancies,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn cross_validation() {
+        let state_transition_generator = Arc::new(|state: i32| {
+            vec![
+                (state, "stay", 0.5),
+                (state + 1, "next", 0.25),
+                (state - 1, "previous", 0.25),
+            ]
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        let position = |state: &i32| *state as f64;
+        let positive = |state: &i32| f64::from(*state > 0);
+        let validation = simulation.cross_validate(
+            10,
+            2000,
+            7,
+            &[("position", &position), ("positive", &positive)],
+        );
+        assert_eq!(simulation.time(), 10);
+        assert_eq!(validation.samples(), 2000);
+        assert_eq!(validation.discrepancies().len(), 2);
+        let discrepancy = validation.discrepancy("positive").unwrap();
+        assert_eq!(discrepancy.exact().len(), 11);
+        assert_eq!(discrepancy.exact()[0], 0.);
+        assert_eq!(discrepancy.errors()[0], 0.);
+        assert!(validation.is_accurate(0.1));
+        assert!(validation
+            .discrepancies()
+            .values()
+            .all(|discrepancy| discrepancy.max_z_score() < 5.));
+        assert!(!validation.is_accurate(0.));
+    }
+}
@@ -0,0 +1,157 @@
+use std::{fmt::Debug, hash::Hash};
+
+use crate::models::rules::*;
+use crate::prelude::*;
+
+// Common modelling bugs among the explored states. Unexplored states can not be diagnosed, so
+// the diagnosis is only complete if there are none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnosis<S> {
+    dead_ends: Vec<S>,
+    sinks: Vec<S>,
+    unfired_rules: Vec<RuleName>,
+    unexplored: usize,
+}
+
+impl<S> Diagnosis<S> {
+    // States without transitions, or where no rule applies if the simulation has rules
+    pub fn dead_ends(&self) -> &Vec<S> {
+        &self.dead_ends
+    }
+
+    // States whose only transitions are self-loops
+    pub fn sinks(&self) -> &Vec<S> {
+        &self.sinks
+    }
+
+    // Rules which apply to none of the explored states, sorted by name
+    pub fn unfired_rules(&self) -> &Vec<RuleName> {
+        &self.unfired_rules
+    }
+
+    pub fn unexplored(&self) -> usize {
+        self.unexplored
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.unexplored == 0
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.dead_ends.is_empty() && self.sinks.is_empty() && self.unfired_rules.is_empty()
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Sinks are often intended, e.g. for absorbing states, but are listed as they are also the
+    // result of rules which never apply
+    pub fn diagnose(&self) -> Diagnosis<S> {
+        let states = self.states_in_graph_order();
+        let explored = states
+            .iter()
+            .filter_map(|state| Some((*state, self.cached_outgoing_transitions(state)?)))
+            .collect::<Vec<_>>();
+        let applying_rules = |state: &S| {
+            self.rules().map(|rules| {
+                rules
+                    .iter()
+                    .filter(|(_, rule)| rule.applies(state.clone()))
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>()
+            })
+        };
+        let dead_ends = explored
+            .iter()
+            .filter(|(state, transitions)| {
+                transitions.is_empty()
+                    || applying_rules(state).is_some_and(|applying| applying.is_empty())
+            })
+            .map(|(state, _)| (*state).clone())
+            .collect();
+        let sinks = explored
+            .iter()
+            .filter(|(state, transitions)| {
+                !transitions.is_empty()
+                    && transitions
+                        .iter()
+                        .all(|(next_state, _, _)| next_state == *state)
+            })
+            .map(|(state, _)| (*state).clone())
+            .collect();
+        let mut unfired_rules = self
+            .rules()
+            .into_iter()
+            .flat_map(|rules| rules.keys())
+            .filter(|name| {
+                !explored.iter().any(|(state, _)| {
+                    applying_rules(state).is_some_and(|applying| applying.contains(name))
+                })
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        unfired_rules.sort();
+        Diagnosis {
+            dead_ends,
+            sinks,
+            unfired_rules,
+            unexplored: states.len() - explored.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use hashbrown::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn diagnose() {
+        // A counter which gets stuck at three, the reset is never possible
+        let rules = HashMap::from([
+            (
+                "increment".to_string(),
+                Rule::new(
+                    "Increment".to_string(),
+                    Arc::new(|count: u8| count < 3),
+                    1.,
+                    Arc::new(|count| count + 1),
+                ),
+            ),
+            (
+                "reset".to_string(),
+                Rule::new(
+                    "Reset".to_string(),
+                    Arc::new(|count: u8| count > 5),
+                    1.,
+                    Arc::new(|_| 0),
+                ),
+            ),
+        ]);
+        let mut simulation = Simulation::from_rules(0, rules);
+        simulation.next_step();
+        let diagnosis = simulation.diagnose();
+        assert!(!diagnosis.is_complete());
+        assert!(diagnosis.sinks().is_empty());
+
+        simulation.explore();
+        let diagnosis = simulation.diagnose();
+        assert!(diagnosis.is_complete());
+        assert_eq!(diagnosis.dead_ends(), &vec![3]);
+        // Nothing happens in a state where no rule applies
+        assert_eq!(diagnosis.sinks(), &vec![3]);
+        assert_eq!(diagnosis.unfired_rules(), &vec!["reset".to_string()]);
+        assert!(!diagnosis.is_healthy());
+
+        let mut cycle =
+            Simulation::new(0, Arc::new(|state: u8| vec![((state + 1) % 3, "next", 1.)]));
+        cycle.explore();
+        assert!(cycle.diagnose().is_healthy());
+    }
+}
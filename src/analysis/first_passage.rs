@@ -0,0 +1,118 @@
+use std::{fmt::Debug, hash::Hash};
+
+use crate::prelude::*;
+
+// Distribution of the first time at which a predicate holds, up to a horizon
+#[derive(Debug, Clone, PartialEq)]
+pub struct FirstPassageTime {
+    probabilities: Vec<Probability>,
+    not_reached: Probability,
+}
+
+impl FirstPassageTime {
+    // Indexed by time, starting at 0
+    pub fn probabilities(&self) -> &Vec<Probability> {
+        &self.probabilities
+    }
+
+    pub fn probability_at(&self, time: Time) -> Probability {
+        self.probabilities.get(time as usize).copied().unwrap_or(0.)
+    }
+
+    // Probability of reaching the predicate at or before the given time
+    pub fn cumulative(&self, time: Time) -> Probability {
+        self.probabilities.iter().take(time as usize + 1).sum()
+    }
+
+    // Mass which has not reached the predicate by the horizon and was not killed
+    pub fn not_reached(&self) -> Probability {
+        self.not_reached
+    }
+
+    // Only defined if the predicate was reached with some probability
+    pub fn conditional_mean(&self) -> Option<f64> {
+        let reached = self.probabilities.iter().sum::<Probability>();
+        (reached > 0.).then(|| {
+            self.probabilities
+                .iter()
+                .enumerate()
+                .map(|(time, probability)| time as f64 * probability)
+                .sum::<f64>()
+                / reached
+        })
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // The stored distributions do not remember whether the predicate held before, so the
+    // initial distribution is propagated again with the mass removed once it reaches the
+    // predicate
+    pub fn first_passage_time(
+        &mut self,
+        horizon: Time,
+        predicate: impl Fn(&S) -> bool,
+    ) -> FirstPassageTime {
+        let mut distribution = self.initial_distribution();
+        let mut probabilities = Vec::with_capacity(horizon as usize + 1);
+        for time in 0..=horizon {
+            let mut reached = 0.;
+            distribution.retain(|state, probability| {
+                let hit = predicate(state);
+                if hit {
+                    reached += *probability;
+                }
+                !hit
+            });
+            probabilities.push(reached);
+            if time == horizon {
+                break;
+            }
+            distribution = self.propagate(&distribution);
+        }
+        FirstPassageTime {
+            probabilities,
+            not_reached: distribution.values().sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn first_passage_time() {
+        // An outbreak which grows by one with probability 0.5 and may also shrink back
+        let state_transition_generator = Arc::new(|size: u32| {
+            vec![
+                (size + 1, "grow", 0.5),
+                (size.saturating_sub(1), "shrink", 0.25),
+                (size, "stay", 0.25),
+            ]
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        let first_passage = simulation.first_passage_time(3, |size| *size >= 2);
+
+        assert_eq!(first_passage.probabilities().len(), 4);
+        assert_eq!(first_passage.probability_at(0), 0.);
+        assert_eq!(first_passage.probability_at(1), 0.);
+        assert_eq!(first_passage.probability_at(2), 0.25);
+        // Paths 0 -> 0 -> 1 -> 2 and 0 -> 1 -> 1 -> 2, shrinking at 0 stays at 0
+        assert_eq!(
+            first_passage.probability_at(3),
+            0.5 * 0.5 * 0.5 + 0.5 * 0.25 * 0.5
+        );
+        assert_eq!(first_passage.cumulative(3), 0.4375);
+        assert!((first_passage.not_reached() - 0.5625).abs() < 1e-12);
+        let mean = (2. * 0.25 + 3. * 0.1875) / 0.4375;
+        assert!((first_passage.conditional_mean().unwrap() - mean).abs() < 1e-12);
+        // The propagated distributions are not touched
+        assert_eq!(simulation.time(), 0);
+    }
+}
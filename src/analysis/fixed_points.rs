@@ -0,0 +1,116 @@
+use std::{fmt::Debug, hash::Hash};
+
+use hashbrown::HashMap;
+
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeterministicStructure<S> {
+    fixed_points: Vec<S>,
+    cycles: Vec<Vec<S>>,
+}
+
+impl<S> DeterministicStructure<S> {
+    pub fn fixed_points(&self) -> &Vec<S> {
+        &self.fixed_points
+    }
+
+    pub fn cycles(&self) -> &Vec<Vec<S>> {
+        &self.cycles
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fixed_points.is_empty() && self.cycles.is_empty()
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Only explored states are considered. A state is deterministic if all of its probability
+    // mass moves to a single successor.
+    pub fn deterministic_structure(&self) -> DeterministicStructure<S> {
+        let states = self.states_in_graph_order();
+        let successors = states
+            .iter()
+            .filter_map(|state| {
+                let transitions = self.cached_outgoing_transitions(state)?;
+                let (successor, _, _) = transitions.first()?;
+                transitions
+                    .iter()
+                    .all(|(target, _, _)| target == successor)
+                    .then_some((*state, successor))
+            })
+            .collect::<HashMap<&S, &S>>();
+
+        let fixed_points = states
+            .iter()
+            .filter(|state| successors.get(**state) == Some(*state))
+            .map(|state| (*state).clone())
+            .collect();
+
+        // Following the successors of a state either ends in a non deterministic state or runs
+        // into a cycle, each cycle is reported once starting at its first state in graph order.
+        let mut visited: HashMap<&S, usize> = HashMap::new();
+        let mut cycles = Vec::new();
+        for (walk, start) in states.iter().enumerate() {
+            let mut path = Vec::new();
+            let mut current = *start;
+            while !visited.contains_key(current) {
+                visited.insert(current, walk);
+                path.push(current);
+                match successors.get(current) {
+                    Some(successor) => current = *successor,
+                    None => break,
+                }
+            }
+            if visited.get(current) == Some(&walk) && successors.contains_key(current) {
+                let cycle_start = path.iter().position(|state| *state == current).unwrap();
+                let cycle = &path[cycle_start..];
+                if cycle.len() > 1 {
+                    cycles.push(cycle.iter().map(|state| (*state).clone()).collect());
+                }
+            }
+        }
+
+        DeterministicStructure {
+            fixed_points,
+            cycles,
+        }
+    }
+
+    pub fn fixed_points(&self) -> Vec<S> {
+        self.deterministic_structure().fixed_points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn fixed_points_and_cycles() {
+        // 0 and 1 choose randomly, 2 -> 3 -> 4 -> 2 is a deterministic cycle and 5 is absorbing
+        let state_transition_generator = Arc::new(|state: i32| match state {
+            0 => vec![(1, "left", 0.5), (5, "right", 0.5)],
+            1 => vec![(2, "enter", 0.5), (1, "stay", 0.5)],
+            2 | 3 => vec![(state + 1, "next", 1.)],
+            4 => vec![(2, "back", 1.)],
+            _ => vec![(state, "stay", 1.)],
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.explore();
+
+        let structure = simulation.deterministic_structure();
+        assert_eq!(structure.fixed_points(), &vec![5]);
+        assert_eq!(structure.cycles().len(), 1);
+        let mut cycle = structure.cycles()[0].clone();
+        cycle.sort();
+        assert_eq!(cycle, vec![2, 3, 4]);
+        assert_eq!(simulation.fixed_points(), vec![5]);
+    }
+}
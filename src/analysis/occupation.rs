@@ -0,0 +1,121 @@
+use std::{fmt::Debug, hash::Hash};
+
+use hashbrown::HashMap;
+
+use crate::models::units::{ContinuousTime, DiscountFactor};
+use crate::prelude::*;
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Expected number of visits of every state at the times 0 to horizon, the initial state counts
+    // as a visit. The simulation is advanced up to the horizon if necessary. Fails if one of the
+    // distributions on the way was forgotten.
+    pub fn occupation_measure(
+        &mut self,
+        horizon: Time,
+    ) -> Result<StateProbabilityDistribution<S>, SimulationError> {
+        while self.time() < horizon {
+            self.advance();
+        }
+        let mut visits: HashMap<S, f64> = HashMap::new();
+        for time in 0..=horizon {
+            self.iter_probability_distribution(time)?
+                .for_each(|(state, probability)| {
+                    *visits.entry(state.clone()).or_insert(0.) += probability;
+                });
+        }
+        Ok(visits)
+    }
+
+    // Total cost accumulated up to the horizon if every visit of a state costs the given amount
+    pub fn expected_cost(
+        &mut self,
+        horizon: Time,
+        cost: impl Fn(&S) -> f64,
+    ) -> Result<f64, SimulationError> {
+        Ok(self
+            .occupation_measure(horizon)?
+            .iter()
+            .map(|(state, visits)| cost(state) * visits)
+            .sum())
+    }
+
+    // Like expected_cost, but the cost of every step is discounted by the model time which passed
+    // until it, given the duration of a step
+    pub fn expected_discounted_cost(
+        &mut self,
+        horizon: Time,
+        step_duration: ContinuousTime,
+        discount: DiscountFactor,
+        cost: impl Fn(&S) -> f64,
+    ) -> Result<f64, SimulationError> {
+        while self.time() < horizon {
+            self.advance();
+        }
+        (0..=horizon)
+            .map(|time| {
+                let step_cost = self
+                    .iter_probability_distribution(time)?
+                    .map(|(state, probability)| cost(state) * probability)
+                    .sum::<f64>();
+                Ok(discount.discount(step_cost, step_duration * time as f64))
+            })
+            .sum()
+    }
+
+    // The states with the most expected visits, most visited first
+    pub fn hot_states(
+        &mut self,
+        horizon: Time,
+        count: usize,
+    ) -> Result<Vec<(S, f64)>, SimulationError> {
+        let mut visits = self
+            .occupation_measure(horizon)?
+            .into_iter()
+            .collect::<Vec<_>>();
+        visits.sort_by(|(_, first), (_, second)| second.total_cmp(first));
+        visits.truncate(count);
+        Ok(visits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn occupation_measure() {
+        // A queue which fills up until it is served at length 2
+        let state_transition_generator = Arc::new(|length: u8| match length {
+            2 => vec![(0, "serve", 1.)],
+            _ => vec![(length + 1, "arrive", 0.5), (length, "wait", 0.5)],
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        let visits = simulation.occupation_measure(2).unwrap();
+        assert_eq!(simulation.time(), 2);
+        assert_eq!(visits[&0], 1.75);
+        assert_eq!(visits[&1], 1.);
+        assert_eq!(visits[&2], 0.25);
+        assert_eq!(visits.values().sum::<f64>(), 3.);
+
+        let cost = simulation
+            .expected_cost(2, |length| *length as f64)
+            .unwrap();
+        assert_eq!(cost, 1.5);
+        let discounted_cost = simulation
+            .expected_discounted_cost(
+                2,
+                ContinuousTime::seconds(1.),
+                DiscountFactor::new(0.5).unwrap(),
+                |length| *length as f64,
+            )
+            .unwrap();
+        assert_eq!(discounted_cost, 0.5);
+        assert_eq!(simulation.hot_states(2, 1).unwrap(), vec![(0, 1.75)]);
+    }
+}
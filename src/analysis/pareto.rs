@@ -0,0 +1,133 @@
+use std::{fmt::Debug, hash::Hash};
+
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParetoPoint<S> {
+    state: S,
+    objectives: Vec<f64>,
+    probability: Probability,
+}
+
+impl<S> ParetoPoint<S> {
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    pub fn objectives(&self) -> &Vec<f64> {
+        &self.objectives
+    }
+
+    pub fn probability(&self) -> Probability {
+        self.probability
+    }
+}
+
+// All objectives are maximized. A point dominates another if it is at least as good in every
+// objective and strictly better in one.
+fn dominates(first: &[f64], second: &[f64]) -> bool {
+    first
+        .iter()
+        .zip(second)
+        .all(|(first, second)| first >= second)
+        && first
+            .iter()
+            .zip(second)
+            .any(|(first, second)| first > second)
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // The states with probability at the given time which are not dominated by any other such
+    // state, sorted by descending probability and objectives. Minimized objectives can be negated.
+    pub fn pareto_front(
+        &self,
+        time: Time,
+        objectives: impl Fn(&S) -> Vec<f64>,
+    ) -> Vec<ParetoPoint<S>> {
+        let points = self
+            .iter_probability_distribution(time)
+            .expect("No probability distribution found for given time")
+            .filter(|(_, probability)| *probability > 0.)
+            .map(|(state, probability)| ParetoPoint {
+                state: state.clone(),
+                objectives: objectives(state),
+                probability,
+            })
+            .collect::<Vec<_>>();
+        let mut front = points
+            .iter()
+            .filter(|point| {
+                !points
+                    .iter()
+                    .any(|other| dominates(&other.objectives, &point.objectives))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        front.sort_by(|first, second| {
+            second
+                .probability
+                .total_cmp(&first.probability)
+                .then_with(|| {
+                    second
+                        .objectives
+                        .iter()
+                        .zip(&first.objectives)
+                        .map(|(second, first)| second.total_cmp(first))
+                        .find(|ordering| ordering.is_ne())
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                })
+        });
+        front
+    }
+
+    // Probability of ending up in a state on the Pareto front
+    pub fn pareto_front_probability(
+        &self,
+        time: Time,
+        objectives: impl Fn(&S) -> Vec<f64>,
+    ) -> Probability {
+        self.pareto_front(time, objectives)
+            .iter()
+            .map(ParetoPoint::probability)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn pareto_front() {
+        // Designs as (performance, cost), every step improves performance at a cost, reduces the
+        // cost or wastes money
+        let state_transition_generator = Arc::new(|(performance, cost): (u8, u8)| {
+            vec![
+                ((performance + 1, cost + 2), "improve", 0.5),
+                ((performance, cost.saturating_sub(1)), "save", 0.25),
+                ((performance, cost + 1), "waste", 0.25),
+            ]
+        });
+        let mut simulation = Simulation::new((0, 2), state_transition_generator);
+        simulation.next_step();
+        simulation.next_step();
+
+        // (1, 5) is dominated by (1, 3), (0, 2) and (0, 4) are dominated by (0, 0)
+        let objectives =
+            |(performance, cost): &(u8, u8)| vec![*performance as f64, -(*cost as f64)];
+        let front = simulation.pareto_front(2, objectives);
+        assert_eq!(
+            front.iter().map(|point| *point.state()).collect::<Vec<_>>(),
+            vec![(2, 6), (1, 3), (0, 0)]
+        );
+        assert_eq!(front[1].probability(), 0.25);
+        assert_eq!(front[1].objectives(), &vec![1., -3.]);
+        assert_eq!(simulation.pareto_front_probability(2, objectives), 0.5625);
+    }
+}
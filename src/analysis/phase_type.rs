@@ -0,0 +1,294 @@
+use std::{fmt::Debug, hash::Hash};
+
+use hashbrown::HashMap;
+
+use crate::prelude::*;
+
+const MAX_ITERATIONS: usize = 1_000_000;
+
+// The time to reach a target set as a discrete phase-type distribution. The phases are the
+// states which can be visited before the target is reached, the sub-stochastic matrix holds the
+// transitions between them and the exit vector the probability of entering the target from them.
+// Mass which is killed on the way never reaches the target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseType<S> {
+    states: Vec<S>,
+    initial: Vec<Probability>,
+    initial_in_target: Probability,
+    sub_matrix: Vec<Vec<(usize, Probability)>>,
+    exit: Vec<Probability>,
+}
+
+impl<S> PhaseType<S> {
+    pub fn states(&self) -> &Vec<S> {
+        &self.states
+    }
+
+    // The initial probability of every phase, the rest is in the target from the start
+    pub fn initial_vector(&self) -> &Vec<Probability> {
+        &self.initial
+    }
+
+    pub fn initial_in_target(&self) -> Probability {
+        self.initial_in_target
+    }
+
+    // The non-zero entries of a row of the sub-stochastic matrix
+    pub fn sub_matrix_row(&self, phase: usize) -> &Vec<(usize, Probability)> {
+        &self.sub_matrix[phase]
+    }
+
+    pub fn exit_vector(&self) -> &Vec<Probability> {
+        &self.exit
+    }
+
+    fn multiply(&self, vector: &[Probability]) -> Vec<Probability> {
+        let mut result = vec![0.; vector.len()];
+        self.sub_matrix.iter().zip(vector).for_each(|(row, value)| {
+            row.iter()
+                .for_each(|(target, probability)| result[*target] += value * probability);
+        });
+        result
+    }
+
+    // Solves x = b + T x by iteration. This converges for the exit vector, and for any vector if
+    // the target is reached almost surely.
+    fn solve(&self, b: &[f64]) -> Vec<f64> {
+        let mut x = b.to_vec();
+        for _ in 0..MAX_ITERATIONS {
+            let next = self
+                .sub_matrix
+                .iter()
+                .zip(b)
+                .map(|(row, value)| {
+                    value
+                        + row
+                            .iter()
+                            .map(|(target, probability)| probability * x[*target])
+                            .sum::<f64>()
+                })
+                .collect::<Vec<_>>();
+            let change = next
+                .iter()
+                .zip(&x)
+                .map(|(new, old)| (new - old).abs() / new.abs().max(1.))
+                .fold(0., f64::max);
+            x = next;
+            if change < 1e-15 {
+                break;
+            }
+        }
+        x
+    }
+
+    // Probability of reaching the target exactly at the given time
+    pub fn probability_at(&self, time: Time) -> Probability {
+        if time == 0 {
+            return self.initial_in_target;
+        }
+        let distribution = (1..time).fold(self.initial.clone(), |distribution, _| {
+            self.multiply(&distribution)
+        });
+        distribution
+            .iter()
+            .zip(&self.exit)
+            .map(|(probability, exit)| probability * exit)
+            .sum()
+    }
+
+    pub fn cumulative(&self, time: Time) -> Probability {
+        let mut distribution = self.initial.clone();
+        let mut cumulative = self.initial_in_target;
+        for _ in 0..time {
+            cumulative += distribution
+                .iter()
+                .zip(&self.exit)
+                .map(|(probability, exit)| probability * exit)
+                .sum::<Probability>();
+            distribution = self.multiply(&distribution);
+        }
+        cumulative
+    }
+
+    // Probability of ever reaching the target
+    pub fn reach_probability(&self) -> Probability {
+        self.initial_in_target
+            + self
+                .initial
+                .iter()
+                .zip(self.solve(&self.exit))
+                .map(|(initial, absorption)| initial * absorption)
+                .sum::<Probability>()
+    }
+
+    // E[N (N - 1) ... (N - k + 1)] = k! alpha T^(k - 1) (I - T)^(-k) 1. None if the target is not
+    // reached almost surely, as the moments are infinite then.
+    pub fn factorial_moment(&self, order: u32) -> Option<f64> {
+        if order == 0 || self.reach_probability() < 1. - 1e-9 {
+            return (order == 0).then_some(1.);
+        }
+        let mut vector = vec![1.; self.states.len()];
+        for _ in 0..order {
+            vector = self.solve(&vector);
+        }
+        let mut initial = self.initial.clone();
+        for _ in 1..order {
+            initial = self.multiply(&initial);
+        }
+        let factorial = (1..=order).map(f64::from).product::<f64>();
+        Some(
+            factorial
+                * initial
+                    .iter()
+                    .zip(vector)
+                    .map(|(initial, value)| initial * value)
+                    .sum::<f64>(),
+        )
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        self.factorial_moment(1)
+    }
+
+    pub fn variance(&self) -> Option<f64> {
+        let mean = self.mean()?;
+        Some(self.factorial_moment(2)? + mean - mean * mean)
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Starts from the initial distribution. All states which can be visited before the target is
+    // reached are explored, so there must be finitely many of them.
+    pub fn phase_type(&mut self, target: impl Fn(&S) -> bool) -> PhaseType<S> {
+        let initial_distribution = self.initial_distribution();
+        let mut states = Vec::new();
+        let mut indices = HashMap::new();
+        let mut frontier = Vec::new();
+        let mut visit = |state: &S, states: &mut Vec<S>, frontier: &mut Vec<S>| {
+            if !target(state) && !indices.contains_key(state) {
+                indices.insert(state.clone(), states.len());
+                states.push(state.clone());
+                frontier.push(state.clone());
+            }
+        };
+        let mut sorted_initial = initial_distribution.iter().collect::<Vec<_>>();
+        sorted_initial.sort_by_key(|(state, _)| hash(*state));
+        sorted_initial
+            .iter()
+            .for_each(|(state, _)| visit(state, &mut states, &mut frontier));
+        while !frontier.is_empty() {
+            let unexplored = frontier
+                .iter()
+                .filter(|state| self.cached_outgoing_transitions(state).is_none())
+                .cloned()
+                .collect::<Vec<_>>();
+            if !unexplored.is_empty() {
+                self.explore_frontier(unexplored);
+            }
+            let mut next_frontier = Vec::new();
+            frontier.iter().for_each(|state| {
+                self.cached_outgoing_transitions(state)
+                    .unwrap()
+                    .iter()
+                    .for_each(|(next_state, _, _)| {
+                        visit(next_state, &mut states, &mut next_frontier)
+                    });
+            });
+            frontier = next_frontier;
+        }
+        let mut sub_matrix = vec![Vec::new(); states.len()];
+        let mut exit = vec![0.; states.len()];
+        states.iter().enumerate().for_each(|(index, state)| {
+            self.cached_outgoing_transitions(state)
+                .unwrap()
+                .iter()
+                .for_each(
+                    |(next_state, _, probability)| match indices.get(next_state) {
+                        Some(next) => {
+                            let row: &mut Vec<(usize, Probability)> = &mut sub_matrix[index];
+                            match row.iter_mut().find(|(target, _)| target == next) {
+                                Some((_, merged)) => *merged += probability,
+                                None => row.push((*next, *probability)),
+                            }
+                        }
+                        None if target(next_state) => exit[index] += probability,
+                        None => {
+                            unreachable!("Every successor of a phase is a phase or in the target")
+                        }
+                    },
+                );
+        });
+        let initial = states
+            .iter()
+            .map(|state| initial_distribution.get(state).copied().unwrap_or(0.))
+            .collect();
+        let initial_in_target = initial_distribution
+            .iter()
+            .filter(|(state, _)| target(state))
+            .map(|(_, probability)| probability)
+            .sum();
+        PhaseType {
+            states,
+            initial,
+            initial_in_target,
+            sub_matrix,
+            exit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn phase_type() {
+        // Two stages which are each left with probability 0.5, the time to reach stage two is
+        // the sum of two geometric distributions
+        let state_transition_generator = Arc::new(|stage: u32| {
+            if stage < 2 {
+                vec![(stage + 1, "advance", 0.5), (stage, "wait", 0.5)]
+            } else {
+                vec![(stage, "done", 1.)]
+            }
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        let phase_type = simulation.phase_type(|stage| *stage == 2);
+        assert_eq!(phase_type.states(), &vec![0, 1]);
+        assert_eq!(phase_type.initial_vector(), &vec![1., 0.]);
+        assert_eq!(phase_type.exit_vector(), &vec![0., 0.5]);
+        assert_eq!(phase_type.probability_at(1), 0.);
+        assert_eq!(phase_type.probability_at(2), 0.25);
+        assert_eq!(phase_type.probability_at(3), 0.25);
+        assert_eq!(phase_type.cumulative(3), 0.5);
+        assert!((phase_type.reach_probability() - 1.).abs() < 1e-12);
+        assert!((phase_type.mean().unwrap() - 4.).abs() < 1e-9);
+        // Each geometric distribution has a variance of (1 - 0.5) / 0.5^2
+        assert!((phase_type.variance().unwrap() - 4.).abs() < 1e-9);
+
+        let first_passage = simulation.first_passage_time(6, |stage| *stage == 2);
+        (0..=6).for_each(|time| {
+            assert!(
+                (phase_type.probability_at(time) - first_passage.probability_at(time)).abs()
+                    < 1e-12
+            )
+        });
+
+        let mut leaking = Simulation::new(
+            0,
+            Arc::new(|stage: u32| match stage {
+                0 => vec![(1, "advance", 0.5), (3, "fail", 0.5)],
+                _ => vec![(stage, "stay", 1.)],
+            }),
+        );
+        let leaking = leaking.phase_type(|stage| *stage == 1);
+        assert!((leaking.reach_probability() - 0.5).abs() < 1e-12);
+        assert_eq!(leaking.mean(), None);
+    }
+}
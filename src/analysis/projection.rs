@@ -0,0 +1,162 @@
+use std::{fmt::Debug, hash::Hash, sync::Arc};
+
+use hashbrown::HashMap;
+
+use crate::models::entities::*;
+use crate::prelude::*;
+
+// The marginal distribution and chain of a function of the state, e.g. one entity. Transitions
+// are averaged over the full states which project onto the same value, weighted by their
+// probability, so the projected chain is exact for one step but in general not Markovian.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Projection<P>
+where
+    P: Hash + Eq,
+{
+    time: Time,
+    distribution: StateProbabilityDistribution<P>,
+    transitions: HashMap<P, Vec<(P, Probability)>>,
+}
+
+impl<P> Projection<P>
+where
+    P: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + 'static,
+{
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn distribution(&self) -> &StateProbabilityDistribution<P> {
+        &self.distribution
+    }
+
+    pub fn probability_of(&self, value: &P) -> Probability {
+        self.distribution.get(value).copied().unwrap_or(0.)
+    }
+
+    // None if none of the states projecting onto the value is explored
+    pub fn transitions(&self, value: &P) -> Option<&Vec<(P, Probability)>> {
+        self.transitions.get(value)
+    }
+
+    // The projected chain as a simulation of its own starting from the projected distribution.
+    // Transitions are labeled with the target value, values without known transitions stay put.
+    pub fn to_simulation(&self) -> Simulation<P, P> {
+        let transitions = Arc::new(self.transitions.clone());
+        Simulation::new_with_distribution(
+            self.distribution.clone(),
+            Arc::new(move |value: P| match transitions.get(&value) {
+                Some(transitions) => transitions
+                    .iter()
+                    .map(|(target, probability)| (target.clone(), target.clone(), *probability))
+                    .collect(),
+                None => vec![(value.clone(), value, 1.)],
+            }),
+        )
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    pub fn project_by<P>(&self, time: Time, projection: impl Fn(&S) -> P) -> Projection<P>
+    where
+        P: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + 'static,
+    {
+        let mut distribution: StateProbabilityDistribution<P> = HashMap::new();
+        let mut explored_mass: HashMap<P, Probability> = HashMap::new();
+        let mut flows: HashMap<P, HashMap<P, Probability>> = HashMap::new();
+        self.iter_probability_distribution(time)
+            .expect("No probability distribution found for given time")
+            .for_each(|(state, probability)| {
+                let value = projection(state);
+                *distribution.entry(value.clone()).or_insert(0.) += probability;
+                if let Some(transitions) = self.cached_outgoing_transitions(state) {
+                    *explored_mass.entry(value.clone()).or_insert(0.) += probability;
+                    let targets = flows.entry(value).or_default();
+                    transitions
+                        .iter()
+                        .for_each(|(target, _, transition_probability)| {
+                            *targets.entry(projection(target)).or_insert(0.) +=
+                                probability * transition_probability;
+                        });
+                }
+            });
+        let transitions = flows
+            .into_iter()
+            .filter(|(value, _)| explored_mass[value] > 0.)
+            .map(|(value, targets)| {
+                let mass = explored_mass[&value];
+                let targets = targets
+                    .into_iter()
+                    .filter(|(_, flow)| *flow > 0.)
+                    .map(|(target, flow)| (target, flow / mass))
+                    .collect();
+                (value, targets)
+            })
+            .collect();
+        Projection {
+            time,
+            distribution,
+            transitions,
+        }
+    }
+}
+
+impl<V, T> Simulation<State<V>, T>
+where
+    V: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + 'static,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Keyed by the parameters of the entity, None where the entity does not exist
+    pub fn project(&self, entity_name: &str, time: Time) -> Projection<Option<StateEntity<V>>> {
+        self.project_by(time, |state| state.entity(entity_name).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project() {
+        // Coin a is flipped or coin b is removed, coin b is only removed once
+        let coins = |a: i32, b: i32| {
+            State::new()
+                .with_entity("a", StateEntity::new().with_parameter("heads", a))
+                .with_entity("b", StateEntity::new().with_parameter("heads", b))
+        };
+        let state_transition_generator = Arc::new(|state: State<i32>| {
+            let mut flip_a = state.clone();
+            flip_a.set_parameter("a", "heads", 1 - state.parameter("a", "heads").unwrap());
+            let mut remove_b = state.clone();
+            remove_b.remove_entity("b");
+            if state.entity("b").is_some() {
+                vec![(flip_a, "flip a", 0.5), (remove_b, "remove b", 0.5)]
+            } else {
+                vec![(flip_a, "flip a", 1.)]
+            }
+        });
+        let mut simulation = Simulation::new(coins(0, 0), state_transition_generator);
+        simulation.next_step();
+
+        let heads = |heads: i32| Some(StateEntity::new().with_parameter("heads", heads));
+        let projection = simulation.project("a", 1);
+        assert_eq!(projection.probability_of(&heads(0)), 0.5);
+        assert_eq!(projection.probability_of(&heads(1)), 0.5);
+        assert!(projection.transitions(&heads(0)).is_none());
+
+        let projection = simulation.project("a", 0);
+        let mut transitions = projection.transitions(&heads(0)).unwrap().clone();
+        transitions.sort_by_key(|(value, _)| value.as_ref().unwrap().parameter("heads").copied());
+        assert_eq!(transitions, vec![(heads(0), 0.5), (heads(1), 0.5)]);
+
+        let b = simulation.project("b", 1);
+        assert_eq!(b.probability_of(&None), 0.5);
+        let mut chain = projection.to_simulation();
+        chain.next_step();
+        assert_eq!(chain.probability_of(&heads(1), 1), 0.5);
+    }
+}
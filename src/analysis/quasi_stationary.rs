@@ -0,0 +1,106 @@
+use std::{fmt::Debug, hash::Hash};
+
+use hashbrown::HashSet;
+
+use crate::prelude::*;
+
+// The long run behavior of a chain conditioned on not having been absorbed yet. Starting from the
+// quasi-stationary distribution, the probability to survive a step is the same in every step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuasiStationaryDistribution<S>
+where
+    S: Hash + Eq,
+{
+    distribution: StateProbabilityDistribution<S>,
+    survival_probability: Probability,
+}
+
+impl<S> QuasiStationaryDistribution<S>
+where
+    S: Hash + Eq,
+{
+    pub fn distribution(&self) -> &StateProbabilityDistribution<S> {
+        &self.distribution
+    }
+
+    pub fn survival_probability(&self) -> Probability {
+        self.survival_probability
+    }
+
+    // Absorption happens after a geometrically distributed number of steps
+    pub fn expected_time_to_absorption(&self) -> f64 {
+        1. / (1. - self.survival_probability)
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + 'static,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // All closed classes are treated as absorbing
+    pub fn quasi_stationary_distribution(&self) -> Option<QuasiStationaryDistribution<S>> {
+        let closed_states = self
+            .communicating_classes()
+            .into_iter()
+            .filter(|class| class.is_closed())
+            .flat_map(|class| class.states().clone())
+            .collect::<HashSet<_>>();
+        self.quasi_stationary_distribution_avoiding(|state| closed_states.contains(state))
+    }
+
+    // Only explored states which are not absorbing are considered, None if there are none
+    pub fn quasi_stationary_distribution_avoiding(
+        &self,
+        absorbing: impl Fn(&S) -> bool,
+    ) -> Option<QuasiStationaryDistribution<S>> {
+        let matrix = self.transition_matrix();
+        let indices = (0..matrix.len())
+            .filter(|index| matrix.is_explored(*index) && !absorbing(matrix.state(*index)))
+            .collect::<Vec<_>>();
+        if indices.is_empty() {
+            return None;
+        }
+        let (distribution, survival_probability) = matrix.dominant_left_eigenvector(&indices);
+        Some(QuasiStationaryDistribution {
+            distribution: indices
+                .into_iter()
+                .zip(distribution)
+                .filter(|(_, probability)| *probability > 0.)
+                .map(|(index, probability)| (matrix.state(index).clone(), probability))
+                .collect(),
+            survival_probability: survival_probability.clamp(0., 1.),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn quasi_stationary_distribution() {
+        // A population which dies out from size 1 and grows or shrinks at size 2
+        let state_transition_generator = Arc::new(|size: u8| match size {
+            0 => vec![(0, "extinct", 1.)],
+            1 => vec![(0, "die", 0.5), (2, "grow", 0.5)],
+            _ => vec![(1, "shrink", 0.5), (2, "stay", 0.5)],
+        });
+        let mut simulation = Simulation::new(2, state_transition_generator);
+        simulation.explore();
+
+        let quasi_stationary = simulation.quasi_stationary_distribution().unwrap();
+        let golden_ratio = (1. + 5f64.sqrt()) / 2.;
+        assert!((quasi_stationary.survival_probability() - golden_ratio / 2.).abs() < 1e-12);
+        let distribution = quasi_stationary.distribution();
+        assert_eq!(distribution.len(), 2);
+        assert!((distribution[&2] / distribution[&1] - golden_ratio).abs() < 1e-9);
+        assert!(quasi_stationary.expected_time_to_absorption() > 5.);
+
+        assert!(simulation
+            .quasi_stationary_distribution_avoiding(|_| true)
+            .is_none());
+    }
+}
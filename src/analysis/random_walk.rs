@@ -0,0 +1,129 @@
+use std::{fmt::Debug, hash::Hash};
+
+use hashbrown::HashMap;
+
+use crate::prelude::*;
+use crate::random::*;
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Only cached transitions are followed, None if the state is not explored yet or the
+    // probability is killed
+    fn cached_step(&self, state: &S, rng: &mut SeededRng) -> Option<&S> {
+        let transitions = self.cached_outgoing_transitions(state)?;
+        let index = rng
+            .choose(transitions.iter().map(|(_, _, probability)| *probability))
+            .or_else(|| {
+                (!self.is_sub_stochastic())
+                    .then(|| transitions.len().checked_sub(1))
+                    .flatten()
+            })?;
+        Some(&transitions[index].0)
+    }
+
+    // A walk over the cached graph, which never generates transitions and so stays cheap for
+    // huge cached models. It ends early when it reaches a state whose transitions are not cached.
+    pub fn random_walk(&self, start: &S, length: usize, seed: u64) -> Vec<S> {
+        let mut rng = SeededRng::new(seed);
+        let mut walk = vec![start.clone()];
+        for _ in 0..length {
+            match self.cached_step(walk.last().unwrap(), &mut rng) {
+                Some(next_state) => walk.push(next_state.clone()),
+                None => break,
+            }
+        }
+        walk
+    }
+
+    // Fraction of all visits of many walks spent in each state, including the start. For a
+    // well mixing model and long walks this approximates the stationary distribution.
+    pub fn visit_frequencies(
+        &self,
+        start: &S,
+        walks: usize,
+        length: usize,
+        seed: u64,
+    ) -> HashMap<S, Probability> {
+        let mut rng = SeededRng::new(seed);
+        let mut visits: HashMap<&S, usize> = HashMap::new();
+        let mut total = 0;
+        for _ in 0..walks {
+            let mut state = start;
+            *visits.entry(state).or_insert(0) += 1;
+            total += 1;
+            for _ in 0..length {
+                let Some(next_state) = self.cached_step(state, &mut rng) else {
+                    break;
+                };
+                state = next_state;
+                *visits.entry(state).or_insert(0) += 1;
+                total += 1;
+            }
+        }
+        visits
+            .into_iter()
+            .map(|(state, count)| (state.clone(), count as Probability / total as Probability))
+            .collect()
+    }
+
+    // Where walks of the given length end, an approximation of the distribution at that time
+    // for a simulation started in the start state. Walks which end early count where they end.
+    pub fn walk_end_frequencies(
+        &self,
+        start: &S,
+        walks: usize,
+        length: usize,
+        seed: u64,
+    ) -> HashMap<S, Probability> {
+        let mut rng = SeededRng::new(seed);
+        let mut ends: HashMap<&S, usize> = HashMap::new();
+        for _ in 0..walks {
+            let mut state = start;
+            for _ in 0..length {
+                let Some(next_state) = self.cached_step(state, &mut rng) else {
+                    break;
+                };
+                state = next_state;
+            }
+            *ends.entry(state).or_insert(0) += 1;
+        }
+        ends.into_iter()
+            .map(|(state, count)| (state.clone(), count as Probability / walks as Probability))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn random_walks() {
+        // A sticky two state chain whose stationary distribution is (0.75, 0.25)
+        let state_transition_generator = Arc::new(|state: u8| match state {
+            0 => vec![(0, "stay", 0.9), (1, "leave", 0.1)],
+            _ => vec![(1, "stay", 0.7), (0, "leave", 0.3)],
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        assert_eq!(simulation.random_walk(&0, 10, 1), vec![0]);
+        simulation.explore();
+
+        let walk = simulation.random_walk(&0, 10, 1);
+        assert_eq!(walk.len(), 11);
+        assert_eq!(walk, simulation.random_walk(&0, 10, 1));
+
+        let frequencies = simulation.visit_frequencies(&0, 100, 1000, 7);
+        assert!((frequencies[&0] - 0.75).abs() < 0.02);
+        assert!((frequencies[&1] - 0.25).abs() < 0.02);
+
+        let ends = simulation.walk_end_frequencies(&0, 20_000, 2, 3);
+        simulation.next_step();
+        simulation.next_step();
+        assert!((ends[&1] - simulation.probability_of(&1, 2)).abs() < 0.01);
+    }
+}
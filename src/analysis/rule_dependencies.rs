@@ -0,0 +1,172 @@
+use hashbrown::HashMap;
+use petgraph::{algo::tarjan_scc, graph::NodeIndex, Direction, Graph};
+
+use crate::models::declarative::*;
+use crate::models::entities::*;
+use crate::models::rules::*;
+
+// An edge from one rule to another means that applying the first rule can enable or disable the
+// second one. The edge is labeled with the parameters through which this happens.
+#[derive(Debug, Clone)]
+pub struct RuleDependencyGraph {
+    graph: Graph<RuleName, Vec<ParameterName>>,
+}
+
+impl RuleDependencyGraph {
+    pub fn new(rules: &HashMap<RuleName, DeclarativeRule>) -> Self {
+        let mut graph = Graph::new();
+        let mut names = rules.keys().collect::<Vec<_>>();
+        names.sort();
+        let nodes = names
+            .into_iter()
+            .map(|name| (name, graph.add_node(name.clone())))
+            .collect::<Vec<_>>();
+        for (source_name, source) in &nodes {
+            for (target_name, target) in &nodes {
+                let parameters = rules[*source_name].affected_parameters(&rules[*target_name]);
+                if !parameters.is_empty() {
+                    graph.add_edge(*source, *target, parameters);
+                }
+            }
+        }
+        Self { graph }
+    }
+
+    pub fn graph(&self) -> &Graph<RuleName, Vec<ParameterName>> {
+        &self.graph
+    }
+
+    fn node(&self, rule_name: &str) -> Option<NodeIndex> {
+        self.graph
+            .node_indices()
+            .find(|node| self.graph[*node] == rule_name)
+    }
+
+    fn neighbors(&self, rule_name: &str, direction: Direction) -> Vec<&RuleName> {
+        let Some(node) = self.node(rule_name) else {
+            return Vec::new();
+        };
+        let mut neighbors = self
+            .graph
+            .neighbors_directed(node, direction)
+            .map(|neighbor| &self.graph[neighbor])
+            .collect::<Vec<_>>();
+        neighbors.sort();
+        neighbors.dedup();
+        neighbors
+    }
+
+    // Rules which have to be reevaluated after the given rule was applied
+    pub fn affected_by(&self, rule_name: &str) -> Vec<&RuleName> {
+        self.neighbors(rule_name, Direction::Outgoing)
+    }
+
+    pub fn affecting(&self, rule_name: &str) -> Vec<&RuleName> {
+        self.neighbors(rule_name, Direction::Incoming)
+    }
+
+    pub fn is_independent(&self, rule_name: &str) -> bool {
+        self.affected_by(rule_name).is_empty() && self.affecting(rule_name).is_empty()
+    }
+
+    // Groups of mutually dependent rules, every group only depends on the groups before it
+    pub fn evaluation_order(&self) -> Vec<Vec<RuleName>> {
+        tarjan_scc(&self.graph)
+            .into_iter()
+            .rev()
+            .map(|component| {
+                let mut names = component
+                    .into_iter()
+                    .map(|node| self.graph[node].clone())
+                    .collect::<Vec<_>>();
+                names.sort();
+                names
+            })
+            .collect()
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph rules {\n");
+        self.graph.node_indices().for_each(|node| {
+            dot.push_str(&format!("    \"{}\";\n", self.graph[node]));
+        });
+        self.graph.edge_indices().for_each(|edge| {
+            let (source, target) = self.graph.edge_endpoints(edge).unwrap();
+            dot.push_str(&format!(
+                "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                self.graph[source],
+                self.graph[target],
+                self.graph[edge].join(", ")
+            ));
+        });
+        dot.push('}');
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{conditions::*, units::*};
+
+    use super::*;
+
+    #[test]
+    fn dependencies() {
+        let condition = |parameter: &str| {
+            AggregateCondition::new(
+                Aggregate::Sum,
+                EntitySelector::All,
+                parameter.to_string(),
+                Comparison::Greater,
+                0.,
+            )
+        };
+        let assignment = |parameter: &str| {
+            Assignment::new(
+                "tank",
+                parameter,
+                Expression::constant(1., Unit::dimensionless()),
+            )
+        };
+        let rules = HashMap::from([
+            (
+                "fill".to_string(),
+                DeclarativeRule::new("Fill", 1.)
+                    .with_condition(condition("valve"))
+                    .with_assignment(assignment("water")),
+            ),
+            (
+                "drain".to_string(),
+                DeclarativeRule::new("Drain", 1.)
+                    .with_condition(condition("water"))
+                    .with_assignment(assignment("pressure")),
+            ),
+            (
+                "release".to_string(),
+                DeclarativeRule::new("Release", 1.)
+                    .with_condition(condition("pressure"))
+                    .with_assignment(assignment("water")),
+            ),
+            (
+                "log".to_string(),
+                DeclarativeRule::new("Log", 1.).with_condition(condition("time")),
+            ),
+        ]);
+        let dependencies = RuleDependencyGraph::new(&rules);
+        assert_eq!(dependencies.affected_by("fill"), vec!["drain"]);
+        assert_eq!(dependencies.affecting("drain"), vec!["fill", "release"]);
+        assert!(dependencies.is_independent("log"));
+        let order = dependencies.evaluation_order();
+        let position = |name: &str| {
+            order
+                .iter()
+                .position(|group| group.contains(&name.to_string()))
+                .unwrap()
+        };
+        assert!(order.contains(&vec!["drain".to_string(), "release".to_string()]));
+        assert!(position("fill") < position("drain"));
+        assert!(dependencies
+            .to_dot()
+            .contains("\"fill\" -> \"drain\" [label=\"water\"];"));
+    }
+}
@@ -0,0 +1,143 @@
+use std::{fmt::Debug, hash::Hash};
+
+use crate::prelude::*;
+
+// Pairwise total variation distances between distributions which started from different initial
+// distributions, at every time up to a horizon
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitialSensitivity {
+    count: usize,
+    distances: Vec<Vec<Probability>>,
+}
+
+impl InitialSensitivity {
+    // Number of initial distributions
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn horizon(&self) -> Time {
+        self.distances.len() as Time - 1
+    }
+
+    fn pair_index(&self, first: usize, second: usize) -> usize {
+        let (first, second) = (first.min(second), first.max(second));
+        first * (2 * self.count - first - 1) / 2 + second - first - 1
+    }
+
+    pub fn distance(&self, time: Time, first: usize, second: usize) -> Probability {
+        if first == second {
+            return 0.;
+        }
+        self.distances[time as usize][self.pair_index(first, second)]
+    }
+
+    pub fn max_distance(&self, time: Time) -> Probability {
+        self.distances[time as usize]
+            .iter()
+            .copied()
+            .fold(0., f64::max)
+    }
+
+    // The first time at which all distributions are within the given distance of each other,
+    // i.e. when the model has forgotten its initial condition up to that distance
+    pub fn forgetting_time(&self, distance: Probability) -> Option<Time> {
+        (0..=self.horizon()).find(|time| self.max_distance(*time) <= distance)
+    }
+}
+
+fn total_variation<S: Hash + Eq>(
+    first: &StateProbabilityDistribution<S>,
+    second: &StateProbabilityDistribution<S>,
+) -> Probability {
+    let first_only = first
+        .iter()
+        .map(|(state, probability)| (probability - second.get(state).unwrap_or(&0.)).abs())
+        .sum::<Probability>();
+    let second_only = second
+        .iter()
+        .filter(|(state, _)| !first.contains_key(*state))
+        .map(|(_, probability)| probability)
+        .sum::<Probability>();
+    (first_only + second_only) / 2.
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // The distributions are propagated side by side and share the cached transitions. The
+    // stored distributions of the simulation are not touched.
+    pub fn initial_sensitivity(
+        &mut self,
+        initial_distributions: Vec<StateProbabilityDistribution<S>>,
+        horizon: Time,
+    ) -> InitialSensitivity {
+        assert!(
+            initial_distributions.len() >= 2,
+            "At least two initial distributions are needed to compare them"
+        );
+        let count = initial_distributions.len();
+        let mut distributions = initial_distributions;
+        let mut distances = Vec::with_capacity(horizon as usize + 1);
+        for time in 0..=horizon {
+            distances.push(
+                (0..count)
+                    .flat_map(|first| (first + 1..count).map(move |second| (first, second)))
+                    .map(|(first, second)| {
+                        total_variation(&distributions[first], &distributions[second])
+                    })
+                    .collect(),
+            );
+            if time < horizon {
+                distributions = distributions
+                    .iter()
+                    .map(|distribution| self.propagate(distribution))
+                    .collect();
+            }
+        }
+        InitialSensitivity { count, distances }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use hashbrown::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn initial_sensitivity() {
+        // A lazy walk on a cycle of four states forgets where it started
+        let state_transition_generator = Arc::new(|position: u8| {
+            vec![
+                (position, "stay", 0.5),
+                ((position + 1) % 4, "forward", 0.25),
+                ((position + 3) % 4, "backward", 0.25),
+            ]
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        let sensitivity = simulation.initial_sensitivity(
+            vec![
+                HashMap::from([(0, 1.)]),
+                HashMap::from([(2, 1.)]),
+                HashMap::from([(0, 0.5), (2, 0.5)]),
+            ],
+            20,
+        );
+        assert_eq!(sensitivity.count(), 3);
+        assert_eq!(sensitivity.distance(0, 0, 1), 1.);
+        assert_eq!(sensitivity.distance(0, 2, 0), 0.5);
+        assert_eq!(sensitivity.distance(1, 0, 1), 0.5);
+        assert!((0..20).all(
+            |time| sensitivity.max_distance(time + 1) <= sensitivity.max_distance(time) + 1e-12
+        ));
+        assert!(sensitivity.max_distance(20) < 1e-3);
+        assert_eq!(sensitivity.forgetting_time(0.5), Some(1));
+        assert_eq!(simulation.time(), 0);
+        assert_eq!(simulation.known_states().len(), 4);
+    }
+}
@@ -0,0 +1,182 @@
+use std::{fmt::Debug, hash::Hash};
+
+use hashbrown::HashSet;
+
+use crate::prelude::*;
+
+use super::condensation::*;
+
+// A reducible chain has no single stationary distribution. In the long run the probability ends up
+// in one of the closed classes, where it follows the stationary distribution of that class.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosedClassLimit<S>
+where
+    S: Hash + Eq,
+{
+    class: CommunicatingClass<S>,
+    absorption_probability: Probability,
+    stationary_distribution: StateProbabilityDistribution<S>,
+}
+
+impl<S> ClosedClassLimit<S>
+where
+    S: Hash + Eq,
+{
+    pub fn class(&self) -> &CommunicatingClass<S> {
+        &self.class
+    }
+
+    pub fn absorption_probability(&self) -> Probability {
+        self.absorption_probability
+    }
+
+    pub fn stationary_distribution(&self) -> &StateProbabilityDistribution<S> {
+        &self.stationary_distribution
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + 'static,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    pub fn stationary_distribution_of(
+        &self,
+        class: &CommunicatingClass<S>,
+    ) -> StateProbabilityDistribution<S> {
+        assert!(
+            class.is_closed(),
+            "Only closed classes have a stationary distribution"
+        );
+        let states = class.states().iter().collect::<HashSet<_>>();
+        let matrix = self.transition_matrix();
+        let indices = (0..matrix.len())
+            .filter(|index| states.contains(matrix.state(*index)))
+            .collect::<Vec<_>>();
+        let (distribution, _) = matrix.dominant_left_eigenvector(&indices);
+        indices
+            .into_iter()
+            .zip(distribution)
+            .map(|(index, probability)| (matrix.state(index).clone(), probability))
+            .collect()
+    }
+
+    // Probability of ending up in each of the closed classes, starting from the initial
+    // distribution. Mass which can reach unexplored states is only partially accounted for, so
+    // the probabilities sum up to less than 1 until the state space is fully explored.
+    pub fn absorption_probabilities(&self) -> Vec<(CommunicatingClass<S>, Probability)> {
+        let condensation = self.condensation();
+        let matrix = self.transition_matrix();
+        let closed_classes = condensation.closed_classes();
+        let class_of = (0..matrix.len())
+            .map(|index| condensation.class_of(matrix.state(index)).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut absorption = (0..matrix.len())
+            .map(|index| {
+                closed_classes
+                    .iter()
+                    .map(|class| if class_of[index] == *class { 1. } else { 0. })
+                    .collect::<Vec<Probability>>()
+            })
+            .collect::<Vec<_>>();
+        // Sinks first, so most states are solved in the first sweep
+        let mut transient = (0..matrix.len())
+            .filter(|index| {
+                matrix.is_explored(*index) && !closed_classes.contains(&class_of[*index])
+            })
+            .collect::<Vec<_>>();
+        transient.sort_by_key(|index| std::cmp::Reverse(class_of[*index]));
+        for _ in 0..100_000 {
+            let mut change: Probability = 0.;
+            for index in &transient {
+                let mut updated = vec![0.; closed_classes.len()];
+                matrix
+                    .row(*index)
+                    .unwrap()
+                    .iter()
+                    .for_each(|(target, probability)| {
+                        updated
+                            .iter_mut()
+                            .zip(&absorption[*target])
+                            .for_each(|(value, target_value)| *value += probability * target_value);
+                    });
+                change = updated
+                    .iter()
+                    .zip(&absorption[*index])
+                    .map(|(new, old)| (new - old).abs())
+                    .fold(change, f64::max);
+                absorption[*index] = updated;
+            }
+            if change < 1e-15 {
+                break;
+            }
+        }
+
+        let initial_distribution = self.initial_distribution();
+        closed_classes
+            .iter()
+            .enumerate()
+            .map(|(position, class)| {
+                let probability = (0..matrix.len())
+                    .map(|index| {
+                        initial_distribution
+                            .get(matrix.state(index))
+                            .copied()
+                            .unwrap_or(0.)
+                            * absorption[index][position]
+                    })
+                    .sum::<Probability>();
+                (condensation.classes()[*class].clone(), probability)
+            })
+            .collect()
+    }
+
+    pub fn stationary_decomposition(&self) -> Vec<ClosedClassLimit<S>> {
+        self.absorption_probabilities()
+            .into_iter()
+            .map(|(class, absorption_probability)| ClosedClassLimit {
+                stationary_distribution: self.stationary_distribution_of(&class),
+                class,
+                absorption_probability,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn stationary_decomposition() {
+        // A fair gambler's ruin between 0 and 3, reaching 3 leads into the cycle between 4 and 5
+        let state_transition_generator = Arc::new(|state: u8| match state {
+            0 => vec![(0, "broke", 1.)],
+            1 | 2 => vec![(state - 1, "lose", 0.5), (state + 1, "win", 0.5)],
+            3 => vec![(4, "retire", 1.)],
+            4 => vec![(5, "travel", 1.)],
+            _ => vec![(4, "travel", 0.5), (5, "stay", 0.5)],
+        });
+        let mut simulation = Simulation::new(1, state_transition_generator);
+        simulation.explore();
+
+        let decomposition = simulation.stationary_decomposition();
+        assert_eq!(decomposition.len(), 2);
+        let broke = decomposition
+            .iter()
+            .find(|limit| limit.class().states() == &vec![0])
+            .unwrap();
+        assert!((broke.absorption_probability() - 2. / 3.).abs() < 1e-12);
+        assert_eq!(broke.stationary_distribution()[&0], 1.);
+        let retired = decomposition
+            .iter()
+            .find(|limit| limit.class().states().contains(&4))
+            .unwrap();
+        assert!((retired.absorption_probability() - 1. / 3.).abs() < 1e-12);
+        assert!((retired.stationary_distribution()[&4] - 1. / 3.).abs() < 1e-12);
+        assert!((retired.stationary_distribution()[&5] - 2. / 3.).abs() < 1e-12);
+    }
+}
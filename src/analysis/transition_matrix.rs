@@ -0,0 +1,132 @@
+use std::{fmt::Debug, hash::Hash};
+
+use hashbrown::HashMap;
+use petgraph::{algo::tarjan_scc, Graph};
+
+use crate::prelude::*;
+
+// The cached transitions as a sparse matrix over the known states in graph order. States whose
+// transitions were not generated yet have no row.
+pub(crate) struct TransitionMatrix<'a, S> {
+    states: Vec<&'a S>,
+    rows: Vec<Option<Vec<(usize, Probability)>>>,
+}
+
+impl<'a, S> TransitionMatrix<'a, S> {
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn state(&self, index: usize) -> &'a S {
+        self.states[index]
+    }
+
+    pub fn row(&self, index: usize) -> Option<&Vec<(usize, Probability)>> {
+        self.rows[index].as_ref()
+    }
+
+    pub fn is_explored(&self, index: usize) -> bool {
+        self.rows[index].is_some()
+    }
+
+    // Left eigenvector to the largest eigenvalue of the matrix restricted to the given states,
+    // normalized to a probability distribution, together with that eigenvalue. The iteration uses
+    // the lazy chain, so periodic classes converge as well.
+    pub fn dominant_left_eigenvector(&self, indices: &[usize]) -> (Vec<Probability>, Probability) {
+        let positions = indices
+            .iter()
+            .enumerate()
+            .map(|(position, index)| (*index, position))
+            .collect::<HashMap<_, _>>();
+        let mut vector = vec![1. / indices.len() as Probability; indices.len()];
+        let mut eigenvalue = 1.;
+        for _ in 0..100_000 {
+            let mut next = vector.iter().map(|value| value / 2.).collect::<Vec<_>>();
+            indices.iter().enumerate().for_each(|(position, index)| {
+                self.row(*index)
+                    .into_iter()
+                    .flatten()
+                    .for_each(|(target, probability)| {
+                        if let Some(target) = positions.get(target) {
+                            next[*target] += vector[position] * probability / 2.;
+                        }
+                    });
+            });
+            let mass = next.iter().sum::<Probability>();
+            eigenvalue = 2. * mass - 1.;
+            if mass == 0. {
+                break;
+            }
+            next.iter_mut().for_each(|value| *value /= mass);
+            let change = next
+                .iter()
+                .zip(&vector)
+                .map(|(new, old)| (new - old).abs())
+                .fold(0., f64::max);
+            vector = next;
+            if change < 1e-15 {
+                break;
+            }
+        }
+        (vector, eigenvalue)
+    }
+
+    // Strongly connected components, sources first
+    pub fn components(&self) -> Vec<Vec<usize>> {
+        let mut graph: Graph<usize, ()> = Graph::new();
+        let nodes = (0..self.len())
+            .map(|index| graph.add_node(index))
+            .collect::<Vec<_>>();
+        self.rows.iter().enumerate().for_each(|(source, row)| {
+            row.iter().flatten().for_each(|(target, probability)| {
+                if *probability > 0. {
+                    graph.add_edge(nodes[source], nodes[*target], ());
+                }
+            });
+        });
+        tarjan_scc(&graph)
+            .into_iter()
+            .rev()
+            .map(|component| {
+                let mut component = component
+                    .into_iter()
+                    .map(|node| graph[node])
+                    .collect::<Vec<_>>();
+                component.sort();
+                component
+            })
+            .collect()
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    pub(crate) fn transition_matrix(&self) -> TransitionMatrix<'_, S> {
+        let states = self.states_in_graph_order();
+        let indices = states
+            .iter()
+            .enumerate()
+            .map(|(index, state)| (*state, index))
+            .collect::<HashMap<_, _>>();
+        let rows = states
+            .iter()
+            .map(|state| {
+                self.cached_outgoing_transitions(state).map(|transitions| {
+                    let mut row: Vec<(usize, Probability)> = Vec::new();
+                    transitions.iter().for_each(|(target, _, probability)| {
+                        let target = indices[target];
+                        match row.iter_mut().find(|(index, _)| *index == target) {
+                            Some((_, merged)) => *merged += probability,
+                            None => row.push((target, *probability)),
+                        }
+                    });
+                    row
+                })
+            })
+            .collect();
+        TransitionMatrix { states, rows }
+    }
+}
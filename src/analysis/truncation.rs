@@ -0,0 +1,205 @@
+use std::{fmt::Debug, hash::Hash};
+
+use hashbrown::{HashMap, HashSet};
+
+use crate::prelude::*;
+
+// The distribution propagated on a finite set of states, the finite state projection. Mass which
+// leaves the set is not propagated any further and only accumulated, so every probability is at
+// most the lost mass above its truncated value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TruncatedDistribution<S>
+where
+    S: Hash + Eq,
+{
+    time: Time,
+    distribution: StateProbabilityDistribution<S>,
+    lost_mass: Probability,
+    pruned_inflow: HashMap<S, Probability>,
+    added_states: Vec<S>,
+}
+
+impl<S> TruncatedDistribution<S>
+where
+    S: Hash + Eq + Clone,
+{
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn distribution(&self) -> &StateProbabilityDistribution<S> {
+        &self.distribution
+    }
+
+    pub fn probability_of(&self, state: &S) -> Probability {
+        self.distribution.get(state).copied().unwrap_or(0.)
+    }
+
+    // Bounds the total variation distance to the exact distribution
+    pub fn lost_mass(&self) -> Probability {
+        self.lost_mass
+    }
+
+    pub fn bounds_of(&self, state: &S) -> ProbabilityInterval {
+        ProbabilityInterval::point(self.probability_of(state)).widen(self.lost_mass)
+    }
+
+    pub fn is_within(&self, tolerance: Probability) -> bool {
+        self.lost_mass <= tolerance
+    }
+
+    // The mass which flowed into each state outside of the set, at any time
+    pub fn pruned_inflow(&self) -> &HashMap<S, Probability> {
+        &self.pruned_inflow
+    }
+
+    // States outside of the predicate which were added to the set by adaptive refinement, in the
+    // order they were added
+    pub fn added_states(&self) -> &Vec<S> {
+        &self.added_states
+    }
+
+    // Pruned states by descending inflow, the candidates for extending the set
+    pub fn pruned_by_inflow(&self) -> Vec<(&S, Probability)> {
+        let mut pruned = self
+            .pruned_inflow
+            .iter()
+            .map(|(state, inflow)| (state, *inflow))
+            .collect::<Vec<_>>();
+        pruned.sort_by(|(_, first), (_, second)| second.total_cmp(first));
+        pruned
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Propagates the initial distribution for the given number of steps on the states for which
+    // the predicate holds. States outside of the set are never explored.
+    pub fn truncated_distribution(
+        &mut self,
+        steps: usize,
+        in_set: impl Fn(&S) -> bool,
+    ) -> TruncatedDistribution<S> {
+        let mut pruned_inflow: HashMap<S, Probability> = HashMap::new();
+        let mut lost_mass = 0.;
+        let mut truncate = |distribution: StateProbabilityDistribution<S>| {
+            distribution
+                .into_iter()
+                .filter(|(state, probability)| {
+                    let keep = in_set(state);
+                    if !keep {
+                        *pruned_inflow.entry(state.clone()).or_insert(0.) += probability;
+                        lost_mass += probability;
+                    }
+                    keep
+                })
+                .collect::<StateProbabilityDistribution<S>>()
+        };
+        let mut distribution = truncate(self.initial_distribution());
+        for _ in 0..steps {
+            distribution = truncate(self.propagate(&distribution));
+        }
+        TruncatedDistribution {
+            time: steps as Time,
+            distribution,
+            lost_mass,
+            pruned_inflow,
+            added_states: Vec::new(),
+        }
+    }
+
+    // Repeats the truncated propagation and adds the pruned states with the most inflow to the
+    // set until the lost mass is within the tolerance. Each refinement adds up to batch_size
+    // states. The result may still exceed the tolerance after the maximal number of refinements.
+    pub fn adaptive_truncated_distribution(
+        &mut self,
+        steps: usize,
+        in_set: impl Fn(&S) -> bool,
+        tolerance: Probability,
+        batch_size: usize,
+        max_refinements: usize,
+    ) -> TruncatedDistribution<S> {
+        assert!(batch_size > 0, "Refinement needs to add at least one state");
+        let mut added_states: Vec<S> = Vec::new();
+        let mut added: HashSet<S> = HashSet::new();
+        let mut refinements = 0;
+        loop {
+            let mut truncated =
+                self.truncated_distribution(steps, |state| in_set(state) || added.contains(state));
+            let candidates = truncated
+                .pruned_by_inflow()
+                .into_iter()
+                .take(batch_size)
+                .map(|(state, _)| state.clone())
+                .collect::<Vec<_>>();
+            if truncated.is_within(tolerance)
+                || refinements == max_refinements
+                || candidates.is_empty()
+            {
+                truncated.added_states = added_states;
+                return truncated;
+            }
+            candidates.into_iter().for_each(|state| {
+                added.insert(state.clone());
+                added_states.push(state);
+            });
+            refinements += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn truncation() {
+        let state_transition_generator =
+            Arc::new(|state: i32| vec![(state - 1, "down", 0.5), (state + 1, "up", 0.5)]);
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        let truncated = simulation.truncated_distribution(6, |state| state.abs() <= 4);
+        for _ in 0..6 {
+            simulation.next_step();
+        }
+
+        assert_eq!(truncated.time(), 6);
+        // Mass reaches 5 or -5 at step 5 with probability 1/32 each
+        assert!((truncated.lost_mass() - 2. / 32.).abs() < 1e-12);
+        assert!(!truncated.is_within(0.05));
+        for state in -6..=6 {
+            assert!(truncated
+                .bounds_of(&state)
+                .contains(simulation.probability_of(&state, 6)));
+        }
+        let pruned = truncated.pruned_by_inflow();
+        assert_eq!(pruned.len(), 2);
+        assert!(pruned.iter().all(|(state, _)| state.abs() == 5));
+
+        let refined =
+            simulation.adaptive_truncated_distribution(6, |state| state.abs() <= 2, 0.05, 2, 10);
+        assert!(refined.is_within(0.05));
+        // The walk is symmetric, so both sides are extended by one state per refinement
+        assert_eq!(refined.added_states().len(), 6);
+        assert!(refined
+            .added_states()
+            .iter()
+            .all(|state| (3..=5).contains(&state.abs())));
+        let limited =
+            simulation.adaptive_truncated_distribution(6, |state| state.abs() <= 2, 0.05, 2, 1);
+        assert!(!limited.is_within(0.05));
+        assert_eq!(limited.added_states().len(), 2);
+
+        let mut fresh = Simulation::new(
+            0,
+            Arc::new(|state: i32| vec![(state - 1, "down", 0.5), (state + 1, "up", 0.5)]),
+        );
+        fresh.truncated_distribution(6, |state| state.abs() <= 2);
+        // Only the states in the set and their successors are known
+        assert_eq!(fresh.known_states().len(), 7);
+    }
+}
@@ -0,0 +1,236 @@
+use std::time::{Duration, Instant};
+
+use hashbrown::HashMap;
+
+use crate::models::condition_index::*;
+use crate::models::conditions::*;
+use crate::models::declarative::*;
+use crate::models::entities::*;
+use crate::models::rules::*;
+use crate::models::units::*;
+use crate::prelude::*;
+
+// A synthetic model with the given number of entities, parameters per entity and rules. Every rule
+// increments one parameter of one entity up to a bound, so the state space grows with the number
+// of steps but stays finite.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchmarkProfile {
+    entities: usize,
+    parameters: usize,
+    rules: usize,
+    steps: usize,
+    bound: i32,
+    threads: Option<usize>,
+    indexed_conditions: bool,
+}
+
+impl BenchmarkProfile {
+    pub fn new(entities: usize, parameters: usize, rules: usize) -> Self {
+        assert!(
+            entities > 0 && parameters > 0,
+            "Benchmark models need at least one entity and parameter"
+        );
+        Self {
+            entities,
+            parameters,
+            rules,
+            steps: 5,
+            bound: 3,
+            threads: None,
+            indexed_conditions: false,
+        }
+    }
+
+    pub fn small() -> Self {
+        Self::new(2, 2, 4)
+    }
+
+    pub fn medium() -> Self {
+        Self::new(4, 4, 16).with_steps(6)
+    }
+
+    pub fn large() -> Self {
+        Self::new(8, 8, 64).with_steps(6)
+    }
+
+    pub fn with_steps(mut self, steps: usize) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    pub fn with_bound(mut self, bound: i32) -> Self {
+        self.bound = bound;
+        self
+    }
+
+    // Runs the benchmark on a thread pool of the given size instead of the global one
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    pub fn with_indexed_conditions(mut self, indexed_conditions: bool) -> Self {
+        self.indexed_conditions = indexed_conditions;
+        self
+    }
+
+    pub fn entities(&self) -> usize {
+        self.entities
+    }
+
+    pub fn parameters(&self) -> usize {
+        self.parameters
+    }
+
+    pub fn rules(&self) -> usize {
+        self.rules
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn bound(&self) -> i32 {
+        self.bound
+    }
+
+    pub fn threads(&self) -> Option<usize> {
+        self.threads
+    }
+
+    pub fn indexed_conditions(&self) -> bool {
+        self.indexed_conditions
+    }
+
+    pub fn initial_state(&self) -> State<i32> {
+        (0..self.entities).fold(State::new(), |state, entity| {
+            let entity_value = (0..self.parameters).fold(
+                StateEntity::of_class(format!("class{entity}")),
+                |entity, parameter| entity.with_parameter(format!("p{parameter}"), 0),
+            );
+            state.with_entity(format!("entity{entity}"), entity_value)
+        })
+    }
+
+    pub fn declarative_rules(&self) -> HashMap<RuleName, DeclarativeRule> {
+        (0..self.rules)
+            .map(|rule| {
+                let entity = rule % self.entities;
+                let parameter = format!("p{}", (rule / self.entities) % self.parameters);
+                let entity_name = format!("entity{entity}");
+                let declarative_rule = DeclarativeRule::new(format!("rule{rule}"), 0.5)
+                    .with_condition(AggregateCondition::new(
+                        Aggregate::Sum,
+                        EntitySelector::Class(format!("class{entity}")),
+                        parameter.clone(),
+                        Comparison::Less,
+                        self.bound as f64,
+                    ))
+                    .with_assignment(Assignment::new(
+                        entity_name.clone(),
+                        parameter.clone(),
+                        Expression::parameter(entity_name, parameter)
+                            + Expression::constant(1., Unit::dimensionless()),
+                    ));
+                (format!("rule{rule}"), declarative_rule)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkResult {
+    profile: BenchmarkProfile,
+    elapsed: Duration,
+    states: usize,
+    transitions: usize,
+}
+
+impl BenchmarkResult {
+    pub fn profile(&self) -> &BenchmarkProfile {
+        &self.profile
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn states(&self) -> usize {
+        self.states
+    }
+
+    pub fn transitions(&self) -> usize {
+        self.transitions
+    }
+
+    pub fn steps_per_second(&self) -> f64 {
+        self.profile.steps as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn states_per_second(&self) -> f64 {
+        self.states as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+impl Simulation<State<i32>, String> {
+    pub fn benchmark(profile: &BenchmarkProfile) -> BenchmarkResult {
+        let run = || {
+            let rules = profile.declarative_rules();
+            let initial_state = profile.initial_state();
+            let start = Instant::now();
+            // The generated rules only refer to parameters of the generated initial state
+            let mut simulation = if profile.indexed_conditions {
+                Simulation::new(
+                    initial_state.clone(),
+                    get_indexed_state_transition_generator(
+                        rules,
+                        &initial_state,
+                        RuleOptions::default(),
+                    )
+                    .unwrap(),
+                )
+            } else {
+                let rules = rules
+                    .iter()
+                    .map(|(name, rule)| (name.clone(), rule.to_rule(&initial_state).unwrap()))
+                    .collect();
+                Simulation::from_rules(initial_state, rules)
+            };
+            for _ in 0..profile.steps {
+                simulation.next_step();
+            }
+            BenchmarkResult {
+                profile: profile.clone(),
+                elapsed: start.elapsed(),
+                states: simulation.num_known_states(),
+                transitions: simulation.state_transition_graph().edge_count(),
+            }
+        };
+        match profile.threads {
+            Some(threads) => rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .expect("Failed to build thread pool for the benchmark")
+                .install(run),
+            None => run(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn benchmark() {
+        let profile = BenchmarkProfile::small().with_threads(2);
+        let result = Simulation::benchmark(&profile);
+        // 4 counters up to 3 with at most 5 increments in total
+        assert_eq!(result.states(), 106);
+        assert!(result.states_per_second() > 0.);
+
+        let indexed = Simulation::benchmark(&profile.with_indexed_conditions(true));
+        assert_eq!(indexed.states(), result.states());
+        assert_eq!(indexed.transitions(), result.transitions());
+    }
+}
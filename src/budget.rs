@@ -0,0 +1,196 @@
+use std::{
+    fmt::Debug,
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+use crate::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Budget {
+    max_duration: Option<Duration>,
+    max_states: Option<usize>,
+    max_steps: Option<usize>,
+}
+
+impl Budget {
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    pub fn with_max_seconds(self, max_seconds: f64) -> Self {
+        self.with_max_duration(Duration::from_secs_f64(max_seconds))
+    }
+
+    pub fn with_max_states(mut self, max_states: usize) -> Self {
+        self.max_states = Some(max_states);
+        self
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    pub fn max_duration(&self) -> Option<Duration> {
+        self.max_duration
+    }
+
+    pub fn max_states(&self) -> Option<usize> {
+        self.max_states
+    }
+
+    pub fn max_steps(&self) -> Option<usize> {
+        self.max_steps
+    }
+
+    fn exhausted(&self, elapsed: Duration, num_states: usize, steps: usize) -> Option<StopReason> {
+        if self.max_steps.is_some_and(|max_steps| steps >= max_steps) {
+            Some(StopReason::MaxSteps)
+        } else if self
+            .max_states
+            .is_some_and(|max_states| num_states >= max_states)
+        {
+            Some(StopReason::MaxStates)
+        } else if self
+            .max_duration
+            .is_some_and(|max_duration| elapsed >= max_duration)
+        {
+            Some(StopReason::MaxDuration)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    MaxDuration,
+    MaxStates,
+    MaxSteps,
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    fn partial_result(&self, reason: StopReason, start: Instant, steps: usize) -> PartialResult {
+        PartialResult {
+            reason,
+            steps,
+            time: self.time(),
+            num_states: self.num_known_states(),
+            elapsed: start.elapsed(),
+        }
+    }
+
+    fn check_budget(
+        &self,
+        budget: &Budget,
+        start: Instant,
+        steps: usize,
+    ) -> Result<(), PartialResult> {
+        match budget.exhausted(start.elapsed(), self.num_known_states(), steps) {
+            Some(reason) => Err(self.partial_result(reason, start, steps)),
+            None => Ok(()),
+        }
+    }
+
+    // A step which would exceed the maximal number of states is not taken at all
+    fn stop_within<R>(
+        &self,
+        result: Result<R, SimulationError>,
+        start: Instant,
+        steps: usize,
+    ) -> Result<R, PartialResult> {
+        result.map_err(|error| match error {
+            SimulationError::TooManyStates { .. } => {
+                self.partial_result(StopReason::MaxStates, start, steps)
+            }
+            error => panic!("{error}"),
+        })
+    }
+
+    // Steps until no new states are discovered, like `full_traversal(false)`
+    pub fn run_with_budget(&mut self, budget: &Budget) -> Result<Time, PartialResult> {
+        let start = Instant::now();
+        let mut steps = 0;
+        let mut num_known_states = 0;
+        while num_known_states != self.num_known_states() {
+            self.check_budget(budget, start, steps)?;
+            num_known_states = self.num_known_states();
+            let result = self.try_advance(budget.max_states());
+            self.stop_within(result, start, steps)?;
+            steps += 1;
+        }
+        Ok(self.time())
+    }
+
+    // Explores breadth first and returns the number of known states once everything is explored
+    pub fn explore_with_budget(&mut self, budget: &Budget) -> Result<usize, PartialResult> {
+        let start = Instant::now();
+        let mut steps = 0;
+        let mut frontier = self
+            .iter_known_states()
+            .filter(|state| self.cached_outgoing_transitions(state).is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        while !frontier.is_empty() {
+            self.check_budget(budget, start, steps)?;
+            let result = self.try_explore_frontier(frontier, budget.max_states());
+            frontier = self.stop_within(result, start, steps)?;
+            steps += 1;
+        }
+        Ok(self.num_known_states())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn budgets() {
+        let state_transition_generator =
+            Arc::new(|state: i32| vec![(state + 1, "next", 0.5), (state - 1, "previous", 0.5)]);
+        let mut simulation = Simulation::new(0, state_transition_generator.clone());
+        let partial = simulation
+            .run_with_budget(&Budget::unlimited().with_max_steps(3))
+            .unwrap_err();
+        assert_eq!(partial.reason(), StopReason::MaxSteps);
+        assert_eq!(partial.steps(), 3);
+        assert_eq!(partial.time(), 3);
+        assert_eq!(simulation.time(), 3);
+
+        let partial = simulation
+            .explore_with_budget(&Budget::unlimited().with_max_states(20))
+            .unwrap_err();
+        assert_eq!(partial.reason(), StopReason::MaxStates);
+        // The layer which would have grown the known states past the limit is not explored
+        assert_eq!(partial.num_states(), 19);
+        assert_eq!(simulation.num_known_states(), 19);
+        assert_eq!(partial.time(), 3);
+
+        let bounded_generator = Arc::new(|state: i32| {
+            vec![
+                ((state + 1).min(5), "next", 0.5),
+                ((state - 1).max(0), "previous", 0.5),
+            ]
+        });
+        let mut bounded = Simulation::new(0, bounded_generator.clone());
+        assert_eq!(bounded.explore_with_budget(&Budget::unlimited()), Ok(6));
+        let mut bounded = Simulation::new(0, bounded_generator);
+        assert_eq!(
+            bounded.run_with_budget(&Budget::unlimited().with_max_seconds(60.)),
+            Ok(6)
+        );
+    }
+}
@@ -1,12 +1,20 @@
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use hashbrown::HashMap;
+
 use rayon::prelude::*;
 
+use crate::prelude::*;
+
+pub type InsertObserver<I, O> = Arc<dyn Fn(&I, &O) + Send + Sync>;
+
 #[derive(Clone)]
 pub struct CachedFunction<I, O> {
-    cache: HashMap<I, O>,
+    // Outputs are shared with the callers of call_many_parallel, so a step never copies them
+    cache: SharedMap<I, Arc<O>>,
     function: Arc<dyn Fn(I) -> O + Send + Sync>,
+    shared_cache: Option<Arc<RwLock<HashMap<I, O>>>>,
+    insert_observer: Option<InsertObserver<I, O>>,
 }
 
 impl<I, O> CachedFunction<I, O>
@@ -16,49 +24,139 @@ where
 {
     pub fn new(function: Arc<dyn Fn(I) -> O + Send + Sync>) -> Self {
         Self {
-            cache: HashMap::new(),
+            cache: SharedMap::new(),
             function,
+            shared_cache: None,
+            insert_observer: None,
         }
     }
 
-    #[allow(dead_code)]
     pub fn call(&mut self, input: I) -> O {
         if let Some(output) = self.cache.get(&input) {
-            output.clone()
+            (**output).clone()
         } else {
-            let output = self.bypass(input.clone());
-            self.cache.insert(input, output.clone());
+            let output = self.compute(input.clone());
+            self.insert(input, output.clone());
             output
         }
     }
 
-    #[allow(dead_code)]
-    pub fn clear(&mut self) {
-        self.cache.clear();
+    pub fn retain(&mut self, mut keep: impl FnMut(&I, &O) -> bool) {
+        self.cache.retain(|input, output| keep(input, output));
+    }
+
+    pub fn set_function(&mut self, function: Arc<dyn Fn(I) -> O + Send + Sync>) {
+        // The shared cache belongs to the old function
+        self.shared_cache = None;
+        self.function = function;
+    }
+
+    pub fn fork(&self) -> Self {
+        Self {
+            cache: self.cache.fork(),
+            function: self.function.clone(),
+            shared_cache: self.shared_cache.clone(),
+            // Otherwise the fork would report its inserts to the same observer
+            insert_observer: None,
+        }
+    }
+
+    pub fn get(&self, input: &I) -> Option<&O> {
+        self.cache.get(input).map(Arc::as_ref)
+    }
+
+    pub fn insert(&mut self, input: I, output: O) {
+        self.insert_shared(input, Arc::new(output));
+    }
+
+    fn insert_shared(&mut self, input: I, output: Arc<O>) {
+        if let Some(insert_observer) = &self.insert_observer {
+            insert_observer(&input, &output);
+        }
+        self.cache.insert(input, output);
+    }
+
+    pub fn set_insert_observer(&mut self, insert_observer: Option<InsertObserver<I, O>>) {
+        self.insert_observer = insert_observer;
     }
 
     pub fn bypass(&self, input: I) -> O {
         (self.function)(input)
     }
 
-    #[allow(dead_code)]
-    pub fn call_many(&mut self, inputs: impl Iterator<Item = I>) -> Vec<O> {
-        inputs.map(|input| self.call(input)).collect()
+    pub fn set_shared_cache(&mut self, shared_cache: Arc<RwLock<HashMap<I, O>>>) {
+        self.shared_cache = Some(shared_cache);
     }
 
-    pub fn call_many_parallel(&mut self, inputs: impl IntoParallelIterator<Item = I>) -> Vec<O> {
+    // Like bypass, but consults and fills the cache shared with other instances first
+    pub fn compute(&self, input: I) -> O {
+        let Some(shared_cache) = &self.shared_cache else {
+            return self.bypass(input);
+        };
+        if let Some(output) = shared_cache.read().unwrap().get(&input) {
+            return output.clone();
+        }
+        let output = self.bypass(input.clone());
+        shared_cache.write().unwrap().insert(input, output.clone());
+        output
+    }
+
+    // Inputs are only cloned if they are not cached yet, cached outputs are shared instead of
+    // copied, so a step does not allocate anything for states it has seen before
+    pub fn call_many_parallel<'a>(
+        &mut self,
+        inputs: impl IntoParallelIterator<Item = &'a I>,
+    ) -> Vec<Arc<O>>
+    where
+        I: 'a,
+    {
         let pairs = inputs
             .into_par_iter()
-            .map(|input| (input.clone(), self.bypass(input)))
-            .collect::<Vec<(I, O)>>();
-        pairs.iter().for_each(|(input, output)| {
-            self.cache.insert(input.clone(), output.clone());
-        });
-        pairs.into_iter().map(|(_, output)| output).collect()
+            .map(|input| match self.cache.get(input) {
+                Some(output) => (None, output.clone()),
+                None => {
+                    let output = Arc::new(self.compute(input.clone()));
+                    (Some(input.clone()), output)
+                }
+            })
+            .collect::<Vec<(Option<I>, Arc<O>)>>();
+        pairs
+            .into_iter()
+            .map(|(input, output)| {
+                if let Some(input) = input {
+                    self.insert_shared(input, output.clone());
+                }
+                output
+            })
+            .collect()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
 
-    #[allow(dead_code)]
-    pub fn function(&self) -> Arc<dyn Fn(I) -> O + Send + Sync> {
-        self.function.clone()
+    #[test]
+    fn call_many_parallel_reuses_cache() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted_calls = calls.clone();
+        let mut function = CachedFunction::new(Arc::new(move |input: i32| {
+            counted_calls.fetch_add(1, Ordering::SeqCst);
+            input * 2
+        }));
+        let outputs = function.call_many_parallel(&[1, 2, 3]);
+        assert_eq!(outputs, vec![Arc::new(2), Arc::new(4), Arc::new(6)]);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        let outputs = function.call_many_parallel(&[2, 3, 4]);
+        assert_eq!(outputs, vec![Arc::new(4), Arc::new(6), Arc::new(8)]);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert_eq!(function.get(&4), Some(&8));
+        // Cached outputs are shared, not copied
+        assert!(Arc::ptr_eq(
+            &outputs[0],
+            &function.call_many_parallel(&[2])[0]
+        ));
     }
 }
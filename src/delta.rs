@@ -0,0 +1,248 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    hash::Hash,
+};
+
+use crate::models::entities::*;
+use crate::models::rules::*;
+use crate::prelude::*;
+
+pub trait Diff: Sized {
+    type Delta: Clone + Debug;
+
+    fn diff(&self, base: &Self) -> Self::Delta;
+
+    fn patch(base: &Self, delta: &Self::Delta) -> Self;
+}
+
+type Link = (RelationName, EntityName, EntityName);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDelta<T> {
+    entities: BTreeMap<EntityName, Option<StateEntity<T>>>,
+    added_links: BTreeSet<Link>,
+    removed_links: BTreeSet<Link>,
+}
+
+impl<T> StateDelta<T> {
+    pub fn changed_entities(&self) -> impl Iterator<Item = (&EntityName, Option<&StateEntity<T>>)> {
+        self.entities
+            .iter()
+            .map(|(name, entity)| (name, entity.as_ref()))
+    }
+
+    pub fn added_links(&self) -> impl Iterator<Item = &Link> {
+        self.added_links.iter()
+    }
+
+    pub fn removed_links(&self) -> impl Iterator<Item = &Link> {
+        self.removed_links.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty() && self.added_links.is_empty() && self.removed_links.is_empty()
+    }
+}
+
+fn owned_links<T>(state: &State<T>) -> BTreeSet<Link> {
+    state
+        .links()
+        .map(|(relation, source, target)| (relation.clone(), source.clone(), target.clone()))
+        .collect()
+}
+
+impl<T: Clone + PartialEq + Debug> Diff for State<T> {
+    type Delta = StateDelta<T>;
+
+    fn diff(&self, base: &Self) -> Self::Delta {
+        let mut entities = self
+            .entities()
+            .filter(|(name, entity)| base.entity(name) != Some(entity))
+            .map(|(name, entity)| (name.clone(), Some(entity.clone())))
+            .collect::<BTreeMap<_, _>>();
+        base.entities()
+            .filter(|(name, _)| self.entity(name).is_none())
+            .for_each(|(name, _)| {
+                entities.insert(name.clone(), None);
+            });
+        let (links, base_links) = (owned_links(self), owned_links(base));
+        StateDelta {
+            entities,
+            added_links: links.difference(&base_links).cloned().collect(),
+            removed_links: base_links.difference(&links).cloned().collect(),
+        }
+    }
+
+    fn patch(base: &Self, delta: &Self::Delta) -> Self {
+        let mut state = base.clone();
+        delta
+            .entities
+            .iter()
+            .for_each(|(name, entity)| match entity {
+                Some(entity) => state.insert_entity(name.clone(), entity.clone()),
+                None => {
+                    state.remove_entity(name);
+                }
+            });
+        delta
+            .removed_links
+            .iter()
+            .for_each(|(relation, source, target)| {
+                state.unlink(relation, source, target);
+            });
+        delta
+            .added_links
+            .iter()
+            .for_each(|(relation, source, target)| {
+                state.link(relation.clone(), source.clone(), target.clone())
+            });
+        state
+    }
+}
+
+// The outcome of applying a single rule to a state, see Simulation::what_if
+#[derive(Debug, Clone)]
+pub struct WhatIf<S: Diff> {
+    applies: RuleApplies,
+    successor: S,
+    weight: ProbabilityWeight,
+    probability: Probability,
+    delta: S::Delta,
+}
+
+impl<S: Diff> WhatIf<S> {
+    pub fn applies(&self) -> RuleApplies {
+        self.applies
+    }
+
+    // The successor is computed even if the condition of the rule does not hold
+    pub fn successor(&self) -> &S {
+        &self.successor
+    }
+
+    pub fn weight(&self) -> ProbabilityWeight {
+        self.weight
+    }
+
+    // The probability with which the rule fires in the state under the current rule options,
+    // zero if it does not apply
+    pub fn probability(&self) -> Probability {
+        self.probability
+    }
+
+    pub fn delta(&self) -> &S::Delta {
+        &self.delta
+    }
+}
+
+impl<S> Simulation<S, String>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + Diff,
+{
+    // A dry run of a rule, neither the cache nor the distribution are changed. None if there is no
+    // rule with this name.
+    pub fn what_if(&self, state: &S, rule_name: &str) -> Option<WhatIf<S>> {
+        let rules = self.rules()?;
+        let rule = rules.get(rule_name)?;
+        let applies = rule.applies(state.clone());
+        let successor = rule.apply(state.clone());
+        let probability = if applies {
+            let (probabilities, _) = rule_probabilities(rules, self.rule_options(), state);
+            probabilities
+                .into_iter()
+                .find(|(name, _)| name == rule_name)
+                .map_or(0., |(_, probability)| probability)
+        } else {
+            0.
+        };
+        Some(WhatIf {
+            applies,
+            delta: successor.diff(state),
+            successor,
+            weight: rule.weight(),
+            probability,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use hashbrown::HashMap;
+
+    use super::*;
+
+    fn counter_state(values: &[i32]) -> State<i32> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                (
+                    format!("counter{index}"),
+                    StateEntity::new().with_parameter("value", *value),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn diff_and_patch() {
+        let base = counter_state(&[0, 0, 0]).with_link("next", "counter0", "counter1");
+        let mut changed = counter_state(&[0, 1, 0]).with_link("next", "counter0", "counter1");
+        changed.remove_entity("counter2");
+
+        let delta = changed.diff(&base);
+        assert_eq!(delta.changed_entities().count(), 2);
+        assert_eq!(State::patch(&base, &delta), changed);
+        assert!(base.diff(&base).is_empty());
+
+        let mut relinked = base.clone().with_link("next", "counter1", "counter2");
+        relinked.unlink("next", "counter0", "counter1");
+        let delta = relinked.diff(&base);
+        assert_eq!(delta.added_links().count(), 1);
+        assert_eq!(delta.removed_links().count(), 1);
+        assert_eq!(State::patch(&base, &delta), relinked);
+    }
+
+    #[test]
+    fn what_if() {
+        let increment = |counter: &'static str| {
+            Rule::new(
+                format!("Increment {counter}"),
+                Arc::new(move |state: State<i32>| state.parameter(counter, "value") < Some(&1)),
+                0.25,
+                Arc::new(move |mut state: State<i32>| {
+                    let value = state.parameter(counter, "value").unwrap() + 1;
+                    state.set_parameter(counter, "value", value);
+                    state
+                }),
+            )
+        };
+        let rules = HashMap::from([
+            ("first".to_string(), increment("counter0")),
+            ("second".to_string(), increment("counter1")),
+        ]);
+        let initial_state = counter_state(&[0, 0]);
+        let mut simulation = Simulation::from_rules(initial_state.clone(), rules);
+        let what_if = simulation.what_if(&initial_state, "first").unwrap();
+        assert!(what_if.applies());
+        assert_eq!(what_if.successor(), &counter_state(&[1, 0]));
+        assert_eq!(what_if.weight(), 0.25);
+        // Nothing happens with a weight of (1 - 0.25)^2, as the rules are independent
+        assert!((what_if.probability() - 0.25 / (0.5 + 0.5625)).abs() < 1e-12);
+        assert_eq!(what_if.delta().changed_entities().count(), 1);
+        assert_eq!(simulation.known_states().len(), 1);
+
+        let blocked = simulation
+            .what_if(&counter_state(&[1, 0]), "first")
+            .unwrap();
+        assert!(!blocked.applies());
+        assert_eq!(blocked.probability(), 0.);
+        assert_eq!(blocked.successor(), &counter_state(&[2, 0]));
+        assert!(simulation.what_if(&initial_state, "third").is_none());
+        simulation.next_step();
+        assert_eq!(simulation.known_states().len(), 3);
+    }
+}
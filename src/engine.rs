@@ -0,0 +1,114 @@
+use std::{fmt::Debug, hash::Hash};
+
+use crate::prelude::*;
+
+// The common interface of the engines, so applications can hold a Box<dyn SimulationEngine<S>>
+// and swap the exact simulation for a sequential or sampled one
+pub trait SimulationEngine<S> {
+    fn time(&self) -> Time;
+
+    fn step(&mut self);
+
+    fn run(&mut self, steps: usize) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+
+    // The distribution at the current time
+    fn distribution(&self) -> StateProbabilityDistribution<S>;
+
+    fn entropy(&self) -> f64 {
+        distribution_entropy(&self.distribution())
+    }
+}
+
+impl<S, T> SimulationEngine<S> for Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    fn time(&self) -> Time {
+        Simulation::time(self)
+    }
+
+    fn step(&mut self) {
+        self.advance();
+    }
+
+    fn distribution(&self) -> StateProbabilityDistribution<S> {
+        self.probability_distribution(Simulation::time(self))
+    }
+
+    fn entropy(&self) -> f64 {
+        Simulation::entropy(self, Simulation::time(self))
+    }
+}
+
+impl<S, T> SimulationEngine<S> for SequentialSimulation<S, T>
+where
+    S: Hash + Eq + Clone,
+    T: Clone,
+{
+    fn time(&self) -> Time {
+        SequentialSimulation::time(self)
+    }
+
+    fn step(&mut self) {
+        self.next_step();
+    }
+
+    fn distribution(&self) -> StateProbabilityDistribution<S> {
+        SequentialSimulation::distribution(self).clone()
+    }
+}
+
+// Steps without observations only predict, so the particles approximate the distribution of
+// the simulation
+impl<S, T, O> SimulationEngine<S> for ParticleFilter<S, T, O>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    O: PartialEq + Debug,
+{
+    fn time(&self) -> Time {
+        ParticleFilter::time(self)
+    }
+
+    fn step(&mut self) {
+        self.predict();
+    }
+
+    fn distribution(&self) -> StateProbabilityDistribution<S> {
+        self.belief()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn engines() {
+        let state_transition_generator =
+            Arc::new(|state: u8| vec![((state + 1) % 4, "next", 0.5), (state, "stay", 0.5)]);
+        let simulation = Simulation::new(0, state_transition_generator.clone());
+        let emission: Emission<u8, ()> = Arc::new(|_| vec![((), 1.)]);
+        let mut engines: Vec<Box<dyn SimulationEngine<u8>>> = vec![
+            Box::new(simulation.clone()),
+            Box::new(SequentialSimulation::new(0, state_transition_generator)),
+            Box::new(ParticleFilter::new(simulation, emission, 2000, 3)),
+        ];
+        engines.iter_mut().for_each(|engine| engine.run(3));
+        assert!(engines.iter().all(|engine| engine.time() == 3));
+        let exact = engines[0].distribution();
+        assert_eq!(engines[1].distribution(), exact);
+        assert_eq!(engines[0].entropy(), engines[1].entropy());
+        let sampled = engines[2].distribution();
+        assert!(exact
+            .iter()
+            .all(|(state, probability)| (sampled[state] - probability).abs() < 0.05));
+    }
+}
@@ -0,0 +1,215 @@
+use std::{fmt::Debug, hash::Hash};
+
+use crate::prelude::*;
+use crate::random::*;
+
+// One sampled path through the chain. It ends early if the mass was killed in a sub-stochastic
+// model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trajectory<S, T> {
+    states: Vec<S>,
+    transitions: Vec<T>,
+}
+
+impl<S, T> Trajectory<S, T> {
+    pub fn states(&self) -> &Vec<S> {
+        &self.states
+    }
+
+    pub fn transitions(&self) -> &Vec<T> {
+        &self.transitions
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+}
+
+// Independent trajectories sampled from the initial distribution
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ensemble<S, T> {
+    trajectories: Vec<Trajectory<S, T>>,
+}
+
+impl<S, T> Ensemble<S, T> {
+    pub fn trajectories(&self) -> &Vec<Trajectory<S, T>> {
+        &self.trajectories
+    }
+
+    pub fn samples(&self) -> usize {
+        self.trajectories.iter().map(Trajectory::len).sum()
+    }
+
+    // Autocorrelation of the observable for the lags 0 to max_lag, with the mean and variance
+    // pooled over all trajectories. None if the observable is constant.
+    pub fn autocorrelation(
+        &self,
+        observable: impl Fn(&S) -> f64,
+        max_lag: usize,
+    ) -> Option<Vec<f64>> {
+        let series = self
+            .trajectories
+            .iter()
+            .map(|trajectory| {
+                trajectory
+                    .states
+                    .iter()
+                    .map(&observable)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let samples = series.iter().map(Vec::len).sum::<usize>() as f64;
+        let mean = series.iter().flatten().sum::<f64>() / samples;
+        let variance = series
+            .iter()
+            .flatten()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / samples;
+        if variance.is_nan() || variance <= 0. {
+            return None;
+        }
+        Some(
+            (0..=max_lag)
+                .map_while(|lag| {
+                    let (mut sum, mut pairs) = (0., 0);
+                    series
+                        .iter()
+                        .filter(|values| values.len() > lag)
+                        .for_each(|values| {
+                            values
+                                .iter()
+                                .zip(&values[lag..])
+                                .for_each(|(first, second)| {
+                                    sum += (first - mean) * (second - mean);
+                                    pairs += 1;
+                                });
+                        });
+                    (pairs > 0).then(|| sum / pairs as f64 / variance)
+                })
+                .collect(),
+        )
+    }
+
+    // The number of independent samples with the same variance of the mean. The integrated
+    // autocorrelation time is summed up to the first lag without positive correlation.
+    pub fn effective_sample_size(&self, observable: impl Fn(&S) -> f64) -> f64 {
+        let samples = self.samples() as f64;
+        let max_lag = self
+            .trajectories
+            .iter()
+            .map(Trajectory::len)
+            .max()
+            .unwrap_or(0)
+            .saturating_sub(1);
+        let Some(autocorrelation) = self.autocorrelation(observable, max_lag) else {
+            return samples;
+        };
+        let correlation_time = 1.
+            + 2. * autocorrelation
+                .iter()
+                .skip(1)
+                .take_while(|correlation| **correlation > 0.)
+                .sum::<f64>();
+        samples / correlation_time
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Transitions of visited states are generated and cached like during propagation
+    pub fn sample_ensemble(&mut self, count: usize, steps: usize, seed: u64) -> Ensemble<S, T> {
+        let mut rng = SeededRng::new(seed);
+        let initial_distribution = self
+            .iter_probability_distribution(0)
+            .expect("No probability distribution found for given time")
+            .map(|(state, probability)| (state.clone(), probability))
+            .collect::<Vec<_>>();
+        let trajectories = (0..count)
+            .filter_map(|_| {
+                let initial = rng
+                    .choose(
+                        initial_distribution
+                            .iter()
+                            .map(|(_, probability)| *probability),
+                    )
+                    .or(initial_distribution.len().checked_sub(1))?;
+                let mut state = initial_distribution[initial].0.clone();
+                let mut trajectory = Trajectory {
+                    states: vec![state.clone()],
+                    transitions: Vec::new(),
+                };
+                for _ in 0..steps {
+                    let Some((next_state, transition)) = self.sample_transition(&state, &mut rng)
+                    else {
+                        break;
+                    };
+                    trajectory.states.push(next_state.clone());
+                    trajectory.transitions.push(transition);
+                    state = next_state;
+                }
+                Some(trajectory)
+            })
+            .collect();
+        Ensemble { trajectories }
+    }
+
+    pub(crate) fn sample_transition(&mut self, state: &S, rng: &mut SeededRng) -> Option<(S, T)> {
+        if self.cached_outgoing_transitions(state).is_none() {
+            self.explore_frontier(vec![state.clone()]);
+        }
+        let transitions = self.cached_outgoing_transitions(state).unwrap();
+        let index = rng
+            .choose(transitions.iter().map(|(_, _, probability)| *probability))
+            .or_else(|| {
+                (!self.is_sub_stochastic())
+                    .then(|| transitions.len().checked_sub(1))
+                    .flatten()
+            })?;
+        let (next_state, transition, _) = &transitions[index];
+        Some((next_state.clone(), transition.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use hashbrown::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn autocorrelation() {
+        // A sticky coin started in its stationary distribution, its autocorrelation is 0.8^lag
+        let state_transition_generator =
+            Arc::new(|heads: bool| vec![(heads, "stay", 0.9), (!heads, "flip", 0.1)]);
+        let mut simulation = Simulation::new_with_distribution(
+            HashMap::from([(false, 0.5), (true, 0.5)]),
+            state_transition_generator,
+        );
+        let ensemble = simulation.sample_ensemble(200, 199, 42);
+        assert_eq!(ensemble.trajectories().len(), 200);
+        assert_eq!(ensemble.samples(), 40_000);
+        assert_eq!(ensemble, simulation.sample_ensemble(200, 199, 42));
+
+        let observable = |heads: &bool| if *heads { 1. } else { 0. };
+        let autocorrelation = ensemble.autocorrelation(observable, 3).unwrap();
+        assert_eq!(autocorrelation[0], 1.);
+        assert!((autocorrelation[1] - 0.8).abs() < 0.05);
+        assert!((autocorrelation[3] - 0.512).abs() < 0.05);
+        // The correlation time is (1 + 0.8) / (1 - 0.8) = 9
+        let effective_sample_size = ensemble.effective_sample_size(observable);
+        assert!((3000. ..6000.).contains(&effective_sample_size));
+
+        assert_eq!(ensemble.autocorrelation(|_| 1., 3), None);
+        assert_eq!(ensemble.effective_sample_size(|_| 1.), 40_000.);
+    }
+}
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::models::{entities::*, schema::ParameterType, units::Unit};
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SimulationError {
+    #[error("No probability distribution found for time {time}")]
+    NoProbabilityDistribution { time: Time },
+    #[error("No state with hash {state_hash} is known")]
+    UnknownState { state_hash: u64 },
+    #[error("No transition with hash {transition_hash} is known")]
+    UnknownTransition { transition_hash: u64 },
+    #[error("State with hash {state_hash} is invalid: {error}")]
+    InvalidState { state_hash: u64, error: SchemaError },
+    #[error("Invalid probability of a transition from the state with hash {state_hash}: {error}")]
+    InvalidProbability {
+        state_hash: u64,
+        error: ProbabilityError,
+    },
+    #[error(
+        "Probabilities of the transitions from the state with hash {state_hash} sum up to {sum}"
+    )]
+    ProbabilitySum { state_hash: u64, sum: Probability },
+    #[error("No rule applies to the state with hash {state_hash}")]
+    NoRuleApplies { state_hash: u64 },
+    #[error("Simulation was not created from rules")]
+    NoRules,
+    #[error(
+        "Step would grow the known states to {num_states}, more than the limit of {max_states}"
+    )]
+    TooManyStates {
+        num_states: usize,
+        max_states: usize,
+    },
+}
+
+// Everything computed before the budget ran out stays in the simulation, the partial result only
+// describes how far the computation got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Budget exhausted ({reason:?}) after {steps} steps with {num_states} known states")]
+pub struct PartialResult {
+    pub(crate) reason: StopReason,
+    pub(crate) steps: usize,
+    pub(crate) time: Time,
+    pub(crate) num_states: usize,
+    pub(crate) elapsed: Duration,
+}
+
+impl PartialResult {
+    pub fn reason(&self) -> StopReason {
+        self.reason
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn num_states(&self) -> usize {
+        self.num_states
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ModelImportError {
+    #[error("Model bundle version {version} is not supported")]
+    UnsupportedVersion { version: u32 },
+    #[error("Model bundle is malformed: {0}")]
+    Malformed(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Error)]
+pub enum PrismExportError {
+    #[error("State with hash {state_hash} is not explored")]
+    UnexploredState { state_hash: u64 },
+    #[error("Probabilities of the transitions from the state with hash {state_hash} sum up to {sum} instead of 1")]
+    ProbabilitySum { state_hash: u64, sum: Probability },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Error)]
+pub enum ProbabilityError {
+    #[error("Probability is NaN")]
+    NotANumber,
+    #[error("Probability {value} is negative")]
+    Negative { value: f64 },
+    #[error("Probability {value} is out of range")]
+    OutOfRange { value: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SchemaError {
+    #[error("Entity {entity} does not exist")]
+    UnknownEntity { entity: EntityName },
+    #[error("Entity {entity} has no class")]
+    UnclassifiedEntity { entity: EntityName },
+    #[error("Entity {entity} is of unknown class {class}")]
+    UnknownClass {
+        entity: EntityName,
+        class: ClassName,
+    },
+    #[error("Entity {entity} has undeclared parameter {parameter}")]
+    UnknownParameter {
+        entity: EntityName,
+        parameter: ParameterName,
+    },
+    #[error("Entity {entity} is missing required parameter {parameter}")]
+    MissingParameter {
+        entity: EntityName,
+        parameter: ParameterName,
+    },
+    #[error("Parameter {parameter} of entity {entity} should be {expected:?} but is {value}")]
+    TypeMismatch {
+        entity: EntityName,
+        parameter: ParameterName,
+        expected: ParameterType,
+        value: f64,
+    },
+    #[error("Parameter {parameter} of entity {entity} is out of bounds: {value} not in [{minimum:?}, {maximum:?}]")]
+    OutOfBounds {
+        entity: EntityName,
+        parameter: ParameterName,
+        value: f64,
+        minimum: Option<f64>,
+        maximum: Option<f64>,
+    },
+    #[error("Incompatible units: expected {expected} but found {found}")]
+    UnitMismatch { expected: Unit, found: Unit },
+    #[error("Parameter {parameter} of the selected entity is used outside of an assignment to selected entities")]
+    NoSelectedEntity { parameter: ParameterName },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PersistenceError {
+    #[error("I/O error: {0}")]
+    Io(String),
+    #[error("Checkpoint is malformed: {0}")]
+    Malformed(String),
+    #[error(transparent)]
+    Import(#[from] ModelImportError),
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for PersistenceError {
+    fn from(error: serde_json::Error) -> Self {
+        PersistenceError::Malformed(error.to_string())
+    }
+}
+
+impl From<std::io::Error> for PersistenceError {
+    fn from(error: std::io::Error) -> Self {
+        PersistenceError::Io(error.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RemoteError {
+    #[error("Invalid endpoint {0}")]
+    InvalidEndpoint(String),
+    #[error("Connection failed: {0}")]
+    Connection(String),
+    #[error("Unexpected response: {0}")]
+    Protocol(String),
+    #[error("Response is malformed: {0}")]
+    Malformed(String),
+}
+
+impl From<std::io::Error> for RemoteError {
+    fn from(error: std::io::Error) -> Self {
+        RemoteError::Connection(error.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum FilterError {
+    #[error("Observation {observation} is impossible at time {time}")]
+    ImpossibleObservation { observation: String, time: Time },
+}
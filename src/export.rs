@@ -0,0 +1,6 @@
+pub mod bundle;
+pub mod catalog;
+pub mod graph;
+pub mod joint_table;
+pub mod prism;
+pub mod sankey;
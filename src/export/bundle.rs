@@ -0,0 +1,191 @@
+use std::{fmt::Debug, hash::Hash};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::models::rules::*;
+use crate::models::schema::*;
+use crate::prelude::*;
+
+pub const MODEL_BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleSummary {
+    name: RuleName,
+    description: String,
+    weight: ProbabilityWeight,
+}
+
+impl RuleSummary {
+    pub fn name(&self) -> &RuleName {
+        &self.name
+    }
+
+    pub fn description(&self) -> &String {
+        &self.description
+    }
+
+    pub fn weight(&self) -> ProbabilityWeight {
+        self.weight
+    }
+}
+
+// Rules are closures and can't be stored, so only their names, descriptions and weights are
+// exported. Importing a bundle requires the state transition generator of the same model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelBundle<S, T> {
+    version: u32,
+    schema: Option<Schema>,
+    rules: Vec<RuleSummary>,
+    initial_distribution: Vec<(S, Probability)>,
+    transitions: Option<Vec<(S, OutgoingTransitions<S, T>)>>,
+}
+
+impl<S, T> ModelBundle<S, T>
+where
+    S: Serialize + DeserializeOwned,
+    T: Serialize + DeserializeOwned,
+{
+    pub fn with_schema(mut self, schema: Schema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn schema(&self) -> Option<&Schema> {
+        self.schema.as_ref()
+    }
+
+    pub fn rules(&self) -> &Vec<RuleSummary> {
+        &self.rules
+    }
+
+    pub fn initial_distribution(&self) -> &Vec<(S, Probability)> {
+        &self.initial_distribution
+    }
+
+    pub fn has_transitions(&self) -> bool {
+        self.transitions.is_some()
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<Self, ModelImportError> {
+        let bundle = serde_json::from_str::<Self>(json)
+            .map_err(|error| ModelImportError::Malformed(error.to_string()))?;
+        if bundle.version > MODEL_BUNDLE_VERSION {
+            return Err(ModelImportError::UnsupportedVersion {
+                version: bundle.version,
+            });
+        }
+        Ok(bundle)
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + Serialize + DeserializeOwned,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + Serialize + DeserializeOwned,
+{
+    pub fn export_model(&self, include_transitions: bool) -> ModelBundle<S, T> {
+        let mut rules = self
+            .rules()
+            .map(|rules| {
+                rules
+                    .iter()
+                    .map(|(name, rule)| RuleSummary {
+                        name: name.clone(),
+                        description: rule.description().clone(),
+                        weight: rule.weight(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        rules.sort_by(|a, b| a.name.cmp(&b.name));
+        let transitions = include_transitions.then(|| {
+            self.states_in_graph_order()
+                .into_iter()
+                .filter_map(|state| {
+                    self.cached_outgoing_transitions(state)
+                        .map(|transitions| (state.clone(), transitions.clone()))
+                })
+                .collect()
+        });
+        ModelBundle {
+            version: MODEL_BUNDLE_VERSION,
+            schema: None,
+            rules,
+            initial_distribution: self.initial_distribution().into_iter().collect(),
+            transitions,
+        }
+    }
+
+    pub fn import_model(
+        bundle: ModelBundle<S, T>,
+        state_transition_generator: StateTransitionGenerator<S, T>,
+    ) -> Self {
+        let mut simulation = Simulation::new_with_distribution(
+            bundle.initial_distribution.into_iter().collect(),
+            state_transition_generator,
+        );
+        bundle
+            .transitions
+            .into_iter()
+            .flatten()
+            .for_each(|(state, transitions)| simulation.insert_transitions(state, transitions));
+        simulation
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use std::sync::Arc;
+
+    use hashbrown::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let forward_rule: Rule<i32> = Rule::new(
+            "Forward".to_string(),
+            Arc::new(|state| state < 3),
+            0.5,
+            Arc::new(|state| state + 1),
+        );
+        let rules = HashMap::from([("forward".to_string(), forward_rule)]);
+        let mut simulation = Simulation::from_rules(0, rules.clone());
+        simulation.explore();
+
+        let bundle = simulation
+            .export_model(true)
+            .with_schema(Schema::new().with_class("counter", ClassSchema::new()));
+        let json = bundle.to_json().unwrap();
+        let imported_bundle = ModelBundle::<i32, String>::from_json(&json).unwrap();
+        assert_eq!(imported_bundle, bundle);
+        assert_eq!(imported_bundle.rules()[0].weight(), 0.5);
+        assert!(imported_bundle.schema().unwrap().class("counter").is_some());
+
+        let imported =
+            Simulation::import_model(imported_bundle, get_state_transition_generator(rules));
+        assert_eq!(imported.known_states().len(), 4);
+        assert!(imported.cached_outgoing_transitions(&3).is_some());
+        assert_eq!(
+            imported.initial_distribution(),
+            simulation.initial_distribution()
+        );
+
+        let future = json.replacen("\"version\":1", "\"version\":99", 1);
+        assert_eq!(
+            ModelBundle::<i32, String>::from_json(&future),
+            Err(ModelImportError::UnsupportedVersion { version: 99 })
+        );
+        assert!(ModelBundle::<i32, String>::from_json("{").is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use std::{fmt::Debug, fmt::Write, hash::Hash};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::rules::*;
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleDescription {
+    name: RuleName,
+    description: String,
+    weight: ProbabilityWeight,
+    batched: bool,
+    metadata: RuleMetadata,
+    access: Option<RuleAccess>,
+}
+
+impl RuleDescription {
+    pub fn name(&self) -> &RuleName {
+        &self.name
+    }
+
+    pub fn description(&self) -> &String {
+        &self.description
+    }
+
+    pub fn weight(&self) -> ProbabilityWeight {
+        self.weight
+    }
+
+    pub fn is_batched(&self) -> bool {
+        self.batched
+    }
+
+    pub fn metadata(&self) -> &RuleMetadata {
+        &self.metadata
+    }
+
+    // None if the rule does not declare which parameters it reads and writes
+    pub fn access(&self) -> Option<&RuleAccess> {
+        self.access.as_ref()
+    }
+}
+
+// A machine readable description of all rules of a model, sorted by name
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleCatalog {
+    rules: Vec<RuleDescription>,
+}
+
+impl RuleCatalog {
+    pub fn rules(&self) -> &Vec<RuleDescription> {
+        &self.rules
+    }
+
+    pub fn rule(&self, rule_name: &str) -> Option<&RuleDescription> {
+        self.rules.iter().find(|rule| rule.name == rule_name)
+    }
+
+    pub fn tagged(&self, tag: &str) -> Vec<&RuleDescription> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.metadata.has_tag(tag))
+            .collect()
+    }
+
+    // Rules which write the given parameter of the given entity selector
+    pub fn writers_of(&self, selector: &str, parameter: &str) -> Vec<&RuleDescription> {
+        self.rules
+            .iter()
+            .filter(|rule| {
+                rule.access.as_ref().is_some_and(|access| {
+                    access.writes().iter().any(|(written, written_parameter)| {
+                        written == selector && written_parameter == parameter
+                    })
+                })
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+        let list = |parameters: &Vec<AccessedParameter>| {
+            parameters
+                .iter()
+                .map(|(selector, parameter)| format!("`{selector}.{parameter}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        for rule in &self.rules {
+            writeln!(markdown, "## {}\n\n{}\n", rule.name, rule.description).unwrap();
+            writeln!(markdown, "- Weight: {}", rule.weight).unwrap();
+            if let Some(author) = rule.metadata.author() {
+                writeln!(markdown, "- Author: {author}").unwrap();
+            }
+            if !rule.metadata.tags().is_empty() {
+                writeln!(markdown, "- Tags: {}", rule.metadata.tags().join(", ")).unwrap();
+            }
+            if let Some(access) = &rule.access {
+                writeln!(markdown, "- Reads: {}", list(access.reads())).unwrap();
+                writeln!(markdown, "- Writes: {}", list(access.writes())).unwrap();
+            }
+            for reference in rule.metadata.references() {
+                writeln!(markdown, "- Reference: {reference}").unwrap();
+            }
+            if let Some(rationale) = rule.metadata.rationale() {
+                writeln!(markdown, "\n{rationale}").unwrap();
+            }
+            markdown.push('\n');
+        }
+        markdown
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Empty if the simulation was not created from rules
+    pub fn describe(&self) -> RuleCatalog {
+        let mut rules = self
+            .rules()
+            .map(|rules| {
+                rules
+                    .iter()
+                    .map(|(name, rule)| RuleDescription {
+                        name: name.clone(),
+                        description: rule.description().clone(),
+                        weight: rule.weight(),
+                        batched: rule.condition().is_batched(),
+                        metadata: rule.metadata().clone(),
+                        access: rule.access().cloned(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        rules.sort_by(|a, b| a.name.cmp(&b.name));
+        RuleCatalog { rules }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use hashbrown::HashMap;
+
+    use crate::models::conditions::*;
+    use crate::models::declarative::*;
+    use crate::models::entities::*;
+    use crate::models::units::*;
+
+    use super::*;
+
+    #[test]
+    fn rule_catalog() {
+        let state = State::new()
+            .with_entity(
+                "oak",
+                StateEntity::of_class("Tree").with_parameter("fire", 0),
+            )
+            .with_entity(
+                "pine",
+                StateEntity::of_class("Tree").with_parameter("heat", 1),
+            );
+        let ignite = DeclarativeRule::new("A tree catches fire", 0.1)
+            .with_condition(AggregateCondition::new(
+                Aggregate::Max,
+                EntitySelector::Class("Tree".to_string()),
+                "fire".to_string(),
+                Comparison::Equal,
+                0.,
+            ))
+            .with_assignment(Assignment::increment(
+                "oak",
+                "fire",
+                Expression::parameter("pine", "heat"),
+            ))
+            .to_rule(&state)
+            .unwrap()
+            .with_metadata(
+                RuleMetadata::new()
+                    .with_author("Forestry team")
+                    .with_tag("fire")
+                    .with_reference("Rothermel 1972")
+                    .with_rationale("Lightning strikes are rare"),
+            );
+        let grow = Rule::new(
+            "The oak grows".to_string(),
+            Arc::new(|_| true),
+            0.5,
+            Arc::new(|mut state: State<i32>| {
+                let height = state.parameter("oak", "height").copied().unwrap_or(0);
+                state.set_parameter("oak", "height", height + 1);
+                state
+            }),
+        );
+        let simulation = Simulation::from_rules(
+            state,
+            HashMap::from([("ignite".to_string(), ignite), ("grow".to_string(), grow)]),
+        );
+        let catalog = simulation.describe();
+        assert_eq!(
+            catalog
+                .rules()
+                .iter()
+                .map(|rule| rule.name().as_str())
+                .collect::<Vec<_>>(),
+            vec!["grow", "ignite"]
+        );
+        assert_eq!(catalog.rule("grow").unwrap().access(), None);
+        let ignite = catalog.rule("ignite").unwrap();
+        assert_eq!(
+            ignite.access().unwrap().reads(),
+            &vec![
+                ("class Tree".to_string(), "fire".to_string()),
+                ("oak".to_string(), "fire".to_string()),
+                ("pine".to_string(), "heat".to_string()),
+            ]
+        );
+        assert_eq!(
+            ignite.metadata().author(),
+            Some(&"Forestry team".to_string())
+        );
+        assert_eq!(catalog.tagged("fire"), vec![ignite]);
+        assert_eq!(catalog.writers_of("oak", "fire"), vec![ignite]);
+        assert!(catalog.to_markdown().contains("- Writes: `oak.fire`"));
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            serde_json::from_str::<RuleCatalog>(&catalog.to_json().unwrap()).unwrap(),
+            catalog
+        );
+    }
+}
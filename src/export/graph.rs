@@ -0,0 +1,308 @@
+use std::{fmt::Debug, fmt::Write, hash::Hash};
+
+use hashbrown::HashMap;
+
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum NodeColoring {
+    #[default]
+    Uniform,
+    // From white to red, relative to the most probable state
+    Probability,
+    // The color of the first label in the list which the state has
+    Labels(Vec<(LabelName, String)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphStyle {
+    time: Time,
+    node_coloring: NodeColoring,
+    default_color: String,
+    sized_nodes: bool,
+    weighted_edges: bool,
+}
+
+impl Default for GraphStyle {
+    fn default() -> Self {
+        Self {
+            time: 0,
+            node_coloring: NodeColoring::default(),
+            default_color: "#ffffff".to_string(),
+            sized_nodes: false,
+            weighted_edges: false,
+        }
+    }
+}
+
+impl GraphStyle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The time of the probability distribution used for colors and sizes
+    pub fn with_time(mut self, time: Time) -> Self {
+        self.time = time;
+        self
+    }
+
+    pub fn with_node_coloring(mut self, node_coloring: NodeColoring) -> Self {
+        self.node_coloring = node_coloring;
+        self
+    }
+
+    pub fn with_default_color(mut self, color: impl Into<String>) -> Self {
+        self.default_color = color.into();
+        self
+    }
+
+    // Node sizes grow with the square root of the probability, so the area is proportional
+    pub fn with_sized_nodes(mut self) -> Self {
+        self.sized_nodes = true;
+        self
+    }
+
+    // Edge widths grow with the transition probability
+    pub fn with_weighted_edges(mut self) -> Self {
+        self.weighted_edges = true;
+        self
+    }
+
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn node_coloring(&self) -> &NodeColoring {
+        &self.node_coloring
+    }
+}
+
+struct StyledNode {
+    name: String,
+    probability: Probability,
+    color: String,
+    size: f64,
+}
+
+struct StyledEdge {
+    source: usize,
+    target: usize,
+    transition: String,
+    probability: Probability,
+    width: f64,
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn probability_color(relative: f64) -> String {
+    let fade = (255. * (1. - relative.clamp(0., 1.))).round() as u8;
+    format!("#ff{fade:02x}{fade:02x}")
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    fn styled_graph(&self, style: &GraphStyle) -> (Vec<StyledNode>, Vec<StyledEdge>) {
+        let states = self.states_in_graph_order();
+        let probabilities = states
+            .iter()
+            .map(|state| self.probability_of(state, style.time))
+            .collect::<Vec<_>>();
+        let max_probability = probabilities.iter().copied().fold(0., f64::max);
+        let relative = |probability: Probability| {
+            if max_probability > 0. {
+                probability / max_probability
+            } else {
+                0.
+            }
+        };
+        let nodes = states
+            .iter()
+            .zip(&probabilities)
+            .map(|(state, probability)| {
+                let color = match &style.node_coloring {
+                    NodeColoring::Uniform => style.default_color.clone(),
+                    NodeColoring::Probability => probability_color(relative(*probability)),
+                    NodeColoring::Labels(colors) => colors
+                        .iter()
+                        .find(|(label, _)| self.has_label(state, label))
+                        .map(|(_, color)| color.clone())
+                        .unwrap_or_else(|| style.default_color.clone()),
+                };
+                let size = if style.sized_nodes {
+                    0.3 + 1.2 * relative(*probability).sqrt()
+                } else {
+                    0.75
+                };
+                StyledNode {
+                    name: format!("{state:?}"),
+                    probability: *probability,
+                    color,
+                    size,
+                }
+            })
+            .collect::<Vec<_>>();
+        let indices = states
+            .iter()
+            .enumerate()
+            .map(|(index, state)| (hash(*state), index))
+            .collect::<HashMap<_, _>>();
+        let edges = states
+            .iter()
+            .enumerate()
+            .flat_map(|(source, state)| {
+                self.cached_outgoing_transitions(state)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|(next_state, transition, probability)| {
+                        Some(StyledEdge {
+                            source,
+                            target: *indices.get(&hash(next_state))?,
+                            transition: format!("{transition:?}"),
+                            probability: *probability,
+                            width: if style.weighted_edges {
+                                1. + 4. * probability
+                            } else {
+                                1.
+                            },
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        (nodes, edges)
+    }
+
+    pub fn to_dot(&self, style: &GraphStyle) -> String {
+        let (nodes, edges) = self.styled_graph(style);
+        let mut dot = String::from("digraph states {\n    node [style=filled];\n");
+        nodes.iter().enumerate().for_each(|(index, node)| {
+            writeln!(
+                dot,
+                "    {index} [label=\"{}\\n{:.4}\", fillcolor=\"{}\", width={:.3}, height={:.3}];",
+                escape_dot(&node.name),
+                node.probability,
+                escape_dot(&node.color),
+                node.size,
+                node.size
+            )
+            .unwrap();
+        });
+        edges.iter().for_each(|edge| {
+            writeln!(
+                dot,
+                "    {} -> {} [label=\"{} ({:.4})\", penwidth={:.3}];",
+                edge.source,
+                edge.target,
+                escape_dot(&edge.transition),
+                edge.probability,
+                edge.width
+            )
+            .unwrap();
+        });
+        dot.push('}');
+        dot
+    }
+
+    pub fn to_graphml(&self, style: &GraphStyle) -> String {
+        let (nodes, edges) = self.styled_graph(style);
+        let mut graphml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"name\" for=\"node\" attr.name=\"name\" attr.type=\"string\"/>\n",
+            "  <key id=\"probability\" for=\"node\" attr.name=\"probability\" attr.type=\"double\"/>\n",
+            "  <key id=\"color\" for=\"node\" attr.name=\"color\" attr.type=\"string\"/>\n",
+            "  <key id=\"size\" for=\"node\" attr.name=\"size\" attr.type=\"double\"/>\n",
+            "  <key id=\"transition\" for=\"edge\" attr.name=\"transition\" attr.type=\"string\"/>\n",
+            "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"double\"/>\n",
+            "  <key id=\"width\" for=\"edge\" attr.name=\"width\" attr.type=\"double\"/>\n",
+            "  <graph id=\"states\" edgedefault=\"directed\">\n",
+        ));
+        nodes.iter().enumerate().for_each(|(index, node)| {
+            writeln!(
+                graphml,
+                concat!(
+                    "    <node id=\"n{}\"><data key=\"name\">{}</data>",
+                    "<data key=\"probability\">{}</data><data key=\"color\">{}</data>",
+                    "<data key=\"size\">{}</data></node>"
+                ),
+                index,
+                escape_xml(&node.name),
+                node.probability,
+                escape_xml(&node.color),
+                node.size
+            )
+            .unwrap();
+        });
+        edges.iter().for_each(|edge| {
+            writeln!(
+                graphml,
+                concat!(
+                    "    <edge source=\"n{}\" target=\"n{}\"><data key=\"transition\">{}</data>",
+                    "<data key=\"weight\">{}</data><data key=\"width\">{}</data></edge>"
+                ),
+                edge.source,
+                edge.target,
+                escape_xml(&edge.transition),
+                edge.probability,
+                edge.width
+            )
+            .unwrap();
+        });
+        graphml.push_str("  </graph>\n</graphml>\n");
+        graphml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn styled_export() {
+        let state_transition_generator = Arc::new(|state: u8| {
+            vec![
+                (state.saturating_add(1).min(2), "up", 0.75),
+                (0, "reset", 0.25),
+            ]
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.next_step();
+        simulation.next_step();
+        simulation.label("top", |state: &u8| *state == 2);
+
+        let dot = simulation.to_dot(
+            &GraphStyle::new()
+                .with_time(1)
+                .with_node_coloring(NodeColoring::Probability)
+                .with_sized_nodes()
+                .with_weighted_edges(),
+        );
+        assert!(dot.starts_with("digraph states {"));
+        assert!(dot.contains("0 [label=\"0\\n0.2500\", fillcolor=\"#ffaaaa\", width=0.993"));
+        assert!(dot.contains("1 [label=\"1\\n0.7500\", fillcolor=\"#ff0000\", width=1.500"));
+        assert!(dot.contains("0 -> 1 [label=\"\\\"up\\\" (0.7500)\", penwidth=4.000];"));
+
+        let graphml = simulation.to_graphml(&GraphStyle::new().with_node_coloring(
+            NodeColoring::Labels(vec![("top".to_string(), "gold".to_string())]),
+        ));
+        assert!(graphml.contains("<data key=\"name\">2</data><data key=\"probability\">0</data><data key=\"color\">gold</data>"));
+        assert!(graphml.contains("<data key=\"name\">0</data><data key=\"probability\">1</data><data key=\"color\">#ffffff</data>"));
+        assert!(graphml.contains(
+            "<data key=\"transition\">&quot;reset&quot;</data><data key=\"weight\">0.25</data>"
+        ));
+    }
+}
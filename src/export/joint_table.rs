@@ -0,0 +1,144 @@
+use std::{fmt::Debug, fmt::Write, hash::Hash};
+
+use hashbrown::HashMap;
+use serde::Serialize;
+
+use crate::models::entities::*;
+use crate::prelude::*;
+
+pub type JointTableColumn = (EntityName, ParameterName);
+
+// The joint distribution of a few parameters with all other parameters marginalized out. Values
+// are None where the entity or the parameter does not exist. Rows are sorted by descending
+// probability.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JointTable<V> {
+    columns: Vec<JointTableColumn>,
+    rows: Vec<(Vec<Option<V>>, Probability)>,
+}
+
+impl<V> JointTable<V>
+where
+    V: Serialize + Debug + PartialEq,
+{
+    pub fn columns(&self) -> &Vec<JointTableColumn> {
+        &self.columns
+    }
+
+    pub fn rows(&self) -> &Vec<(Vec<Option<V>>, Probability)> {
+        &self.rows
+    }
+
+    pub fn probability_of(&self, values: &[Option<V>]) -> Probability {
+        self.rows
+            .iter()
+            .find(|(row, _)| row.as_slice() == values)
+            .map(|(_, probability)| *probability)
+            .unwrap_or(0.)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = self
+            .columns
+            .iter()
+            .map(|(entity, parameter)| format!("\"{entity}.{parameter}\""))
+            .chain(["probability".to_string()])
+            .collect::<Vec<_>>()
+            .join(",");
+        csv.push('\n');
+        self.rows.iter().for_each(|(values, probability)| {
+            values.iter().for_each(|value| {
+                if let Some(value) = value {
+                    write!(csv, "{value:?}").unwrap();
+                }
+                csv.push(',');
+            });
+            writeln!(csv, "{probability}").unwrap();
+        });
+        csv
+    }
+}
+
+impl<V, T> Simulation<State<V>, T>
+where
+    V: Serialize + Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    pub fn joint_table(&self, time: Time, columns: &[(&str, &str)]) -> JointTable<V> {
+        let mut probabilities: HashMap<Vec<Option<V>>, Probability> = HashMap::new();
+        self.iter_probability_distribution(time)
+            .expect("No probability distribution found for given time")
+            .for_each(|(state, probability)| {
+                let values = columns
+                    .iter()
+                    .map(|(entity, parameter)| state.parameter(entity, parameter).cloned())
+                    .collect();
+                *probabilities.entry(values).or_insert(0.) += probability;
+            });
+        let mut rows = probabilities.into_iter().collect::<Vec<_>>();
+        rows.sort_by(|(first_values, first), (second_values, second)| {
+            second
+                .total_cmp(first)
+                .then_with(|| format!("{first_values:?}").cmp(&format!("{second_values:?}")))
+        });
+        JointTable {
+            columns: columns
+                .iter()
+                .map(|(entity, parameter)| (entity.to_string(), parameter.to_string()))
+                .collect(),
+            rows,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn joint_table() {
+        // One of two counters is incremented per step, the toggled flag is marginalized out
+        let state_transition_generator = Arc::new(|state: State<i32>| {
+            let mut next_states = Vec::new();
+            for (entity, probability) in [("a", 0.25), ("b", 0.75)] {
+                let mut next_state = state.clone();
+                let count = state.parameter(entity, "count").unwrap();
+                next_state.set_parameter(entity, "count", count + 1);
+                next_state.set_parameter("a", "flag", 1 - state.parameter("a", "flag").unwrap());
+                next_states.push((next_state, entity, probability));
+            }
+            next_states
+        });
+        let initial_state = State::new()
+            .with_entity(
+                "a",
+                StateEntity::new()
+                    .with_parameter("count", 0)
+                    .with_parameter("flag", 0),
+            )
+            .with_entity("b", StateEntity::new().with_parameter("count", 0));
+        let mut simulation = Simulation::new(initial_state, state_transition_generator);
+        simulation.next_step();
+        simulation.next_step();
+
+        let table = simulation.joint_table(2, &[("a", "count"), ("b", "count"), ("c", "count")]);
+        assert_eq!(table.rows().len(), 3);
+        assert_eq!(table.rows()[0], (vec![Some(0), Some(2), None], 0.5625));
+        assert_eq!(table.probability_of(&[Some(1), Some(1), None]), 0.375);
+        assert_eq!(table.probability_of(&[Some(2), Some(0), None]), 0.0625);
+        assert_eq!(
+            table.to_csv().lines().take(2).collect::<Vec<_>>(),
+            vec![
+                "\"a.count\",\"b.count\",\"c.count\",probability",
+                "0,2,,0.5625"
+            ]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use std::{fmt::Debug, fmt::Write, hash::Hash};
+
+use hashbrown::HashMap;
+use itertools::Itertools;
+
+use crate::prelude::*;
+
+const MODULE_NAME: &str = "entromatica";
+const STATE_VARIABLE: &str = "s";
+
+// Every known state has to be explored and its transitions have to sum up to 1, so a
+// sub-stochastic model can only be exported if no mass is killed. PRISM only supports a single
+// initial state, so an initial distribution is modelled with an additional state leading into it,
+// in which case step n of the simulation is step n + 1 of the exported model.
+pub fn prism_model<S, T>(simulation: &Simulation<S, T>) -> Result<String, PrismExportError>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    let states = simulation.states_in_graph_order();
+    let indices = states
+        .iter()
+        .enumerate()
+        .map(|(index, state)| (*state, index))
+        .collect::<HashMap<&S, usize>>();
+
+    let initial_distribution = simulation
+        .initial_distribution()
+        .into_iter()
+        .map(|(state, probability)| (indices[&state], probability))
+        .sorted_by_key(|(index, _)| *index)
+        .collect_vec();
+    let initial_index = match initial_distribution.as_slice() {
+        [(index, _)] => *index,
+        _ => states.len(),
+    };
+    let max_index = if initial_index == states.len() {
+        states.len()
+    } else {
+        states.len() - 1
+    };
+
+    let mut model = String::new();
+    writeln!(model, "dtmc").unwrap();
+    writeln!(model).unwrap();
+    writeln!(model, "module {MODULE_NAME}").unwrap();
+    writeln!(
+        model,
+        "    {STATE_VARIABLE} : [0..{max_index}] init {initial_index};"
+    )
+    .unwrap();
+    for (index, state) in states.iter().enumerate() {
+        writeln!(model).unwrap();
+        writeln!(model, "    // {state:?}").unwrap();
+        let transitions = simulation.cached_outgoing_transitions(state).ok_or(
+            PrismExportError::UnexploredState {
+                state_hash: hash(*state),
+            },
+        )?;
+        let sum = transitions
+            .iter()
+            .map(|(_, _, probability)| probability)
+            .sum::<Probability>();
+        if !sum.approx_eq(&1., Tolerance::Absolute(1e-10)) {
+            return Err(PrismExportError::ProbabilitySum {
+                state_hash: hash(*state),
+                sum,
+            });
+        }
+        let updates = transitions
+            .iter()
+            .map(|(target, _, probability)| (indices[target], *probability))
+            .into_group_map()
+            .into_iter()
+            .map(|(target, probabilities)| (target, probabilities.iter().sum::<f64>()))
+            .sorted_by_key(|(target, _)| *target)
+            .collect_vec();
+        writeln!(model, "    {}", command(index, &updates)).unwrap();
+    }
+    if initial_index == states.len() {
+        writeln!(model).unwrap();
+        writeln!(model, "    // initial distribution").unwrap();
+        writeln!(
+            model,
+            "    {}",
+            command(initial_index, &initial_distribution)
+        )
+        .unwrap();
+    }
+    writeln!(model, "endmodule").unwrap();
+    Ok(model)
+}
+
+fn command(source: usize, updates: &[(usize, Probability)]) -> String {
+    let updates = updates
+        .iter()
+        .map(|(target, probability)| format!("{probability}:({STATE_VARIABLE}'={target})"))
+        .join(" + ");
+    format!("[] {STATE_VARIABLE}={source} -> {updates};")
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    pub fn to_prism(&self) -> Result<String, PrismExportError> {
+        prism_model(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn cycle() {
+        let state_transition_generator = Arc::new(|state: i32| -> OutgoingTransitions<i32, &str> {
+            vec![((state + 1) % 3, "forward", 0.5), (state, "stay", 0.5)]
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.full_traversal(false);
+        let model = simulation.to_prism().unwrap();
+        println!("{model}");
+        assert!(model.starts_with("dtmc\n"));
+        assert!(model.contains("s : [0..2] init 0;"));
+        assert_eq!(model.matches("[] s=").count(), 3);
+        assert!(model.contains("[] s=0 -> 0.5:(s'=0) + 0.5:(s'=1);"));
+    }
+
+    #[test]
+    fn initial_distribution() {
+        let state_transition_generator = Arc::new(|state: i32| {
+            vec![
+                ((state + 1).min(2), "next", 0.5),
+                ((state - 1).max(-1), "previous", 0.5),
+            ]
+        });
+        let mut simulation = Simulation::new_with_distribution(
+            HashMap::from([(0, 0.5), (1, 0.5)]),
+            state_transition_generator,
+        );
+        simulation.next_step();
+        assert!(matches!(
+            prism_model(&simulation),
+            Err(PrismExportError::UnexploredState { .. })
+        ));
+
+        simulation.full_traversal(false);
+        let model = prism_model(&simulation).unwrap();
+        println!("{model}");
+        assert!(model.contains("s : [0..4] init 4;"));
+        assert!(model.contains("// initial distribution"));
+        assert_eq!(model.matches("[] s=").count(), 5);
+    }
+
+    #[test]
+    fn killed_mass() {
+        let state_transition_generator = Arc::new(|state: i32| vec![(state, "stay", 0.5)]);
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.set_sub_stochastic(true);
+        simulation.next_step();
+        assert_eq!(
+            simulation.to_prism(),
+            Err(PrismExportError::ProbabilitySum {
+                state_hash: hash(&0),
+                sum: 0.5
+            })
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::{fmt::Debug, fmt::Write, hash::Hash};
+
+use hashbrown::HashMap;
+use serde::Serialize;
+
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SankeyNode {
+    name: String,
+    time: Time,
+}
+
+impl SankeyNode {
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn time(&self) -> Time {
+        self.time
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SankeyLink {
+    source: usize,
+    target: usize,
+    transition: String,
+    value: Probability,
+}
+
+impl SankeyLink {
+    pub fn source(&self) -> usize {
+        self.source
+    }
+
+    pub fn target(&self) -> usize {
+        self.target
+    }
+
+    pub fn transition(&self) -> &String {
+        &self.transition
+    }
+
+    pub fn value(&self) -> Probability {
+        self.value
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct SankeyDiagram {
+    nodes: Vec<SankeyNode>,
+    links: Vec<SankeyLink>,
+}
+
+impl SankeyDiagram {
+    pub fn nodes(&self) -> &Vec<SankeyNode> {
+        &self.nodes
+    }
+
+    pub fn links(&self) -> &Vec<SankeyLink> {
+        &self.links
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("source,target,transition,mass\n");
+        self.links.iter().for_each(|link| {
+            writeln!(
+                csv,
+                "{},{},{},{}",
+                csv_field(&self.nodes[link.source].name),
+                csv_field(&self.nodes[link.target].name),
+                csv_field(&link.transition),
+                link.value
+            )
+            .unwrap();
+        });
+        csv
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+pub fn sankey_diagram<S, T>(simulation: &Simulation<S, T>, time: Time) -> SankeyDiagram
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    let mut diagram = SankeyDiagram::default();
+    let mut node_indices: HashMap<(String, Time), usize> = HashMap::new();
+    let mut node_index = |name: String, time: Time, nodes: &mut Vec<SankeyNode>| -> usize {
+        *node_indices.entry((name.clone(), time)).or_insert_with(|| {
+            nodes.push(SankeyNode { name, time });
+            nodes.len() - 1
+        })
+    };
+    for flow in simulation.probability_flows(time) {
+        let source = node_index(format!("{:?}", flow.source()), time, &mut diagram.nodes);
+        let target = node_index(format!("{:?}", flow.target()), time + 1, &mut diagram.nodes);
+        diagram.links.push(SankeyLink {
+            source,
+            target,
+            transition: format!("{:?}", flow.transition()),
+            value: flow.mass(),
+        });
+    }
+    diagram
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    pub fn to_sankey(&self, time: Time) -> SankeyDiagram {
+        sankey_diagram(self, time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn random_walk() {
+        let state_transition_generator =
+            Arc::new(|state: i32| vec![(state + 1, "next", 0.5), (state - 1, "previous", 0.5)]);
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.next_step();
+        simulation.next_step();
+
+        let first_step = simulation.to_sankey(0);
+        assert_eq!(first_step.nodes().len(), 3);
+        assert_eq!(first_step.links().len(), 2);
+        assert!(first_step.links().iter().all(|link| link.value() == 0.5));
+
+        let second_step = simulation.to_sankey(1);
+        assert_eq!(second_step.nodes().len(), 5);
+        assert_eq!(second_step.links().len(), 4);
+        assert_eq!(
+            second_step
+                .links()
+                .iter()
+                .map(|link| link.value())
+                .sum::<Probability>(),
+            1.0
+        );
+
+        let csv = second_step.to_csv();
+        assert!(csv.starts_with("source,target,transition,mass\n"));
+        assert!(csv.contains("\"1\",\"2\",\"\"\"next\"\"\",0.25"));
+        assert_eq!(csv.lines().count(), 5);
+
+        #[cfg(feature = "serde")]
+        {
+            let json: serde_json::Value =
+                serde_json::from_str(&second_step.to_json().unwrap()).unwrap();
+            assert_eq!(json["links"].as_array().unwrap().len(), 4);
+            assert_eq!(json["nodes"].as_array().unwrap().len(), 5);
+        }
+    }
+}
@@ -0,0 +1,480 @@
+use std::{fmt::Debug, hash::Hash};
+
+use crate::prelude::*;
+use crate::random::*;
+
+// A discrete Bayes filter. The belief starts as the distribution of the simulation at its current
+// time, is conditioned on observations with update and moves with the model with predict. The
+// stored distributions of the simulation are not touched, only its cache grows.
+#[derive(Clone)]
+pub struct Filter<S, T, O> {
+    simulation: Simulation<S, T>,
+    emission: Emission<S, O>,
+    time: Time,
+    belief: StateProbabilityDistribution<S>,
+    log_likelihood: f64,
+}
+
+impl<S, T, O> Debug for Filter<S, T, O>
+where
+    S: Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Filter")
+            .field("time", &self.time)
+            .field("belief", &self.belief)
+            .field("log_likelihood", &self.log_likelihood)
+            .finish()
+    }
+}
+
+impl<S, T, O> Filter<S, T, O>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    O: PartialEq + Debug,
+{
+    pub fn new(simulation: Simulation<S, T>, emission: Emission<S, O>) -> Self {
+        let time = simulation.time();
+        let belief = simulation
+            .iter_probability_distribution(time)
+            .expect("The simulation has no distribution at its current time")
+            .map(|(state, probability)| (state.clone(), probability))
+            .collect();
+        Self {
+            simulation,
+            emission,
+            time,
+            belief,
+            log_likelihood: 0.,
+        }
+    }
+
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn belief(&self) -> &StateProbabilityDistribution<S> {
+        &self.belief
+    }
+
+    pub fn probability_of(&self, state: &S) -> Probability {
+        self.belief.get(state).copied().unwrap_or(0.)
+    }
+
+    // The natural logarithm of the probability of all observations so far
+    pub fn log_likelihood(&self) -> f64 {
+        self.log_likelihood
+    }
+
+    pub fn simulation(&self) -> &Simulation<S, T> {
+        &self.simulation
+    }
+
+    pub fn into_simulation(self) -> Simulation<S, T> {
+        self.simulation
+    }
+
+    fn likelihood(&self, state: &S, observation: &O) -> Probability {
+        (self.emission)(state)
+            .into_iter()
+            .filter(|(emitted, _)| emitted == observation)
+            .map(|(_, likelihood)| likelihood)
+            .sum()
+    }
+
+    // The belief is left unchanged if the observation is impossible
+    pub fn update(&mut self, observation: &O) -> Result<(), FilterError> {
+        let evidence = self
+            .belief
+            .iter()
+            .map(|(state, probability)| probability * self.likelihood(state, observation))
+            .sum::<Probability>();
+        let posterior = condition_on(&self.belief, self.emission.as_ref(), observation)
+            .ok_or_else(|| FilterError::ImpossibleObservation {
+                observation: format!("{observation:?}"),
+                time: self.time,
+            })?;
+        self.belief = posterior;
+        self.log_likelihood += evidence.ln();
+        Ok(())
+    }
+
+    pub fn predict(&mut self) {
+        self.belief = self.simulation.propagate(&self.belief);
+        self.time += 1;
+    }
+
+    // Predicts a step and conditions on what was observed after it
+    pub fn step(&mut self, observation: &O) -> Result<(), FilterError> {
+        self.predict();
+        self.update(observation)
+    }
+
+    // Fixed-interval smoothing with the forward-backward algorithm. The first observation is
+    // made at the current time and every further one a step later. Returns the distribution at
+    // each of these times given all observations, and leaves the filter at the last of them.
+    pub fn smooth(
+        &mut self,
+        observations: &[O],
+    ) -> Result<Vec<StateProbabilityDistribution<S>>, FilterError> {
+        let mut filtered = Vec::with_capacity(observations.len());
+        for (index, observation) in observations.iter().enumerate() {
+            if index > 0 {
+                self.predict();
+            }
+            self.update(observation)?;
+            filtered.push(self.belief.clone());
+        }
+        let Some(last) = filtered.last() else {
+            return Ok(Vec::new());
+        };
+        // The likelihood of the later observations given the state, scaled to avoid underflow
+        let mut backward: StateProbabilityDistribution<S> =
+            last.keys().map(|state| (state.clone(), 1.)).collect();
+        let mut smoothed = vec![last.clone()];
+        for index in (0..filtered.len() - 1).rev() {
+            let next_observation = &observations[index + 1];
+            let mut next_backward = filtered[index]
+                .keys()
+                .map(|state| {
+                    let value = self
+                        .simulation
+                        .cached_outgoing_transitions(state)
+                        .unwrap()
+                        .iter()
+                        .map(|(next_state, _, probability)| {
+                            probability
+                                * self.likelihood(next_state, next_observation)
+                                * backward.get(next_state).copied().unwrap_or(0.)
+                        })
+                        .sum::<Probability>();
+                    (state.clone(), value)
+                })
+                .collect::<StateProbabilityDistribution<S>>();
+            let scale = next_backward.values().copied().fold(0., f64::max);
+            next_backward.values_mut().for_each(|value| *value /= scale);
+            backward = next_backward;
+            let unnormalized = filtered[index]
+                .iter()
+                .map(|(state, probability)| (state.clone(), probability * backward[state]))
+                .filter(|(_, probability)| *probability > 0.)
+                .collect::<StateProbabilityDistribution<S>>();
+            let total = unnormalized.values().sum::<Probability>();
+            smoothed.push(
+                unnormalized
+                    .into_iter()
+                    .map(|(state, probability)| (state, probability / total))
+                    .collect(),
+            );
+        }
+        smoothed.reverse();
+        Ok(smoothed)
+    }
+}
+
+// The same filter approximated by weighted particles for state spaces which are too large to
+// propagate exactly. Particles move by sampling the transitions of the model, so only the states
+// they visit are explored. They are resampled once the effective sample size drops below half of
+// their number.
+#[derive(Clone)]
+pub struct ParticleFilter<S, T, O> {
+    simulation: Simulation<S, T>,
+    emission: Emission<S, O>,
+    time: Time,
+    particles: Vec<(S, Probability)>,
+    count: usize,
+    rng: SeededRng,
+    log_likelihood: f64,
+}
+
+impl<S, T, O> Debug for ParticleFilter<S, T, O>
+where
+    S: Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParticleFilter")
+            .field("time", &self.time)
+            .field("particles", &self.particles)
+            .field("log_likelihood", &self.log_likelihood)
+            .finish()
+    }
+}
+
+impl<S, T, O> ParticleFilter<S, T, O>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    O: PartialEq + Debug,
+{
+    pub fn new(
+        simulation: Simulation<S, T>,
+        emission: Emission<S, O>,
+        count: usize,
+        seed: u64,
+    ) -> Self {
+        assert!(count > 0, "A particle filter needs at least one particle");
+        let time = simulation.time();
+        let mut distribution = simulation
+            .iter_probability_distribution(time)
+            .expect("The simulation has no distribution at its current time")
+            .map(|(state, probability)| (state.clone(), probability))
+            .collect::<Vec<_>>();
+        distribution.sort_by_key(|(state, _)| hash(state));
+        let mut rng = SeededRng::new(seed);
+        let particles = (0..count)
+            .map(|_| {
+                let index = rng
+                    .choose(distribution.iter().map(|(_, probability)| *probability))
+                    .unwrap_or(distribution.len() - 1);
+                (distribution[index].0.clone(), 1. / count as Probability)
+            })
+            .collect();
+        Self {
+            simulation,
+            emission,
+            time,
+            particles,
+            count,
+            rng,
+            log_likelihood: 0.,
+        }
+    }
+
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    // Normalized weights, particles whose mass was killed are dropped
+    pub fn particles(&self) -> &Vec<(S, Probability)> {
+        &self.particles
+    }
+
+    // The particles merged by state
+    pub fn belief(&self) -> StateProbabilityDistribution<S> {
+        let mut belief = StateProbabilityDistribution::new();
+        self.particles.iter().for_each(|(state, weight)| {
+            *belief.entry(state.clone()).or_insert(0.) += weight;
+        });
+        belief
+    }
+
+    pub fn probability_of(&self, state: &S) -> Probability {
+        self.particles
+            .iter()
+            .filter(|(particle, _)| particle == state)
+            .map(|(_, weight)| weight)
+            .sum()
+    }
+
+    // An estimate of the natural logarithm of the probability of all observations so far
+    pub fn log_likelihood(&self) -> f64 {
+        self.log_likelihood
+    }
+
+    pub fn effective_sample_size(&self) -> f64 {
+        1. / self
+            .particles
+            .iter()
+            .map(|(_, weight)| weight * weight)
+            .sum::<f64>()
+    }
+
+    pub fn simulation(&self) -> &Simulation<S, T> {
+        &self.simulation
+    }
+
+    pub fn into_simulation(self) -> Simulation<S, T> {
+        self.simulation
+    }
+
+    fn normalize(&mut self) -> Probability {
+        let total = self.particles.iter().map(|(_, weight)| weight).sum::<f64>();
+        self.particles
+            .iter_mut()
+            .for_each(|(_, weight)| *weight /= total);
+        total
+    }
+
+    // Systematic resampling, which keeps the number of particles and gives them equal weights
+    fn resample(&mut self) {
+        let offset = self.rng.next_probability() / self.count as f64;
+        let mut cumulative = 0.;
+        let mut particles = self.particles.iter();
+        let mut current = particles.next();
+        let mut resampled = Vec::with_capacity(self.count);
+        for index in 0..self.count {
+            let position = offset + index as f64 / self.count as f64;
+            while let Some((_, weight)) = current {
+                if cumulative + weight > position {
+                    break;
+                }
+                cumulative += weight;
+                current = particles.next();
+            }
+            // Rounding errors may leave the last position behind the total weight
+            let (state, _) = current.unwrap_or_else(|| self.particles.last().unwrap());
+            resampled.push((state.clone(), 1. / self.count as Probability));
+        }
+        self.particles = resampled;
+    }
+
+    // The weights are left unchanged if no particle explains the observation
+    pub fn update(&mut self, observation: &O) -> Result<(), FilterError> {
+        let weights = self
+            .particles
+            .iter()
+            .map(|(state, weight)| {
+                weight
+                    * (self.emission)(state)
+                        .into_iter()
+                        .filter(|(emitted, _)| emitted == observation)
+                        .map(|(_, likelihood)| likelihood)
+                        .sum::<Probability>()
+            })
+            .collect::<Vec<_>>();
+        if weights.iter().sum::<Probability>() <= 0. {
+            return Err(FilterError::ImpossibleObservation {
+                observation: format!("{observation:?}"),
+                time: self.time,
+            });
+        }
+        self.particles
+            .iter_mut()
+            .zip(weights)
+            .for_each(|((_, weight), new_weight)| *weight = new_weight);
+        self.particles.retain(|(_, weight)| *weight > 0.);
+        self.log_likelihood += self.normalize().ln();
+        if self.effective_sample_size() < self.count as f64 / 2. {
+            self.resample();
+        }
+        Ok(())
+    }
+
+    pub fn predict(&mut self) {
+        let mut particles = std::mem::take(&mut self.particles);
+        particles = particles
+            .into_iter()
+            .filter_map(|(state, weight)| {
+                let (next_state, _) = self.simulation.sample_transition(&state, &mut self.rng)?;
+                Some((next_state, weight))
+            })
+            .collect();
+        assert!(
+            !particles.is_empty(),
+            "The mass of all particles was killed at time {}",
+            self.time
+        );
+        self.particles = particles;
+        self.normalize();
+        self.time += 1;
+    }
+
+    pub fn step(&mut self, observation: &O) -> Result<(), FilterError> {
+        self.predict();
+        self.update(observation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use hashbrown::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn filter() {
+        // A sticky coin seen through a sensor which is wrong with probability 0.2
+        let state_transition_generator =
+            Arc::new(|heads: bool| vec![(heads, "stay", 0.9), (!heads, "flip", 0.1)]);
+        let simulation = Simulation::new_with_distribution(
+            HashMap::from([(false, 0.5), (true, 0.5)]),
+            state_transition_generator,
+        );
+        let sensor: Emission<bool, bool> = Arc::new(|heads| vec![(*heads, 0.8), (!*heads, 0.2)]);
+        let mut filter = Filter::new(simulation, sensor);
+
+        filter.update(&true).unwrap();
+        assert!((filter.probability_of(&true) - 0.8).abs() < 1e-12);
+        assert!((filter.log_likelihood() - 0.5f64.ln()).abs() < 1e-12);
+
+        filter.predict();
+        assert_eq!(filter.time(), 1);
+        assert!((filter.probability_of(&true) - (0.8 * 0.9 + 0.2 * 0.1)).abs() < 1e-12);
+
+        filter.step(&true).unwrap();
+        assert!(filter.probability_of(&true) > 0.85);
+        assert!((filter.belief().values().sum::<f64>() - 1.).abs() < 1e-12);
+        assert_eq!(filter.simulation().time(), 0);
+
+        let certain: Emission<bool, bool> = Arc::new(|heads| vec![(*heads, 1.)]);
+        let mut filter = Filter::new(filter.into_simulation(), certain);
+        filter.update(&false).unwrap();
+        assert_eq!(
+            filter.update(&true),
+            Err(FilterError::ImpossibleObservation {
+                observation: "true".to_string(),
+                time: 0
+            })
+        );
+        assert_eq!(filter.probability_of(&false), 1.);
+
+        // Smoothing agrees with brute force over the four possible paths of two steps
+        let sensor: Emission<bool, bool> = Arc::new(|heads| vec![(*heads, 0.8), (!*heads, 0.2)]);
+        let mut filter = Filter::new(filter.into_simulation(), sensor);
+        let observations = [true, false];
+        let smoothed = filter.smooth(&observations).unwrap();
+        let likelihood =
+            |heads: bool, observation: bool| if heads == observation { 0.8 } else { 0.2 };
+        let transition = |from: bool, to: bool| if from == to { 0.9 } else { 0.1 };
+        let path = |first: bool, second: bool| {
+            0.5 * likelihood(first, true) * transition(first, second) * likelihood(second, false)
+        };
+        let evidence =
+            path(true, true) + path(true, false) + path(false, true) + path(false, false);
+        assert_eq!(smoothed.len(), 2);
+        assert!(
+            (smoothed[0][&true] - (path(true, true) + path(true, false)) / evidence).abs() < 1e-12
+        );
+        assert!(
+            (smoothed[1][&true] - (path(true, true) + path(false, true)) / evidence).abs() < 1e-12
+        );
+        assert_eq!(&smoothed[1], filter.belief());
+        assert!((filter.log_likelihood() - evidence.ln()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn particle_filter() {
+        let state_transition_generator = Arc::new(|position: i32| {
+            vec![
+                (position - 1, "left", 0.25),
+                (position, "stay", 0.5),
+                (position + 1, "right", 0.25),
+            ]
+        });
+        let simulation = Simulation::new(0, state_transition_generator);
+        // The sign of the position is seen correctly with probability 0.9
+        let sensor: Emission<i32, bool> =
+            Arc::new(|position| vec![(*position >= 0, 0.9), (*position < 0, 0.1)]);
+        let mut exact = Filter::new(simulation.clone(), sensor.clone());
+        let mut particles = ParticleFilter::new(simulation, sensor, 20_000, 11);
+        for observation in [true, true, false, true, false, false] {
+            exact.step(&observation).unwrap();
+            particles.step(&observation).unwrap();
+        }
+        assert_eq!(particles.time(), 6);
+        assert!((particles.belief().values().sum::<f64>() - 1.).abs() < 1e-9);
+        (-6..=6).for_each(|position| {
+            assert!(
+                (particles.probability_of(&position) - exact.probability_of(&position)).abs()
+                    < 0.02
+            )
+        });
+        assert!((particles.log_likelihood() - exact.log_likelihood()).abs() < 0.05);
+        assert!(particles.effective_sample_size() > 10_000.);
+        // Only the states visited by particles are explored
+        assert!(particles.simulation().known_states().len() <= 13);
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use hashbrown::{HashMap, HashSet};
+
+use crate::models::entities::*;
+use crate::prelude::*;
+
+// Entry points for fuzzers like cargo-fuzz, e.g.
+// fuzz_target!(|data: &[u8]| entromatica::fuzz::fuzz_cache_roundtrip(data));
+// They panic if an invariant is violated. Longer inputs are truncated, so memory and time per
+// input stay bounded.
+pub const MAX_FUZZ_INPUT_LENGTH: usize = 4096;
+
+const MAX_FORKS: usize = 4;
+
+fn scramble(input: u8) -> u8 {
+    input.wrapping_mul(31) ^ 0x5a
+}
+
+fn bounded(data: &[u8]) -> &[u8] {
+    &data[..data.len().min(MAX_FUZZ_INPUT_LENGTH)]
+}
+
+// Interprets the input as operations on forked caches and checks them against plain maps. Values
+// written to one fork must never show up in another one, and a cached function must neither
+// recompute nor overwrite a cached output.
+pub fn fuzz_cache_roundtrip(data: &[u8]) {
+    let data = bounded(data);
+    let mut maps = vec![(SharedMap::<u8, u8>::new(), HashMap::<u8, u8>::new())];
+    let mut current = 0;
+    for operation in data.chunks_exact(3) {
+        let (key, value) = (operation[1], operation[2]);
+        let forks = maps.len();
+        let (map, reference) = &mut maps[current];
+        match operation[0] % 5 {
+            0 => {
+                map.insert(key, value);
+                reference.insert(key, value);
+            }
+            1 => assert_eq!(map.get(&key), reference.get(&key)),
+            2 => {
+                map.retain(|other, _| *other != key);
+                reference.retain(|other, _| *other != key);
+            }
+            3 if forks < MAX_FORKS => {
+                let fork = (map.fork(), reference.clone());
+                maps.push(fork);
+            }
+            _ => current = key as usize % forks,
+        }
+        maps.iter().for_each(|(map, reference)| {
+            assert_eq!(map.len(), reference.len());
+            map.iter()
+                .for_each(|(key, value)| assert_eq!(reference.get(key), Some(value)));
+        });
+    }
+
+    let computations = Arc::new(AtomicUsize::new(0));
+    let counter = computations.clone();
+    let inserts = Arc::new(AtomicUsize::new(0));
+    let insert_counter = inserts.clone();
+    let mut cached_function = CachedFunction::new(Arc::new(move |input: u8| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        scramble(input)
+    }));
+    cached_function.set_insert_observer(Some(Arc::new(move |_: &u8, _: &u8| {
+        insert_counter.fetch_add(1, Ordering::SeqCst);
+    })));
+    data.iter().for_each(|input| {
+        assert_eq!(cached_function.call(*input), scramble(*input));
+    });
+    let distinct_inputs = data.iter().collect::<HashSet<_>>().len();
+    let outputs = cached_function.call_many_parallel(data);
+    assert!(outputs
+        .iter()
+        .zip(data)
+        .all(|(output, input)| cached_function.get(input) == Some(output.as_ref())));
+    assert_eq!(computations.load(Ordering::SeqCst), distinct_inputs);
+    assert_eq!(inserts.load(Ordering::SeqCst), distinct_inputs);
+}
+
+// Builds the same state from the input in two different orders and checks that equal states
+// hash equally, and that a changed state is unequal until it is changed back.
+pub fn fuzz_state_hash_consistency(data: &[u8]) {
+    let data = bounded(data);
+    let mut state = State::new();
+    let mut parameters = BTreeMap::new();
+    let mut links = Vec::new();
+    for operation in data.chunks_exact(3) {
+        let entity = format!("entity{}", operation[0] % 8);
+        if operation[0] & 0x80 == 0 {
+            let parameter = format!("parameter{}", operation[1] % 8);
+            let value = operation[2] as i64;
+            state.set_parameter(&entity, &parameter, value);
+            parameters.insert((entity, parameter), value);
+        } else {
+            let target = format!("entity{}", operation[1] % 8);
+            state.link("relation", &entity, &target);
+            links.push((entity, target));
+        }
+    }
+    let mut reordered = State::new();
+    links.iter().rev().for_each(|(source, target)| {
+        reordered.link("relation", source, target);
+    });
+    parameters
+        .iter()
+        .rev()
+        .for_each(|((entity, parameter), value)| {
+            reordered.set_parameter(entity, parameter, *value);
+        });
+    assert_eq!(state, reordered);
+    assert_eq!(hash(&state), hash(&reordered));
+
+    if let Some(((entity, parameter), value)) = parameters.iter().next() {
+        reordered.set_parameter(entity, parameter, value + 1);
+        assert_ne!(state, reordered);
+        reordered.set_parameter(entity, parameter, *value);
+        assert_eq!(state, reordered);
+        assert_eq!(hash(&state), hash(&reordered));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzz_entry_points() {
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let data = (0..MAX_FUZZ_INPUT_LENGTH + 100)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed as u8
+            })
+            .collect::<Vec<_>>();
+        for length in [0, 1, 3, 64, data.len()] {
+            fuzz_cache_roundtrip(&data[..length]);
+            fuzz_state_hash_consistency(&data[..length]);
+        }
+    }
+}
@@ -1,10 +1,172 @@
-use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
-};
+use core::hash::{Hash, Hasher};
+
+// SipHash-1-3 with fixed keys, implemented here instead of using DefaultHasher, whose algorithm
+// may change between Rust versions. Integers are written in little endian and usize as u64, so
+// state hashes are the same on every platform and can be persisted or shared between workers.
+const STATE_HASH_KEYS: (u64, u64) = (0x656e_7472_6f6d_6174, 0x6963_612d_7374_6174);
+
+#[derive(Clone)]
+pub(crate) struct StateHasher {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+    tail: u64,
+    tail_length: usize,
+    length: usize,
+}
+
+impl StateHasher {
+    pub fn new() -> Self {
+        Self::with_keys(STATE_HASH_KEYS.0, STATE_HASH_KEYS.1)
+    }
+
+    pub fn with_keys(key0: u64, key1: u64) -> Self {
+        Self {
+            v0: key0 ^ 0x736f_6d65_7073_6575,
+            v1: key1 ^ 0x646f_7261_6e64_6f6d,
+            v2: key0 ^ 0x6c79_6765_6e65_7261,
+            v3: key1 ^ 0x7465_6462_7974_6573,
+            tail: 0,
+            tail_length: 0,
+            length: 0,
+        }
+    }
+
+    fn round(&mut self) {
+        self.v0 = self.v0.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(13) ^ self.v0;
+        self.v0 = self.v0.rotate_left(32);
+        self.v2 = self.v2.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(16) ^ self.v2;
+        self.v0 = self.v0.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(21) ^ self.v0;
+        self.v2 = self.v2.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(17) ^ self.v2;
+        self.v2 = self.v2.rotate_left(32);
+    }
+
+    fn compress(&mut self, word: u64) {
+        self.v3 ^= word;
+        self.round();
+        self.v0 ^= word;
+    }
+}
+
+fn little_endian(bytes: &[u8]) -> u64 {
+    bytes.iter().enumerate().fold(0, |word, (index, byte)| {
+        word | (*byte as u64) << (8 * index)
+    })
+}
+
+impl Hasher for StateHasher {
+    fn write(&mut self, mut bytes: &[u8]) {
+        self.length = self.length.wrapping_add(bytes.len());
+        if self.tail_length > 0 {
+            let filled = (8 - self.tail_length).min(bytes.len());
+            self.tail |= little_endian(&bytes[..filled]) << (8 * self.tail_length);
+            self.tail_length += filled;
+            bytes = &bytes[filled..];
+            if self.tail_length < 8 {
+                return;
+            }
+            self.compress(self.tail);
+        }
+        let mut words = bytes.chunks_exact(8);
+        for word in &mut words {
+            self.compress(little_endian(word));
+        }
+        self.tail = little_endian(words.remainder());
+        self.tail_length = words.remainder().len();
+    }
+
+    fn write_u8(&mut self, value: u8) {
+        self.write(&[value]);
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, value: u128) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    fn write_i8(&mut self, value: i8) {
+        self.write_u8(value as u8);
+    }
+
+    fn write_i16(&mut self, value: i16) {
+        self.write_u16(value as u16);
+    }
+
+    fn write_i32(&mut self, value: i32) {
+        self.write_u32(value as u32);
+    }
+
+    fn write_i64(&mut self, value: i64) {
+        self.write_u64(value as u64);
+    }
+
+    fn write_i128(&mut self, value: i128) {
+        self.write_u128(value as u128);
+    }
+
+    fn write_isize(&mut self, value: isize) {
+        self.write_u64(value as i64 as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        let mut hasher = self.clone();
+        let last = ((self.length as u64 & 0xff) << 56) | self.tail;
+        hasher.compress(last);
+        hasher.v2 ^= 0xff;
+        (0..3).for_each(|_| hasher.round());
+        hasher.v0 ^ hasher.v1 ^ hasher.v2 ^ hasher.v3
+    }
+}
 
 pub(crate) fn hash(hashable: &impl Hash) -> u64 {
-    let mut hasher = DefaultHasher::new();
+    let mut hasher = StateHasher::new();
     hashable.hash(&mut hasher);
     hasher.finish()
 }
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::{collections::hash_map::DefaultHasher, vec::Vec};
+
+    use super::*;
+
+    #[test]
+    fn stable_hashes() {
+        // The standard library uses SipHash-1-3 with zero keys, which pins the algorithm
+        let bytes = (0..=40).collect::<Vec<u8>>();
+        (0..bytes.len()).for_each(|split| {
+            let mut hasher = StateHasher::with_keys(0, 0);
+            hasher.write(&bytes[..split]);
+            hasher.write(&bytes[split..]);
+            let mut default_hasher = DefaultHasher::new();
+            default_hasher.write(&bytes);
+            assert_eq!(hasher.finish(), default_hasher.finish());
+        });
+
+        // Pinned values, which must never change as hashes are persisted
+        assert_eq!(hash(&42u64), 12212587279728973297);
+        assert_eq!(hash(&-1i32), 2271012318189072363);
+        assert_eq!(hash(&"state"), 571101237948055882);
+        assert_eq!(hash(&(7usize, vec![1u8, 2, 3])), 14946358484040391822);
+    }
+}
@@ -0,0 +1,165 @@
+use hashbrown::HashMap;
+use rayon::prelude::*;
+
+use crate::prelude::*;
+
+// Probabilities are kept in a contiguous vector so sums, entropy and normalization can be
+// autovectorized, while the index map keeps lookups by state hash cheap. They are stored with
+// the precision of StoredProbability, but all arithmetic on them happens in f64.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct HashedDistribution {
+    indices: HashMap<u64, usize>,
+    state_hashes: Vec<u64>,
+    probabilities: Vec<StoredProbability>,
+}
+
+impl HashedDistribution {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, state_hash: &u64) -> Option<Probability> {
+        self.indices
+            .get(state_hash)
+            .map(|index| self.probabilities[*index].to_probability())
+    }
+
+    // A missing state is inserted with probability zero, with a single lookup of the hash
+    pub fn entry(&mut self, state_hash: u64) -> ProbabilityEntry<'_> {
+        let next_index = self.probabilities.len();
+        let index = *self.indices.entry(state_hash).or_insert(next_index);
+        if index == next_index {
+            self.state_hashes.push(state_hash);
+            self.probabilities
+                .push(StoredProbability::from_probability(0.));
+        }
+        ProbabilityEntry {
+            probability: &mut self.probabilities[index],
+        }
+    }
+
+    pub fn add(&mut self, state_hash: u64, probability: Probability) {
+        self.entry(state_hash).add(probability);
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.indices.reserve(additional);
+        self.state_hashes.reserve(additional);
+        self.probabilities.reserve(additional);
+    }
+
+    // For states which are known to be missing, e.g. when the hashes come from a map. A
+    // duplicate hash would leave an unreachable probability behind.
+    pub fn push_unchecked(&mut self, state_hash: u64, probability: Probability) {
+        debug_assert!(!self.indices.contains_key(&state_hash));
+        self.indices.insert(state_hash, self.probabilities.len());
+        self.state_hashes.push(state_hash);
+        self.probabilities
+            .push(StoredProbability::from_probability(probability));
+    }
+
+    pub fn from_unique(states: impl IntoIterator<Item = (u64, Probability)>) -> Self {
+        let states = states.into_iter();
+        let mut distribution = Self::new();
+        distribution.reserve(states.size_hint().0);
+        states.for_each(|(state_hash, probability)| {
+            distribution.push_unchecked(state_hash, probability)
+        });
+        distribution
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&u64, Probability)> {
+        self.state_hashes.iter().zip(
+            self.probabilities
+                .iter()
+                .map(|probability| probability.to_probability()),
+        )
+    }
+
+    pub fn par_iter(&self) -> impl IndexedParallelIterator<Item = (&u64, Probability)> {
+        self.state_hashes.par_iter().zip(
+            self.probabilities
+                .par_iter()
+                .map(|probability| probability.to_probability()),
+        )
+    }
+
+    pub fn sum(&self) -> Probability {
+        self.probabilities
+            .iter()
+            .map(|probability| probability.to_probability())
+            .sum()
+    }
+
+    pub fn entropy(&self) -> f64 {
+        -self
+            .probabilities
+            .iter()
+            .map(|probability| probability.to_probability())
+            .filter(|probability| *probability > 0.)
+            .map(|probability| probability * probability.log2())
+            .sum::<f64>()
+    }
+}
+
+pub(crate) struct ProbabilityEntry<'a> {
+    probability: &'a mut StoredProbability,
+}
+
+impl ProbabilityEntry<'_> {
+    pub fn set(&mut self, probability: Probability) {
+        *self.probability = StoredProbability::from_probability(probability);
+    }
+
+    pub fn add(&mut self, probability: Probability) {
+        self.set(self.probability.to_probability() + probability);
+    }
+}
+
+// Merges probabilities of the same state in a single pass, with the capacity reserved up front
+impl Extend<(u64, Probability)> for HashedDistribution {
+    fn extend<I: IntoIterator<Item = (u64, Probability)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        iter.for_each(|(state_hash, probability)| self.add(state_hash, probability));
+    }
+}
+
+impl FromIterator<(u64, Probability)> for HashedDistribution {
+    fn from_iter<I: IntoIterator<Item = (u64, Probability)>>(iter: I) -> Self {
+        let mut distribution = Self::new();
+        distribution.extend(iter);
+        distribution
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregation() {
+        let mut distribution = HashedDistribution::from_iter([(1, 0.25), (2, 0.25), (1, 0.25)]);
+        assert_eq!(distribution.iter().count(), 2);
+        assert_eq!(distribution.get(&1), Some(0.5));
+        assert_eq!(distribution.get(&3), None);
+        assert_eq!(distribution.sum(), 0.75);
+
+        distribution.entry(2).set(0.5);
+        assert_eq!(distribution.entropy(), 1.);
+        assert_eq!(distribution.par_iter().count(), 2);
+
+        distribution.extend([(3, 0.25), (1, 0.25), (3, 0.25)]);
+        assert_eq!(distribution.iter().count(), 3);
+        assert_eq!(distribution.get(&1), Some(0.75));
+        assert_eq!(distribution.get(&3), Some(0.5));
+        distribution.entry(4).add(0.25);
+        assert_eq!(distribution.get(&4), Some(0.25));
+        assert_eq!(distribution.iter().count(), 4);
+
+        let unique = HashedDistribution::from_unique([(1, 0.5), (2, 0.5)]);
+        assert_eq!(unique, HashedDistribution::from_iter([(1, 0.5), (2, 0.5)]));
+        let with_zero = HashedDistribution::from_unique([(1, 0.5), (2, 0.5), (3, 0.)]);
+        assert_eq!(with_zero.entropy(), 1.);
+    }
+}
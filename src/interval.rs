@@ -0,0 +1,217 @@
+use std::{fmt::Debug, hash::Hash};
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+// Rigorous bounds on a probability. Every operation rounds the lower bound down and the upper
+// bound up, so the exact value stays inside the interval despite float roundoff.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct ProbabilityInterval {
+    lower: Probability,
+    upper: Probability,
+}
+
+impl ProbabilityInterval {
+    pub fn new(lower: Probability, upper: Probability) -> Self {
+        assert!(
+            0. <= lower && lower <= upper,
+            "Lower bound {lower} must be non-negative and not above upper bound {upper}"
+        );
+        Self {
+            lower,
+            upper: upper.min(1.),
+        }
+    }
+
+    pub fn point(probability: Probability) -> Self {
+        Self::new(probability, probability)
+    }
+
+    pub fn zero() -> Self {
+        Self::point(0.)
+    }
+
+    pub fn lower(&self) -> Probability {
+        self.lower
+    }
+
+    pub fn upper(&self) -> Probability {
+        self.upper
+    }
+
+    pub fn width(&self) -> Probability {
+        self.upper - self.lower
+    }
+
+    pub fn midpoint(&self) -> Probability {
+        (self.lower + self.upper) / 2.
+    }
+
+    pub fn contains(&self, probability: Probability) -> bool {
+        self.lower <= probability && probability <= self.upper
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        Self::new(
+            (self.lower + other.lower).next_down().max(0.),
+            (self.upper + other.upper).next_up(),
+        )
+    }
+
+    pub fn scale(&self, probability: Probability) -> Self {
+        Self::new(
+            (self.lower * probability).next_down().max(0.),
+            (self.upper * probability).next_up(),
+        )
+    }
+
+    // Mass which may or may not be present, e.g. because it was pruned
+    pub fn widen(&self, mass: Probability) -> Self {
+        Self::new(self.lower, (self.upper + mass).next_up())
+    }
+}
+
+// A distribution of probability intervals. Mass below the pruning threshold is dropped and
+// accounted for in pruned_mass, which could have ended up in any state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntervalDistribution<S>
+where
+    S: Hash + Eq,
+{
+    time: Time,
+    bounds: HashMap<S, ProbabilityInterval>,
+    pruned_mass: ProbabilityInterval,
+}
+
+impl<S> IntervalDistribution<S>
+where
+    S: Hash + Eq,
+{
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn tracked_states(&self) -> impl Iterator<Item = &S> {
+        self.bounds.keys()
+    }
+
+    pub fn pruned_mass(&self) -> ProbabilityInterval {
+        self.pruned_mass
+    }
+
+    pub fn bounds_of(&self, state: &S) -> ProbabilityInterval {
+        self.bounds
+            .get(state)
+            .copied()
+            .unwrap_or_else(ProbabilityInterval::zero)
+            .widen(self.pruned_mass.upper())
+    }
+
+    // The largest width of any state, which bounds the error of every reported probability
+    pub fn max_width(&self) -> Probability {
+        self.bounds
+            .values()
+            .map(|bounds| bounds.width())
+            .fold(0., f64::max)
+            + self.pruned_mass.upper()
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Propagates the initial distribution for the given number of steps in interval arithmetic.
+    // The initial probabilities and the transition probabilities are taken as exact.
+    pub fn interval_distribution(
+        &mut self,
+        steps: usize,
+        pruning_threshold: Probability,
+    ) -> IntervalDistribution<S> {
+        let mut distribution = IntervalDistribution {
+            time: 0,
+            bounds: self
+                .initial_distribution()
+                .into_iter()
+                .map(|(state, probability)| (state, ProbabilityInterval::point(probability)))
+                .collect(),
+            pruned_mass: ProbabilityInterval::zero(),
+        };
+        for _ in 0..steps {
+            let unexplored = distribution
+                .bounds
+                .keys()
+                .filter(|state| self.cached_outgoing_transitions(state).is_none())
+                .cloned()
+                .collect::<Vec<_>>();
+            if !unexplored.is_empty() {
+                self.explore_frontier(unexplored);
+            }
+            let mut bounds: HashMap<S, ProbabilityInterval> = HashMap::new();
+            distribution.bounds.iter().for_each(|(state, interval)| {
+                self.cached_outgoing_transitions(state)
+                    .unwrap()
+                    .iter()
+                    .for_each(|(next_state, _, probability)| {
+                        let entry = bounds
+                            .entry(next_state.clone())
+                            .or_insert_with(ProbabilityInterval::zero);
+                        *entry = entry.add(&interval.scale(*probability));
+                    });
+            });
+            let mut pruned_mass = distribution.pruned_mass;
+            bounds.retain(|_, interval| {
+                let keep = interval.upper() >= pruning_threshold;
+                if !keep {
+                    pruned_mass = pruned_mass.add(interval);
+                }
+                keep
+            });
+            distribution = IntervalDistribution {
+                time: distribution.time + 1,
+                bounds,
+                pruned_mass,
+            };
+        }
+        distribution
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn interval_distribution() {
+        let state_transition_generator =
+            Arc::new(|state: i32| vec![(state - 1, "down", 0.5), (state + 1, "up", 0.5)]);
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        let intervals = simulation.interval_distribution(6, 0.05);
+        for _ in 0..6 {
+            simulation.next_step();
+        }
+
+        assert_eq!(intervals.time(), 6);
+        // The outermost states of step 5 have a probability of 1/32 each
+        assert!(intervals.pruned_mass().contains(2. / 32.));
+        assert!(intervals.pruned_mass().width() < 1e-15);
+        assert_eq!(intervals.tracked_states().count(), 5);
+        for state in -7..=7 {
+            assert!(intervals
+                .bounds_of(&state)
+                .contains(simulation.probability_of(&state, 6)));
+        }
+        assert!(intervals.bounds_of(&0).width() < 2. / 32. + 1e-12);
+        assert!(intervals.max_width() < 2. / 32. + 1e-12);
+
+        let exact = simulation.interval_distribution(6, 0.);
+        assert_eq!(exact.pruned_mass(), ProbabilityInterval::zero());
+        assert!(exact.max_width() < 1e-12);
+        assert!(exact.bounds_of(&0).contains(20. / 64.));
+    }
+}
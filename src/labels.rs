@@ -0,0 +1,147 @@
+use std::{fmt::Debug, hash::Hash, sync::Arc};
+
+use crate::prelude::*;
+
+pub type LabelName = String;
+
+// An atomic proposition on states. The labeled states are materialized when a label is added and
+// whenever a new state becomes known, so looking up labels does not evaluate the predicate again.
+// They are shared with forks of the simulation.
+#[derive(Clone)]
+pub(crate) struct Labeling<S> {
+    predicate: Observable<S>,
+    states: SharedMap<u64, ()>,
+}
+
+impl<S> Labeling<S> {
+    pub fn record(&mut self, state_hash: u64, state: &S) {
+        if (self.predicate)(state) {
+            self.states.insert(state_hash, ());
+        }
+    }
+
+    pub fn forget(&mut self, keep: impl Fn(&u64) -> bool) {
+        self.states.retain(|state_hash, _| keep(state_hash));
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Labeling with an existing name replaces the label
+    pub fn label(
+        &mut self,
+        name: impl Into<LabelName>,
+        predicate: impl Fn(&S) -> bool + Send + Sync + 'static,
+    ) {
+        let mut labeling = Labeling {
+            predicate: Arc::new(predicate),
+            states: SharedMap::new(),
+        };
+        self.iter_known_states()
+            .for_each(|state| labeling.record(hash(state), state));
+        self.labelings_mut().insert(name.into(), labeling);
+    }
+
+    pub fn unlabel(&mut self, name: &str) -> bool {
+        self.labelings_mut().remove(name).is_some()
+    }
+
+    // Sorted by name
+    pub fn label_names(&self) -> Vec<&LabelName> {
+        self.labelings().keys().collect()
+    }
+
+    // The predicate of a label, e.g. to use it as an observable
+    pub fn labeler(&self, name: &str) -> Option<Observable<S>> {
+        self.labelings()
+            .get(name)
+            .map(|labeling| labeling.predicate.clone())
+    }
+
+    // States which are not known yet are labeled by evaluating the predicate
+    pub fn has_label(&self, state: &S, name: &str) -> bool {
+        let Some(labeling) = self.labelings().get(name) else {
+            return false;
+        };
+        let state_hash = hash(state);
+        if self.known_state(state_hash).is_ok() {
+            labeling.states.contains_key(&state_hash)
+        } else {
+            (labeling.predicate)(state)
+        }
+    }
+
+    pub fn labels_of(&self, state: &S) -> Vec<&LabelName> {
+        self.labelings()
+            .keys()
+            .filter(|name| self.has_label(state, name))
+            .collect()
+    }
+
+    pub fn labeled_states(&self, name: &str) -> Vec<&S> {
+        self.labelings()
+            .get(name)
+            .into_iter()
+            .flat_map(|labeling| labeling.states.keys())
+            .filter_map(|state_hash| self.known_state(*state_hash).ok())
+            .collect()
+    }
+
+    pub fn label_probability(
+        &self,
+        name: &str,
+        time: Time,
+    ) -> Result<Probability, SimulationError> {
+        let labeling = self
+            .labelings()
+            .get(name)
+            .expect("There is no label with this name");
+        Ok(self
+            .iter_probability_distribution(time)?
+            .filter(|(state, _)| labeling.states.contains_key(&hash(*state)))
+            .map(|(_, probability)| probability)
+            .sum())
+    }
+
+    // Records the probability of the label like any other tracked observable
+    pub fn track_label(&mut self, name: &str) {
+        let labeler = self
+            .labeler(name)
+            .expect("There is no label with this name");
+        self.track(labeler, name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels() {
+        let state_transition_generator = Arc::new(|wear: u8| match wear {
+            0..=2 => vec![(wear + 1, "wear", 0.5), (wear, "idle", 0.5)],
+            _ => vec![(wear, "broken", 1.)],
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.next_step();
+        simulation.label("failed", |wear: &u8| *wear >= 3);
+        simulation.label("new", |wear: &u8| *wear == 0);
+        simulation.track_label("failed");
+        assert_eq!(simulation.label_names(), vec!["failed", "new"]);
+        assert!(simulation.labeled_states("failed").is_empty());
+        assert_eq!(simulation.labels_of(&0), vec!["new"]);
+        // Unknown states are labeled on demand
+        assert!(simulation.has_label(&5, "failed"));
+
+        simulation.next_step();
+        simulation.next_step();
+        assert_eq!(simulation.labeled_states("failed"), vec![&3]);
+        assert_eq!(simulation.label_probability("failed", 3), Ok(0.125));
+        assert_eq!(simulation.time_series("failed").unwrap()[&3], 0.125);
+        assert!(simulation.unlabel("new"));
+        assert!(!simulation.has_label(&0, "new"));
+    }
+}
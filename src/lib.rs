@@ -1,5 +1,58 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod analysis;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "std")]
+pub mod budget;
+#[cfg(feature = "std")]
 mod cached_function;
+#[cfg(feature = "std")]
+pub mod delta;
+#[cfg(feature = "std")]
+pub mod engine;
+#[cfg(feature = "std")]
+pub mod ensemble;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "std")]
+pub mod filtering;
+#[cfg(feature = "std")]
+pub mod fuzz;
 mod hash;
+#[cfg(feature = "std")]
+mod hashed_distribution;
+#[cfg(feature = "std")]
+pub mod interval;
+#[cfg(feature = "std")]
+pub mod labels;
 pub mod models;
+#[cfg(feature = "std")]
+pub mod observations;
+#[cfg(feature = "serde")]
+pub mod persistence;
 pub mod prelude;
+#[cfg(feature = "std")]
+pub mod probability;
+#[cfg(feature = "std")]
+pub mod provider;
+#[cfg(feature = "std")]
+mod random;
+#[cfg(feature = "std")]
+pub mod reports;
+#[cfg(feature = "std")]
+pub mod schedule;
+pub mod sequential;
+#[cfg(feature = "std")]
+mod shared_map;
+#[cfg(feature = "std")]
 pub mod simulation;
+#[cfg(feature = "std")]
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod tracking;
@@ -1 +1,36 @@
+#[cfg(feature = "std")]
+pub mod actions;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod condition_index;
+#[cfg(feature = "std")]
+pub mod conditions;
+#[cfg(feature = "std")]
+pub mod declarative;
+pub mod entities;
+#[cfg(feature = "std")]
+pub mod flags;
+#[cfg(feature = "std")]
+pub mod initial_states;
+#[cfg(feature = "std")]
+pub mod petri_net;
+#[cfg(feature = "std")]
+pub mod population;
+#[cfg(feature = "std")]
+pub mod probe;
+#[cfg(feature = "std")]
+pub mod queueing;
+#[cfg(feature = "std")]
+pub mod random_models;
+#[cfg(feature = "std")]
+pub mod read_cache;
 pub mod rules;
+#[cfg(feature = "std")]
+pub mod schema;
+#[cfg(feature = "std")]
+pub mod topology;
+#[cfg(feature = "std")]
+pub mod transactions;
+#[cfg(feature = "std")]
+pub mod units;
@@ -0,0 +1,216 @@
+use std::sync::{Arc, RwLock};
+
+use hashbrown::HashMap;
+
+use crate::models::{conditions::*, entities::*, rules::*};
+use crate::prelude::*;
+
+pub type StateAction<T> = Arc<dyn Fn(State<T>) -> State<T> + Send + Sync>;
+
+pub type ParameterFunction<T> = Arc<dyn Fn(&State<T>) -> T + Send + Sync>;
+
+// Ready-made actions on a single parameter. They panic if the parameter does not exist, guard
+// them with a condition like parameter_at_least if it is optional.
+pub fn increment<T>(
+    entity: impl Into<EntityName>,
+    parameter: impl Into<ParameterName>,
+    delta: f64,
+) -> StateAction<T>
+where
+    T: Numeric + 'static,
+{
+    let (entity, parameter) = (entity.into(), parameter.into());
+    Arc::new(move |mut state: State<T>| {
+        let value = state
+            .parameter(&entity, &parameter)
+            .expect("Action refers to a parameter which does not exist")
+            .to_f64();
+        state.set_parameter(&entity, &parameter, T::from_f64(value + delta));
+        state
+    })
+}
+
+pub fn decrement<T>(
+    entity: impl Into<EntityName>,
+    parameter: impl Into<ParameterName>,
+    delta: f64,
+) -> StateAction<T>
+where
+    T: Numeric + 'static,
+{
+    increment(entity, parameter, -delta)
+}
+
+pub fn set<T>(
+    entity: impl Into<EntityName>,
+    parameter: impl Into<ParameterName>,
+    value: T,
+) -> StateAction<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    let (entity, parameter) = (entity.into(), parameter.into());
+    Arc::new(move |mut state: State<T>| {
+        state.set_parameter(&entity, &parameter, value.clone());
+        state
+    })
+}
+
+// Sets a parameter to a value computed from the whole state
+pub fn set_function<T>(
+    entity: impl Into<EntityName>,
+    parameter: impl Into<ParameterName>,
+    function: ParameterFunction<T>,
+) -> StateAction<T>
+where
+    T: 'static,
+{
+    let (entity, parameter) = (entity.into(), parameter.into());
+    Arc::new(move |mut state: State<T>| {
+        let value = function(&state);
+        state.set_parameter(&entity, &parameter, value);
+        state
+    })
+}
+
+// The values of the read parameters in the state, which is all a function with this read set
+// can depend on
+fn read_key<T: Numeric>(reads: &[(EntitySelector, ParameterName)], state: &State<T>) -> u64 {
+    let values = reads
+        .iter()
+        .enumerate()
+        .flat_map(|(index, (selector, parameter))| {
+            selector.select(state).map(move |(name, entity)| {
+                let value = entity
+                    .parameter(parameter)
+                    .map(|value| value.to_f64().to_bits());
+                (index, name, value)
+            })
+        })
+        .collect::<Vec<_>>();
+    hash(&values)
+}
+
+// Like set_function, but results are cached by the values of the parameters in the read set, e.g.
+// the declared or probed reads of the rule, so states which only differ in parameters the
+// function does not read share one computation. A read set which misses a parameter the function
+// reads leads to wrong results.
+pub fn cached_set_function<T>(
+    entity: impl Into<EntityName>,
+    parameter: impl Into<ParameterName>,
+    reads: Vec<AccessedParameter>,
+    function: ParameterFunction<T>,
+) -> StateAction<T>
+where
+    T: Numeric + Clone + Send + Sync + 'static,
+{
+    let (entity, parameter) = (entity.into(), parameter.into());
+    let reads = reads
+        .into_iter()
+        .map(|(selector, parameter)| (EntitySelector::from_description(&selector), parameter))
+        .collect::<Vec<_>>();
+    let cache: RwLock<HashMap<u64, T>> = RwLock::new(HashMap::new());
+    Arc::new(move |mut state: State<T>| {
+        let key = read_key(&reads, &state);
+        let cached = cache.read().unwrap().get(&key).cloned();
+        let value = cached.unwrap_or_else(|| {
+            let value = function(&state);
+            cache.write().unwrap().insert(key, value.clone());
+            value
+        });
+        state.set_parameter(&entity, &parameter, value);
+        state
+    })
+}
+
+// Applies the actions from first to last
+pub fn sequence<T>(actions: Vec<StateAction<T>>) -> StateAction<T>
+where
+    T: 'static,
+{
+    Arc::new(move |state: State<T>| actions.iter().fold(state, |state, action| action(state)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::conditions::*;
+    use crate::models::units::*;
+
+    use super::*;
+
+    #[test]
+    fn combinators() {
+        let state = State::new().with_entity(
+            "tank",
+            StateEntity::new()
+                .with_parameter("level", 2)
+                .with_parameter("valve", 0),
+        );
+        let drain = Rule::new(
+            "Drain".to_string(),
+            all_of(vec![
+                parameter_at_least("tank", "level", 1.),
+                not(parameter_equals("tank", "valve", 1.)),
+            ]),
+            0.5,
+            sequence(vec![
+                decrement("tank", "level", 1.),
+                set("tank", "valve", 1),
+            ]),
+        );
+        assert!(drain.applies(state.clone()));
+        let drained = drain.apply(state.clone());
+        assert_eq!(drained.parameter("tank", "level"), Some(&1));
+        assert!(!drain.applies(drained.clone()));
+        assert!(any_of(vec![
+            parameter_at_most("tank", "level", 0.),
+            parameter_equals("tank", "valve", 1.)
+        ])(drained.clone()));
+        assert!(!parameter_at_least::<i32>("pipe", "level", 0.)(
+            drained.clone()
+        ));
+
+        // The pressure only depends on the level, so the valve does not cause a recomputation
+        let computations = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = computations.clone();
+        let pressure = cached_set_function(
+            "tank",
+            "pressure",
+            vec![("tank".to_string(), "level".to_string())],
+            Arc::new(move |state: &State<i32>| {
+                counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                state.parameter("tank", "level").unwrap() * 10
+            }),
+        );
+        assert_eq!(
+            pressure(state.clone()).parameter("tank", "pressure"),
+            Some(&20)
+        );
+        assert_eq!(
+            pressure(drained.clone()).parameter("tank", "pressure"),
+            Some(&10)
+        );
+        let mut closed = state.clone();
+        closed.set_parameter("tank", "valve", 1);
+        assert_eq!(pressure(closed).parameter("tank", "pressure"), Some(&20));
+        assert_eq!(computations.load(std::sync::atomic::Ordering::Relaxed), 2);
+        assert_eq!(
+            set_function(
+                "tank",
+                "pressure",
+                Arc::new(|state: &State<i32>| state.parameter("tank", "level").unwrap() * 10)
+            )(drained.clone()),
+            pressure(drained.clone())
+        );
+
+        let fill = Assignment::increment(
+            "tank",
+            "level",
+            Expression::constant(2., Unit::dimensionless()),
+        );
+        assert_eq!(
+            fill.apply(state.clone()),
+            increment("tank", "level", 2.)(state)
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use std::{fmt::Debug, hash::Hash, marker::PhantomData, sync::Arc};
+
+use hashbrown::HashMap;
+
+use crate::models::rules::*;
+use crate::prelude::*;
+
+// The type states of the builder. A simulation can only be built once it has an initial
+// distribution and at least one rule, so forgetting either is a compile error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NoInitialDistribution;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NoRules;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HasRules;
+
+#[derive(Clone)]
+pub struct SimulationBuilder<S, I, R> {
+    initial_distribution: I,
+    rules: HashMap<RuleName, Rule<S>>,
+    options: RuleOptions,
+    sub_stochastic: bool,
+    state_validator: Option<StateValidator<S>>,
+    rules_state: PhantomData<R>,
+}
+
+impl<S> SimulationBuilder<S, NoInitialDistribution, NoRules> {
+    pub fn new() -> Self {
+        Self {
+            initial_distribution: NoInitialDistribution,
+            rules: HashMap::new(),
+            options: RuleOptions::default(),
+            sub_stochastic: false,
+            state_validator: None,
+            rules_state: PhantomData,
+        }
+    }
+}
+
+impl<S> Default for SimulationBuilder<S, NoInitialDistribution, NoRules> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S, I, R> SimulationBuilder<S, I, R>
+where
+    S: Hash + Eq,
+{
+    pub fn with_initial_state(
+        self,
+        initial_state: S,
+    ) -> SimulationBuilder<S, StateProbabilityDistribution<S>, R> {
+        self.with_initial_distribution(StateProbabilityDistribution::from([(initial_state, 1.)]))
+    }
+
+    // Replaces an initial state or distribution given before
+    pub fn with_initial_distribution(
+        self,
+        initial_distribution: StateProbabilityDistribution<S>,
+    ) -> SimulationBuilder<S, StateProbabilityDistribution<S>, R> {
+        assert!(
+            !initial_distribution.is_empty(),
+            "The initial distribution is empty"
+        );
+        SimulationBuilder {
+            initial_distribution,
+            rules: self.rules,
+            options: self.options,
+            sub_stochastic: self.sub_stochastic,
+            state_validator: self.state_validator,
+            rules_state: PhantomData,
+        }
+    }
+
+    // A rule with an existing name replaces it
+    pub fn with_rule(
+        mut self,
+        rule_name: impl Into<RuleName>,
+        rule: Rule<S>,
+    ) -> SimulationBuilder<S, I, HasRules> {
+        self.rules.insert(rule_name.into(), rule);
+        SimulationBuilder {
+            initial_distribution: self.initial_distribution,
+            rules: self.rules,
+            options: self.options,
+            sub_stochastic: self.sub_stochastic,
+            state_validator: self.state_validator,
+            rules_state: PhantomData,
+        }
+    }
+
+    pub fn with_options(mut self, options: RuleOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_sub_stochastic(mut self, sub_stochastic: bool) -> Self {
+        self.sub_stochastic = sub_stochastic;
+        self
+    }
+
+    pub fn with_state_validator(mut self, state_validator: StateValidator<S>) -> Self {
+        self.state_validator = Some(state_validator);
+        self
+    }
+}
+
+impl<S> SimulationBuilder<S, StateProbabilityDistribution<S>, HasRules>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + 'static,
+{
+    pub fn build(self) -> Simulation<S, String> {
+        let rule_generator = Arc::new(rule_state_transition_generator) as RuleGenerator<S, String>;
+        let (state_transition_generator, frontier_hook) =
+            rule_generator(self.rules.clone(), self.options);
+        let mut simulation = Simulation::new_with_distribution(
+            self.initial_distribution,
+            state_transition_generator,
+        );
+        simulation.set_frontier_hook(frontier_hook);
+        simulation.set_rules(self.rules);
+        simulation.set_rule_options(self.options);
+        simulation.set_rule_generator(rule_generator);
+        simulation.set_sub_stochastic(self.sub_stochastic);
+        if let Some(state_validator) = self.state_validator {
+            simulation.set_state_validator(state_validator);
+        }
+        simulation
+    }
+
+    pub fn run(self, steps: usize) -> Simulation<S, String> {
+        let mut simulation = self.build();
+        for _ in 0..steps {
+            simulation.next_step();
+        }
+        simulation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn builder() {
+        let increment = Rule::new(
+            "Increment".to_string(),
+            Arc::new(|count: u8| count < 2),
+            0.5,
+            Arc::new(|count| count + 1),
+        );
+        let built = SimulationBuilder::new()
+            .with_rule("increment", increment.clone())
+            .with_initial_state(0)
+            .run(2);
+        let mut expected = Simulation::from_rules(
+            0,
+            HashMap::from([("increment".to_string(), increment.clone())]),
+        );
+        expected.next_step();
+        expected.next_step();
+        assert_eq!(built.time(), 2);
+        assert_eq!(
+            built.probability_distribution(2),
+            expected.probability_distribution(2)
+        );
+        assert!(built.rules().unwrap().contains_key("increment"));
+
+        let from_distribution = SimulationBuilder::new()
+            .with_initial_distribution(StateProbabilityDistribution::from([(0, 0.5), (1, 0.5)]))
+            .with_options(RuleOptions::new().with_nothing_happens(NothingHappens::Remainder))
+            .with_rule("increment", increment)
+            .build();
+        assert_eq!(from_distribution.known_states().len(), 2);
+        assert_eq!(
+            from_distribution.rule_options().nothing_happens(),
+            NothingHappens::Remainder
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use std::{
+    fmt::Debug,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+
+use crate::error::SchemaError;
+use crate::models::conditions::*;
+use crate::models::declarative::*;
+use crate::models::entities::*;
+use crate::models::rules::*;
+use crate::prelude::*;
+
+// Indexes declarative rules by the parameters their conditions read, so after a rule was applied
+// only the rules reading one of the written parameters have to be evaluated again.
+#[derive(Debug, Clone, Default)]
+pub struct ConditionIndex {
+    readers: HashMap<ParameterName, Vec<(RuleName, EntitySelector)>>,
+}
+
+impl ConditionIndex {
+    pub fn new(rules: &HashMap<RuleName, DeclarativeRule>) -> Self {
+        let mut readers: HashMap<ParameterName, Vec<(RuleName, EntitySelector)>> = HashMap::new();
+        rules.iter().for_each(|(name, rule)| {
+            rule.reads().into_iter().for_each(|(selector, parameter)| {
+                readers
+                    .entry(parameter.clone())
+                    .or_default()
+                    .push((name.clone(), selector.clone()));
+            });
+        });
+        Self { readers }
+    }
+
+    // Indexes rules by their declared or probed read sets
+    pub fn from_access(rules: &HashMap<RuleName, RuleAccess>) -> Self {
+        let mut readers: HashMap<ParameterName, Vec<(RuleName, EntitySelector)>> = HashMap::new();
+        rules.iter().for_each(|(name, access)| {
+            access.reads().iter().for_each(|(selector, parameter)| {
+                readers
+                    .entry(parameter.clone())
+                    .or_default()
+                    .push((name.clone(), EntitySelector::from_description(selector)));
+            });
+        });
+        Self { readers }
+    }
+
+    pub fn readers(&self, parameter: &str) -> Vec<&RuleName> {
+        self.readers
+            .get(parameter)
+            .map(|readers| {
+                readers
+                    .iter()
+                    .map(|(name, _)| name)
+                    .unique()
+                    .sorted()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn affected_rules<'a>(
+        &self,
+        changed: impl IntoIterator<Item = (&'a EntitySelector, &'a ParameterName)>,
+    ) -> HashSet<RuleName> {
+        changed
+            .into_iter()
+            .flat_map(|(target, parameter)| {
+                self.readers
+                    .get(parameter)
+                    .into_iter()
+                    .flatten()
+                    .filter(move |(_, selector)| selector.may_overlap(target))
+                    .map(|(name, _)| name.clone())
+            })
+            .collect()
+    }
+}
+
+// Which rules applied to the state a successor was derived from, and which of them may have
+// changed by applying the rule leading to the successor
+type BaseState = (Arc<HashSet<RuleName>>, Arc<HashSet<RuleName>>);
+
+pub fn get_indexed_state_transition_generator<T>(
+    rules: HashMap<RuleName, DeclarativeRule>,
+    initial_state: &State<T>,
+    options: RuleOptions,
+) -> Result<StateTransitionGenerator<State<T>, String>, SchemaError>
+where
+    T: Numeric + Debug + Clone + Send + Sync + 'static + PartialEq + Eq + Hash,
+{
+    let index = ConditionIndex::new(&rules);
+    let affected = rules
+        .iter()
+        .map(|(name, rule)| (name.clone(), Arc::new(index.affected_rules(rule.writes()))))
+        .collect::<HashMap<_, _>>();
+    let rules = rules
+        .iter()
+        .map(|(name, rule)| Ok((name.clone(), rule.to_rule(initial_state)?)))
+        .collect::<Result<_, SchemaError>>()?;
+    Ok(indexed_state_transition_generator(rules, affected, options))
+}
+
+// The same as for declarative rules, but the index is built from the access of the rules, e.g.
+// after probing them. Panics if a rule neither declares nor was probed for its access.
+pub fn get_indexed_rule_state_transition_generator<T>(
+    rules: HashMap<RuleName, Rule<State<T>>>,
+    options: RuleOptions,
+) -> StateTransitionGenerator<State<T>, String>
+where
+    T: Numeric + Debug + Clone + Send + Sync + 'static + PartialEq + Eq + Hash,
+{
+    let access = rules
+        .iter()
+        .map(|(name, rule)| {
+            let access = rule
+                .access()
+                .unwrap_or_else(|| panic!("The access of rule {name} is unknown"));
+            (name.clone(), access.clone())
+        })
+        .collect::<HashMap<_, _>>();
+    let index = ConditionIndex::from_access(&access);
+    let affected = access
+        .iter()
+        .map(|(name, access)| {
+            let writes = access
+                .writes()
+                .iter()
+                .map(|(selector, parameter)| {
+                    (EntitySelector::from_description(selector), parameter)
+                })
+                .collect_vec();
+            let affected = index.affected_rules(
+                writes
+                    .iter()
+                    .map(|(selector, parameter)| (selector, *parameter)),
+            );
+            (name.clone(), Arc::new(affected))
+        })
+        .collect::<HashMap<_, _>>();
+    indexed_state_transition_generator(rules, affected, options)
+}
+
+fn indexed_state_transition_generator<T>(
+    rules: HashMap<RuleName, Rule<State<T>>>,
+    affected: HashMap<RuleName, Arc<HashSet<RuleName>>>,
+    options: RuleOptions,
+) -> StateTransitionGenerator<State<T>, String>
+where
+    T: Numeric + Debug + Clone + Send + Sync + 'static + PartialEq + Eq + Hash,
+{
+    let rules = options
+        .tie_breaking()
+        .order(&rules)
+        .into_iter()
+        .map(|(name, rule)| (name.clone(), rule.clone()))
+        .collect_vec();
+    let base_states: Mutex<HashMap<u64, BaseState>> = Mutex::new(HashMap::new());
+    Arc::new(
+        move |state: State<T>| -> OutgoingTransitions<State<T>, String> {
+            let base_state = base_states.lock().unwrap().remove(&hash(&state));
+            let applying_rules = rules
+                .iter()
+                .filter(|(name, rule)| match &base_state {
+                    Some((applying, affected)) if !affected.contains(name) => {
+                        applying.contains(name)
+                    }
+                    _ => rule.applies(state.clone()),
+                })
+                .collect_vec();
+            let applying = Arc::new(
+                applying_rules
+                    .iter()
+                    .map(|(name, _)| name.clone())
+                    .collect::<HashSet<_>>(),
+            );
+            let new_states = applying_rules
+                .into_iter()
+                .map(|(name, rule)| {
+                    let new_state = rule.apply(state.clone());
+                    base_states
+                        .lock()
+                        .unwrap()
+                        .entry(hash(&new_state))
+                        .or_insert_with(|| (applying.clone(), affected[name].clone()));
+                    (new_state, rule.weight(), rule.description().clone())
+                })
+                .collect_vec();
+            outgoing_transitions_with(state, new_states, options.nothing_happens())
+        },
+    ) as StateTransitionGenerator<State<T>, String>
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::units::*;
+
+    use super::*;
+
+    #[test]
+    fn indexed_generator() {
+        let level = |comparison, threshold| {
+            AggregateCondition::new(
+                Aggregate::Sum,
+                EntitySelector::Class("Tank".to_string()),
+                "level".to_string(),
+                comparison,
+                threshold,
+            )
+        };
+        let change = |parameter: &str, delta: f64| {
+            Assignment::new(
+                "tank",
+                parameter,
+                Expression::parameter("tank", parameter)
+                    + Expression::constant(delta, Unit::dimensionless()),
+            )
+        };
+        let rules = HashMap::from([
+            (
+                "fill".to_string(),
+                DeclarativeRule::new("Fill", 0.5)
+                    .with_condition(level(Comparison::Less, 3.))
+                    .with_assignment(change("level", 1.)),
+            ),
+            (
+                "drain".to_string(),
+                DeclarativeRule::new("Drain", 0.3)
+                    .with_condition(level(Comparison::Greater, 0.))
+                    .with_assignment(change("level", -1.)),
+            ),
+            (
+                "age".to_string(),
+                DeclarativeRule::new("Age", 0.1)
+                    .with_condition(AggregateCondition::new(
+                        Aggregate::Max,
+                        EntitySelector::All,
+                        "age".to_string(),
+                        Comparison::Less,
+                        2.,
+                    ))
+                    .with_assignment(change("age", 1.)),
+            ),
+        ]);
+        let index = ConditionIndex::new(&rules);
+        assert_eq!(index.readers("level"), vec!["drain", "fill"]);
+        let tank = EntitySelector::Name("tank".to_string());
+        let age = "age".to_string();
+        assert_eq!(
+            index.affected_rules([(&tank, &age)]),
+            HashSet::from(["age".to_string()])
+        );
+
+        let initial_state = State::new().with_entity(
+            "tank",
+            StateEntity::of_class("Tank")
+                .with_parameter("level", 0)
+                .with_parameter("age", 0),
+        );
+        let plain_rules = rules
+            .iter()
+            .map(|(name, rule)| (name.clone(), rule.to_rule(&initial_state).unwrap()))
+            .collect::<HashMap<_, _>>();
+        let mut indexed = Simulation::new(
+            initial_state.clone(),
+            get_indexed_state_transition_generator(rules, &initial_state, RuleOptions::default())
+                .unwrap(),
+        );
+        let mut plain = Simulation::from_rules(initial_state, plain_rules);
+        for _ in 0..6 {
+            indexed.next_step();
+            plain.next_step();
+        }
+        assert_eq!(indexed.known_states().len(), 12);
+        assert!(indexed.distribution_approx_eq(&plain, 6, Tolerance::Absolute(1e-12)));
+    }
+}
@@ -0,0 +1,399 @@
+use std::{
+    fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use crate::models::entities::*;
+use crate::models::rules::*;
+
+type EntityPredicateFunction = Arc<dyn Fn(&str, &StateEntity<f64>) -> bool + Send + Sync>;
+
+// A predicate on the name and the numeric parameters of an entity. Predicates are compared and
+// hashed by their description, so equal descriptions have to mean equal predicates.
+#[derive(Clone)]
+pub struct EntityPredicate {
+    description: String,
+    predicate: EntityPredicateFunction,
+}
+
+impl EntityPredicate {
+    pub fn new(
+        description: impl Into<String>,
+        predicate: impl Fn(&str, &StateEntity<f64>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            description: description.into(),
+            predicate: Arc::new(predicate),
+        }
+    }
+
+    pub fn description(&self) -> &String {
+        &self.description
+    }
+
+    pub fn evaluate<T: Numeric>(&self, entity_name: &str, entity: &StateEntity<T>) -> bool {
+        let numeric_entity = entity.parameters().fold(
+            entity
+                .class()
+                .map(StateEntity::of_class)
+                .unwrap_or_default(),
+            |numeric_entity, (parameter_name, value)| {
+                numeric_entity.with_parameter(parameter_name.clone(), value.to_f64())
+            },
+        );
+        (self.predicate)(entity_name, &numeric_entity)
+    }
+}
+
+impl Debug for EntityPredicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("EntityPredicate")
+            .field(&self.description)
+            .finish()
+    }
+}
+
+impl PartialEq for EntityPredicate {
+    fn eq(&self, other: &Self) -> bool {
+        self.description == other.description
+    }
+}
+
+impl Eq for EntityPredicate {}
+
+impl Hash for EntityPredicate {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.description.hash(state);
+    }
+}
+
+// Selectors are resolved against each state when a condition or assignment is evaluated
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EntitySelector {
+    All,
+    Name(EntityName),
+    Class(ClassName),
+    Predicate(EntityPredicate),
+}
+
+// Entity names are written as they are, so a class can't be told apart from an entity which
+// is named like "class Tree"
+impl Display for EntitySelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EntitySelector::All => write!(f, "*"),
+            EntitySelector::Name(name) => write!(f, "{name}"),
+            EntitySelector::Class(class) => write!(f, "class {class}"),
+            EntitySelector::Predicate(predicate) => write!(f, "where {}", predicate.description),
+        }
+    }
+}
+
+impl EntitySelector {
+    // The inverse of Display. A predicate can't be restored from its description, so it becomes
+    // All, which selects at least the same entities.
+    pub fn from_description(description: &str) -> Self {
+        if description == "*" || description.starts_with("where ") {
+            EntitySelector::All
+        } else if let Some(class) = description.strip_prefix("class ") {
+            EntitySelector::Class(class.to_string())
+        } else {
+            EntitySelector::Name(description.to_string())
+        }
+    }
+
+    pub fn matches<T: Numeric>(&self, entity_name: &str, entity: &StateEntity<T>) -> bool {
+        match self {
+            EntitySelector::All => true,
+            EntitySelector::Name(name) => name == entity_name,
+            EntitySelector::Class(class) => entity.is_of_class(class),
+            EntitySelector::Predicate(predicate) => predicate.evaluate(entity_name, entity),
+        }
+    }
+
+    // Without a state only the entity name is known, so this errs on the side of selecting
+    pub fn may_select(&self, entity_name: &str) -> bool {
+        match self {
+            EntitySelector::Name(name) => name == entity_name,
+            EntitySelector::All | EntitySelector::Class(_) | EntitySelector::Predicate(_) => true,
+        }
+    }
+
+    // Whether there may be a state in which both selectors select the same entity
+    pub fn may_overlap(&self, other: &EntitySelector) -> bool {
+        match (self, other) {
+            (EntitySelector::Name(name), other) | (other, EntitySelector::Name(name)) => {
+                other.may_select(name)
+            }
+            (EntitySelector::Class(class), EntitySelector::Class(other_class)) => {
+                class == other_class
+            }
+            _ => true,
+        }
+    }
+
+    pub fn select<'a, T: Numeric>(
+        &'a self,
+        state: &'a State<T>,
+    ) -> impl Iterator<Item = (&'a EntityName, &'a StateEntity<T>)> + 'a {
+        state
+            .entities()
+            .filter(move |(name, entity)| self.matches(name, entity))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Aggregate {
+    Sum,
+    Mean,
+    Min,
+    Max,
+    Count,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    pub fn compare(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Less => value < threshold,
+            Comparison::LessOrEqual => value <= threshold,
+            Comparison::Equal => value == threshold,
+            Comparison::NotEqual => value != threshold,
+            Comparison::GreaterOrEqual => value >= threshold,
+            Comparison::Greater => value > threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateCondition {
+    aggregate: Aggregate,
+    selector: EntitySelector,
+    parameter: ParameterName,
+    comparison: Comparison,
+    threshold: f64,
+}
+
+impl AggregateCondition {
+    pub fn new(
+        aggregate: Aggregate,
+        selector: EntitySelector,
+        parameter: ParameterName,
+        comparison: Comparison,
+        threshold: f64,
+    ) -> Self {
+        Self {
+            aggregate,
+            selector,
+            parameter,
+            comparison,
+            threshold,
+        }
+    }
+
+    pub fn aggregate(&self) -> Aggregate {
+        self.aggregate
+    }
+
+    pub fn selector(&self) -> &EntitySelector {
+        &self.selector
+    }
+
+    pub fn parameter(&self) -> &ParameterName {
+        &self.parameter
+    }
+
+    pub fn comparison(&self) -> Comparison {
+        self.comparison
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    pub fn value<T: Numeric>(&self, state: &State<T>) -> Option<f64> {
+        let values = self
+            .selector
+            .select(state)
+            .filter_map(|(_, entity)| entity.parameter(&self.parameter))
+            .map(|value| value.to_f64());
+        match self.aggregate {
+            Aggregate::Sum => Some(values.sum()),
+            Aggregate::Count => Some(values.count() as f64),
+            Aggregate::Mean => {
+                let (sum, count) =
+                    values.fold((0., 0), |(sum, count), value| (sum + value, count + 1));
+                (count > 0).then(|| sum / count as f64)
+            }
+            Aggregate::Min => values.reduce(f64::min),
+            Aggregate::Max => values.reduce(f64::max),
+        }
+    }
+
+    pub fn evaluate<T: Numeric>(&self, state: &State<T>) -> RuleApplies {
+        self.value(state)
+            .map(|value| self.comparison.compare(value, self.threshold))
+            .unwrap_or(false)
+    }
+
+    pub fn to_condition<T: Numeric>(&self) -> Arc<dyn Fn(State<T>) -> RuleApplies + Send + Sync> {
+        let condition = self.clone();
+        Arc::new(move |state: State<T>| condition.evaluate(&state))
+    }
+}
+
+pub type StateCondition<T> = Arc<dyn Fn(State<T>) -> RuleApplies + Send + Sync>;
+
+// Ready-made conditions on a single parameter, false if the parameter does not exist
+pub fn parameter_compares<T>(
+    entity: impl Into<EntityName>,
+    parameter: impl Into<ParameterName>,
+    comparison: Comparison,
+    threshold: f64,
+) -> StateCondition<T>
+where
+    T: Numeric + 'static,
+{
+    let (entity, parameter) = (entity.into(), parameter.into());
+    Arc::new(move |state: State<T>| {
+        state
+            .parameter(&entity, &parameter)
+            .is_some_and(|value| comparison.compare(value.to_f64(), threshold))
+    })
+}
+
+pub fn parameter_at_least<T>(
+    entity: impl Into<EntityName>,
+    parameter: impl Into<ParameterName>,
+    value: f64,
+) -> StateCondition<T>
+where
+    T: Numeric + 'static,
+{
+    parameter_compares(entity, parameter, Comparison::GreaterOrEqual, value)
+}
+
+pub fn parameter_at_most<T>(
+    entity: impl Into<EntityName>,
+    parameter: impl Into<ParameterName>,
+    value: f64,
+) -> StateCondition<T>
+where
+    T: Numeric + 'static,
+{
+    parameter_compares(entity, parameter, Comparison::LessOrEqual, value)
+}
+
+pub fn parameter_equals<T>(
+    entity: impl Into<EntityName>,
+    parameter: impl Into<ParameterName>,
+    value: f64,
+) -> StateCondition<T>
+where
+    T: Numeric + 'static,
+{
+    parameter_compares(entity, parameter, Comparison::Equal, value)
+}
+
+pub fn all_of<T>(conditions: Vec<StateCondition<T>>) -> StateCondition<T>
+where
+    T: Clone + 'static,
+{
+    Arc::new(move |state: State<T>| conditions.iter().all(|condition| condition(state.clone())))
+}
+
+pub fn any_of<T>(conditions: Vec<StateCondition<T>>) -> StateCondition<T>
+where
+    T: Clone + 'static,
+{
+    Arc::new(move |state: State<T>| conditions.iter().any(|condition| condition(state.clone())))
+}
+
+pub fn not<T>(condition: StateCondition<T>) -> StateCondition<T>
+where
+    T: 'static,
+{
+    Arc::new(move |state: State<T>| !condition(state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregates() {
+        let state = State::new()
+            .with_entity("a", StateEntity::of_class("Tree").with_parameter("fire", 2))
+            .with_entity("b", StateEntity::of_class("Tree").with_parameter("fire", 0))
+            .with_entity("c", StateEntity::of_class("Tree").with_parameter("fire", 1))
+            .with_entity("d", StateEntity::of_class("Lake").with_parameter("fire", 5));
+        let trees = EntitySelector::Class("Tree".to_string());
+        let condition = |aggregate, comparison, threshold| {
+            AggregateCondition::new(
+                aggregate,
+                trees.clone(),
+                "fire".to_string(),
+                comparison,
+                threshold,
+            )
+        };
+        assert_eq!(
+            condition(Aggregate::Sum, Comparison::Equal, 3.).value(&state),
+            Some(3.)
+        );
+        assert_eq!(
+            condition(Aggregate::Mean, Comparison::Equal, 1.).value(&state),
+            Some(1.)
+        );
+        assert!(condition(Aggregate::Max, Comparison::GreaterOrEqual, 2.).evaluate(&state));
+        assert!(condition(Aggregate::Min, Comparison::Equal, 0.).evaluate(&state));
+        assert!(!condition(Aggregate::Count, Comparison::Greater, 3.).evaluate(&state));
+        assert!(AggregateCondition::new(
+            Aggregate::Max,
+            EntitySelector::All,
+            "fire".to_string(),
+            Comparison::Equal,
+            5.
+        )
+        .to_condition()(state.clone()));
+        assert!(!AggregateCondition::new(
+            Aggregate::Max,
+            EntitySelector::Class("Cloud".to_string()),
+            "fire".to_string(),
+            Comparison::Less,
+            1.
+        )
+        .evaluate(&state));
+
+        let oak = EntitySelector::Name("a".to_string());
+        let burning = EntitySelector::Predicate(EntityPredicate::new("burning", |_, entity| {
+            entity.parameter("fire").is_some_and(|fire| *fire > 0.)
+        }));
+        assert_eq!(
+            oak.select(&state).map(|(name, _)| name).collect::<Vec<_>>(),
+            vec!["a"]
+        );
+        assert_eq!(
+            burning
+                .select(&state)
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>(),
+            vec!["a", "c", "d"]
+        );
+        assert!(!oak.may_select("b"));
+        assert!(oak.may_overlap(&trees));
+        assert!(!oak.may_overlap(&EntitySelector::Name("b".to_string())));
+        assert!(!trees.may_overlap(&EntitySelector::Class("Lake".to_string())));
+        assert!(burning.may_overlap(&trees));
+    }
+}
@@ -0,0 +1,210 @@
+use std::sync::Arc;
+
+use crate::error::SchemaError;
+use crate::models::conditions::*;
+use crate::models::entities::*;
+use crate::models::rules::*;
+use crate::models::units::*;
+
+// A rule built only from aggregate conditions and assignments. Unlike a closure based rule it
+// can be inspected, e.g. to find out which parameters it reads and writes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeclarativeRule {
+    description: String,
+    conditions: Vec<AggregateCondition>,
+    weight: ProbabilityWeight,
+    assignments: Vec<Assignment>,
+}
+
+impl DeclarativeRule {
+    pub fn new(description: impl Into<String>, weight: ProbabilityWeight) -> Self {
+        Self {
+            description: description.into(),
+            conditions: Vec::new(),
+            weight,
+            assignments: Vec::new(),
+        }
+    }
+
+    pub fn with_condition(mut self, condition: AggregateCondition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    pub fn with_assignment(mut self, assignment: Assignment) -> Self {
+        self.assignments.push(assignment);
+        self
+    }
+
+    pub fn description(&self) -> &String {
+        &self.description
+    }
+
+    pub fn conditions(&self) -> &Vec<AggregateCondition> {
+        &self.conditions
+    }
+
+    pub fn weight(&self) -> ProbabilityWeight {
+        self.weight
+    }
+
+    pub fn assignments(&self) -> &Vec<Assignment> {
+        &self.assignments
+    }
+
+    // Parameters the conditions depend on
+    pub fn reads(&self) -> Vec<(&EntitySelector, &ParameterName)> {
+        self.conditions
+            .iter()
+            .map(|condition| (condition.selector(), condition.parameter()))
+            .collect()
+    }
+
+    pub fn writes(&self) -> Vec<(&EntitySelector, &ParameterName)> {
+        self.assignments
+            .iter()
+            .map(|assignment| (assignment.target(), assignment.parameter()))
+            .collect()
+    }
+
+    // Whether applying this rule can change whether the other rule applies
+    pub fn affects(&self, other: &DeclarativeRule) -> bool {
+        !self.affected_parameters(other).is_empty()
+    }
+
+    pub fn affected_parameters(&self, other: &DeclarativeRule) -> Vec<ParameterName> {
+        let mut parameters = self
+            .writes()
+            .into_iter()
+            .filter(|(target, parameter)| {
+                other.reads().into_iter().any(|(selector, read_parameter)| {
+                    read_parameter == *parameter && selector.may_overlap(target)
+                })
+            })
+            .map(|(_, parameter)| parameter.clone())
+            .collect::<Vec<_>>();
+        parameters.sort();
+        parameters.dedup();
+        parameters
+    }
+
+    pub fn applies<T: Numeric>(&self, state: &State<T>) -> RuleApplies {
+        self.conditions
+            .iter()
+            .all(|condition| condition.evaluate(state))
+    }
+
+    pub fn apply<T: Numeric>(&self, state: State<T>) -> State<T> {
+        self.assignments
+            .iter()
+            .fold(state, |state, assignment| assignment.apply(state))
+    }
+
+    pub fn check_parameters<T: Numeric>(&self, state: &State<T>) -> Result<(), SchemaError> {
+        self.assignments
+            .iter()
+            .try_for_each(|assignment| assignment.check_parameters(state))
+    }
+
+    // The assignments are checked against the initial state of the model
+    pub fn to_rule<T>(&self, initial_state: &State<T>) -> Result<Rule<State<T>>, SchemaError>
+    where
+        T: Numeric + 'static,
+    {
+        self.check_parameters(initial_state)?;
+        let condition = self.clone();
+        let action = self.clone();
+        Ok(Rule::new(
+            self.description.clone(),
+            Arc::new(move |state: State<T>| condition.applies(&state)),
+            self.weight,
+            Arc::new(move |state: State<T>| action.apply(state)),
+        )
+        .with_access(self.access()))
+    }
+
+    // Unlike reads, this includes the parameters the assignments are computed from
+    pub fn access(&self) -> RuleAccess {
+        let accessed = |(selector, parameter): (&EntitySelector, &ParameterName)| {
+            (selector.to_string(), parameter.clone())
+        };
+        let expression_reads = self.assignments.iter().flat_map(|assignment| {
+            assignment
+                .expression()
+                .parameters()
+                .into_iter()
+                .map(|(entity, parameter)| match entity {
+                    Some(entity) => (entity.clone(), parameter.clone()),
+                    None => accessed((assignment.target(), parameter)),
+                })
+        });
+        let reads = self
+            .reads()
+            .into_iter()
+            .map(accessed)
+            .chain(expression_reads)
+            .collect();
+        RuleAccess::new(reads, self.writes().into_iter().map(accessed).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declarative_rule() {
+        let ignite = DeclarativeRule::new("Ignite", 0.5)
+            .with_condition(AggregateCondition::new(
+                Aggregate::Max,
+                EntitySelector::Class("Tree".to_string()),
+                "fire".to_string(),
+                Comparison::Equal,
+                0.,
+            ))
+            .with_assignment(Assignment::new(
+                "oak",
+                "fire",
+                Expression::parameter("oak", "fire")
+                    + Expression::constant(1., Unit::dimensionless()),
+            ));
+        let state = State::new().with_entity(
+            "oak",
+            StateEntity::of_class("Tree").with_parameter("fire", 0),
+        );
+        assert!(ignite.applies(&state));
+        let burning = ignite.apply(state.clone());
+        assert_eq!(burning.parameter("oak", "fire"), Some(&1));
+        assert!(!ignite.applies(&burning));
+        assert!(ignite.affects(&ignite));
+        assert_eq!(
+            ignite.affected_parameters(&ignite),
+            vec!["fire".to_string()]
+        );
+
+        let rule = ignite.to_rule(&state).unwrap();
+        assert_eq!(
+            ignite.to_rule(&State::<i32>::new()).err(),
+            Some(SchemaError::UnknownEntity {
+                entity: "oak".to_string()
+            })
+        );
+        assert!(rule.applies(state.clone()));
+        assert_eq!(rule.apply(state.clone()), burning);
+
+        let spread = DeclarativeRule::new("Spread", 0.5).with_assignment(Assignment::to_selected(
+            EntitySelector::Class("Tree".to_string()),
+            "fire",
+            Expression::selected("fire") + Expression::parameter("oak", "fire"),
+        ));
+        let forest = burning.with_entity(
+            "pine",
+            StateEntity::of_class("Tree").with_parameter("fire", 2),
+        );
+        let spread_forest = spread.apply(forest);
+        assert_eq!(spread_forest.parameter("oak", "fire"), Some(&2));
+        assert_eq!(spread_forest.parameter("pine", "fire"), Some(&3));
+        assert!(spread.affects(&ignite));
+        assert!(!ignite.affects(&spread));
+    }
+}
@@ -0,0 +1,453 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hash::hash;
+
+pub type EntityName = String;
+pub type ParameterName = String;
+pub type RelationName = String;
+pub type ClassName = String;
+
+pub trait Numeric {
+    fn to_f64(&self) -> f64;
+
+    fn from_f64(value: f64) -> Self;
+}
+
+macro_rules! impl_numeric {
+    ($($numeric:ty),*) => {
+        $(
+            impl Numeric for $numeric {
+                fn to_f64(&self) -> f64 {
+                    *self as f64
+                }
+
+                fn from_f64(value: f64) -> Self {
+                    value as $numeric
+                }
+            }
+        )*
+    };
+}
+
+impl_numeric!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64);
+
+impl Numeric for bool {
+    fn to_f64(&self) -> f64 {
+        if *self {
+            1.
+        } else {
+            0.
+        }
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value != 0.
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct StateEntity<T> {
+    class: Option<ClassName>,
+    parameters: BTreeMap<ParameterName, T>,
+}
+
+impl<T> StateEntity<T> {
+    pub fn new() -> Self {
+        Self {
+            class: None,
+            parameters: BTreeMap::new(),
+        }
+    }
+
+    pub fn of_class(class: impl Into<ClassName>) -> Self {
+        Self {
+            class: Some(class.into()),
+            parameters: BTreeMap::new(),
+        }
+    }
+
+    pub fn class(&self) -> Option<&ClassName> {
+        self.class.as_ref()
+    }
+
+    pub fn is_of_class(&self, class: &str) -> bool {
+        self.class.as_deref() == Some(class)
+    }
+
+    pub fn parameter(&self, parameter_name: &str) -> Option<&T> {
+        self.parameters.get(parameter_name)
+    }
+
+    pub fn parameter_mut(&mut self, parameter_name: &str) -> Option<&mut T> {
+        self.parameters.get_mut(parameter_name)
+    }
+
+    pub fn set_parameter(&mut self, parameter_name: impl Into<ParameterName>, value: T) {
+        self.parameters.insert(parameter_name.into(), value);
+    }
+
+    pub fn with_parameter(mut self, parameter_name: impl Into<ParameterName>, value: T) -> Self {
+        self.set_parameter(parameter_name, value);
+        self
+    }
+
+    pub fn parameters(&self) -> impl Iterator<Item = (&ParameterName, &T)> {
+        self.parameters.iter()
+    }
+}
+
+impl<T> From<BTreeMap<ParameterName, T>> for StateEntity<T> {
+    fn from(parameters: BTreeMap<ParameterName, T>) -> Self {
+        Self {
+            class: None,
+            parameters,
+        }
+    }
+}
+
+impl<T, N: Into<ParameterName>> FromIterator<(N, T)> for StateEntity<T> {
+    fn from_iter<I: IntoIterator<Item = (N, T)>>(iter: I) -> Self {
+        iter.into_iter()
+            .map(|(name, value)| (name.into(), value))
+            .collect::<BTreeMap<_, _>>()
+            .into()
+    }
+}
+
+// The known states and distributions of a simulation of states are persisted with a Checkpoint
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct State<T> {
+    entities: BTreeMap<EntityName, StateEntity<T>>,
+    links: BTreeMap<RelationName, BTreeSet<(EntityName, EntityName)>>,
+}
+
+// The hash of a state is composed of the hashes of its entities, so caches can be keyed on only
+// the entities they depend on
+impl<T: Hash> Hash for State<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.entity_hashes().for_each(|(name, entity_hash)| {
+            name.hash(state);
+            entity_hash.hash(state);
+        });
+        self.links.hash(state);
+    }
+}
+
+impl<T: Hash> State<T> {
+    pub fn entity_hash(&self, entity_name: &str) -> Option<u64> {
+        self.entities.get(entity_name).map(hash)
+    }
+
+    pub fn entity_hashes(&self) -> impl Iterator<Item = (&EntityName, u64)> {
+        self.entities
+            .iter()
+            .map(|(name, entity)| (name, hash(entity)))
+    }
+}
+
+impl<T> State<T> {
+    pub fn new() -> Self {
+        Self {
+            entities: BTreeMap::new(),
+            links: BTreeMap::new(),
+        }
+    }
+
+    pub fn entity(&self, entity_name: &str) -> Option<&StateEntity<T>> {
+        self.entities.get(entity_name)
+    }
+
+    pub fn entity_mut(&mut self, entity_name: &str) -> Option<&mut StateEntity<T>> {
+        self.entities.get_mut(entity_name)
+    }
+
+    pub fn insert_entity(&mut self, entity_name: impl Into<EntityName>, entity: StateEntity<T>) {
+        self.entities.insert(entity_name.into(), entity);
+    }
+
+    pub fn with_entity(
+        mut self,
+        entity_name: impl Into<EntityName>,
+        entity: StateEntity<T>,
+    ) -> Self {
+        self.insert_entity(entity_name, entity);
+        self
+    }
+
+    pub fn remove_entity(&mut self, entity_name: &str) -> Option<StateEntity<T>> {
+        self.links.values_mut().for_each(|links| {
+            links.retain(|(source, target)| source != entity_name && target != entity_name)
+        });
+        self.links.retain(|_, links| !links.is_empty());
+        self.entities.remove(entity_name)
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = (&EntityName, &StateEntity<T>)> {
+        self.entities.iter()
+    }
+
+    pub fn entities_of_class<'a>(
+        &'a self,
+        class: &'a str,
+    ) -> impl Iterator<Item = (&'a EntityName, &'a StateEntity<T>)> + 'a {
+        self.entities
+            .iter()
+            .filter(move |(_, entity)| entity.is_of_class(class))
+    }
+
+    pub fn parameter(&self, entity_name: &str, parameter_name: &str) -> Option<&T> {
+        self.entity(entity_name)
+            .and_then(|entity| entity.parameter(parameter_name))
+    }
+
+    pub fn set_parameter(
+        &mut self,
+        entity_name: impl Into<EntityName>,
+        parameter_name: impl Into<ParameterName>,
+        value: T,
+    ) {
+        self.entities
+            .entry(entity_name.into())
+            .or_insert_with(StateEntity::new)
+            .set_parameter(parameter_name, value);
+    }
+
+    pub fn link(
+        &mut self,
+        relation: impl Into<RelationName>,
+        source: impl Into<EntityName>,
+        target: impl Into<EntityName>,
+    ) {
+        self.links
+            .entry(relation.into())
+            .or_default()
+            .insert((source.into(), target.into()));
+    }
+
+    pub fn with_link(
+        mut self,
+        relation: impl Into<RelationName>,
+        source: impl Into<EntityName>,
+        target: impl Into<EntityName>,
+    ) -> Self {
+        self.link(relation, source, target);
+        self
+    }
+
+    pub fn unlink(&mut self, relation: &str, source: &str, target: &str) -> bool {
+        let Some(links) = self.links.get_mut(relation) else {
+            return false;
+        };
+        let removed = links.remove(&(source.to_string(), target.to_string()));
+        if links.is_empty() {
+            self.links.remove(relation);
+        }
+        removed
+    }
+
+    pub fn is_linked(&self, relation: &str, source: &str, target: &str) -> bool {
+        self.links
+            .get(relation)
+            .map(|links| links.contains(&(source.to_string(), target.to_string())))
+            .unwrap_or(false)
+    }
+
+    pub fn linked<'a>(
+        &'a self,
+        relation: &str,
+        source: &'a str,
+    ) -> impl Iterator<Item = &'a EntityName> + 'a {
+        self.links
+            .get(relation)
+            .into_iter()
+            .flatten()
+            .filter(move |(link_source, _)| link_source == source)
+            .map(|(_, target)| target)
+    }
+
+    pub fn linked_entities<'a>(
+        &'a self,
+        relation: &str,
+        source: &'a str,
+    ) -> impl Iterator<Item = (&'a EntityName, &'a StateEntity<T>)> + 'a {
+        self.linked(relation, source)
+            .filter_map(|target| self.entity(target).map(|entity| (target, entity)))
+    }
+
+    pub fn links(&self) -> impl Iterator<Item = (&RelationName, &EntityName, &EntityName)> {
+        self.links.iter().flat_map(|(relation, links)| {
+            links
+                .iter()
+                .map(move |(source, target)| (relation, source, target))
+        })
+    }
+}
+
+impl<T: Clone> State<T> {
+    pub fn set_parameter_of_linked(
+        &mut self,
+        relation: &str,
+        source: &str,
+        parameter_name: &str,
+        value: T,
+    ) {
+        let targets = self.linked(relation, source).cloned().collect::<Vec<_>>();
+        targets.into_iter().for_each(|target| {
+            self.set_parameter(target, parameter_name, value.clone());
+        });
+    }
+}
+
+impl<T> From<BTreeMap<EntityName, StateEntity<T>>> for State<T> {
+    fn from(entities: BTreeMap<EntityName, StateEntity<T>>) -> Self {
+        Self {
+            entities,
+            links: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T, N: Into<EntityName>> FromIterator<(N, StateEntity<T>)> for State<T> {
+    fn from_iter<I: IntoIterator<Item = (N, StateEntity<T>)>>(iter: I) -> Self {
+        iter.into_iter()
+            .map(|(name, entity)| (name.into(), entity))
+            .collect::<BTreeMap<_, _>>()
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameters() {
+        let mut state = State::new()
+            .with_entity("a", StateEntity::new().with_parameter("x", 1))
+            .with_entity("b", StateEntity::from_iter([("x", 2), ("y", 3)]));
+        assert_eq!(state.parameter("a", "x"), Some(&1));
+        assert_eq!(state.parameter("b", "y"), Some(&3));
+        assert_eq!(state.parameter("c", "x"), None);
+
+        state.set_parameter("c", "x", 4);
+        assert_eq!(state.parameter("c", "x"), Some(&4));
+        assert_eq!(state.entities().count(), 3);
+
+        let other = State::new()
+            .with_entity("c", StateEntity::new().with_parameter("x", 4))
+            .with_entity("b", StateEntity::from_iter([("y", 3), ("x", 2)]))
+            .with_entity("a", StateEntity::new().with_parameter("x", 1));
+        assert_eq!(state, other);
+        assert_eq!(hash(&state), hash(&other));
+    }
+
+    #[test]
+    fn links() {
+        let mut state = State::new()
+            .with_entity("owner", StateEntity::new().with_parameter("wealth", 10))
+            .with_entity("house", StateEntity::new().with_parameter("value", 5))
+            .with_entity("car", StateEntity::new().with_parameter("value", 2))
+            .with_link("owns", "owner", "house")
+            .with_link("owns", "owner", "car");
+        assert!(state.is_linked("owns", "owner", "car"));
+        assert!(!state.is_linked("owns", "car", "owner"));
+        assert_eq!(state.linked("owns", "owner").count(), 2);
+        assert_eq!(
+            state
+                .linked_entities("owns", "owner")
+                .filter_map(|(_, entity)| entity.parameter("value"))
+                .sum::<i32>(),
+            7
+        );
+
+        state.set_parameter_of_linked("owns", "owner", "value", 0);
+        assert_eq!(state.parameter("house", "value"), Some(&0));
+        assert_eq!(state.parameter("car", "value"), Some(&0));
+
+        let unlinked = state.clone();
+        assert!(state.unlink("owns", "owner", "car"));
+        assert_ne!(hash(&state), hash(&unlinked));
+
+        state.remove_entity("house");
+        assert_eq!(state.links().count(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn serialization() {
+        let state = State::new()
+            .with_entity(
+                "owner",
+                StateEntity::of_class("person").with_parameter("wealth", 10),
+            )
+            .with_entity("house", StateEntity::new().with_parameter("value", 5))
+            .with_link("owns", "owner", "house");
+        let json = serde_json::to_string(&state).unwrap();
+        let deserialized = serde_json::from_str::<State<i32>>(&json).unwrap();
+        assert_eq!(deserialized, state);
+        assert_eq!(hash(&deserialized), hash(&state));
+        assert_eq!(
+            deserialized.entity_hash("owner"),
+            state.entity_hash("owner")
+        );
+
+        // Maps with states as keys have no JSON representation, so distributions are pairs
+        let distribution = vec![(state.clone(), 0.25), (State::new(), 0.75)];
+        let json = serde_json::to_string(&distribution).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<(State<i32>, f64)>>(&json).unwrap(),
+            distribution
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn checkpoint() {
+        use std::sync::Arc;
+
+        use crate::persistence::checkpoint::Checkpoint;
+        use crate::prelude::*;
+
+        let state_transition_generator = Arc::new(|state: State<i32>| {
+            let wealth = *state.parameter("owner", "wealth").unwrap();
+            let mut richer = state.clone();
+            richer.set_parameter("owner".to_string(), "wealth", (wealth + 1).min(2));
+            vec![
+                (richer, "earn".to_string(), 0.5),
+                (state, "stay".to_string(), 0.5),
+            ]
+        });
+        let initial_state = State::new().with_entity(
+            "owner",
+            StateEntity::of_class("person").with_parameter("wealth", 0),
+        );
+        let mut simulation = Simulation::new(initial_state, state_transition_generator.clone());
+        simulation.next_step();
+        simulation.next_step();
+        let json = simulation.checkpoint().to_json().unwrap();
+        let restored = Simulation::restore_checkpoint(
+            Checkpoint::from_json(&json).unwrap(),
+            state_transition_generator,
+        );
+        assert_eq!(
+            restored.probability_distribution(2),
+            simulation.probability_distribution(2)
+        );
+        let sorted = |mut states: Vec<State<i32>>| {
+            states.sort_by_key(|state| state.parameter("owner", "wealth").copied());
+            states
+        };
+        assert_eq!(restored.known_states().len(), 3);
+        assert_eq!(
+            sorted(restored.known_states()),
+            sorted(simulation.known_states())
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt::Debug;
+
+use hashbrown::HashMap;
+
+use crate::models::entities::*;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+#[derive(Clone, PartialEq, Eq, Hash, Default)]
+pub struct PackedState {
+    words: Box<[u64]>,
+}
+
+impl PackedState {
+    fn with_bits(num_bits: usize) -> Self {
+        Self {
+            words: vec![0; num_bits.div_ceil(WORD_BITS)].into_boxed_slice(),
+        }
+    }
+
+    pub fn bit(&self, index: usize) -> bool {
+        self.words[index / WORD_BITS] & (1 << (index % WORD_BITS)) != 0
+    }
+
+    pub fn set_bit(&mut self, index: usize, value: bool) {
+        let mask = 1 << (index % WORD_BITS);
+        if value {
+            self.words[index / WORD_BITS] |= mask;
+        } else {
+            self.words[index / WORD_BITS] &= !mask;
+        }
+    }
+
+    pub fn count_ones(&self) -> u32 {
+        self.words.iter().map(|word| word.count_ones()).sum()
+    }
+}
+
+impl Debug for PackedState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PackedState(")?;
+        for word in self.words.iter() {
+            write!(f, "{word:064b}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FlagSchema {
+    entities: Vec<(EntityName, Vec<ParameterName>)>,
+    offsets: HashMap<EntityName, (usize, usize)>,
+    num_bits: usize,
+}
+
+impl FlagSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_entity(
+        mut self,
+        entity_name: impl Into<EntityName>,
+        parameters: impl IntoIterator<Item = impl Into<ParameterName>>,
+    ) -> Self {
+        let entity_name = entity_name.into();
+        let parameters = parameters.into_iter().map(Into::into).collect::<Vec<_>>();
+        // Declaring an entity again replaces its parameters, so the offsets of all following
+        // entities have to be recomputed
+        match self.offsets.get(&entity_name) {
+            Some((_, position)) => self.entities[*position].1 = parameters,
+            None => self.entities.push((entity_name, parameters)),
+        }
+        self.offsets.clear();
+        self.num_bits = 0;
+        self.entities
+            .iter()
+            .enumerate()
+            .for_each(|(position, (entity_name, parameters))| {
+                self.offsets
+                    .insert(entity_name.clone(), (self.num_bits, position));
+                self.num_bits += parameters.len();
+            });
+        self
+    }
+
+    pub fn num_bits(&self) -> usize {
+        self.num_bits
+    }
+
+    pub fn index(&self, entity_name: &str, parameter_name: &str) -> Option<usize> {
+        let (offset, position) = self.offsets.get(entity_name)?;
+        let (_, parameters) = &self.entities[*position];
+        parameters
+            .iter()
+            .position(|parameter| parameter == parameter_name)
+            .map(|position| offset + position)
+    }
+
+    pub fn empty_state(&self) -> PackedState {
+        PackedState::with_bits(self.num_bits)
+    }
+
+    pub fn get(
+        &self,
+        state: &PackedState,
+        entity_name: &str,
+        parameter_name: &str,
+    ) -> Option<bool> {
+        self.index(entity_name, parameter_name)
+            .map(|index| state.bit(index))
+    }
+
+    pub fn set(
+        &self,
+        state: &mut PackedState,
+        entity_name: &str,
+        parameter_name: &str,
+        value: bool,
+    ) -> Option<()> {
+        let index = self.index(entity_name, parameter_name)?;
+        state.set_bit(index, value);
+        Some(())
+    }
+
+    pub fn pack(&self, state: &State<bool>) -> PackedState {
+        let mut packed = self.empty_state();
+        let mut index = 0;
+        self.entities.iter().for_each(|(entity_name, parameters)| {
+            parameters.iter().for_each(|parameter_name| {
+                let value = state
+                    .parameter(entity_name, parameter_name)
+                    .copied()
+                    .unwrap_or(false);
+                packed.set_bit(index, value);
+                index += 1;
+            });
+        });
+        packed
+    }
+
+    pub fn unpack(&self, packed: &PackedState) -> State<bool> {
+        let mut index = 0;
+        self.entities
+            .iter()
+            .map(|(entity_name, parameters)| {
+                let entity = parameters
+                    .iter()
+                    .map(|parameter_name| {
+                        let value = packed.bit(index);
+                        index += 1;
+                        (parameter_name.clone(), value)
+                    })
+                    .collect::<StateEntity<bool>>();
+                (entity_name.clone(), entity)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn roundtrip() {
+        let schema = FlagSchema::new()
+            .with_entity("door", ["open", "locked"])
+            .with_entity("light", ["on"]);
+        assert_eq!(schema.num_bits(), 3);
+        assert_eq!(schema.index("light", "on"), Some(2));
+        assert_eq!(schema.index("light", "off"), None);
+
+        let state = State::new()
+            .with_entity(
+                "door",
+                StateEntity::from_iter([("open", true), ("locked", false)]),
+            )
+            .with_entity("light", StateEntity::from_iter([("on", true)]));
+        let packed = schema.pack(&state);
+        assert_eq!(packed.count_ones(), 2);
+        assert_eq!(schema.get(&packed, "door", "open"), Some(true));
+        assert_eq!(schema.unpack(&packed), state);
+
+        let redeclared = FlagSchema::new()
+            .with_entity("door", ["open"])
+            .with_entity("light", ["on"])
+            .with_entity("door", ["open", "locked"]);
+        assert_eq!(redeclared, schema);
+        assert_eq!(redeclared.index("light", "on"), Some(2));
+
+        let mut wide_schema = FlagSchema::new();
+        for index in 0..100 {
+            wide_schema = wide_schema.with_entity(format!("cell{index}"), ["alive"]);
+        }
+        let mut wide_state = wide_schema.empty_state();
+        wide_schema.set(&mut wide_state, "cell99", "alive", true);
+        assert!(wide_state.bit(99));
+        assert_eq!(wide_state.count_ones(), 1);
+    }
+
+    #[test]
+    fn packed_simulation() {
+        let schema = Arc::new(FlagSchema::new().with_entity("switch", ["a", "b"]));
+        let transition_schema = schema.clone();
+        let state_transition_generator = Arc::new(move |state: PackedState| {
+            ["a", "b"]
+                .into_iter()
+                .map(|parameter| {
+                    let mut new_state = state.clone();
+                    let value = transition_schema.get(&state, "switch", parameter).unwrap();
+                    transition_schema.set(&mut new_state, "switch", parameter, !value);
+                    (new_state, parameter, 0.5)
+                })
+                .collect::<Vec<_>>()
+        });
+        let mut simulation = Simulation::new(schema.empty_state(), state_transition_generator);
+        simulation.explore();
+        assert_eq!(simulation.known_states().len(), 4);
+    }
+}
@@ -0,0 +1,164 @@
+use std::{fmt::Debug, hash::Hash};
+
+use rayon::prelude::*;
+
+use crate::models::conditions::*;
+use crate::models::entities::*;
+use crate::prelude::*;
+
+// All states which can be built from a template by choosing a value from the range of every free
+// parameter, restricted by declarative constraints like bounds on sums.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitialStateSpace<V> {
+    template: State<V>,
+    ranges: Vec<(EntityName, ParameterName, Vec<V>)>,
+    constraints: Vec<AggregateCondition>,
+}
+
+impl<V> InitialStateSpace<V>
+where
+    V: Numeric + Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Entities of the template which do not exist yet are created by the ranges
+    pub fn new(template: State<V>) -> Self {
+        Self {
+            template,
+            ranges: Vec::new(),
+            constraints: Vec::new(),
+        }
+    }
+
+    pub fn with_range(
+        mut self,
+        entity_name: impl Into<EntityName>,
+        parameter_name: impl Into<ParameterName>,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        self.ranges.push((
+            entity_name.into(),
+            parameter_name.into(),
+            values.into_iter().collect(),
+        ));
+        self
+    }
+
+    pub fn with_constraint(mut self, constraint: AggregateCondition) -> Self {
+        self.constraints.push(constraint);
+        self
+    }
+
+    pub fn template(&self) -> &State<V> {
+        &self.template
+    }
+
+    pub fn constraints(&self) -> &Vec<AggregateCondition> {
+        &self.constraints
+    }
+
+    // Number of combinations before the constraints are applied
+    pub fn combinations(&self) -> usize {
+        self.ranges
+            .iter()
+            .map(|(_, _, values)| values.len())
+            .product()
+    }
+
+    pub fn states(&self) -> Vec<State<V>> {
+        let combinations = self.combinations();
+        (0..combinations)
+            .filter_map(|mut combination| {
+                let mut state = self.template.clone();
+                for (entity_name, parameter_name, values) in self.ranges.iter().rev() {
+                    let value = values[combination % values.len()].clone();
+                    combination /= values.len();
+                    match state.entity_mut(entity_name) {
+                        Some(entity) => entity.set_parameter(parameter_name.clone(), value),
+                        None => state.insert_entity(
+                            entity_name.clone(),
+                            StateEntity::new().with_parameter(parameter_name.clone(), value),
+                        ),
+                    }
+                }
+                self.constraints
+                    .iter()
+                    .all(|constraint| constraint.evaluate(&state))
+                    .then_some(state)
+            })
+            .collect()
+    }
+
+    pub fn uniform_distribution(&self) -> StateProbabilityDistribution<State<V>> {
+        let states = self.states();
+        let probability = 1. / states.len() as Probability;
+        states
+            .into_iter()
+            .map(|state| (state, probability))
+            .collect()
+    }
+
+    // Runs a study for every initial state in parallel, in the order of states
+    pub fn sweep<R>(&self, run: impl Fn(&State<V>) -> R + Send + Sync) -> Vec<(State<V>, R)>
+    where
+        R: Send,
+    {
+        self.states()
+            .into_par_iter()
+            .map(|state| {
+                let result = run(&state);
+                (state, result)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn initial_state_space() {
+        // Three servers with up to two jobs each and at most three jobs in total
+        let template = ["a", "b", "c"]
+            .into_iter()
+            .fold(State::new(), |state, server| {
+                state.with_entity(server, StateEntity::of_class("server"))
+            });
+        let space = ["a", "b", "c"]
+            .into_iter()
+            .fold(InitialStateSpace::new(template), |space, server| {
+                space.with_range(server, "jobs", 0..=2)
+            })
+            .with_constraint(AggregateCondition::new(
+                Aggregate::Sum,
+                EntitySelector::Class("server".to_string()),
+                "jobs".to_string(),
+                Comparison::LessOrEqual,
+                3.,
+            ));
+        assert_eq!(space.combinations(), 27);
+        let states = space.states();
+        // All combinations except those with a sum of 4, 5 or 6
+        assert_eq!(states.len(), 27 - 6 - 3 - 1);
+        assert!(states
+            .iter()
+            .all(|state| state.entity("a").unwrap().is_of_class("server")));
+        assert_eq!(states[1].parameter("c", "jobs"), Some(&1));
+
+        let results = space.sweep(|initial_state| {
+            let mut simulation = Simulation::new(
+                initial_state.clone(),
+                Arc::new(|state: State<i32>| vec![(state, "idle", 1.)]),
+            );
+            simulation.next_step();
+            simulation.known_states().len()
+        });
+        assert_eq!(results.len(), states.len());
+        assert!(results.iter().all(|(_, known_states)| *known_states == 1));
+        assert_eq!(
+            space.uniform_distribution().values().sum::<f64>().round(),
+            1.
+        );
+    }
+}
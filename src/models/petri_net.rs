@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use hashbrown::HashMap;
+
+use crate::models::entities::*;
+use crate::models::rules::*;
+
+pub type PlaceName = EntityName;
+pub type Tokens = u64;
+pub type Marking = State<Tokens>;
+pub type ArcWeights = HashMap<PlaceName, Tokens>;
+
+pub const TOKENS: &str = "tokens";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PetriNetTransition {
+    description: String,
+    weight: ProbabilityWeight,
+    inputs: ArcWeights,
+    outputs: ArcWeights,
+}
+
+impl PetriNetTransition {
+    pub fn new(
+        description: String,
+        weight: ProbabilityWeight,
+        inputs: ArcWeights,
+        outputs: ArcWeights,
+    ) -> Self {
+        Self {
+            description,
+            weight,
+            inputs,
+            outputs,
+        }
+    }
+
+    pub fn description(&self) -> &String {
+        &self.description
+    }
+
+    pub fn weight(&self) -> ProbabilityWeight {
+        self.weight
+    }
+
+    pub fn inputs(&self) -> &ArcWeights {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &ArcWeights {
+        &self.outputs
+    }
+
+    pub fn is_enabled(&self, marking: &Marking) -> bool {
+        self.inputs
+            .iter()
+            .all(|(place, weight)| tokens(marking, place) >= *weight)
+    }
+
+    // None if the transition is not enabled in the marking
+    pub fn fire(&self, marking: Marking) -> Option<Marking> {
+        let mut marking = marking;
+        for (place, weight) in &self.inputs {
+            let remaining = tokens(&marking, place).checked_sub(*weight)?;
+            marking.set_parameter(place.clone(), TOKENS, remaining);
+        }
+        self.outputs.iter().for_each(|(place, weight)| {
+            let produced = tokens(&marking, place) + weight;
+            marking.set_parameter(place.clone(), TOKENS, produced);
+        });
+        Some(marking)
+    }
+
+    pub fn to_rule(&self) -> Rule<Marking> {
+        let enabled_transition = self.clone();
+        let fired_transition = self.clone();
+        Rule::new(
+            self.description.clone(),
+            Arc::new(move |marking: Marking| enabled_transition.is_enabled(&marking)),
+            self.weight,
+            // A disabled transition leaves the marking unchanged, e.g. in a dry run of the rule
+            Arc::new(move |marking: Marking| {
+                fired_transition.fire(marking.clone()).unwrap_or(marking)
+            }),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PetriNet {
+    places: HashMap<PlaceName, Tokens>,
+    transitions: HashMap<RuleName, PetriNetTransition>,
+}
+
+impl PetriNet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_place(&mut self, place: PlaceName, initial_tokens: Tokens) {
+        self.places.insert(place, initial_tokens);
+    }
+
+    pub fn add_transition(&mut self, name: RuleName, transition: PetriNetTransition) {
+        transition
+            .inputs
+            .keys()
+            .chain(transition.outputs.keys())
+            .for_each(|place| {
+                self.places.entry(place.clone()).or_insert(0);
+            });
+        self.transitions.insert(name, transition);
+    }
+
+    pub fn places(&self) -> &HashMap<PlaceName, Tokens> {
+        &self.places
+    }
+
+    pub fn transitions(&self) -> &HashMap<RuleName, PetriNetTransition> {
+        &self.transitions
+    }
+
+    pub fn initial_marking(&self) -> Marking {
+        self.places
+            .iter()
+            .map(|(place, initial_tokens)| {
+                (
+                    place.clone(),
+                    StateEntity::new().with_parameter(TOKENS, *initial_tokens),
+                )
+            })
+            .collect()
+    }
+
+    pub fn rules(&self) -> HashMap<RuleName, Rule<Marking>> {
+        self.transitions
+            .iter()
+            .map(|(name, transition)| (name.clone(), transition.to_rule()))
+            .collect()
+    }
+}
+
+pub fn tokens(marking: &Marking, place: &str) -> Tokens {
+    marking.parameter(place, TOKENS).copied().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn producer_consumer() {
+        let mut petri_net = PetriNet::new();
+        petri_net.add_place("buffer".to_string(), 0);
+        petri_net.add_place("capacity".to_string(), 2);
+        petri_net.add_transition(
+            "produce".to_string(),
+            PetriNetTransition::new(
+                "Produce".to_string(),
+                0.5,
+                HashMap::from([("capacity".to_string(), 1)]),
+                HashMap::from([("buffer".to_string(), 1)]),
+            ),
+        );
+        petri_net.add_transition(
+            "consume".to_string(),
+            PetriNetTransition::new(
+                "Consume".to_string(),
+                0.5,
+                HashMap::from([("buffer".to_string(), 1)]),
+                HashMap::from([("capacity".to_string(), 1)]),
+            ),
+        );
+
+        let initial_marking = petri_net.initial_marking();
+        assert_eq!(tokens(&initial_marking, "buffer"), 0);
+        assert_eq!(tokens(&initial_marking, "capacity"), 2);
+        let consume = &petri_net.transitions()["consume"];
+        assert_eq!(consume.fire(initial_marking.clone()), None);
+        assert_eq!(
+            consume.to_rule().apply(initial_marking.clone()),
+            initial_marking
+        );
+
+        let state_transition_generator = get_state_transition_generator(petri_net.rules());
+        let mut simulation = Simulation::new(initial_marking, state_transition_generator);
+        simulation.full_traversal(false);
+        assert_eq!(simulation.known_states().len(), 3);
+        assert!(simulation
+            .known_states()
+            .iter()
+            .all(|marking| tokens(marking, "buffer") + tokens(marking, "capacity") == 2));
+    }
+}
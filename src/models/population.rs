@@ -0,0 +1,200 @@
+use std::{fmt::Debug, hash::Hash, sync::Arc};
+
+use hashbrown::HashMap;
+
+use crate::models::entities::*;
+use crate::models::rules::*;
+use crate::prelude::*;
+
+pub type PopulationCondition<T> =
+    Arc<dyn Fn(&EntityName, &StateEntity<T>, &State<T>) -> RuleApplies + Send + Sync>;
+pub type PopulationAction<T> = Arc<dyn Fn(&EntityName, State<T>) -> State<T> + Send + Sync>;
+
+#[derive(Clone)]
+pub struct PopulationRule<T> {
+    description: String,
+    class: ClassName,
+    condition: PopulationCondition<T>,
+    weight: ProbabilityWeight,
+    action: PopulationAction<T>,
+}
+
+impl<T> Debug for PopulationRule<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "PopulationRule:")?;
+        writeln!(f, "Description: {}", self.description)?;
+        writeln!(f, "Class: {}", self.class)?;
+        writeln!(f, "Weight: {}", self.weight)?;
+        Ok(())
+    }
+}
+
+impl<T> PopulationRule<T> {
+    pub fn new(
+        description: String,
+        class: ClassName,
+        condition: PopulationCondition<T>,
+        probability_weight: ProbabilityWeight,
+        action: PopulationAction<T>,
+    ) -> Self {
+        Self {
+            description,
+            class,
+            condition,
+            weight: probability_weight,
+            action,
+        }
+    }
+
+    pub fn description(&self) -> &String {
+        &self.description
+    }
+
+    pub fn class(&self) -> &ClassName {
+        &self.class
+    }
+
+    pub fn weight(&self) -> ProbabilityWeight {
+        self.weight
+    }
+
+    pub fn matching_entities<'a>(
+        &'a self,
+        state: &'a State<T>,
+    ) -> impl Iterator<Item = &'a EntityName> + 'a {
+        state
+            .entities_of_class(&self.class)
+            .filter(move |(name, entity)| (self.condition)(name, entity, state))
+            .map(|(name, _)| name)
+    }
+
+    pub fn instantiate(&self, entity_name: EntityName) -> Rule<State<T>>
+    where
+        T: 'static,
+    {
+        let condition = self.condition.clone();
+        let condition_entity = entity_name.clone();
+        let action = self.action.clone();
+        let action_entity = entity_name.clone();
+        Rule::new(
+            format!("{} ({entity_name})", self.description),
+            Arc::new(move |state: State<T>| {
+                state
+                    .entity(&condition_entity)
+                    .map(|entity| condition(&condition_entity, entity, &state))
+                    .unwrap_or(false)
+            }),
+            self.weight,
+            Arc::new(move |state: State<T>| action(&action_entity, state)),
+        )
+    }
+}
+
+pub fn get_population_state_transition_generator<T>(
+    rules: HashMap<RuleName, Rule<State<T>>>,
+    population_rules: HashMap<RuleName, PopulationRule<T>>,
+    options: RuleOptions,
+) -> StateTransitionGenerator<State<T>, String>
+where
+    T: Debug + Clone + Send + Sync + 'static + PartialEq + Eq + Hash,
+{
+    let rules = options
+        .tie_breaking()
+        .order(&rules)
+        .into_iter()
+        .map(|(_, rule)| rule.clone())
+        .collect::<Vec<_>>();
+    let population_rules = options
+        .tie_breaking()
+        .order(&population_rules)
+        .into_iter()
+        .map(|(_, rule)| rule.clone())
+        .collect::<Vec<_>>();
+    Arc::new(
+        move |state: State<T>| -> OutgoingTransitions<State<T>, String> {
+            let new_states = rules
+                .iter()
+                .filter(|rule| rule.applies(state.clone()))
+                .map(|rule| {
+                    (
+                        rule.apply(state.clone()),
+                        rule.weight(),
+                        rule.description().clone(),
+                    )
+                })
+                .chain(population_rules.iter().flat_map(|population_rule| {
+                    population_rule
+                        .matching_entities(&state)
+                        .map(|entity_name| {
+                            (
+                                (population_rule.action)(entity_name, state.clone()),
+                                population_rule.weight,
+                                format!("{} ({entity_name})", population_rule.description),
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                }))
+                .collect::<Vec<_>>();
+            outgoing_transitions_with(state, new_states, options.nothing_happens())
+        },
+    ) as StateTransitionGenerator<State<T>, String>
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_dead_agents() {
+        let initial_state = State::new()
+            .with_entity(
+                "alice",
+                StateEntity::of_class("Agent").with_parameter("hp", 0),
+            )
+            .with_entity(
+                "bob",
+                StateEntity::of_class("Agent").with_parameter("hp", 0),
+            )
+            .with_entity(
+                "carol",
+                StateEntity::of_class("Agent").with_parameter("hp", 3),
+            )
+            .with_entity(
+                "rock",
+                StateEntity::of_class("Obstacle").with_parameter("hp", 0),
+            );
+        let remove_dead = PopulationRule::new(
+            "Remove dead agent".to_string(),
+            "Agent".to_string(),
+            Arc::new(|_, entity: &StateEntity<i32>, _| entity.parameter("hp") < Some(&1)),
+            1.,
+            Arc::new(|entity_name, mut state| {
+                state.remove_entity(entity_name);
+                state
+            }),
+        );
+        assert_eq!(remove_dead.matching_entities(&initial_state).count(), 2);
+
+        let state_transition_generator = get_population_state_transition_generator(
+            HashMap::new(),
+            HashMap::from([("remove_dead".to_string(), remove_dead.clone())]),
+            RuleOptions::new().with_nothing_happens(NothingHappens::Remainder),
+        );
+        let mut simulation = Simulation::new(initial_state.clone(), state_transition_generator);
+        simulation.full_traversal(false);
+        let final_distribution = simulation.probability_distribution(simulation.time());
+        assert_eq!(final_distribution.len(), 1);
+        let final_state = final_distribution.keys().next().unwrap();
+        assert!(final_state.entity("alice").is_none());
+        assert!(final_state.entity("bob").is_none());
+        assert!(final_state.entity("carol").is_some());
+        assert!(final_state.entity("rock").is_some());
+
+        let rule = remove_dead.instantiate("alice".to_string());
+        assert!(rule.applies(initial_state.clone()));
+        assert!(!remove_dead
+            .instantiate("carol".to_string())
+            .applies(initial_state.clone()));
+        assert!(rule.apply(initial_state).entity("alice").is_none());
+    }
+}
@@ -0,0 +1,269 @@
+use std::{fmt::Debug, hash::Hash};
+
+use hashbrown::HashMap;
+
+use crate::models::conditions::*;
+use crate::models::entities::*;
+use crate::models::rules::*;
+use crate::prelude::*;
+
+// Perturbations applied to every parameter of a probe state to find out whether it is read
+const PERTURBATIONS: [f64; 2] = [1., -1.];
+
+fn changed_parameters<V: PartialEq>(before: &State<V>, after: &State<V>) -> Vec<AccessedParameter> {
+    let changed = |from: &State<V>, to: &State<V>| {
+        from.entities()
+            .flat_map(|(entity_name, entity)| {
+                entity
+                    .parameters()
+                    .filter(|(parameter_name, value)| {
+                        to.parameter(entity_name, parameter_name) != Some(*value)
+                    })
+                    .map(|(parameter_name, _)| (entity_name.clone(), parameter_name.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut parameters = changed(after, before);
+    parameters.extend(changed(before, after));
+    parameters
+}
+
+// Whether the action reads the perturbed parameter. The successor of the perturbed state may
+// only differ from the original successor in the perturbed parameter, and only if the action
+// did not overwrite it.
+fn action_reads<V>(
+    rule: &Rule<State<V>>,
+    successor: &State<V>,
+    perturbed: &State<V>,
+    (entity_name, parameter_name): (&str, &str),
+) -> bool
+where
+    V: Clone + PartialEq,
+{
+    let perturbed_successor = rule.apply(perturbed.clone());
+    let original_value = successor.parameter(entity_name, parameter_name);
+    let perturbed_value = perturbed_successor.parameter(entity_name, parameter_name);
+    if perturbed_value != original_value
+        && perturbed_value != perturbed.parameter(entity_name, parameter_name)
+    {
+        return true;
+    }
+    let mut normalized = perturbed_successor.clone();
+    if let Some(value) = original_value {
+        normalized.set_parameter(entity_name, parameter_name, value.clone());
+    }
+    normalized != *successor
+}
+
+// Infers which parameters an opaque rule reads and writes by running it on the given states.
+// Writes are the parameters its action changes, reads are the parameters whose perturbation
+// changes whether the condition holds or what the action does. The result is only as complete
+// as the probe states are representative, and the action is only run on perturbed states where
+// the condition still holds.
+pub fn probe_access<V>(rule: &Rule<State<V>>, states: &[State<V>]) -> RuleAccess
+where
+    V: Numeric + Clone + PartialEq,
+{
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+    for state in states {
+        let applies = rule.applies(state.clone());
+        let successor = applies.then(|| rule.apply(state.clone()));
+        if let Some(successor) = &successor {
+            writes.extend(changed_parameters(state, successor));
+        }
+        for (entity_name, entity) in state.entities() {
+            for (parameter_name, value) in entity.parameters() {
+                let is_read = PERTURBATIONS.iter().any(|delta| {
+                    let mut perturbed = state.clone();
+                    let perturbed_value = V::from_f64(value.to_f64() + delta);
+                    perturbed.set_parameter(entity_name, parameter_name, perturbed_value);
+                    if perturbed == *state {
+                        return false;
+                    }
+                    if rule.applies(perturbed.clone()) != applies {
+                        return true;
+                    }
+                    successor.as_ref().is_some_and(|successor| {
+                        action_reads(rule, successor, &perturbed, (entity_name, parameter_name))
+                    })
+                });
+                if is_read {
+                    reads.push((entity_name.clone(), parameter_name.clone()));
+                }
+            }
+        }
+    }
+    RuleAccess::new(reads, writes)
+}
+
+// Parameters of the first list which may also be in the second one
+fn overlapping(writes: &[AccessedParameter], accessed: &[AccessedParameter]) -> Vec<ParameterName> {
+    writes
+        .iter()
+        .filter(|(written, parameter)| {
+            accessed.iter().any(|(selector, accessed_parameter)| {
+                accessed_parameter == parameter
+                    && EntitySelector::from_description(selector)
+                        .may_overlap(&EntitySelector::from_description(written))
+            })
+        })
+        .map(|(_, parameter)| parameter.clone())
+        .collect()
+}
+
+impl RuleAccess {
+    // Parameters which one of the rules writes and the other one reads or writes, so the order in
+    // which the rules are applied may matter
+    pub fn conflicts_with(&self, other: &RuleAccess) -> Vec<ParameterName> {
+        let mut parameters = overlapping(self.writes(), other.reads());
+        parameters.extend(overlapping(self.writes(), other.writes()));
+        parameters.extend(overlapping(other.writes(), self.reads()));
+        parameters.sort();
+        parameters.dedup();
+        parameters
+    }
+
+    // Parameters both rules write, so applying both overwrites the result of one of them
+    pub fn shared_writes(&self, other: &RuleAccess) -> Vec<ParameterName> {
+        let mut parameters = overlapping(self.writes(), other.writes());
+        parameters.sort();
+        parameters.dedup();
+        parameters
+    }
+}
+
+impl<V> Simulation<State<V>, String>
+where
+    V: Numeric + Debug + Clone + Send + Sync + 'static + PartialEq + Eq + Hash,
+{
+    // Probes all rules which don't declare their access on the known states, so explore the
+    // simulation a few steps first. Returns the names of the probed rules.
+    pub fn probe_rule_access(&mut self) -> Result<Vec<RuleName>, SimulationError> {
+        let states = self.known_states();
+        let mut rules = self.rules().ok_or(SimulationError::NoRules)?.clone();
+        let mut probed = rules
+            .iter_mut()
+            .filter(|(_, rule)| rule.access().is_none())
+            .map(|(name, rule)| {
+                *rule = rule.clone().with_access(probe_access(rule, &states));
+                name.clone()
+            })
+            .collect::<Vec<_>>();
+        probed.sort();
+        // The access does not change any transitions, so nothing has to be recomputed
+        self.set_rules(rules);
+        Ok(probed)
+    }
+
+    pub fn rule_conflicts(
+        &self,
+    ) -> Result<HashMap<(RuleName, RuleName), Vec<ParameterName>>, SimulationError> {
+        let rules = self
+            .rules()
+            .ok_or(SimulationError::NoRules)?
+            .iter()
+            .filter_map(|(name, rule)| rule.access().map(|access| (name, access)))
+            .collect::<Vec<_>>();
+        Ok(rules
+            .iter()
+            .flat_map(|(name, access)| {
+                rules
+                    .iter()
+                    .filter(move |(other_name, _)| name < other_name)
+                    .filter_map(move |(other_name, other_access)| {
+                        let parameters = access.conflicts_with(other_access);
+                        (!parameters.is_empty())
+                            .then(|| (((*name).clone(), (*other_name).clone()), parameters))
+                    })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::models::condition_index::*;
+
+    use super::*;
+
+    #[test]
+    fn probed_rules() {
+        let fill = Rule::new(
+            "Fill".to_string(),
+            Arc::new(|state: State<i32>| state.parameter("tank", "level") < Some(&3)),
+            0.5,
+            Arc::new(|mut state: State<i32>| {
+                let level = state.parameter("tank", "level").unwrap() + 1;
+                state.set_parameter("tank", "level", level);
+                state
+            }),
+        );
+        let drain = Rule::new(
+            "Drain".to_string(),
+            Arc::new(|state: State<i32>| state.parameter("tank", "level") > Some(&0)),
+            0.3,
+            Arc::new(|mut state: State<i32>| {
+                let level = state.parameter("tank", "level").unwrap() - 1;
+                state.set_parameter("tank", "level", level);
+                state.set_parameter("tank", "valve", 1);
+                state
+            }),
+        );
+        let age = Rule::new(
+            "Age".to_string(),
+            Arc::new(|state: State<i32>| state.parameter("tank", "age") < Some(&2)),
+            0.1,
+            Arc::new(|mut state: State<i32>| {
+                let age = state.parameter("tank", "age").unwrap() + 1;
+                state.set_parameter("tank", "age", age);
+                state
+            }),
+        );
+        let initial_state = State::new().with_entity(
+            "tank",
+            StateEntity::new()
+                .with_parameter("level", 0)
+                .with_parameter("age", 0)
+                .with_parameter("valve", 0),
+        );
+        let rules = HashMap::from([
+            ("fill".to_string(), fill),
+            ("drain".to_string(), drain),
+            ("age".to_string(), age),
+        ]);
+        let mut simulation = Simulation::from_rules(initial_state.clone(), rules);
+        simulation.next_step();
+        simulation.next_step();
+        assert_eq!(
+            simulation.probe_rule_access().unwrap(),
+            vec!["age", "drain", "fill"]
+        );
+        let probed_rules = simulation.rules().unwrap().clone();
+        let drain = probed_rules["drain"].access().unwrap();
+        let level = ("tank".to_string(), "level".to_string());
+        let valve = ("tank".to_string(), "valve".to_string());
+        assert_eq!(drain.reads(), &vec![level.clone()]);
+        assert_eq!(drain.writes(), &vec![level, valve]);
+        assert_eq!(
+            simulation.rule_conflicts().unwrap(),
+            HashMap::from([(
+                ("drain".to_string(), "fill".to_string()),
+                vec!["level".to_string()]
+            )])
+        );
+
+        let mut indexed = Simulation::new(
+            initial_state,
+            get_indexed_rule_state_transition_generator(probed_rules, RuleOptions::default()),
+        );
+        for _ in 0..6 {
+            indexed.next_step();
+            simulation.next_step();
+        }
+        assert!(indexed.distribution_approx_eq(&simulation, 6, Tolerance::Absolute(1e-12)));
+    }
+}
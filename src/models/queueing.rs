@@ -0,0 +1,286 @@
+use std::sync::Arc;
+
+use hashbrown::HashMap;
+
+use crate::error::SchemaError;
+use crate::models::entities::*;
+use crate::models::rules::*;
+
+pub type QueueName = EntityName;
+pub type QueueLength = u64;
+pub type QueueState = State<QueueLength>;
+
+pub const LENGTH: &str = "length";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Queue {
+    capacity: Option<QueueLength>,
+    initial_length: QueueLength,
+}
+
+impl Queue {
+    pub fn unbounded() -> Self {
+        Self {
+            capacity: None,
+            initial_length: 0,
+        }
+    }
+
+    pub fn finite(capacity: QueueLength) -> Self {
+        Self {
+            capacity: Some(capacity),
+            initial_length: 0,
+        }
+    }
+
+    pub fn with_initial_length(mut self, initial_length: QueueLength) -> Self {
+        self.initial_length = initial_length;
+        self
+    }
+
+    pub fn capacity(&self) -> Option<QueueLength> {
+        self.capacity
+    }
+
+    pub fn initial_length(&self) -> QueueLength {
+        self.initial_length
+    }
+
+    fn is_full(&self, length: QueueLength) -> bool {
+        self.capacity
+            .map(|capacity| length >= capacity)
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrivalProcess {
+    queue: QueueName,
+    probability: ProbabilityWeight,
+}
+
+impl ArrivalProcess {
+    pub fn new(queue: QueueName, probability: ProbabilityWeight) -> Self {
+        Self { queue, probability }
+    }
+
+    pub fn queue(&self) -> &QueueName {
+        &self.queue
+    }
+
+    pub fn probability(&self) -> ProbabilityWeight {
+        self.probability
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Server {
+    queue: QueueName,
+    service_probability: ProbabilityWeight,
+    destination: Option<QueueName>,
+}
+
+impl Server {
+    pub fn new(queue: QueueName, service_probability: ProbabilityWeight) -> Self {
+        Self {
+            queue,
+            service_probability,
+            destination: None,
+        }
+    }
+
+    pub fn with_destination(mut self, destination: QueueName) -> Self {
+        self.destination = Some(destination);
+        self
+    }
+
+    pub fn queue(&self) -> &QueueName {
+        &self.queue
+    }
+
+    pub fn service_probability(&self) -> ProbabilityWeight {
+        self.service_probability
+    }
+
+    pub fn destination(&self) -> Option<&QueueName> {
+        self.destination.as_ref()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueueingModel {
+    queues: HashMap<QueueName, Queue>,
+    arrivals: HashMap<RuleName, ArrivalProcess>,
+    servers: HashMap<RuleName, Server>,
+}
+
+impl QueueingModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_queue(mut self, name: impl Into<QueueName>, queue: Queue) -> Self {
+        self.queues.insert(name.into(), queue);
+        self
+    }
+
+    pub fn with_arrival(mut self, name: impl Into<RuleName>, arrival: ArrivalProcess) -> Self {
+        self.arrivals.insert(name.into(), arrival);
+        self
+    }
+
+    pub fn with_server(mut self, name: impl Into<RuleName>, server: Server) -> Self {
+        self.servers.insert(name.into(), server);
+        self
+    }
+
+    pub fn queues(&self) -> &HashMap<QueueName, Queue> {
+        &self.queues
+    }
+
+    pub fn initial_state(&self) -> QueueState {
+        self.queues
+            .iter()
+            .map(|(name, queue)| {
+                (
+                    name.clone(),
+                    StateEntity::new().with_parameter(LENGTH, queue.initial_length),
+                )
+            })
+            .collect()
+    }
+
+    // Arrivals and servers may only refer to declared queues
+    pub fn rules(&self) -> Result<HashMap<RuleName, Rule<QueueState>>, SchemaError> {
+        let arrival_rules = self.arrivals.iter().map(|(name, arrival)| {
+            let queue = self.queue(&arrival.queue)?;
+            let queue_name = arrival.queue.clone();
+            let target_name = arrival.queue.clone();
+            let rule = Rule::new(
+                format!("Arrival at {}", arrival.queue),
+                Arc::new(move |state: QueueState| !queue.is_full(length(&state, &queue_name))),
+                arrival.probability,
+                Arc::new(move |state: QueueState| add_to_queue(state, &target_name, 1)),
+            );
+            Ok((name.clone(), rule))
+        });
+        let server_rules = self.servers.iter().map(|(name, server)| {
+            self.queue(&server.queue)?;
+            let destination = server
+                .destination
+                .clone()
+                .map(|destination| Ok((self.queue(&destination)?, destination)))
+                .transpose()?;
+            let condition_destination = destination.clone();
+            let queue_name = server.queue.clone();
+            let source_name = server.queue.clone();
+            let description = match &server.destination {
+                Some(destination) => format!("Service from {} to {}", server.queue, destination),
+                None => format!("Service at {}", server.queue),
+            };
+            let rule = Rule::new(
+                description,
+                Arc::new(move |state: QueueState| {
+                    length(&state, &queue_name) > 0
+                        && condition_destination
+                            .as_ref()
+                            .map(|(queue, name)| !queue.is_full(length(&state, name)))
+                            .unwrap_or(true)
+                }),
+                server.service_probability,
+                Arc::new(move |state: QueueState| {
+                    let state = remove_from_queue(state, &source_name, 1);
+                    match &destination {
+                        Some((_, destination)) => add_to_queue(state, destination, 1),
+                        None => state,
+                    }
+                }),
+            );
+            Ok((name.clone(), rule))
+        });
+        arrival_rules.chain(server_rules).collect()
+    }
+
+    fn queue(&self, name: &str) -> Result<Queue, SchemaError> {
+        self.queues
+            .get(name)
+            .copied()
+            .ok_or_else(|| SchemaError::UnknownEntity {
+                entity: name.to_string(),
+            })
+    }
+}
+
+pub fn length(state: &QueueState, queue: &str) -> QueueLength {
+    state.parameter(queue, LENGTH).copied().unwrap_or(0)
+}
+
+fn add_to_queue(mut state: QueueState, queue: &str, amount: QueueLength) -> QueueState {
+    let new_length = length(&state, queue) + amount;
+    state.set_parameter(queue, LENGTH, new_length);
+    state
+}
+
+fn remove_from_queue(mut state: QueueState, queue: &str, amount: QueueLength) -> QueueState {
+    let new_length = length(&state, queue) - amount;
+    state.set_parameter(queue, LENGTH, new_length);
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn mm1k() {
+        let model = QueueingModel::new()
+            .with_queue("queue", Queue::finite(3))
+            .with_arrival("arrival", ArrivalProcess::new("queue".to_string(), 0.3))
+            .with_server("server", Server::new("queue".to_string(), 0.5));
+        let mut simulation = Simulation::new(
+            model.initial_state(),
+            get_state_transition_generator(model.rules().unwrap()),
+        );
+        simulation.full_traversal(false);
+        let mut lengths = simulation
+            .known_states()
+            .iter()
+            .map(|state| length(state, "queue"))
+            .collect::<Vec<_>>();
+        lengths.sort();
+        assert_eq!(lengths, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn tandem() {
+        let model = QueueingModel::new()
+            .with_queue("first", Queue::finite(1).with_initial_length(1))
+            .with_queue("second", Queue::finite(1))
+            .with_server(
+                "transfer",
+                Server::new("first".to_string(), 1.).with_destination("second".to_string()),
+            );
+        let mut simulation = Simulation::new(
+            model.initial_state(),
+            get_state_transition_generator(model.rules().unwrap()),
+        );
+        simulation.next_step();
+        let distribution = simulation.probability_distribution(1);
+        assert_eq!(distribution.len(), 1);
+        let state = distribution.keys().next().unwrap();
+        assert_eq!(length(state, "first"), 0);
+        assert_eq!(length(state, "second"), 1);
+
+        let undeclared = model.with_server(
+            "exit",
+            Server::new("second".to_string(), 1.).with_destination("third".to_string()),
+        );
+        assert_eq!(
+            undeclared.rules().err(),
+            Some(SchemaError::UnknownEntity {
+                entity: "third".to_string()
+            })
+        );
+    }
+}
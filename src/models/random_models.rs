@@ -0,0 +1,159 @@
+use std::{fmt::Debug, hash::Hash, sync::Arc};
+
+use hashbrown::HashMap;
+
+use crate::models::entities::*;
+use crate::models::rules::*;
+use crate::models::schema::*;
+use crate::random::*;
+
+// Parameters without bounds are drawn from a small range, so random models stay finite
+const DEFAULT_RANGE: f64 = 10.;
+
+fn bounds(spec: &ParameterSpec) -> (f64, f64) {
+    match spec.parameter_type() {
+        ParameterType::Boolean => (0., 1.),
+        _ => {
+            let minimum = spec.minimum().unwrap_or(0.);
+            let maximum = spec.maximum().unwrap_or(minimum + DEFAULT_RANGE);
+            match spec.parameter_type() {
+                ParameterType::Real => (minimum, maximum),
+                _ => (minimum.ceil(), maximum.floor()),
+            }
+        }
+    }
+}
+
+fn random_value(spec: &ParameterSpec, rng: &mut SeededRng) -> f64 {
+    let (minimum, maximum) = bounds(spec);
+    match spec.parameter_type() {
+        ParameterType::Real => minimum + rng.next_probability() * (maximum - minimum),
+        _ => minimum + (rng.next_u64() % ((maximum - minimum) as u64 + 1)) as f64,
+    }
+}
+
+impl<V> State<V>
+where
+    V: Numeric + Clone,
+{
+    // Entities are named entity0, entity1, ... and of a random class of the schema. Required
+    // parameters are always set, optional ones with probability 0.5, and all values are valid.
+    pub fn random(schema: &Schema, entities: usize, seed: u64) -> Self {
+        let classes = schema.classes().collect::<Vec<_>>();
+        assert!(
+            !classes.is_empty(),
+            "Random states need a schema with at least one class"
+        );
+        let mut rng = SeededRng::new(seed);
+        (0..entities).fold(State::new(), |state, index| {
+            let (class, class_schema) = classes[rng.next_u64() as usize % classes.len()];
+            let entity = class_schema.parameters().fold(
+                StateEntity::of_class(class.clone()),
+                |entity, (parameter_name, spec)| {
+                    if spec.is_required() || rng.next_bool() {
+                        let value = V::from_f64(random_value(spec, &mut rng));
+                        entity.with_parameter(parameter_name.clone(), value)
+                    } else {
+                        entity
+                    }
+                },
+            );
+            state.with_entity(format!("entity{index}"), entity)
+        })
+    }
+}
+
+// Rules which increment or decrement a random parameter of the state by one, but only while the
+// result stays within the bounds of the schema. Weights are between 0.1 and 0.9.
+pub fn random_rules<V>(
+    schema: &Schema,
+    state: &State<V>,
+    count: usize,
+    seed: u64,
+) -> HashMap<RuleName, Rule<State<V>>>
+where
+    V: Numeric + Clone + Debug + Hash + Send + Sync + 'static,
+{
+    let parameters = state
+        .entities()
+        .flat_map(|(entity_name, entity)| {
+            entity
+                .parameters()
+                .filter_map(|(parameter_name, _)| {
+                    let spec = schema
+                        .parameter_spec(state, entity_name, parameter_name)
+                        .ok()?;
+                    Some((entity_name.clone(), parameter_name.clone(), bounds(spec)))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert!(
+        !parameters.is_empty(),
+        "Random rules need a state with at least one parameter declared in the schema"
+    );
+    let mut rng = SeededRng::new(seed);
+    (0..count)
+        .map(|index| {
+            let (entity_name, parameter_name, (minimum, maximum)) =
+                parameters[rng.next_u64() as usize % parameters.len()].clone();
+            let delta = if rng.next_bool() { 1. } else { -1. };
+            let weight = 0.1 + 0.8 * rng.next_probability();
+            let name = format!("rule{index}");
+            let (condition_entity, condition_parameter) =
+                (entity_name.clone(), parameter_name.clone());
+            let rule = Rule::new(
+                format!("{name}: {entity_name}.{parameter_name} {delta:+}"),
+                Arc::new(move |state: State<V>| {
+                    state
+                        .parameter(&condition_entity, &condition_parameter)
+                        .map(|value| value.to_f64() + delta)
+                        .is_some_and(|value| minimum <= value && value <= maximum)
+                }),
+                weight,
+                Arc::new(move |mut state: State<V>| {
+                    let value = state.parameter(&entity_name, &parameter_name).unwrap();
+                    let value = V::from_f64(value.to_f64() + delta);
+                    state.set_parameter(&entity_name, &parameter_name, value);
+                    state
+                }),
+            );
+            (name, rule)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn random_model() {
+        let schema = Schema::new()
+            .with_class(
+                "tank",
+                ClassSchema::new()
+                    .with_parameter("level", ParameterSpec::integer().with_bounds(0., 3.))
+                    .with_parameter("leaking", ParameterSpec::boolean().optional()),
+            )
+            .with_class(
+                "pump",
+                ClassSchema::new().with_parameter("speed", ParameterSpec::integer()),
+            );
+        let state = State::<i32>::random(&schema, 4, 7);
+        assert_eq!(state.entities().count(), 4);
+        assert_eq!(schema.validate(&state), Ok(()));
+        assert_eq!(state, State::random(&schema, 4, 7));
+        assert_ne!(state, State::random(&schema, 4, 8));
+
+        let rules = random_rules(&schema, &state, 6, 7);
+        assert_eq!(rules.len(), 6);
+        let mut simulation = Simulation::from_rules(state, rules);
+        simulation.register_schema(schema).unwrap();
+        simulation.explore();
+        assert!(simulation.known_states().len() > 1);
+        assert_eq!(simulation.validate_known_states(), Ok(()));
+    }
+}
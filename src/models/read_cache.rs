@@ -0,0 +1,154 @@
+use std::{
+    hash::Hash,
+    sync::{Arc, RwLock},
+};
+
+use hashbrown::HashMap;
+
+use crate::models::{conditions::*, entities::*, rules::*};
+use crate::prelude::*;
+
+// The names and hashes of the entities selected by any of the selectors, which is all a function
+// reading only from them can depend on
+pub fn read_set_hash<T>(state: &State<T>, selectors: &[EntitySelector]) -> u64
+where
+    T: Numeric + Hash,
+{
+    let hashes = state
+        .entities()
+        .filter(|(name, entity)| {
+            selectors
+                .iter()
+                .any(|selector| selector.matches(name, entity))
+        })
+        .map(|(name, _)| (name, state.entity_hash(name).unwrap()))
+        .collect::<Vec<_>>();
+    hash(&hashes)
+}
+
+impl<T> Rule<State<T>>
+where
+    T: Numeric + Hash + Clone + Send + Sync + 'static,
+{
+    // Caches the condition by the hashes of the entities in the read set of the rule instead of
+    // the whole state, so states which only differ in entities the rule does not read share one
+    // evaluation. Panics if the access of the rule is not known, probe or declare it first.
+    pub fn with_read_cache(self) -> Self {
+        let selectors = self
+            .access()
+            .expect("The read set of the rule is not known")
+            .reads()
+            .iter()
+            .map(|(selector, _)| EntitySelector::from_description(selector))
+            .collect::<Vec<_>>();
+        let cache: Arc<RwLock<HashMap<u64, RuleApplies>>> = Arc::new(RwLock::new(HashMap::new()));
+        let condition = match self.condition().clone() {
+            Condition::Function(condition) => {
+                Condition::Function(Arc::new(move |state: State<T>| {
+                    let key = read_set_hash(&state, &selectors);
+                    if let Some(applies) = cache.read().unwrap().get(&key) {
+                        return *applies;
+                    }
+                    let applies = condition(state);
+                    cache.write().unwrap().insert(key, applies);
+                    applies
+                }))
+            }
+            Condition::BatchFunction(condition) => {
+                Condition::BatchFunction(Arc::new(move |states: &[State<T>]| {
+                    let keys = states
+                        .iter()
+                        .map(|state| read_set_hash(state, &selectors))
+                        .collect::<Vec<_>>();
+                    let mut results = {
+                        let cache = cache.read().unwrap();
+                        keys.iter()
+                            .map(|key| cache.get(key).copied())
+                            .collect::<Vec<_>>()
+                    };
+                    let misses = (0..states.len())
+                        .filter(|index| results[*index].is_none())
+                        .collect::<Vec<_>>();
+                    if !misses.is_empty() {
+                        let missed_states = misses
+                            .iter()
+                            .map(|index| states[*index].clone())
+                            .collect::<Vec<_>>();
+                        let mut cache = cache.write().unwrap();
+                        misses.iter().zip(condition(&missed_states)).for_each(
+                            |(index, applies)| {
+                                cache.insert(keys[*index], applies);
+                                results[*index] = Some(applies);
+                            },
+                        );
+                    }
+                    results.into_iter().map(Option::unwrap).collect()
+                }))
+            }
+        };
+        self.replace_condition(condition)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::models::actions::*;
+
+    use super::*;
+
+    #[test]
+    fn read_cache() {
+        let state = |level: i32, noise: i32| {
+            State::new()
+                .with_entity("tank", StateEntity::new().with_parameter("level", level))
+                .with_entity("noise", StateEntity::new().with_parameter("value", noise))
+        };
+        assert_eq!(
+            state(1, 0).entity_hash("tank"),
+            state(1, 5).entity_hash("tank")
+        );
+        assert_ne!(hash(&state(1, 0)), hash(&state(1, 5)));
+
+        let evaluations = Arc::new(AtomicUsize::new(0));
+        let counter = evaluations.clone();
+        let drain = Rule::new(
+            "Drain".to_string(),
+            Arc::new(move |state: State<i32>| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                *state.parameter("tank", "level").unwrap() > 0
+            }),
+            1.,
+            decrement("tank", "level", 1.),
+        )
+        .with_access(RuleAccess::new(
+            vec![("tank".to_string(), "level".to_string())],
+            vec![("tank".to_string(), "level".to_string())],
+        ))
+        .with_read_cache();
+        assert!((0..10).all(|noise| drain.applies(state(1, noise))));
+        assert!(!drain.applies(state(0, 3)));
+        assert_eq!(evaluations.load(Ordering::Relaxed), 2);
+
+        let batched = Rule::new_batched(
+            "Drain".to_string(),
+            Arc::new(|states: &[State<i32>]| {
+                states
+                    .iter()
+                    .map(|state| *state.parameter("tank", "level").unwrap() > 0)
+                    .collect()
+            }),
+            1.,
+            decrement("tank", "level", 1.),
+        )
+        .with_access(drain.access().unwrap().clone())
+        .with_read_cache();
+        assert_eq!(
+            batched
+                .condition()
+                .evaluate_batch(&[state(1, 0), state(0, 0), state(1, 7)]),
+            vec![true, false, true]
+        );
+    }
+}
@@ -1,31 +1,179 @@
-use std::{fmt::Display, sync::Arc};
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    fmt::{Debug, Display},
+    hash::Hash,
+};
+#[cfg(feature = "std")]
+use std::sync::Mutex;
 
 use derive_more::{From, Into};
 use hashbrown::HashMap;
 use itertools::Itertools;
-use std::fmt::Debug;
-use std::hash::Hash;
+use serde::{Deserialize, Serialize};
 
+use crate::models::entities::StateEntity;
 use crate::prelude::*;
 
-pub type EntityName = String;
-pub type ParameterName = String;
+pub use crate::models::entities::{EntityName, ParameterName};
 pub type Entity<T> = HashMap<ParameterName, T>;
 
 pub type RuleName = String;
 pub type RuleApplies = bool;
 pub type ProbabilityWeight = f64;
 
+pub type BatchCondition<T> = Arc<dyn Fn(&[T]) -> Vec<RuleApplies> + Send + Sync>;
+
+// Entities given as plain parameter maps become entities of a state without a class
+impl<T> From<Entity<T>> for StateEntity<T> {
+    fn from(parameters: Entity<T>) -> Self {
+        parameters.into_iter().collect()
+    }
+}
+
+// A batch condition is evaluated for all states of a frontier at once, e.g. to vectorize an
+// expensive model backed guard. Single states are evaluated as a batch of one.
+#[derive(Clone)]
+pub enum Condition<T> {
+    Function(Arc<dyn Fn(T) -> RuleApplies + Send + Sync>),
+    BatchFunction(BatchCondition<T>),
+}
+
+impl<T: Clone> Condition<T> {
+    pub fn evaluate(&self, state: T) -> RuleApplies {
+        match self {
+            Condition::Function(condition) => condition(state),
+            Condition::BatchFunction(condition) => condition(&[state])[0],
+        }
+    }
+
+    pub fn evaluate_batch(&self, states: &[T]) -> Vec<RuleApplies> {
+        match self {
+            Condition::Function(condition) => states
+                .iter()
+                .map(|state| condition(state.clone()))
+                .collect(),
+            Condition::BatchFunction(condition) => {
+                let applies = condition(states);
+                assert_eq!(
+                    applies.len(),
+                    states.len(),
+                    "Batch condition returned a wrong number of results"
+                );
+                applies
+            }
+        }
+    }
+
+    pub fn is_batched(&self) -> bool {
+        matches!(self, Condition::BatchFunction(_))
+    }
+}
+
+// Documentation of a rule, used for generated model documentation and audits
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleMetadata {
+    author: Option<String>,
+    tags: Vec<String>,
+    references: Vec<String>,
+    rationale: Option<String>,
+}
+
+impl RuleMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn with_reference(mut self, reference: impl Into<String>) -> Self {
+        self.references.push(reference.into());
+        self
+    }
+
+    pub fn with_rationale(mut self, rationale: impl Into<String>) -> Self {
+        self.rationale = Some(rationale.into());
+        self
+    }
+
+    pub fn author(&self) -> Option<&String> {
+        self.author.as_ref()
+    }
+
+    pub fn tags(&self) -> &Vec<String> {
+        &self.tags
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own_tag| own_tag == tag)
+    }
+
+    pub fn references(&self) -> &Vec<String> {
+        &self.references
+    }
+
+    pub fn rationale(&self) -> Option<&String> {
+        self.rationale.as_ref()
+    }
+}
+
+// A selector of the accessed entities, e.g. an entity name, and the name of the parameter
+pub type AccessedParameter = (String, String);
+
+// The parameters a rule reads in its condition and writes in its action. Closures can't be
+// inspected, so this is only known if it was declared, e.g. by a declarative rule.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleAccess {
+    reads: Vec<AccessedParameter>,
+    writes: Vec<AccessedParameter>,
+}
+
+impl RuleAccess {
+    pub fn new(reads: Vec<AccessedParameter>, writes: Vec<AccessedParameter>) -> Self {
+        let normalize = |mut parameters: Vec<AccessedParameter>| {
+            parameters.sort();
+            parameters.dedup();
+            parameters
+        };
+        Self {
+            reads: normalize(reads),
+            writes: normalize(writes),
+        }
+    }
+
+    pub fn reads(&self) -> &Vec<AccessedParameter> {
+        &self.reads
+    }
+
+    pub fn writes(&self) -> &Vec<AccessedParameter> {
+        &self.writes
+    }
+}
+
 #[derive(From, Into, Clone)]
 pub struct Rule<T> {
     description: String,
-    condition: Arc<dyn Fn(T) -> RuleApplies + Send + Sync>,
+    condition: Condition<T>,
     weight: ProbabilityWeight,
     action: Arc<dyn Fn(T) -> T + Send + Sync>,
+    metadata: RuleMetadata,
+    access: Option<RuleAccess>,
 }
 
 impl<T: Debug> Debug for Rule<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         writeln!(f, "Rule:")?;
         writeln!(f, "Description: {}", self.description)?;
         writeln!(f, "Weight: {}", self.weight)?;
@@ -34,7 +182,7 @@ impl<T: Debug> Debug for Rule<T> {
 }
 
 impl<T> Display for Rule<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         writeln!(f, "Rule:")?;
         writeln!(f, "Description: {}", self.description)?;
         writeln!(f, "Weight: {}", self.weight)?;
@@ -48,17 +196,60 @@ impl<T> Rule<T> {
         condition: Arc<dyn Fn(T) -> RuleApplies + Send + Sync>,
         probability_weight: ProbabilityWeight,
         action: Arc<dyn Fn(T) -> T + Send + Sync>,
+    ) -> Self {
+        Self::with_condition(
+            description,
+            Condition::Function(condition),
+            probability_weight,
+            action,
+        )
+    }
+
+    pub fn new_batched(
+        description: String,
+        condition: BatchCondition<T>,
+        probability_weight: ProbabilityWeight,
+        action: Arc<dyn Fn(T) -> T + Send + Sync>,
+    ) -> Self {
+        Self::with_condition(
+            description,
+            Condition::BatchFunction(condition),
+            probability_weight,
+            action,
+        )
+    }
+
+    fn with_condition(
+        description: String,
+        condition: Condition<T>,
+        probability_weight: ProbabilityWeight,
+        action: Arc<dyn Fn(T) -> T + Send + Sync>,
     ) -> Self {
         Self {
             description,
             condition,
             weight: probability_weight,
             action,
+            metadata: RuleMetadata::default(),
+            access: None,
         }
     }
 
-    pub fn applies(&self, state: T) -> RuleApplies {
-        (self.condition)(state)
+    pub fn with_metadata(mut self, metadata: RuleMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn with_access(mut self, access: RuleAccess) -> Self {
+        self.access = Some(access);
+        self
+    }
+
+    pub fn applies(&self, state: T) -> RuleApplies
+    where
+        T: Clone,
+    {
+        self.condition.evaluate(state)
     }
 
     pub fn apply(&self, state: T) -> T {
@@ -73,82 +264,514 @@ impl<T> Rule<T> {
         &self.description
     }
 
-    pub fn condition(&self) -> &(dyn Fn(T) -> RuleApplies + Send + Sync) {
-        &*self.condition
+    pub fn condition(&self) -> &Condition<T> {
+        &self.condition
     }
 
     pub fn action(&self) -> &(dyn Fn(T) -> T + Send + Sync) {
         &*self.action
     }
+
+    pub fn metadata(&self) -> &RuleMetadata {
+        &self.metadata
+    }
+
+    pub fn access(&self) -> Option<&RuleAccess> {
+        self.access.as_ref()
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn replace_condition(mut self, condition: Condition<T>) -> Self {
+        self.condition = condition;
+        self
+    }
+}
+
+pub type TemplateCondition<T, P> = Arc<dyn Fn(&P, T) -> RuleApplies + Send + Sync>;
+pub type TemplateAction<T, P> = Arc<dyn Fn(&P, T) -> T + Send + Sync>;
+
+#[derive(Clone)]
+pub struct RuleTemplate<T, P> {
+    description: String,
+    condition: TemplateCondition<T, P>,
+    weight: ProbabilityWeight,
+    action: TemplateAction<T, P>,
+}
+
+impl<T, P> Debug for RuleTemplate<T, P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "RuleTemplate:")?;
+        writeln!(f, "Description: {}", self.description)?;
+        writeln!(f, "Weight: {}", self.weight)?;
+        Ok(())
+    }
+}
+
+impl<T, P> RuleTemplate<T, P>
+where
+    T: 'static,
+    P: Debug + Clone + Send + Sync + 'static,
+{
+    pub fn new(
+        description: String,
+        condition: TemplateCondition<T, P>,
+        probability_weight: ProbabilityWeight,
+        action: TemplateAction<T, P>,
+    ) -> Self {
+        Self {
+            description,
+            condition,
+            weight: probability_weight,
+            action,
+        }
+    }
+
+    pub fn description(&self) -> &String {
+        &self.description
+    }
+
+    pub fn weight(&self) -> ProbabilityWeight {
+        self.weight
+    }
+
+    pub fn instantiate(&self, parameters: P) -> Rule<T> {
+        let condition = self.condition.clone();
+        let condition_parameters = parameters.clone();
+        let action = self.action.clone();
+        Rule::new(
+            format!("{} {parameters:?}", self.description),
+            Arc::new(move |state: T| condition(&condition_parameters, state)),
+            self.weight,
+            Arc::new(move |state: T| action(&parameters, state)),
+        )
+    }
+
+    pub fn instantiate_all(
+        &self,
+        rule_name: &str,
+        parameters: impl IntoIterator<Item = P>,
+    ) -> HashMap<RuleName, Rule<T>> {
+        parameters
+            .into_iter()
+            .map(|parameters| {
+                (
+                    format!("{rule_name} {parameters:?}"),
+                    self.instantiate(parameters),
+                )
+            })
+            .collect()
+    }
+}
+
+// Rules are applied in a fixed order, so merged descriptions and floating point sums are the
+// same in every run, independent of the iteration order of the rule map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TieBreaking {
+    #[default]
+    ByName,
+    Seeded(u64),
+}
+
+impl TieBreaking {
+    pub fn order<'a, R>(&self, rules: &'a HashMap<RuleName, R>) -> Vec<(&'a RuleName, &'a R)> {
+        match self {
+            TieBreaking::ByName => rules.iter().sorted_by(|(a, _), (b, _)| a.cmp(b)).collect(),
+            TieBreaking::Seeded(seed) => rules
+                .iter()
+                .sorted_by_key(|(name, _)| (hash(&(seed, name)), (*name).clone()))
+                .collect(),
+        }
+    }
+}
+
+// How the probability of nothing happening in a state is derived from the weights of the
+// applying rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum NothingHappens {
+    // Rules fire independently, nothing happens with the product of (1 - weight)
+    #[default]
+    Independent,
+    // Weights are probabilities and the remaining probability is a self-loop
+    Remainder,
+    // Weights are normalized, states where no rule applies get a self-loop
+    Absorb,
+    // Weights are normalized, states where no rule applies are a modelling error
+    Error,
+}
+
+// How the history of a running simulation is migrated when a rule is replaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RuleMigration {
+    // Every step up to the current time is recomputed under the new rule
+    #[default]
+    Replay,
+    // Past distributions are kept and only the following steps use the new rule
+    KeepHistory,
+    // Past distributions are kept, but the current one is recomputed from the previous one
+    ReevaluateFrontier,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RuleOptions {
+    tie_breaking: TieBreaking,
+    nothing_happens: NothingHappens,
+}
+
+impl RuleOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tie_breaking(mut self, tie_breaking: TieBreaking) -> Self {
+        self.tie_breaking = tie_breaking;
+        self
+    }
+
+    pub fn with_nothing_happens(mut self, nothing_happens: NothingHappens) -> Self {
+        self.nothing_happens = nothing_happens;
+        self
+    }
+
+    pub fn tie_breaking(&self) -> TieBreaking {
+        self.tie_breaking
+    }
+
+    pub fn nothing_happens(&self) -> NothingHappens {
+        self.nothing_happens
+    }
+}
+
+#[cfg(feature = "std")]
 pub fn get_state_transition_generator<T>(
     rules: HashMap<RuleName, Rule<T>>,
 ) -> StateTransitionGenerator<T, String>
 where
     T: Debug + Clone + Send + Sync + 'static + PartialEq + Eq + Hash,
 {
-    Arc::new(move |state: T| -> OutgoingTransitions<T, String> {
-        let new_states_by_weight = rules
+    get_state_transition_generator_with_tie_breaking(rules, TieBreaking::default())
+}
+
+#[cfg(feature = "std")]
+pub fn get_state_transition_generator_with_tie_breaking<T>(
+    rules: HashMap<RuleName, Rule<T>>,
+    tie_breaking: TieBreaking,
+) -> StateTransitionGenerator<T, String>
+where
+    T: Debug + Clone + Send + Sync + 'static + PartialEq + Eq + Hash,
+{
+    get_state_transition_generator_with_options(
+        rules,
+        RuleOptions::new().with_tie_breaking(tie_breaking),
+    )
+}
+
+#[cfg(feature = "std")]
+pub fn get_state_transition_generator_with_options<T>(
+    rules: HashMap<RuleName, Rule<T>>,
+    options: RuleOptions,
+) -> StateTransitionGenerator<T, String>
+where
+    T: Debug + Clone + Send + Sync + 'static + PartialEq + Eq + Hash,
+{
+    rule_state_transition_generator(rules, options).0
+}
+
+// Batch conditions are evaluated for the whole frontier by the returned hook, the generator then
+// only looks up the results. States the hook didn't see are evaluated as a batch of one.
+#[cfg(feature = "std")]
+pub(crate) fn rule_state_transition_generator<T>(
+    rules: HashMap<RuleName, Rule<T>>,
+    options: RuleOptions,
+) -> (StateTransitionGenerator<T, String>, Option<FrontierHook<T>>)
+where
+    T: Debug + Clone + Send + Sync + 'static + PartialEq + Eq + Hash,
+{
+    let rules = options
+        .tie_breaking()
+        .order(&rules)
+        .into_iter()
+        .map(|(_, rule)| rule.clone())
+        .collect_vec();
+    let batch_results: Arc<Mutex<HashMap<(u64, usize), RuleApplies>>> = Arc::default();
+    let frontier_hook = rules
+        .iter()
+        .any(|rule| rule.condition().is_batched())
+        .then(|| {
+            let rules = rules.clone();
+            let batch_results = batch_results.clone();
+            Arc::new(move |frontier: &[T]| {
+                let hashes = frontier.iter().map(hash).collect_vec();
+                let mut results = HashMap::new();
+                rules
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, rule)| rule.condition().is_batched())
+                    .for_each(|(index, rule)| {
+                        let applies = rule.condition().evaluate_batch(frontier);
+                        hashes
+                            .iter()
+                            .zip(applies)
+                            .for_each(|(state_hash, applies)| {
+                                results.insert((*state_hash, index), applies);
+                            });
+                    });
+                // Batches explored concurrently are only removed once their states are generated
+                batch_results.lock().unwrap().extend(results);
+            }) as FrontierHook<T>
+        });
+    let state_transition_generator = Arc::new(move |state: T| -> OutgoingTransitions<T, String> {
+        let state_hash = hash(&state);
+        let new_states = rules
             .iter()
-            .filter(|(_, rule)| rule.applies(state.clone()))
+            .enumerate()
+            .filter(|(index, rule)| {
+                let batch_result = rule
+                    .condition()
+                    .is_batched()
+                    .then(|| batch_results.lock().unwrap().remove(&(state_hash, *index)))
+                    .flatten();
+                batch_result.unwrap_or_else(|| rule.applies(state.clone()))
+            })
             .map(|(_, rule)| {
-                let new_state: T = rule.apply(state.clone());
-                let weight = rule.weight();
-                let description = rule.description().clone();
-                (hash(&new_state), (new_state, weight, description))
+                (
+                    rule.apply(state.clone()),
+                    rule.weight(),
+                    rule.description().clone(),
+                )
             })
-            .fold(
-                HashMap::new(),
-                |acc: HashMap<u64, (T, ProbabilityWeight, String)>,
-                 (_, (state, weight, description))| {
-                    let mut new_acc = acc;
-                    if let Some(e) = new_acc.get_mut(&hash(&state)) {
-                        e.1 += weight;
-                        e.2 = format!("{} | {}", e.2, description);
-                    } else {
-                        new_acc.insert(hash(&state), (state.clone(), weight, description));
-                    }
-                    new_acc
-                },
-            );
-        let base_state_hash = hash(&state);
-        let nothing_probability = new_states_by_weight
-            .iter()
-            .map(|(_, (_, weight, _))| 1. - *weight)
-            .product::<ProbabilityWeight>();
-        let weight_sum = new_states_by_weight
-            .iter()
-            .map(|(_, (_, weight, _))| weight)
-            .sum::<ProbabilityWeight>()
-            + nothing_probability;
-        let mut new_states = new_states_by_weight
+            .collect_vec();
+        outgoing_transitions_with(state, new_states, options.nothing_happens())
+    }) as StateTransitionGenerator<T, String>;
+    (state_transition_generator, frontier_hook)
+}
+
+#[cfg(feature = "std")]
+impl<S> Simulation<S, String>
+where
+    S: Debug + Clone + Send + Sync + 'static + PartialEq + Eq + Hash,
+{
+    pub fn from_rules(initial_state: S, rules: HashMap<RuleName, Rule<S>>) -> Self {
+        Self::from_rules_with_options(initial_state, rules, RuleOptions::default())
+    }
+
+    pub fn from_rules_with_options(
+        initial_state: S,
+        rules: HashMap<RuleName, Rule<S>>,
+        options: RuleOptions,
+    ) -> Self {
+        let rule_generator = Arc::new(rule_state_transition_generator) as RuleGenerator<S, String>;
+        let (state_transition_generator, frontier_hook) = rule_generator(rules.clone(), options);
+        let mut simulation = Simulation::new(initial_state, state_transition_generator);
+        simulation.set_frontier_hook(frontier_hook);
+        simulation.set_rules(rules);
+        simulation.set_rule_options(options);
+        simulation.set_rule_generator(rule_generator);
+        simulation
+    }
+
+    // Changing the options affects every state, so all cached transitions are recomputed
+    pub fn change_rule_options(&mut self, options: RuleOptions) -> Result<(), SimulationError> {
+        let rules = self.rules().ok_or(SimulationError::NoRules)?.clone();
+        let rule_generator = self.rule_generator().ok_or(SimulationError::NoRules)?;
+        self.set_rule_options(options);
+        let (state_transition_generator, frontier_hook) = rule_generator(rules, options);
+        self.set_frontier_hook(frontier_hook);
+        self.replace_state_transition_generator(state_transition_generator, |_| true);
+        Ok(())
+    }
+
+    pub fn insert_rule(
+        &mut self,
+        rule_name: impl Into<RuleName>,
+        rule: Rule<S>,
+    ) -> Result<Option<Rule<S>>, SimulationError> {
+        self.update_rule(rule_name.into(), Some(rule), RuleMigration::Replay)
+    }
+
+    pub fn remove_rule(&mut self, rule_name: &str) -> Result<Option<Rule<S>>, SimulationError> {
+        self.update_rule(rule_name.to_string(), None, RuleMigration::Replay)
+    }
+
+    // Swaps a rule while the simulation is running, e.g. to tune a model interactively. Only
+    // states where the old or the new rule applies are explored again.
+    pub fn replace_rule(
+        &mut self,
+        rule_name: impl Into<RuleName>,
+        rule: Rule<S>,
+        migration: RuleMigration,
+    ) -> Result<Option<Rule<S>>, SimulationError> {
+        self.update_rule(rule_name.into(), Some(rule), migration)
+    }
+
+    fn update_rule(
+        &mut self,
+        rule_name: RuleName,
+        rule: Option<Rule<S>>,
+        migration: RuleMigration,
+    ) -> Result<Option<Rule<S>>, SimulationError> {
+        let mut rules = self.rules().ok_or(SimulationError::NoRules)?.clone();
+        let rule_generator = self.rule_generator().ok_or(SimulationError::NoRules)?;
+        let previous_rule = match rule.clone() {
+            Some(rule) => rules.insert(rule_name, rule),
+            None => rules.remove(&rule_name),
+        };
+        let (state_transition_generator, frontier_hook) =
+            rule_generator(rules.clone(), self.rule_options());
+        self.set_frontier_hook(frontier_hook);
+        self.set_rules(rules);
+        // A state's transitions can only change if the old or the new version of the rule applies
+        let changed_rules = [previous_rule.clone(), rule]
             .into_iter()
-            .map(|(state_hash, (state, weight, description))| {
-                (state_hash, (state, weight / weight_sum, description))
-            })
-            .collect::<HashMap<u64, (T, f64, String)>>();
-        if nothing_probability > 0. {
-            new_states
-                .entry(base_state_hash)
-                .and_modify(|(_, probability, description)| {
-                    *probability += nothing_probability / weight_sum;
-                    description.push_str(" | Nothing");
-                })
-                .or_insert((
-                    state,
-                    nothing_probability / weight_sum,
-                    "Nothing".to_string(),
-                ));
+            .flatten()
+            .collect_vec();
+        let is_affected = |state: &S| changed_rules.iter().any(|rule| rule.applies(state.clone()));
+        match migration {
+            RuleMigration::Replay => {
+                self.replace_state_transition_generator(state_transition_generator, is_affected)
+            }
+            RuleMigration::KeepHistory => {
+                self.swap_state_transition_generator(state_transition_generator, is_affected)
+            }
+            RuleMigration::ReevaluateFrontier => {
+                self.swap_state_transition_generator(state_transition_generator, is_affected);
+                self.redo_last_step();
+            }
         }
-        new_states
-            .into_iter()
-            .map(|(_, (state, probability, description))| (state, description, probability))
-            .collect_vec()
-    }) as StateTransitionGenerator<T, String>
+        Ok(previous_rule)
+    }
+}
+
+// The weights of rules leading to the same state are merged before they are passed in here
+fn nothing_weight(
+    merged_weights: &[ProbabilityWeight],
+    nothing_happens: NothingHappens,
+) -> ProbabilityWeight {
+    let applying_weight = merged_weights.iter().sum::<ProbabilityWeight>();
+    match nothing_happens {
+        NothingHappens::Independent => merged_weights
+            .iter()
+            .map(|weight| 1. - *weight)
+            .product::<ProbabilityWeight>(),
+        NothingHappens::Remainder => (1. - applying_weight).max(0.),
+        NothingHappens::Absorb => f64::from(applying_weight == 0.),
+        // A state without transitions, which the step returns as an error
+        NothingHappens::Error => 0.,
+    }
+}
+
+// The probability with which each applying rule fires in the given state, and the probability
+// of nothing happening
+#[cfg(feature = "std")]
+pub(crate) fn rule_probabilities<T>(
+    rules: &HashMap<RuleName, Rule<T>>,
+    options: RuleOptions,
+    state: &T,
+) -> (Vec<(RuleName, Probability)>, Probability)
+where
+    T: Debug + Clone + Hash,
+{
+    let applying_rules = options
+        .tie_breaking()
+        .order(rules)
+        .into_iter()
+        .filter(|(_, rule)| rule.applies(state.clone()))
+        .collect_vec();
+    let mut indices: HashMap<u64, usize> = HashMap::new();
+    let mut merged_weights: Vec<ProbabilityWeight> = Vec::new();
+    applying_rules.iter().for_each(|(_, rule)| {
+        let target_hash = hash(&rule.apply(state.clone()));
+        match indices.get(&target_hash) {
+            Some(index) => merged_weights[*index] += rule.weight(),
+            None => {
+                indices.insert(target_hash, merged_weights.len());
+                merged_weights.push(rule.weight());
+            }
+        }
+    });
+    let nothing_probability = nothing_weight(&merged_weights, options.nothing_happens());
+    let weight_sum = merged_weights.iter().sum::<ProbabilityWeight>() + nothing_probability;
+    let probabilities = applying_rules
+        .into_iter()
+        .map(|(name, rule)| (name.clone(), rule.weight() / weight_sum))
+        .collect();
+    if nothing_probability > 0. {
+        (probabilities, nothing_probability / weight_sum)
+    } else {
+        (probabilities, 0.)
+    }
+}
+
+pub(crate) fn outgoing_transitions_with<T>(
+    state: T,
+    new_states: Vec<(T, ProbabilityWeight, String)>,
+    nothing_happens: NothingHappens,
+) -> OutgoingTransitions<T, String>
+where
+    T: Debug + Clone + Send + Sync + 'static + PartialEq + Eq + Hash,
+{
+    // New states are merged in order of their first appearance to keep the result deterministic
+    let mut indices: HashMap<u64, usize> = HashMap::new();
+    let mut new_states_by_weight: Vec<(T, ProbabilityWeight, String)> = Vec::new();
+    new_states
+        .into_iter()
+        .for_each(
+            |(new_state, weight, description)| match indices.get(&hash(&new_state)) {
+                Some(index) => {
+                    let entry = &mut new_states_by_weight[*index];
+                    entry.1 += weight;
+                    entry.2 = format!("{} | {}", entry.2, description);
+                }
+                None => {
+                    indices.insert(hash(&new_state), new_states_by_weight.len());
+                    new_states_by_weight.push((new_state, weight, description));
+                }
+            },
+        );
+    let merged_weights = new_states_by_weight
+        .iter()
+        .map(|(_, weight, _)| *weight)
+        .collect_vec();
+    let applying_weight = merged_weights.iter().sum::<ProbabilityWeight>();
+    let nothing_probability = nothing_weight(&merged_weights, nothing_happens);
+    let weight_sum = applying_weight + nothing_probability;
+    let mut new_states = new_states_by_weight
+        .into_iter()
+        .map(|(new_state, weight, description)| (new_state, description, weight / weight_sum))
+        .collect_vec();
+    if nothing_probability > 0. {
+        match indices.get(&hash(&state)) {
+            Some(index) => {
+                let (_, description, probability) = &mut new_states[*index];
+                *probability += nothing_probability / weight_sum;
+                description.push_str(" | Nothing");
+            }
+            None => new_states.push((
+                state,
+                "Nothing".to_string(),
+                nothing_probability / weight_sum,
+            )),
+        }
+    }
+    new_states
 }
 
-#[cfg(test)]
+// A rule moving states below the limit by the step, shared by the tests of rules and reports
+#[cfg(all(test, feature = "std"))]
+pub(crate) fn step_rule(
+    description: &str,
+    weight: ProbabilityWeight,
+    step: i32,
+    limit: i32,
+) -> Rule<i32> {
+    Rule::new(
+        description.to_string(),
+        Arc::new(move |state| state < limit),
+        weight,
+        Arc::new(move |state| state + step),
+    )
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
 
@@ -256,4 +879,330 @@ mod tests {
         assert_eq!(simulation.state_transition_graph().edge_count(), 3);
         dbg!(simulation.entropy(1));
     }
+
+    #[test]
+    fn bounded_walk_template() {
+        let step_template: RuleTemplate<i32, (i32, i32)> = RuleTemplate::new(
+            "Step".to_string(),
+            Arc::new(|(delta, bound), state| (state + delta).abs() <= *bound),
+            1.,
+            Arc::new(|(delta, _), state| state + delta),
+        );
+        let rules = step_template.instantiate_all("step", [(1, 2), (-1, 2)]);
+        assert_eq!(rules.len(), 2);
+        assert!(rules.contains_key("step (1, 2)"));
+        assert_eq!(rules["step (-1, 2)"].description(), "Step (-1, 2)");
+        assert!(!rules["step (1, 2)"].applies(2));
+        assert_eq!(rules["step (-1, 2)"].apply(2), 1);
+
+        let mut simulation = Simulation::new(0, get_state_transition_generator(rules));
+        simulation.full_traversal(false);
+        assert_eq!(simulation.known_states().len(), 5);
+    }
+
+    #[test]
+    fn incremental_rule_editing() {
+        let forward_rule: Rule<i32> = Rule::new(
+            "Forward".to_string(),
+            Arc::new(|state| state < 3),
+            0.5,
+            Arc::new(|state| state + 1),
+        );
+        let backward_rule: Rule<i32> = Rule::new(
+            "Backward".to_string(),
+            Arc::new(|state| state > 1),
+            0.5,
+            Arc::new(|state| state - 1),
+        );
+        let mut simulation = Simulation::from_rules(
+            0,
+            HashMap::from([("forward".to_string(), forward_rule.clone())]),
+        );
+        (0..4).for_each(|_| {
+            simulation.next_step();
+        });
+        assert_eq!(simulation.probability_of(&3, 4), 0.3125);
+
+        assert!(simulation
+            .insert_rule("backward", backward_rule.clone())
+            .unwrap()
+            .is_none());
+        let mut expected = Simulation::from_rules(
+            0,
+            HashMap::from([
+                ("forward".to_string(), forward_rule),
+                ("backward".to_string(), backward_rule),
+            ]),
+        );
+        (0..4).for_each(|_| {
+            expected.next_step();
+        });
+        assert_eq!(simulation.time(), 4);
+        assert_eq!(simulation.known_states().len(), 4);
+        (0..4).for_each(|state| {
+            assert!(
+                (simulation.probability_of(&state, 4) - expected.probability_of(&state, 4)).abs()
+                    < 1e-12
+            );
+        });
+        // States the changed rule does not apply to keep their cached transitions
+        assert!(simulation.cached_outgoing_transitions(&0).is_some());
+        assert!(simulation.cached_outgoing_transitions(&1).is_some());
+
+        assert!(simulation.remove_rule("backward").unwrap().is_some());
+        assert_eq!(simulation.probability_of(&3, 4), 0.3125);
+        assert_eq!(simulation.rules().unwrap().len(), 1);
+
+        // Added states and their labels are kept when the history is replayed
+        simulation.label("added", |state: &i32| *state >= 10);
+        simulation.extend_known_states([10]).unwrap();
+        simulation.remove_rule("forward").unwrap();
+        assert_eq!(simulation.known_states().len(), 2);
+        assert_eq!(simulation.labeled_states("added"), vec![&10]);
+
+        let mut without_rules = Simulation::new(
+            0,
+            Arc::new(|state: i32| vec![(state, "Stay".to_string(), 1.)]),
+        );
+        assert!(matches!(
+            without_rules.remove_rule("forward"),
+            Err(SimulationError::NoRules)
+        ));
+    }
+
+    #[test]
+    fn rule_migration() {
+        let forward = |weight| -> Rule<i32> {
+            Rule::new(
+                "Forward".to_string(),
+                Arc::new(|state| state < 3),
+                weight,
+                Arc::new(|state| state + 1),
+            )
+        };
+        let mut simulation =
+            Simulation::from_rules(0, HashMap::from([("forward".to_string(), forward(0.5))]));
+        simulation.next_step();
+        simulation.next_step();
+        assert_eq!(simulation.graph().edge_count(), 4);
+
+        let mut kept = simulation.clone();
+        let previous = kept
+            .replace_rule("forward", forward(1.), RuleMigration::KeepHistory)
+            .unwrap();
+        assert_eq!(previous.unwrap().weight(), 0.5);
+        assert_eq!(kept.probability_of(&0, 2), 0.25);
+        assert_eq!(kept.graph().edge_count(), 0);
+        kept.next_step();
+        assert_eq!(kept.probability_of(&3, 3), 0.25);
+        assert_eq!(kept.graph().edge_count(), 3);
+
+        let mut reevaluated = simulation.clone();
+        reevaluated
+            .replace_rule("forward", forward(1.), RuleMigration::ReevaluateFrontier)
+            .unwrap();
+        assert_eq!(reevaluated.time(), 2);
+        assert_eq!(reevaluated.probability_of(&0, 1), 0.5);
+        assert_eq!(reevaluated.probability_of(&2, 2), 0.5);
+
+        simulation
+            .replace_rule("forward", forward(1.), RuleMigration::Replay)
+            .unwrap();
+        assert_eq!(simulation.probability_of(&2, 2), 1.);
+    }
+
+    #[test]
+    fn deterministic_tie_breaking() {
+        let rule = |description: &str| -> Rule<i32> {
+            Rule::new(
+                description.to_string(),
+                Arc::new(|_| true),
+                0.2,
+                Arc::new(|state| state + 1),
+            )
+        };
+        let names = ["c", "a", "d", "b"];
+        let forward = names
+            .iter()
+            .map(|name| (name.to_string(), rule(&name.to_uppercase())))
+            .collect::<HashMap<_, _>>();
+        let backward = names
+            .iter()
+            .rev()
+            .map(|name| (name.to_string(), rule(&name.to_uppercase())))
+            .collect::<HashMap<_, _>>();
+
+        let transitions = get_state_transition_generator(forward.clone())(0);
+        assert_eq!(
+            transitions,
+            get_state_transition_generator(backward.clone())(0)
+        );
+        assert_eq!(transitions[0].1, "A | B | C | D");
+        assert_eq!(transitions[1].1, "Nothing");
+
+        let seeded = get_state_transition_generator_with_tie_breaking(
+            forward.clone(),
+            TieBreaking::Seeded(7),
+        )(0);
+        assert_eq!(
+            seeded,
+            get_state_transition_generator_with_tie_breaking(backward, TieBreaking::Seeded(7))(0)
+        );
+        let seeded_names = |seed| {
+            TieBreaking::Seeded(seed)
+                .order(&forward)
+                .into_iter()
+                .map(|(name, _)| name.clone())
+                .collect_vec()
+        };
+        assert_eq!(seeded_names(7), seeded_names(7));
+        assert_eq!(seeded_names(7).len(), 4);
+    }
+
+    #[test]
+    fn nothing_happens_policies() {
+        let rule = |description: &str, weight, step| step_rule(description, weight, step, 1);
+        let rules = HashMap::from([
+            ("a".to_string(), rule("A", 0.5, 1)),
+            ("b".to_string(), rule("B", 0.25, 2)),
+        ]);
+        let probabilities = |nothing_happens, state| {
+            let options = RuleOptions::new().with_nothing_happens(nothing_happens);
+            get_state_transition_generator_with_options(rules.clone(), options)(state)
+                .into_iter()
+                .map(|(_, description, probability)| (description, probability))
+                .collect_vec()
+        };
+        let expected = |probabilities: &[(&str, f64)]| {
+            probabilities
+                .iter()
+                .map(|(description, probability)| (description.to_string(), *probability))
+                .collect_vec()
+        };
+        assert_eq!(
+            probabilities(NothingHappens::Independent, 0),
+            expected(&[
+                ("A", 0.5 / 1.125),
+                ("B", 0.25 / 1.125),
+                ("Nothing", 0.375 / 1.125)
+            ])
+        );
+        assert_eq!(
+            probabilities(NothingHappens::Remainder, 0),
+            expected(&[("A", 0.5), ("B", 0.25), ("Nothing", 0.25)])
+        );
+        assert_eq!(
+            probabilities(NothingHappens::Absorb, 0),
+            expected(&[("A", 0.5 / 0.75), ("B", 0.25 / 0.75)])
+        );
+        assert_eq!(
+            probabilities(NothingHappens::Absorb, 1),
+            expected(&[("Nothing", 1.)])
+        );
+
+        let mut simulation = Simulation::from_rules(0, rules);
+        simulation.next_step();
+        assert!(simulation
+            .probability_of(&1, 1)
+            .approx_eq(&(0.5 / 1.125), Tolerance::stored()));
+        simulation
+            .change_rule_options(RuleOptions::new().with_nothing_happens(NothingHappens::Remainder))
+            .unwrap();
+        assert_eq!(simulation.probability_of(&1, 1), 0.5);
+        simulation.insert_rule("c", rule("C", 0.5, 3)).unwrap();
+        assert_eq!(
+            simulation.rule_options().nothing_happens(),
+            NothingHappens::Remainder
+        );
+        assert_eq!(simulation.probability_of(&0, 1), 0.);
+    }
+
+    #[test]
+    fn nothing_happens_error() {
+        let rule: Rule<i32> = Rule::new(
+            "Forward".to_string(),
+            Arc::new(|state| state < 1),
+            1.,
+            Arc::new(|state| state + 1),
+        );
+        let mut simulation = Simulation::from_rules_with_options(
+            0,
+            HashMap::from([("forward".to_string(), rule)]),
+            RuleOptions::new().with_nothing_happens(NothingHappens::Error),
+        );
+        simulation.next_step();
+        assert_eq!(
+            simulation.try_next_step(),
+            Err(SimulationError::NoRuleApplies {
+                state_hash: hash(&1)
+            })
+        );
+        assert_eq!(simulation.time(), 1);
+    }
+
+    #[test]
+    fn batch_conditions() {
+        let batch_sizes = Arc::new(Mutex::new(Vec::new()));
+        let recorded_batch_sizes = batch_sizes.clone();
+        let batched: Rule<i32> = Rule::new_batched(
+            "Forward".to_string(),
+            Arc::new(move |states: &[i32]| {
+                recorded_batch_sizes.lock().unwrap().push(states.len());
+                states.iter().map(|state| state % 2 == 0).collect()
+            }),
+            0.5,
+            Arc::new(|state| state + 1),
+        );
+        let plain: Rule<i32> = Rule::new(
+            "Forward".to_string(),
+            Arc::new(|state| state % 2 == 0),
+            0.5,
+            Arc::new(|state| state + 1),
+        );
+        let backward = |state: i32| state - 2;
+        let backward_rule: Rule<i32> = Rule::new(
+            "Backward".to_string(),
+            Arc::new(|state| state > -3),
+            0.5,
+            Arc::new(backward),
+        );
+        let rules = |forward: Rule<i32>| {
+            HashMap::from([
+                ("forward".to_string(), forward),
+                ("backward".to_string(), backward_rule.clone()),
+            ])
+        };
+        let mut simulation = Simulation::from_rules(0, rules(batched.clone()));
+        let mut expected = Simulation::from_rules(0, rules(plain.clone()));
+        for _ in 0..4 {
+            simulation.next_step();
+            expected.next_step();
+        }
+        assert_eq!(
+            simulation.probability_distribution(4),
+            expected.probability_distribution(4)
+        );
+        // One batch per step for all states whose transitions were not cached yet
+        assert_eq!(*batch_sizes.lock().unwrap(), vec![1, 2, 2, 1]);
+        assert!(batched.condition().is_batched());
+        assert!(batched.applies(2));
+        assert_eq!(*batch_sizes.lock().unwrap(), vec![1, 2, 2, 1, 1]);
+
+        // Exploring evaluates every state in one of the batches of discovered states
+        batch_sizes.lock().unwrap().clear();
+        let mut simulation = Simulation::from_rules(0, rules(batched));
+        let mut expected = Simulation::from_rules(0, rules(plain));
+        simulation.try_explore().unwrap();
+        expected.try_explore().unwrap();
+        assert_eq!(
+            batch_sizes.lock().unwrap().iter().sum::<usize>(),
+            simulation.num_known_states()
+        );
+        expected.known_states().iter().for_each(|state| {
+            assert_eq!(
+                simulation.cached_outgoing_transitions(state),
+                expected.cached_outgoing_transitions(state)
+            );
+        });
+    }
 }
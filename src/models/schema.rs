@@ -0,0 +1,336 @@
+use std::{collections::BTreeMap, fmt::Debug, hash::Hash, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::entities::*;
+use crate::models::units::*;
+use crate::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ParameterType {
+    Boolean,
+    Integer,
+    Real,
+}
+
+impl ParameterType {
+    pub fn admits(&self, value: f64) -> bool {
+        match self {
+            ParameterType::Boolean => value == 0. || value == 1.,
+            ParameterType::Integer => value.fract() == 0.,
+            ParameterType::Real => value.is_finite(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParameterSpec {
+    parameter_type: ParameterType,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    unit: Unit,
+    required: bool,
+}
+
+impl ParameterSpec {
+    pub fn new(parameter_type: ParameterType) -> Self {
+        Self {
+            parameter_type,
+            minimum: None,
+            maximum: None,
+            unit: Unit::dimensionless(),
+            required: true,
+        }
+    }
+
+    pub fn boolean() -> Self {
+        Self::new(ParameterType::Boolean)
+    }
+
+    pub fn integer() -> Self {
+        Self::new(ParameterType::Integer)
+    }
+
+    pub fn real() -> Self {
+        Self::new(ParameterType::Real)
+    }
+
+    pub fn with_minimum(mut self, minimum: f64) -> Self {
+        self.minimum = Some(minimum);
+        self
+    }
+
+    pub fn with_maximum(mut self, maximum: f64) -> Self {
+        self.maximum = Some(maximum);
+        self
+    }
+
+    pub fn with_bounds(self, minimum: f64, maximum: f64) -> Self {
+        self.with_minimum(minimum).with_maximum(maximum)
+    }
+
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+
+    pub fn parameter_type(&self) -> ParameterType {
+        self.parameter_type
+    }
+
+    pub fn minimum(&self) -> Option<f64> {
+        self.minimum
+    }
+
+    pub fn maximum(&self) -> Option<f64> {
+        self.maximum
+    }
+
+    pub fn unit(&self) -> &Unit {
+        &self.unit
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+    pub fn is_within_bounds(&self, value: f64) -> bool {
+        self.minimum.is_none_or(|minimum| value >= minimum)
+            && self.maximum.is_none_or(|maximum| value <= maximum)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ClassSchema {
+    parameters: BTreeMap<ParameterName, ParameterSpec>,
+}
+
+impl ClassSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_parameter(
+        mut self,
+        parameter_name: impl Into<ParameterName>,
+        spec: ParameterSpec,
+    ) -> Self {
+        self.parameters.insert(parameter_name.into(), spec);
+        self
+    }
+
+    pub fn parameter(&self, parameter_name: &str) -> Option<&ParameterSpec> {
+        self.parameters.get(parameter_name)
+    }
+
+    pub fn parameters(&self) -> impl Iterator<Item = (&ParameterName, &ParameterSpec)> {
+        self.parameters.iter()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Schema {
+    classes: BTreeMap<ClassName, ClassSchema>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_class(mut self, class: impl Into<ClassName>, class_schema: ClassSchema) -> Self {
+        self.classes.insert(class.into(), class_schema);
+        self
+    }
+
+    pub fn class(&self, class: &str) -> Option<&ClassSchema> {
+        self.classes.get(class)
+    }
+
+    pub fn classes(&self) -> impl Iterator<Item = (&ClassName, &ClassSchema)> {
+        self.classes.iter()
+    }
+
+    pub fn parameter_spec<T>(
+        &self,
+        state: &State<T>,
+        entity_name: &str,
+        parameter_name: &str,
+    ) -> Result<&ParameterSpec, SchemaError> {
+        let entity = state
+            .entity(entity_name)
+            .ok_or_else(|| SchemaError::UnknownEntity {
+                entity: entity_name.to_string(),
+            })?;
+        let class = entity
+            .class()
+            .ok_or_else(|| SchemaError::UnclassifiedEntity {
+                entity: entity_name.to_string(),
+            })?;
+        self.class(class)
+            .ok_or_else(|| SchemaError::UnknownClass {
+                entity: entity_name.to_string(),
+                class: class.clone(),
+            })?
+            .parameter(parameter_name)
+            .ok_or_else(|| SchemaError::UnknownParameter {
+                entity: entity_name.to_string(),
+                parameter: parameter_name.to_string(),
+            })
+    }
+
+    pub fn validate_entity<T: Numeric>(
+        &self,
+        entity_name: &str,
+        entity: &StateEntity<T>,
+    ) -> Result<(), SchemaError> {
+        let class = entity
+            .class()
+            .ok_or_else(|| SchemaError::UnclassifiedEntity {
+                entity: entity_name.to_string(),
+            })?;
+        let class_schema = self.class(class).ok_or_else(|| SchemaError::UnknownClass {
+            entity: entity_name.to_string(),
+            class: class.clone(),
+        })?;
+        for (parameter_name, value) in entity.parameters() {
+            let spec = class_schema.parameter(parameter_name).ok_or_else(|| {
+                SchemaError::UnknownParameter {
+                    entity: entity_name.to_string(),
+                    parameter: parameter_name.clone(),
+                }
+            })?;
+            let value = value.to_f64();
+            if !spec.parameter_type().admits(value) {
+                return Err(SchemaError::TypeMismatch {
+                    entity: entity_name.to_string(),
+                    parameter: parameter_name.clone(),
+                    expected: spec.parameter_type(),
+                    value,
+                });
+            }
+            if !spec.is_within_bounds(value) {
+                return Err(SchemaError::OutOfBounds {
+                    entity: entity_name.to_string(),
+                    parameter: parameter_name.clone(),
+                    value,
+                    minimum: spec.minimum(),
+                    maximum: spec.maximum(),
+                });
+            }
+        }
+        match class_schema.parameters().find(|(parameter_name, spec)| {
+            spec.is_required() && entity.parameter(parameter_name).is_none()
+        }) {
+            Some((parameter_name, _)) => Err(SchemaError::MissingParameter {
+                entity: entity_name.to_string(),
+                parameter: parameter_name.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn validate<T: Numeric>(&self, state: &State<T>) -> Result<(), SchemaError> {
+        state
+            .entities()
+            .try_for_each(|(entity_name, entity)| self.validate_entity(entity_name, entity))
+    }
+}
+
+impl<V, T> Simulation<State<V>, T>
+where
+    V: Numeric + Hash + Clone + Send + Sync + PartialEq + Eq + Debug + 'static,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    pub fn register_schema(&mut self, schema: Schema) -> Result<(), SchemaError> {
+        let schema = Arc::new(schema);
+        self.iter_known_states()
+            .try_for_each(|state| schema.validate(state))?;
+        self.set_state_validator(Arc::new(move |state: &State<V>| schema.validate(state)));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tank_schema() -> Schema {
+        Schema::new().with_class(
+            "tank",
+            ClassSchema::new()
+                .with_parameter("level", ParameterSpec::integer().with_bounds(0., 3.))
+                .with_parameter("leaking", ParameterSpec::boolean().optional()),
+        )
+    }
+
+    fn tank(level: i32) -> State<i32> {
+        State::new().with_entity(
+            "a",
+            StateEntity::of_class("tank").with_parameter("level", level),
+        )
+    }
+
+    #[test]
+    fn validation() {
+        let schema = tank_schema();
+        assert_eq!(schema.validate(&tank(2)), Ok(()));
+        assert!(matches!(
+            schema.validate(&tank(4)),
+            Err(SchemaError::OutOfBounds { value, .. }) if value == 4.
+        ));
+
+        let mut leaking = tank(1);
+        leaking.set_parameter("a", "leaking", 2);
+        assert!(matches!(
+            schema.validate(&leaking),
+            Err(SchemaError::TypeMismatch {
+                expected: ParameterType::Boolean,
+                ..
+            })
+        ));
+
+        let unknown =
+            State::new().with_entity("b", StateEntity::of_class("pipe").with_parameter("flow", 1));
+        assert_eq!(
+            schema.validate(&unknown),
+            Err(SchemaError::UnknownClass {
+                entity: "b".to_string(),
+                class: "pipe".to_string(),
+            })
+        );
+        assert_eq!(
+            schema.validate(&State::new().with_entity("c", StateEntity::<i32>::of_class("tank"))),
+            Err(SchemaError::MissingParameter {
+                entity: "c".to_string(),
+                parameter: "level".to_string(),
+            })
+        );
+        assert!(tank_schema()
+            .validate(&tank(1).with_entity("d", StateEntity::<i32>::new()))
+            .is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn simulation_rejects_invalid_states() {
+        let state_transition_generator = Arc::new(|state: State<i32>| {
+            let level = *state.parameter("a", "level").unwrap();
+            let mut filled = state.clone();
+            filled.set_parameter("a", "level", level + 1);
+            vec![(state, "wait", 0.5), (filled, "fill", 0.5)]
+        });
+        let mut simulation = Simulation::new(tank(0), state_transition_generator);
+        simulation.register_schema(tank_schema()).unwrap();
+        for _ in 0..3 {
+            simulation.next_step();
+        }
+        assert_eq!(simulation.known_states().len(), 4);
+        simulation.next_step();
+    }
+}
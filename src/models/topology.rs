@@ -0,0 +1,227 @@
+use std::sync::Arc;
+
+use hashbrown::{HashMap, HashSet};
+use itertools::Itertools;
+
+use crate::models::entities::*;
+use crate::models::rules::*;
+
+pub type SiteName = EntityName;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Topology {
+    neighbors: HashMap<SiteName, Vec<SiteName>>,
+}
+
+impl Topology {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn grid(width: usize, height: usize, periodic: bool) -> Self {
+        let mut topology = Self::new();
+        for (x, y) in (0..width).cartesian_product(0..height) {
+            topology.add_site(grid_site(x, y));
+            if x + 1 < width || (periodic && width > 2) {
+                topology.add_link(grid_site(x, y), grid_site((x + 1) % width, y));
+            }
+            if y + 1 < height || (periodic && height > 2) {
+                topology.add_link(grid_site(x, y), grid_site(x, (y + 1) % height));
+            }
+        }
+        topology
+    }
+
+    pub fn from_links(links: impl IntoIterator<Item = (SiteName, SiteName)>) -> Self {
+        let mut topology = Self::new();
+        links
+            .into_iter()
+            .for_each(|(first, second)| topology.add_link(first, second));
+        topology
+    }
+
+    pub fn add_site(&mut self, site: SiteName) {
+        self.neighbors.entry(site).or_default();
+    }
+
+    pub fn add_link(&mut self, first: SiteName, second: SiteName) {
+        let first_neighbors = self.neighbors.entry(first.clone()).or_default();
+        if !first_neighbors.contains(&second) && first != second {
+            first_neighbors.push(second.clone());
+        }
+        let second_neighbors = self.neighbors.entry(second.clone()).or_default();
+        if !second_neighbors.contains(&first) && first != second {
+            second_neighbors.push(first);
+        }
+    }
+
+    pub fn sites(&self) -> impl Iterator<Item = &SiteName> {
+        self.neighbors.keys()
+    }
+
+    pub fn neighbors(&self, site: &str) -> &[SiteName] {
+        self.neighbors
+            .get(site)
+            .map(|neighbors| neighbors.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn are_neighbors(&self, first: &str, second: &str) -> bool {
+        self.neighbors(first).iter().any(|site| site == second)
+    }
+
+    pub fn any_neighbor<T>(
+        &self,
+        state: &State<T>,
+        site: &str,
+        predicate: impl Fn(&StateEntity<T>) -> bool,
+    ) -> bool {
+        self.neighbor_entities(state, site).any(predicate)
+    }
+
+    pub fn all_neighbors<T>(
+        &self,
+        state: &State<T>,
+        site: &str,
+        predicate: impl Fn(&StateEntity<T>) -> bool,
+    ) -> bool {
+        self.neighbor_entities(state, site).all(predicate)
+    }
+
+    pub fn count_neighbors<T>(
+        &self,
+        state: &State<T>,
+        site: &str,
+        predicate: impl Fn(&StateEntity<T>) -> bool,
+    ) -> usize {
+        self.neighbor_entities(state, site)
+            .filter(|entity| predicate(entity))
+            .count()
+    }
+
+    pub fn neighbor_entities<'a, T>(
+        &'a self,
+        state: &'a State<T>,
+        site: &str,
+    ) -> impl Iterator<Item = &'a StateEntity<T>> {
+        self.neighbors(site)
+            .iter()
+            .filter_map(move |neighbor| state.entity(neighbor))
+    }
+
+    pub fn is_connected(&self) -> bool {
+        let Some(start) = self.sites().next() else {
+            return true;
+        };
+        let mut visited: HashSet<&SiteName> = HashSet::from_iter([start]);
+        let mut frontier = vec![start];
+        while let Some(site) = frontier.pop() {
+            self.neighbors(site).iter().for_each(|neighbor| {
+                if visited.insert(neighbor) {
+                    frontier.push(neighbor);
+                }
+            });
+        }
+        visited.len() == self.neighbors.len()
+    }
+
+    pub fn rules_per_site<T, C, A>(
+        &self,
+        rule_name: &str,
+        description: &str,
+        weight: ProbabilityWeight,
+        condition: C,
+        action: A,
+    ) -> HashMap<RuleName, Rule<State<T>>>
+    where
+        T: 'static,
+        C: Fn(&Topology, &str, &State<T>) -> RuleApplies + Send + Sync + 'static,
+        A: Fn(&Topology, &str, State<T>) -> State<T> + Send + Sync + 'static,
+    {
+        let topology = Arc::new(self.clone());
+        let condition = Arc::new(condition);
+        let action = Arc::new(action);
+        self.sites()
+            .map(|site| {
+                let condition_topology = topology.clone();
+                let condition_site = site.clone();
+                let condition = condition.clone();
+                let action_topology = topology.clone();
+                let action_site = site.clone();
+                let action = action.clone();
+                let rule = Rule::new(
+                    format!("{description} at {site}"),
+                    Arc::new(move |state: State<T>| {
+                        condition(&condition_topology, &condition_site, &state)
+                    }),
+                    weight,
+                    Arc::new(move |state: State<T>| action(&action_topology, &action_site, state)),
+                );
+                (format!("{rule_name}@{site}"), rule)
+            })
+            .collect()
+    }
+}
+
+pub fn grid_site(x: usize, y: usize) -> SiteName {
+    format!("({x}, {y})")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn grid() {
+        let topology = Topology::grid(3, 2, false);
+        assert_eq!(topology.sites().count(), 6);
+        assert_eq!(topology.neighbors(&grid_site(0, 0)).len(), 2);
+        assert_eq!(topology.neighbors(&grid_site(1, 0)).len(), 3);
+        assert!(topology.are_neighbors(&grid_site(1, 1), &grid_site(1, 0)));
+        assert!(!topology.are_neighbors(&grid_site(0, 0), &grid_site(2, 0)));
+        assert!(topology.is_connected());
+
+        let periodic = Topology::grid(3, 3, true);
+        assert!(periodic
+            .sites()
+            .all(|site| periodic.neighbors(site).len() == 4));
+    }
+
+    #[test]
+    fn contact_process() {
+        let topology = Topology::from_links([
+            ("a".to_string(), "b".to_string()),
+            ("b".to_string(), "c".to_string()),
+        ]);
+        let initial_state = topology
+            .sites()
+            .map(|site| {
+                let infected = if site == "a" { 1 } else { 0 };
+                (
+                    site.clone(),
+                    StateEntity::new().with_parameter("infected", infected),
+                )
+            })
+            .collect::<State<i32>>();
+        let rules = topology.rules_per_site(
+            "infection",
+            "Infection",
+            0.5,
+            |topology, site, state: &State<i32>| {
+                state.parameter(site, "infected") == Some(&0)
+                    && topology.any_neighbor(state, site, |neighbor| {
+                        neighbor.parameter("infected") == Some(&1)
+                    })
+            },
+            |_, site, mut state| {
+                state.set_parameter(site, "infected", 1);
+                state
+            },
+        );
+        assert_eq!(rules.len(), 3);
+        let mut simulation = Simulation::new(initial_state, get_state_transition_generator(rules));
+        simulation.full_traversal(false);
+        assert_eq!(simulation.known_states().len(), 3);
+    }
+}
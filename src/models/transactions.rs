@@ -0,0 +1,422 @@
+use std::{fmt::Debug, hash::Hash, sync::Arc};
+
+use hashbrown::HashMap;
+use itertools::Itertools;
+
+use crate::models::rules::*;
+use crate::prelude::*;
+
+pub type Invariant<T> = Arc<dyn Fn(&T) -> bool + Send + Sync>;
+// Called with the base state and the result of the transaction
+pub type PostCondition<T> = Arc<dyn Fn(&T, &T) -> bool + Send + Sync>;
+
+// What happens if the combined result of the applying rules is invalid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TransactionFallback {
+    // The transaction is rolled back and the state stays the same
+    #[default]
+    Rollback,
+    // The rules are applied one at a time as without transactions, invalid results are dropped
+    Alternatives,
+    // An invalid result is a modelling error
+    Panic,
+}
+
+// What happens if several applying rules write the same parameter. Conflicts are found by the
+// declared or probed access of the rules, see probe_access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ConflictResolution {
+    // Rules are applied in order, so the last rule writing a parameter wins
+    #[default]
+    Overwrite,
+    // Only one rule of every group of conflicting rules is applied, chosen with a probability
+    // proportional to its weight
+    Split,
+    // Conflicting rules are a modelling error
+    Panic,
+}
+
+// All rules applying to a state are applied together, in the order of the tie breaking, and
+// their combined result is validated before it becomes a state of the simulation
+#[derive(Clone)]
+pub struct Transaction<T> {
+    invariants: Vec<(String, Invariant<T>)>,
+    post_conditions: Vec<(String, PostCondition<T>)>,
+    fallback: TransactionFallback,
+    conflict_resolution: ConflictResolution,
+}
+
+impl<T> Debug for Transaction<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Transaction")
+            .field(
+                "invariants",
+                &self.invariants.iter().map(|(name, _)| name).collect_vec(),
+            )
+            .field(
+                "post_conditions",
+                &self
+                    .post_conditions
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect_vec(),
+            )
+            .field("fallback", &self.fallback)
+            .field("conflict_resolution", &self.conflict_resolution)
+            .finish()
+    }
+}
+
+impl<T> Transaction<T> {
+    pub fn new() -> Self {
+        Self {
+            invariants: Vec::new(),
+            post_conditions: Vec::new(),
+            fallback: TransactionFallback::default(),
+            conflict_resolution: ConflictResolution::default(),
+        }
+    }
+
+    pub fn with_invariant(
+        mut self,
+        description: impl Into<String>,
+        invariant: impl Fn(&T) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.invariants
+            .push((description.into(), Arc::new(invariant)));
+        self
+    }
+
+    pub fn with_post_condition(
+        mut self,
+        description: impl Into<String>,
+        post_condition: impl Fn(&T, &T) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.post_conditions
+            .push((description.into(), Arc::new(post_condition)));
+        self
+    }
+
+    pub fn with_fallback(mut self, fallback: TransactionFallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    pub fn with_conflict_resolution(mut self, conflict_resolution: ConflictResolution) -> Self {
+        self.conflict_resolution = conflict_resolution;
+        self
+    }
+
+    pub fn fallback(&self) -> TransactionFallback {
+        self.fallback
+    }
+
+    pub fn conflict_resolution(&self) -> ConflictResolution {
+        self.conflict_resolution
+    }
+
+    // The description of the first violated invariant or post condition
+    pub fn violation(&self, base: &T, result: &T) -> Option<&String> {
+        let invariant = self
+            .invariants
+            .iter()
+            .find(|(_, invariant)| !invariant(result));
+        invariant.map(|(description, _)| description).or_else(|| {
+            self.post_conditions
+                .iter()
+                .find(|(_, post_condition)| !post_condition(base, result))
+                .map(|(description, _)| description)
+        })
+    }
+}
+
+impl<T> Default for Transaction<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Groups of applying rules which transitively write the same parameters, in the order of the
+// rules. Panics if the access of a rule is unknown.
+fn conflict_groups<T>(rules: &[&Rule<T>]) -> Vec<Vec<usize>> {
+    let access = rules
+        .iter()
+        .map(|rule| {
+            rule.access().unwrap_or_else(|| {
+                panic!(
+                    "The access of rule {} is unknown, conflicts can't be detected",
+                    rule.description()
+                )
+            })
+        })
+        .collect_vec();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    for index in 0..rules.len() {
+        let (conflicting, mut others): (Vec<_>, Vec<_>) = groups.into_iter().partition(|group| {
+            group
+                .iter()
+                .any(|other| !access[index].shared_writes(access[*other]).is_empty())
+        });
+        let mut group = conflicting.into_iter().flatten().collect_vec();
+        group.push(index);
+        group.sort();
+        others.push(group);
+        groups = others;
+    }
+    groups.sort();
+    groups
+}
+
+// The sets of rules which are applied together, with their probabilities
+fn outcomes<'a, T>(
+    rules: &[&'a Rule<T>],
+    conflict_resolution: ConflictResolution,
+) -> Vec<(Vec<&'a Rule<T>>, Probability)> {
+    if conflict_resolution == ConflictResolution::Overwrite {
+        return vec![(rules.to_vec(), 1.)];
+    }
+    let groups = conflict_groups(rules);
+    if conflict_resolution == ConflictResolution::Panic {
+        if let Some(group) = groups.iter().find(|group| group.len() > 1) {
+            let descriptions = group
+                .iter()
+                .map(|index| rules[*index].description())
+                .join(", ");
+            panic!("Conflicting rules {descriptions}");
+        }
+    }
+    groups
+        .iter()
+        .map(|group| {
+            let total_weight = group
+                .iter()
+                .map(|index| rules[*index].weight())
+                .sum::<ProbabilityWeight>();
+            group
+                .iter()
+                .map(|index| (*index, rules[*index].weight() / total_weight))
+                .collect_vec()
+        })
+        .multi_cartesian_product()
+        .map(|choice| {
+            let mut indices = choice.iter().map(|(index, _)| *index).collect_vec();
+            indices.sort();
+            let probability = choice.iter().map(|(_, probability)| probability).product();
+            (
+                indices.into_iter().map(|index| rules[index]).collect(),
+                probability,
+            )
+        })
+        .collect()
+}
+
+// A committed transaction leads to a single successor, or to one per combination of conflicting
+// rules, so the weights of the rules only matter for conflicts and the fallback to alternatives
+pub fn get_transactional_state_transition_generator<T>(
+    rules: HashMap<RuleName, Rule<T>>,
+    transaction: Transaction<T>,
+    options: RuleOptions,
+) -> StateTransitionGenerator<T, String>
+where
+    T: Debug + Clone + Send + Sync + 'static + PartialEq + Eq + Hash,
+{
+    let rules = options
+        .tie_breaking()
+        .order(&rules)
+        .into_iter()
+        .map(|(_, rule)| rule.clone())
+        .collect_vec();
+    Arc::new(move |state: T| -> OutgoingTransitions<T, String> {
+        let applying_rules = rules
+            .iter()
+            .filter(|rule| rule.applies(state.clone()))
+            .collect_vec();
+        if applying_rules.is_empty() {
+            return outgoing_transitions_with(state, Vec::new(), options.nothing_happens());
+        }
+        let mut new_states = Vec::new();
+        for (outcome, probability) in outcomes(&applying_rules, transaction.conflict_resolution) {
+            let result = outcome
+                .iter()
+                .fold(state.clone(), |result, rule| rule.apply(result));
+            let description = outcome.iter().map(|rule| rule.description()).join(" + ");
+            let Some(violation) = transaction.violation(&state, &result) else {
+                new_states.push((result, probability, description));
+                continue;
+            };
+            match transaction.fallback {
+                TransactionFallback::Rollback => new_states.push((
+                    state.clone(),
+                    probability,
+                    format!("Rollback of {description}: {violation}"),
+                )),
+                TransactionFallback::Alternatives => {
+                    let new_states = applying_rules
+                        .into_iter()
+                        .map(|rule| {
+                            (
+                                rule.apply(state.clone()),
+                                rule.weight(),
+                                rule.description().clone(),
+                            )
+                        })
+                        .filter(|(new_state, _, _)| {
+                            transaction.violation(&state, new_state).is_none()
+                        })
+                        .collect_vec();
+                    return outgoing_transitions_with(state, new_states, options.nothing_happens());
+                }
+                TransactionFallback::Panic => {
+                    panic!("Transaction {description} on state {state:?} violates {violation}")
+                }
+            }
+        }
+        // The probabilities of the outcomes already sum up to one
+        outgoing_transitions_with(state, new_states, NothingHappens::Error)
+    }) as StateTransitionGenerator<T, String>
+}
+
+impl<S> Simulation<S, String>
+where
+    S: Debug + Clone + Send + Sync + 'static + PartialEq + Eq + Hash,
+{
+    pub fn from_transactional_rules(
+        initial_state: S,
+        rules: HashMap<RuleName, Rule<S>>,
+        transaction: Transaction<S>,
+        options: RuleOptions,
+    ) -> Self {
+        let rule_generator = Arc::new(move |rules, options| {
+            let state_transition_generator =
+                get_transactional_state_transition_generator(rules, transaction.clone(), options);
+            (state_transition_generator, None)
+        }) as RuleGenerator<S, String>;
+        let (state_transition_generator, _) = rule_generator(rules.clone(), options);
+        let mut simulation = Simulation::new(initial_state, state_transition_generator);
+        simulation.set_rules(rules);
+        simulation.set_rule_options(options);
+        simulation.set_rule_generator(rule_generator);
+        simulation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transactions() {
+        // Two workers take from the same stock of two items, together they would overdraw it
+        let take = |amount: i32| {
+            Rule::new(
+                format!("Take {amount}"),
+                Arc::new(move |stock: i32| stock >= amount),
+                0.5,
+                Arc::new(move |stock: i32| stock - amount),
+            )
+        };
+        let rules = HashMap::from([("one".to_string(), take(1)), ("two".to_string(), take(2))]);
+        let transaction = Transaction::new()
+            .with_invariant("Stock is not negative", |stock: &i32| *stock >= 0)
+            .with_post_condition("Stock only decreases", |base: &i32, stock: &i32| {
+                stock <= base
+            });
+        assert_eq!(
+            transaction.violation(&2, &-1),
+            Some(&"Stock is not negative".to_string())
+        );
+        assert_eq!(
+            transaction.violation(&2, &3),
+            Some(&"Stock only decreases".to_string())
+        );
+
+        let mut committed = Simulation::from_transactional_rules(
+            4,
+            rules.clone(),
+            transaction.clone(),
+            RuleOptions::default(),
+        );
+        committed.next_step();
+        assert_eq!(committed.probability_of(&1, 1), 1.);
+        committed.next_step();
+        // Only the first rule applies to a stock of one
+        assert_eq!(committed.probability_of(&0, 2), 1.);
+
+        let mut rolled_back = Simulation::from_transactional_rules(
+            2,
+            rules.clone(),
+            transaction.clone(),
+            RuleOptions::default(),
+        );
+        rolled_back.next_step();
+        assert_eq!(rolled_back.probability_of(&2, 1), 1.);
+        // Edited rules are still applied as a transaction
+        rolled_back.remove_rule("two").unwrap();
+        assert_eq!(rolled_back.probability_of(&1, 1), 1.);
+
+        let mut alternatives = Simulation::from_transactional_rules(
+            2,
+            rules,
+            transaction.with_fallback(TransactionFallback::Alternatives),
+            RuleOptions::new().with_nothing_happens(NothingHappens::Absorb),
+        );
+        alternatives.next_step();
+        assert_eq!(alternatives.probability_of(&1, 1), 0.5);
+        assert_eq!(alternatives.probability_of(&0, 1), 0.5);
+    }
+
+    #[test]
+    fn conflict_resolution() {
+        let stock = ("*".to_string(), "stock".to_string());
+        let access = RuleAccess::new(vec![stock.clone()], vec![stock]);
+        let take = |amount: i32, weight: ProbabilityWeight| {
+            Rule::new(
+                format!("Take {amount}"),
+                Arc::new(move |stock: i32| stock >= amount),
+                weight,
+                Arc::new(move |_: i32| 4 - amount),
+            )
+            .with_access(access.clone())
+        };
+        let restock = Rule::new(
+            "Restock".to_string(),
+            Arc::new(|stock: i32| stock >= 10),
+            0.5,
+            Arc::new(|stock: i32| stock + 10),
+        )
+        .with_access(RuleAccess::new(
+            Vec::new(),
+            vec![("*".to_string(), "delivery".to_string())],
+        ));
+        let rules = HashMap::from([
+            ("one".to_string(), take(1, 0.2)),
+            ("two".to_string(), take(2, 0.6)),
+            ("restock".to_string(), restock),
+        ]);
+        let mut overwrite = Simulation::from_transactional_rules(
+            4,
+            rules.clone(),
+            Transaction::new(),
+            RuleOptions::default(),
+        );
+        overwrite.next_step();
+        assert_eq!(overwrite.probability_of(&2, 1), 1.);
+
+        let mut split = Simulation::from_transactional_rules(
+            4,
+            rules.clone(),
+            Transaction::new().with_conflict_resolution(ConflictResolution::Split),
+            RuleOptions::default(),
+        );
+        split.next_step();
+        assert!((split.probability_of(&3, 1) - 0.25).abs() < 1e-12);
+        assert!((split.probability_of(&2, 1) - 0.75).abs() < 1e-12);
+
+        let applying = rules.values().collect_vec();
+        let groups = conflict_groups(&applying);
+        assert_eq!(
+            groups.iter().map(Vec::len).sorted().collect_vec(),
+            vec![1, 2]
+        );
+    }
+}
@@ -0,0 +1,588 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    ops::{Add, Div, Mul, Sub},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::conditions::*;
+use crate::models::entities::*;
+use crate::models::schema::*;
+use crate::prelude::*;
+
+// A unit is a product of named base units with integer exponents, so `joule / second` and
+// `watt` are only compatible if both are declared in terms of the same base units.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct Unit {
+    exponents: BTreeMap<String, i32>,
+}
+
+impl Unit {
+    pub fn dimensionless() -> Self {
+        Self::default()
+    }
+
+    pub fn base(name: impl Into<String>) -> Self {
+        Self {
+            exponents: BTreeMap::from([(name.into(), 1)]),
+        }
+    }
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.exponents.is_empty()
+    }
+
+    pub fn exponent(&self, base: &str) -> i32 {
+        self.exponents.get(base).copied().unwrap_or(0)
+    }
+
+    pub fn powi(&self, exponent: i32) -> Self {
+        self.exponents
+            .iter()
+            .map(|(base, base_exponent)| (base.clone(), base_exponent * exponent))
+            .collect()
+    }
+}
+
+impl FromIterator<(String, i32)> for Unit {
+    fn from_iter<I: IntoIterator<Item = (String, i32)>>(iter: I) -> Self {
+        let mut unit = Self::dimensionless();
+        iter.into_iter().for_each(|(base, exponent)| {
+            *unit.exponents.entry(base).or_insert(0) += exponent;
+        });
+        unit.exponents.retain(|_, exponent| *exponent != 0);
+        unit
+    }
+}
+
+impl Mul for Unit {
+    type Output = Unit;
+
+    fn mul(self, other: Unit) -> Unit {
+        self.exponents.into_iter().chain(other.exponents).collect()
+    }
+}
+
+impl Div for Unit {
+    type Output = Unit;
+
+    fn div(self, other: Unit) -> Unit {
+        self * other.powi(-1)
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_dimensionless() {
+            return write!(f, "1");
+        }
+        let factors = self
+            .exponents
+            .iter()
+            .map(|(base, exponent)| match exponent {
+                1 => base.clone(),
+                _ => format!("{base}^{exponent}"),
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", factors.join("*"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Constant {
+        value: f64,
+        unit: Unit,
+    },
+    Parameter {
+        entity: EntityName,
+        parameter: ParameterName,
+    },
+    // A parameter of the entity an assignment to selected entities is currently applied to
+    Selected {
+        parameter: ParameterName,
+    },
+    Add(Box<Expression>, Box<Expression>),
+    Subtract(Box<Expression>, Box<Expression>),
+    Multiply(Box<Expression>, Box<Expression>),
+    Divide(Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    pub fn constant(value: f64, unit: Unit) -> Self {
+        Expression::Constant { value, unit }
+    }
+
+    pub fn parameter(entity: impl Into<EntityName>, parameter: impl Into<ParameterName>) -> Self {
+        Expression::Parameter {
+            entity: entity.into(),
+            parameter: parameter.into(),
+        }
+    }
+
+    pub fn selected(parameter: impl Into<ParameterName>) -> Self {
+        Expression::Selected {
+            parameter: parameter.into(),
+        }
+    }
+
+    // The entities and parameters the expression reads, None for the selected entity
+    pub fn parameters(&self) -> Vec<(Option<&EntityName>, &ParameterName)> {
+        match self {
+            Expression::Constant { .. } => Vec::new(),
+            Expression::Parameter { entity, parameter } => vec![(Some(entity), parameter)],
+            Expression::Selected { parameter } => vec![(None, parameter)],
+            Expression::Add(left, right)
+            | Expression::Subtract(left, right)
+            | Expression::Multiply(left, right)
+            | Expression::Divide(left, right) => {
+                let mut parameters = left.parameters();
+                parameters.extend(right.parameters());
+                parameters
+            }
+        }
+    }
+
+    // Selected parameters evaluate to None, there is no selected entity
+    pub fn evaluate<T: Numeric>(&self, state: &State<T>) -> Option<f64> {
+        self.evaluate_with(state, None)
+    }
+
+    pub fn evaluate_for<T: Numeric>(&self, state: &State<T>, entity_name: &str) -> Option<f64> {
+        self.evaluate_with(state, Some(entity_name))
+    }
+
+    fn evaluate_with<T: Numeric>(&self, state: &State<T>, selected: Option<&str>) -> Option<f64> {
+        let evaluate = |expression: &Expression| expression.evaluate_with(state, selected);
+        match self {
+            Expression::Constant { value, .. } => Some(*value),
+            Expression::Parameter { entity, parameter } => {
+                state.parameter(entity, parameter).map(Numeric::to_f64)
+            }
+            Expression::Selected { parameter } => {
+                state.parameter(selected?, parameter).map(Numeric::to_f64)
+            }
+            Expression::Add(left, right) => Some(evaluate(left)? + evaluate(right)?),
+            Expression::Subtract(left, right) => Some(evaluate(left)? - evaluate(right)?),
+            Expression::Multiply(left, right) => Some(evaluate(left)? * evaluate(right)?),
+            Expression::Divide(left, right) => Some(evaluate(left)? / evaluate(right)?),
+        }
+    }
+
+    // Entity classes are looked up in the given state, usually the initial state of the model
+    pub fn unit<T>(&self, schema: &Schema, state: &State<T>) -> Result<Unit, SchemaError> {
+        self.unit_with(schema, state, None)
+    }
+
+    pub fn unit_for<T>(
+        &self,
+        schema: &Schema,
+        state: &State<T>,
+        entity_name: &str,
+    ) -> Result<Unit, SchemaError> {
+        self.unit_with(schema, state, Some(entity_name))
+    }
+
+    fn unit_with<T>(
+        &self,
+        schema: &Schema,
+        state: &State<T>,
+        selected: Option<&str>,
+    ) -> Result<Unit, SchemaError> {
+        let unit = |expression: &Expression| expression.unit_with(schema, state, selected);
+        match self {
+            Expression::Constant { unit, .. } => Ok(unit.clone()),
+            Expression::Parameter { entity, parameter } => schema
+                .parameter_spec(state, entity, parameter)
+                .map(|spec| spec.unit().clone()),
+            Expression::Selected { parameter } => {
+                let entity = selected.ok_or_else(|| SchemaError::NoSelectedEntity {
+                    parameter: parameter.clone(),
+                })?;
+                schema
+                    .parameter_spec(state, entity, parameter)
+                    .map(|spec| spec.unit().clone())
+            }
+            Expression::Add(left, right) | Expression::Subtract(left, right) => {
+                let left = unit(left)?;
+                let right = unit(right)?;
+                if left == right {
+                    Ok(left)
+                } else {
+                    Err(SchemaError::UnitMismatch {
+                        expected: left,
+                        found: right,
+                    })
+                }
+            }
+            Expression::Multiply(left, right) => Ok(unit(left)? * unit(right)?),
+            Expression::Divide(left, right) => Ok(unit(left)? / unit(right)?),
+        }
+    }
+}
+
+macro_rules! impl_expression_operator {
+    ($($operator:ident, $method:ident, $variant:ident);*) => {
+        $(
+            impl $operator for Expression {
+                type Output = Expression;
+
+                fn $method(self, other: Expression) -> Expression {
+                    Expression::$variant(Box::new(self), Box::new(other))
+                }
+            }
+        )*
+    };
+}
+
+impl_expression_operator!(Add, add, Add; Sub, sub, Subtract; Mul, mul, Multiply; Div, div, Divide);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    target: EntitySelector,
+    parameter: ParameterName,
+    expression: Expression,
+}
+
+impl Assignment {
+    pub fn new(
+        entity: impl Into<EntityName>,
+        parameter: impl Into<ParameterName>,
+        expression: Expression,
+    ) -> Self {
+        Self::to_selected(EntitySelector::Name(entity.into()), parameter, expression)
+    }
+
+    // Assigns to every selected entity, Expression::selected refers to the entity assigned to.
+    // All values are computed from the state before the assignment.
+    pub fn to_selected(
+        target: EntitySelector,
+        parameter: impl Into<ParameterName>,
+        expression: Expression,
+    ) -> Self {
+        Self {
+            target,
+            parameter: parameter.into(),
+            expression,
+        }
+    }
+
+    // The declarative counterpart of actions::increment
+    pub fn increment(
+        entity: impl Into<EntityName>,
+        parameter: impl Into<ParameterName>,
+        delta: Expression,
+    ) -> Self {
+        let (entity, parameter) = (entity.into(), parameter.into());
+        let expression = Expression::parameter(entity.clone(), parameter.clone()) + delta;
+        Self::new(entity, parameter, expression)
+    }
+
+    pub fn target(&self) -> &EntitySelector {
+        &self.target
+    }
+
+    pub fn parameter(&self) -> &ParameterName {
+        &self.parameter
+    }
+
+    pub fn expression(&self) -> &Expression {
+        &self.expression
+    }
+
+    // An entity selected by name is assigned to even if it does not exist yet
+    fn targets<T: Numeric>(&self, state: &State<T>) -> Vec<EntityName> {
+        match &self.target {
+            EntitySelector::Name(name) => vec![name.clone()],
+            target => target.select(state).map(|(name, _)| name.clone()).collect(),
+        }
+    }
+
+    pub fn check_units<T: Numeric>(
+        &self,
+        schema: &Schema,
+        state: &State<T>,
+    ) -> Result<(), SchemaError> {
+        self.targets(state).iter().try_for_each(|entity| {
+            let expected = schema
+                .parameter_spec(state, entity, &self.parameter)?
+                .unit()
+                .clone();
+            let found = self.expression.unit_for(schema, state, entity)?;
+            if expected == found {
+                Ok(())
+            } else {
+                Err(SchemaError::UnitMismatch { expected, found })
+            }
+        })
+    }
+
+    // Every parameter the expression reads has to exist for each target
+    pub fn check_parameters<T: Numeric>(&self, state: &State<T>) -> Result<(), SchemaError> {
+        self.targets(state).iter().try_for_each(|target| {
+            self.expression
+                .parameters()
+                .into_iter()
+                .try_for_each(|(entity, parameter)| {
+                    let entity = entity.unwrap_or(target);
+                    let found = state
+                        .entity(entity)
+                        .ok_or_else(|| SchemaError::UnknownEntity {
+                            entity: entity.clone(),
+                        })?;
+                    match found.parameter(parameter) {
+                        Some(_) => Ok(()),
+                        None => Err(SchemaError::UnknownParameter {
+                            entity: entity.clone(),
+                            parameter: parameter.clone(),
+                        }),
+                    }
+                })
+        })
+    }
+
+    // Targets whose expression cannot be evaluated, e.g. because a parameter was removed after
+    // the parameters were checked, are left unchanged
+    pub fn apply<T: Numeric>(&self, mut state: State<T>) -> State<T> {
+        let values = self
+            .targets(&state)
+            .into_iter()
+            .filter_map(|entity| {
+                let value = self.expression.evaluate_for(&state, &entity)?;
+                Some((entity, value))
+            })
+            .collect::<Vec<_>>();
+        values.into_iter().for_each(|(entity, value)| {
+            state.set_parameter(entity, self.parameter.clone(), T::from_f64(value));
+        });
+        state
+    }
+
+    pub fn to_action<T>(&self) -> Arc<dyn Fn(State<T>) -> State<T> + Send + Sync>
+    where
+        T: Numeric + 'static,
+    {
+        let assignment = self.clone();
+        Arc::new(move |state| assignment.apply(state))
+    }
+}
+
+// Continuous model time in seconds, unlike the discrete step counter of a simulation
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+pub struct ContinuousTime(f64);
+
+impl ContinuousTime {
+    pub fn seconds(seconds: f64) -> Self {
+        Self(seconds)
+    }
+
+    pub fn as_seconds(&self) -> f64 {
+        self.0
+    }
+
+    pub fn unit() -> Unit {
+        Unit::base("s")
+    }
+}
+
+impl Add for ContinuousTime {
+    type Output = ContinuousTime;
+
+    fn add(self, other: ContinuousTime) -> ContinuousTime {
+        ContinuousTime(self.0 + other.0)
+    }
+}
+
+impl Sub for ContinuousTime {
+    type Output = ContinuousTime;
+
+    fn sub(self, other: ContinuousTime) -> ContinuousTime {
+        ContinuousTime(self.0 - other.0)
+    }
+}
+
+impl Mul<f64> for ContinuousTime {
+    type Output = ContinuousTime;
+
+    fn mul(self, factor: f64) -> ContinuousTime {
+        ContinuousTime(self.0 * factor)
+    }
+}
+
+impl Div for ContinuousTime {
+    type Output = f64;
+
+    fn div(self, other: ContinuousTime) -> f64 {
+        self.0 / other.0
+    }
+}
+
+impl From<ContinuousTime> for Expression {
+    fn from(time: ContinuousTime) -> Self {
+        Expression::constant(time.0, ContinuousTime::unit())
+    }
+}
+
+// The factor by which a value is discounted per second. It is deserialized through the same check
+// as in new.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(try_from = "f64", into = "f64")]
+pub struct DiscountFactor(f64);
+
+impl DiscountFactor {
+    pub fn new(factor: f64) -> Option<Self> {
+        (factor > 0. && factor <= 1.).then_some(Self(factor))
+    }
+
+    pub fn none() -> Self {
+        Self(1.)
+    }
+
+    pub fn value(&self) -> f64 {
+        self.0
+    }
+
+    pub fn over(&self, time: ContinuousTime) -> f64 {
+        self.0.powf(time.as_seconds())
+    }
+
+    pub fn discount(&self, value: f64, time: ContinuousTime) -> f64 {
+        value * self.over(time)
+    }
+}
+
+impl Default for DiscountFactor {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl TryFrom<f64> for DiscountFactor {
+    type Error = ProbabilityError;
+
+    fn try_from(factor: f64) -> Result<Self, Self::Error> {
+        if factor.is_nan() {
+            Err(ProbabilityError::NotANumber)
+        } else {
+            Self::new(factor).ok_or(ProbabilityError::OutOfRange { value: factor })
+        }
+    }
+}
+
+impl From<DiscountFactor> for f64 {
+    fn from(factor: DiscountFactor) -> Self {
+        factor.0
+    }
+}
+
+impl Mul for DiscountFactor {
+    type Output = DiscountFactor;
+
+    fn mul(self, other: DiscountFactor) -> DiscountFactor {
+        DiscountFactor(self.0 * other.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_checking() {
+        let joule = Unit::base("kg") * Unit::base("m").powi(2) / Unit::base("s").powi(2);
+        let watt = joule.clone() / Unit::base("s");
+        assert_eq!(watt.to_string(), "kg*m^2*s^-3");
+        assert!((watt.clone() / watt.clone()).is_dimensionless());
+
+        let schema = Schema::new().with_class(
+            "battery",
+            ClassSchema::new()
+                .with_parameter("energy", ParameterSpec::integer().with_unit(joule.clone()))
+                .with_parameter("power", ParameterSpec::integer().with_unit(watt)),
+        );
+        let state = State::new().with_entity(
+            "battery",
+            StateEntity::of_class("battery")
+                .with_parameter("energy", 100)
+                .with_parameter("power", 5),
+        );
+
+        let discharge = Assignment::new(
+            "battery",
+            "energy",
+            Expression::parameter("battery", "energy")
+                - Expression::parameter("battery", "power")
+                    * Expression::constant(2., Unit::base("s")),
+        );
+        assert_eq!(discharge.check_units(&schema, &state), Ok(()));
+        assert_eq!(
+            discharge
+                .apply(state.clone())
+                .parameter("battery", "energy"),
+            Some(&90)
+        );
+
+        let broken = Assignment::new(
+            "battery",
+            "energy",
+            Expression::parameter("battery", "energy") - Expression::parameter("battery", "power"),
+        );
+        assert!(matches!(
+            broken.check_units(&schema, &state),
+            Err(SchemaError::UnitMismatch { .. })
+        ));
+        assert!(matches!(
+            Assignment::new("battery", "energy", Expression::parameter("pump", "power"))
+                .check_units(&schema, &state),
+            Err(SchemaError::UnknownEntity { .. })
+        ));
+
+        assert_eq!(discharge.check_parameters(&state), Ok(()));
+        let typo = Assignment::new(
+            "battery",
+            "energy",
+            Expression::parameter("battery", "powr"),
+        );
+        assert_eq!(
+            typo.check_parameters(&state),
+            Err(SchemaError::UnknownParameter {
+                entity: "battery".to_string(),
+                parameter: "powr".to_string()
+            })
+        );
+        assert_eq!(typo.apply(state.clone()), state);
+    }
+
+    #[test]
+    fn time_and_discounting() {
+        let delay = ContinuousTime::seconds(1.5) + ContinuousTime::seconds(0.5);
+        assert_eq!(delay.as_seconds(), 2.);
+        assert_eq!((delay * 2.) / ContinuousTime::seconds(1.), 4.);
+        assert_eq!(
+            Expression::from(delay).unit(&Schema::new(), &State::<i32>::new()),
+            Ok(Unit::base("s"))
+        );
+
+        let discount = DiscountFactor::new(0.5).unwrap();
+        assert_eq!(discount.over(delay), 0.25);
+        assert_eq!(discount.discount(8., delay), 2.);
+        assert_eq!((discount * discount).value(), 0.25);
+        assert!(DiscountFactor::new(1.5).is_none());
+        assert!(DiscountFactor::new(f64::NAN).is_none());
+
+        let json = serde_json::to_string(&delay).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ContinuousTime>(&json).unwrap(),
+            delay
+        );
+        let json = serde_json::to_string(&discount).unwrap();
+        assert_eq!(json, "0.5");
+        assert_eq!(
+            serde_json::from_str::<DiscountFactor>(&json).unwrap(),
+            discount
+        );
+        assert!(serde_json::from_str::<DiscountFactor>("1.5").is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use std::{fmt::Debug, hash::Hash, sync::Arc};
+
+use hashbrown::HashMap;
+
+use crate::prelude::*;
+
+// The probability of every observation which can be made in a state. The probabilities of a
+// state should sum to one.
+pub type Emission<S, O> = Arc<dyn Fn(&S) -> Vec<(O, Probability)> + Send + Sync + 'static>;
+
+// In bits, like the entropy of a simulation
+pub fn distribution_entropy<S>(distribution: &StateProbabilityDistribution<S>) -> f64 {
+    -distribution
+        .values()
+        .filter(|probability| **probability > 0.)
+        .map(|probability| probability * probability.log2())
+        .sum::<f64>()
+}
+
+pub fn observation_distribution<S, O>(
+    distribution: &StateProbabilityDistribution<S>,
+    emission: impl Fn(&S) -> Vec<(O, Probability)>,
+) -> HashMap<O, Probability>
+where
+    O: Hash + Eq,
+{
+    let mut observations = HashMap::new();
+    distribution.iter().for_each(|(state, probability)| {
+        emission(state)
+            .into_iter()
+            .for_each(|(observation, likelihood)| {
+                *observations.entry(observation).or_insert(0.) += probability * likelihood
+            });
+    });
+    observations
+}
+
+// Bayes' rule, None if the observation is impossible in the distribution
+pub fn condition_on<S, O>(
+    distribution: &StateProbabilityDistribution<S>,
+    emission: impl Fn(&S) -> Vec<(O, Probability)>,
+    observation: &O,
+) -> Option<StateProbabilityDistribution<S>>
+where
+    S: Hash + Eq + Clone,
+    O: PartialEq,
+{
+    let posterior = distribution
+        .iter()
+        .map(|(state, probability)| {
+            let likelihood = emission(state)
+                .into_iter()
+                .filter(|(emitted, _)| emitted == observation)
+                .map(|(_, likelihood)| likelihood)
+                .sum::<Probability>();
+            (state.clone(), probability * likelihood)
+        })
+        .filter(|(_, probability)| *probability > 0.)
+        .collect::<StateProbabilityDistribution<S>>();
+    let evidence = posterior.values().sum::<Probability>();
+    (evidence > 0.).then(|| {
+        posterior
+            .into_iter()
+            .map(|(state, probability)| (state, probability / evidence))
+            .collect()
+    })
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // The entropy of the distribution at the given time minus the expected entropy after
+    // observing, i.e. the mutual information between the state and the observation in bits
+    pub fn expected_information_gain<O>(
+        &self,
+        time: Time,
+        emission: impl Fn(&S) -> Vec<(O, Probability)>,
+    ) -> Result<f64, SimulationError>
+    where
+        O: Hash + Eq,
+    {
+        let distribution = self
+            .iter_probability_distribution(time)?
+            .map(|(state, probability)| (state.clone(), probability))
+            .collect::<StateProbabilityDistribution<S>>();
+        let observations = observation_distribution(&distribution, &emission);
+        let expected_posterior_entropy = observations
+            .iter()
+            .filter(|(_, probability)| **probability > 0.)
+            .map(|(observation, observation_probability)| {
+                let posterior = condition_on(&distribution, &emission, observation).unwrap();
+                observation_probability * distribution_entropy(&posterior)
+            })
+            .sum::<f64>();
+        Ok(distribution_entropy(&distribution) - expected_posterior_entropy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn information_gain() {
+        let state_transition_generator = Arc::new(|heads: bool| {
+            vec![
+                (heads, "stay".to_string(), 0.5),
+                (!heads, "flip".to_string(), 0.5),
+            ]
+        });
+        let mut simulation = Simulation::new(false, state_transition_generator);
+        simulation.next_step();
+
+        let perfect = |heads: &bool| vec![(*heads, 1.)];
+        assert_eq!(simulation.expected_information_gain(1, perfect), Ok(1.));
+        let uninformative = |_: &bool| vec![((), 1.)];
+        assert_eq!(
+            simulation.expected_information_gain(1, uninformative),
+            Ok(0.)
+        );
+        // The observation is wrong with probability 0.1, which leaves 1 - H(0.1) bits
+        let noisy = |heads: &bool| vec![(*heads, 0.9), (!*heads, 0.1)];
+        let binary_entropy = -(0.1f64 * 0.1f64.log2() + 0.9 * 0.9f64.log2());
+        assert!(
+            (simulation.expected_information_gain(1, noisy).unwrap() - (1. - binary_entropy)).abs()
+                < 1e-12
+        );
+        // Nothing is uncertain at the start
+        assert_eq!(simulation.expected_information_gain(0, perfect), Ok(0.));
+        assert!(simulation.expected_information_gain(5, perfect).is_err());
+    }
+}
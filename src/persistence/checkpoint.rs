@@ -0,0 +1,298 @@
+use std::{
+    fmt::Debug,
+    fs,
+    hash::Hash,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::export::bundle::*;
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint<S, T> {
+    time: Time,
+    distribution: Vec<(S, Probability)>,
+    model: ModelBundle<S, T>,
+}
+
+impl<S, T> Checkpoint<S, T>
+where
+    S: Serialize + DeserializeOwned,
+    T: Serialize + DeserializeOwned,
+{
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn distribution(&self) -> &Vec<(S, Probability)> {
+        &self.distribution
+    }
+
+    pub fn model(&self) -> &ModelBundle<S, T> {
+        &self.model
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, PersistenceError> {
+        let checkpoint = serde_json::from_str::<Self>(json)
+            .map_err(|error| PersistenceError::Malformed(error.to_string()))?;
+        if checkpoint.model.version() > MODEL_BUNDLE_VERSION {
+            return Err(ModelImportError::UnsupportedVersion {
+                version: checkpoint.model.version(),
+            }
+            .into());
+        }
+        Ok(checkpoint)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, PersistenceError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointPolicy {
+    directory: PathBuf,
+    every_steps: Option<usize>,
+    every_duration: Option<Duration>,
+    keep_last: Option<usize>,
+}
+
+impl CheckpointPolicy {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            every_steps: None,
+            every_duration: None,
+            keep_last: None,
+        }
+    }
+
+    pub fn with_every_steps(mut self, every_steps: usize) -> Self {
+        assert!(
+            every_steps > 0,
+            "Checkpoint interval must be at least one step"
+        );
+        self.every_steps = Some(every_steps);
+        self
+    }
+
+    pub fn with_every_duration(mut self, every_duration: Duration) -> Self {
+        self.every_duration = Some(every_duration);
+        self
+    }
+
+    pub fn with_every_seconds(self, every_seconds: f64) -> Self {
+        self.with_every_duration(Duration::from_secs_f64(every_seconds))
+    }
+
+    pub fn with_keep_last(mut self, keep_last: usize) -> Self {
+        assert!(keep_last > 0, "At least one checkpoint has to be kept");
+        self.keep_last = Some(keep_last);
+        self
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn every_steps(&self) -> Option<usize> {
+        self.every_steps
+    }
+
+    pub fn every_duration(&self) -> Option<Duration> {
+        self.every_duration
+    }
+
+    pub fn keep_last(&self) -> Option<usize> {
+        self.keep_last
+    }
+
+    fn is_due(&self, steps: usize, elapsed: Duration) -> bool {
+        self.every_steps
+            .is_some_and(|every_steps| steps >= every_steps)
+            || self
+                .every_duration
+                .is_some_and(|every_duration| elapsed >= every_duration)
+    }
+
+    pub fn checkpoint_path(&self, time: Time) -> PathBuf {
+        // Zero padded so the lexicographic order of the file names is the order of the times
+        self.directory.join(format!("checkpoint-{time:020}.json"))
+    }
+
+    // Oldest first
+    pub fn checkpoints(&self) -> Result<Vec<PathBuf>, PersistenceError> {
+        if !self.directory.exists() {
+            return Ok(Vec::new());
+        }
+        let mut checkpoints = fs::read_dir(&self.directory)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("checkpoint-") && name.ends_with(".json"))
+            })
+            .collect::<Vec<_>>();
+        checkpoints.sort();
+        Ok(checkpoints)
+    }
+
+    pub fn latest(&self) -> Result<Option<PathBuf>, PersistenceError> {
+        Ok(self.checkpoints()?.pop())
+    }
+
+    fn write(&self, time: Time, json: &str) -> Result<(), PersistenceError> {
+        // Written to a temporary file first so a crash never leaves a truncated checkpoint behind
+        let path = self.checkpoint_path(time);
+        let temporary_path = path.with_extension("json.tmp");
+        fs::write(&temporary_path, json)?;
+        fs::rename(&temporary_path, &path)?;
+        if let Some(keep_last) = self.keep_last {
+            let checkpoints = self.checkpoints()?;
+            let outdated = checkpoints.len().saturating_sub(keep_last);
+            checkpoints[..outdated]
+                .iter()
+                .try_for_each(fs::remove_file)?;
+        }
+        Ok(())
+    }
+}
+
+// Checkpoints are serialized on the simulating thread, but written to disk in the background
+struct CheckpointWriter {
+    sender: Sender<(Time, String)>,
+    handle: JoinHandle<Result<(), PersistenceError>>,
+}
+
+impl CheckpointWriter {
+    fn new(policy: &CheckpointPolicy) -> Result<Self, PersistenceError> {
+        fs::create_dir_all(policy.directory())?;
+        let policy = policy.clone();
+        let (sender, receiver) = mpsc::channel::<(Time, String)>();
+        let handle = thread::spawn(move || {
+            receiver
+                .into_iter()
+                .try_for_each(|(time, json)| policy.write(time, &json))
+        });
+        Ok(Self { sender, handle })
+    }
+
+    fn send(&self, time: Time, json: String) {
+        // Sending only fails if the writer stopped because of an error, which finish reports
+        let _ = self.sender.send((time, json));
+    }
+
+    fn finish(self) -> Result<(), PersistenceError> {
+        drop(self.sender);
+        self.handle
+            .join()
+            .unwrap_or_else(|_| Err(PersistenceError::Io("Checkpoint writer panicked".into())))
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + Serialize + DeserializeOwned,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + Serialize + DeserializeOwned,
+{
+    pub fn checkpoint(&self) -> Checkpoint<S, T> {
+        let time = self.time();
+        Checkpoint {
+            time,
+            distribution: self.probability_distribution(time).into_iter().collect(),
+            model: self.export_model(true),
+        }
+    }
+
+    // Only the initial distribution and the one at the time of the checkpoint are restored
+    pub fn restore_checkpoint(
+        checkpoint: Checkpoint<S, T>,
+        state_transition_generator: StateTransitionGenerator<S, T>,
+    ) -> Self {
+        let mut simulation = Simulation::import_model(checkpoint.model, state_transition_generator);
+        simulation.insert_probability_distribution(
+            checkpoint.time,
+            checkpoint.distribution.into_iter().collect(),
+        );
+        simulation
+    }
+
+    pub fn run_with_checkpoints(
+        &mut self,
+        steps: usize,
+        policy: &CheckpointPolicy,
+    ) -> Result<Time, PersistenceError> {
+        let writer = CheckpointWriter::new(policy)?;
+        let mut steps_since_checkpoint = 0;
+        let mut last_checkpoint = Instant::now();
+        for _ in 0..steps {
+            self.advance();
+            steps_since_checkpoint += 1;
+            if policy.is_due(steps_since_checkpoint, last_checkpoint.elapsed()) {
+                writer.send(self.time(), self.checkpoint().to_json()?);
+                steps_since_checkpoint = 0;
+                last_checkpoint = Instant::now();
+            }
+        }
+        writer.finish()?;
+        Ok(self.time())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn checkpoints() {
+        let directory =
+            std::env::temp_dir().join(format!("entromatica-checkpoints-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let state_transition_generator = Arc::new(|state: i32| {
+            vec![
+                ((state + 1).min(4), "next".to_string(), 0.5),
+                (state, "stay".to_string(), 0.5),
+            ]
+        });
+        let policy = CheckpointPolicy::new(&directory)
+            .with_every_steps(2)
+            .with_keep_last(2);
+        let mut simulation = Simulation::new(0, state_transition_generator.clone());
+        assert_eq!(simulation.run_with_checkpoints(7, &policy), Ok(7));
+
+        let checkpoints = policy.checkpoints().unwrap();
+        assert_eq!(
+            checkpoints,
+            vec![policy.checkpoint_path(4), policy.checkpoint_path(6)]
+        );
+        let checkpoint = Checkpoint::load(&policy.latest().unwrap().unwrap()).unwrap();
+        assert_eq!(checkpoint.time(), 6);
+
+        let mut restored = Simulation::restore_checkpoint(checkpoint, state_transition_generator);
+        assert_eq!(restored.time(), 6);
+        assert_eq!(
+            restored.probability_distribution(6),
+            simulation.probability_distribution(6)
+        );
+        restored.next_step();
+        assert_eq!(
+            restored.probability_distribution(7),
+            simulation.probability_distribution(7)
+        );
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
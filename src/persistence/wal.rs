@@ -0,0 +1,206 @@
+use std::{
+    fmt::Debug,
+    fs::{self, File, OpenOptions},
+    hash::Hash,
+    io::{Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::prelude::*;
+
+pub type LogEntry<S, T> = (S, OutgoingTransitions<S, T>);
+
+struct LogFile {
+    file: File,
+    entries: usize,
+    error: Option<PersistenceError>,
+}
+
+// Every explored state is appended as one line of JSON as soon as its transitions are cached.
+// Together with the last checkpoint this allows recovering nearly all work after a crash.
+#[derive(Clone)]
+pub struct WriteAheadLog {
+    path: PathBuf,
+    log_file: Arc<Mutex<LogFile>>,
+}
+
+impl WriteAheadLog {
+    pub fn create(path: impl Into<PathBuf>) -> Result<Self, PersistenceError> {
+        let path = path.into();
+        let file = File::create(&path)?;
+        Ok(Self::with_file(path, file))
+    }
+
+    // Appends to an existing log, e.g. when continuing a recovered simulation
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, PersistenceError> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self::with_file(path, file))
+    }
+
+    fn with_file(path: PathBuf, file: File) -> Self {
+        Self {
+            path,
+            log_file: Arc::new(Mutex::new(LogFile {
+                file,
+                entries: 0,
+                error: None,
+            })),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Number of entries appended through this log since it was created or opened
+    pub fn entries(&self) -> usize {
+        self.log_file.lock().unwrap().entries
+    }
+
+    // Appending happens while simulating, so the first error is kept here instead of aborting
+    pub fn error(&self) -> Option<PersistenceError> {
+        self.log_file.lock().unwrap().error.clone()
+    }
+
+    pub fn append<S, T>(&self, state: &S, transitions: &OutgoingTransitions<S, T>)
+    where
+        S: Serialize,
+        T: Serialize,
+    {
+        let mut log_file = self.log_file.lock().unwrap();
+        let written = serde_json::to_string(&(state, transitions))
+            .map_err(PersistenceError::from)
+            .and_then(|mut line| {
+                line.push('\n');
+                Ok(log_file.file.write_all(line.as_bytes())?)
+            });
+        match written {
+            Ok(()) => log_file.entries += 1,
+            Err(error) => {
+                log_file.error.get_or_insert(error);
+            }
+        }
+    }
+
+    pub fn sync(&self) -> Result<(), PersistenceError> {
+        Ok(self.log_file.lock().unwrap().file.sync_data()?)
+    }
+
+    // Everything logged so far is covered by a checkpoint written afterwards
+    pub fn truncate(&self) -> Result<(), PersistenceError> {
+        let mut log_file = self.log_file.lock().unwrap();
+        log_file.file.set_len(0)?;
+        log_file.file.seek(SeekFrom::Start(0))?;
+        log_file.entries = 0;
+        Ok(())
+    }
+
+    // A partially written last line from a crash is ignored, anything else malformed is an error
+    pub fn read<S, T>(path: &Path) -> Result<Vec<LogEntry<S, T>>, PersistenceError>
+    where
+        S: DeserializeOwned,
+        T: DeserializeOwned,
+    {
+        let content = fs::read_to_string(path)?;
+        let complete = match content.rfind('\n') {
+            Some(end) => &content[..end],
+            None => "",
+        };
+        complete
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .map_err(|error| PersistenceError::Malformed(error.to_string()))
+            })
+            .collect()
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + Serialize + DeserializeOwned + 'static,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + Serialize + DeserializeOwned + 'static,
+{
+    pub fn attach_write_ahead_log(&mut self, write_ahead_log: &WriteAheadLog) {
+        let write_ahead_log = write_ahead_log.clone();
+        self.set_transition_observer(Some(Arc::new(move |state, transitions| {
+            write_ahead_log.append(state, transitions)
+        })));
+    }
+
+    pub fn detach_write_ahead_log(&mut self) {
+        self.set_transition_observer(None);
+    }
+
+    // Returns the number of recovered states. Recover before attaching a log, otherwise the
+    // recovered transitions are logged again.
+    pub fn recover_from_write_ahead_log(&mut self, path: &Path) -> Result<usize, PersistenceError> {
+        let entries = WriteAheadLog::read::<S, T>(path)?;
+        let recovered = entries.len();
+        entries
+            .into_iter()
+            .for_each(|(state, transitions)| self.insert_transitions(state, transitions));
+        Ok(recovered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn recovery() {
+        let path =
+            std::env::temp_dir().join(format!("entromatica-wal-{}.jsonl", std::process::id()));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted_calls = calls.clone();
+        let state_transition_generator: StateTransitionGenerator<i32, String> =
+            Arc::new(move |state: i32| {
+                counted_calls.fetch_add(1, Ordering::SeqCst);
+                vec![
+                    (state + 1, "next".to_string(), 0.5),
+                    (state, "stay".to_string(), 0.5),
+                ]
+            });
+
+        let write_ahead_log = WriteAheadLog::create(&path).unwrap();
+        let mut simulation = Simulation::new(0, state_transition_generator.clone());
+        simulation.attach_write_ahead_log(&write_ahead_log);
+        for _ in 0..3 {
+            simulation.next_step();
+        }
+        assert_eq!(write_ahead_log.entries(), 3);
+        assert_eq!(write_ahead_log.error(), None);
+        write_ahead_log.sync().unwrap();
+
+        // Simulate a crash in the middle of writing an entry
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"[3,[[4,\"ne").unwrap();
+
+        calls.store(0, Ordering::SeqCst);
+        let mut recovered = Simulation::new(0, state_transition_generator);
+        assert_eq!(recovered.recover_from_write_ahead_log(&path), Ok(3));
+        for _ in 0..3 {
+            recovered.next_step();
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(
+            recovered.probability_distribution(3),
+            simulation.probability_distribution(3)
+        );
+
+        write_ahead_log.truncate().unwrap();
+        assert_eq!(
+            WriteAheadLog::read::<i32, String>(&path).unwrap(),
+            Vec::new()
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
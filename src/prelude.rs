@@ -1,4 +1,39 @@
+#[cfg(feature = "std")]
+pub use crate::budget::*;
+#[cfg(feature = "std")]
 pub(crate) use crate::cached_function::*;
+#[cfg(feature = "std")]
+pub use crate::engine::*;
+#[cfg(feature = "std")]
+pub use crate::ensemble::*;
+#[cfg(feature = "std")]
+pub use crate::error::*;
+#[cfg(feature = "std")]
+pub use crate::filtering::*;
 pub(crate) use crate::hash::*;
+#[cfg(feature = "std")]
+pub(crate) use crate::hashed_distribution::*;
+#[cfg(feature = "std")]
+pub use crate::interval::*;
+#[cfg(feature = "std")]
+pub use crate::labels::*;
 pub use crate::models::*;
+#[cfg(feature = "std")]
+pub use crate::observations::*;
+#[cfg(feature = "std")]
+pub use crate::probability::*;
+#[cfg(feature = "std")]
+pub use crate::provider::*;
+#[cfg(feature = "std")]
+pub use crate::reports::*;
+#[cfg(feature = "std")]
+pub use crate::schedule::*;
+pub use crate::sequential::*;
+#[cfg(feature = "std")]
+pub(crate) use crate::shared_map::*;
+#[cfg(feature = "std")]
 pub use crate::simulation::*;
+#[cfg(feature = "std")]
+pub use crate::snapshot::*;
+#[cfg(feature = "std")]
+pub use crate::tracking::*;
@@ -0,0 +1,309 @@
+use std::{fmt::Debug, fmt::Display, hash::Hash};
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::models::rules::*;
+use crate::prelude::*;
+
+// Stored distributions dominate the memory of long simulations. With the f32 feature they are
+// kept in single precision, while sums, entropies and normalization are still computed in f64.
+pub trait ProbabilityFloat: Copy + Default + Debug + PartialEq + Send + Sync + 'static {
+    fn from_probability(probability: Probability) -> Self;
+
+    fn to_probability(self) -> Probability;
+}
+
+impl ProbabilityFloat for f64 {
+    fn from_probability(probability: Probability) -> Self {
+        probability
+    }
+
+    fn to_probability(self) -> Probability {
+        self
+    }
+}
+
+impl ProbabilityFloat for f32 {
+    fn from_probability(probability: Probability) -> Self {
+        probability as f32
+    }
+
+    fn to_probability(self) -> Probability {
+        self as Probability
+    }
+}
+
+#[cfg(not(feature = "f32"))]
+pub type StoredProbability = f64;
+#[cfg(feature = "f32")]
+pub type StoredProbability = f32;
+
+// Probabilities and weights are plain f64 throughout the simulation for speed. These wrappers
+// check the probabilities of the transitions of every step, so NaN or negative values from rules
+// or providers are returned as errors instead of silently poisoning sums and entropies later on.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+pub struct CheckedProbability(Probability);
+
+impl CheckedProbability {
+    pub fn try_from_f64(value: f64) -> Result<Self, ProbabilityError> {
+        if value.is_nan() {
+            Err(ProbabilityError::NotANumber)
+        } else if value < 0. {
+            Err(ProbabilityError::Negative { value })
+        } else if value > 1. {
+            Err(ProbabilityError::OutOfRange { value })
+        } else {
+            Ok(Self(value))
+        }
+    }
+
+    pub fn zero() -> Self {
+        Self(0.)
+    }
+
+    pub fn one() -> Self {
+        Self(1.)
+    }
+
+    pub fn value(&self) -> Probability {
+        self.0
+    }
+
+    pub fn complement(&self) -> Self {
+        Self(1. - self.0)
+    }
+
+    pub fn checked_add(self, other: Self) -> Result<Self, ProbabilityError> {
+        Self::try_from_f64(self.0 + other.0)
+    }
+
+    pub fn checked_mul(self, other: Self) -> Result<Self, ProbabilityError> {
+        Self::try_from_f64(self.0 * other.0)
+    }
+}
+
+impl TryFrom<f64> for CheckedProbability {
+    type Error = ProbabilityError;
+
+    fn try_from(value: f64) -> Result<Self, Self::Error> {
+        Self::try_from_f64(value)
+    }
+}
+
+impl From<CheckedProbability> for f64 {
+    fn from(probability: CheckedProbability) -> Self {
+        probability.0
+    }
+}
+
+impl Display for CheckedProbability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+pub struct CheckedProbabilityWeight(ProbabilityWeight);
+
+impl CheckedProbabilityWeight {
+    pub fn try_from_f64(value: f64) -> Result<Self, ProbabilityError> {
+        if value.is_nan() {
+            Err(ProbabilityError::NotANumber)
+        } else if value < 0. {
+            Err(ProbabilityError::Negative { value })
+        } else if value.is_infinite() {
+            Err(ProbabilityError::OutOfRange { value })
+        } else {
+            Ok(Self(value))
+        }
+    }
+
+    pub fn value(&self) -> ProbabilityWeight {
+        self.0
+    }
+
+    pub fn checked_add(self, other: Self) -> Result<Self, ProbabilityError> {
+        Self::try_from_f64(self.0 + other.0)
+    }
+
+    pub fn checked_mul(self, other: Self) -> Result<Self, ProbabilityError> {
+        Self::try_from_f64(self.0 * other.0)
+    }
+}
+
+impl TryFrom<f64> for CheckedProbabilityWeight {
+    type Error = ProbabilityError;
+
+    fn try_from(value: f64) -> Result<Self, Self::Error> {
+        Self::try_from_f64(value)
+    }
+}
+
+impl From<CheckedProbabilityWeight> for f64 {
+    fn from(weight: CheckedProbabilityWeight) -> Self {
+        weight.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tolerance {
+    Absolute(f64),
+    Relative(f64),
+}
+
+impl Tolerance {
+    // Relative rounding error of stored probabilities, which is much larger with the f32 feature
+    pub fn stored() -> Self {
+        Tolerance::Relative(1024. * StoredProbability::EPSILON.to_probability())
+    }
+
+    pub fn is_close(&self, a: f64, b: f64) -> bool {
+        let difference = (a - b).abs();
+        match self {
+            Tolerance::Absolute(tolerance) => difference <= *tolerance,
+            Tolerance::Relative(tolerance) => difference <= tolerance * a.abs().max(b.abs()),
+        }
+    }
+}
+
+pub trait ApproxEq {
+    fn approx_eq(&self, other: &Self, tolerance: Tolerance) -> bool;
+}
+
+impl ApproxEq for f64 {
+    fn approx_eq(&self, other: &Self, tolerance: Tolerance) -> bool {
+        tolerance.is_close(*self, *other)
+    }
+}
+
+// States missing in one of the distributions count as having probability zero
+impl<S> ApproxEq for HashMap<S, Probability>
+where
+    S: Hash + Eq,
+{
+    fn approx_eq(&self, other: &Self, tolerance: Tolerance) -> bool {
+        let is_close = |state: &S, probability: &Probability| {
+            tolerance.is_close(*probability, other.get(state).copied().unwrap_or(0.))
+        };
+        self.iter()
+            .all(|(state, probability)| is_close(state, probability))
+            && other
+                .iter()
+                .filter(|(state, _)| !self.contains_key(*state))
+                .all(|(_, probability)| tolerance.is_close(*probability, 0.))
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    pub fn distribution_approx_eq(
+        &self,
+        other: &Simulation<S, T>,
+        time: Time,
+        tolerance: Tolerance,
+    ) -> bool {
+        self.probability_distribution(time)
+            .approx_eq(&other.probability_distribution(time), tolerance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn checked_arithmetic() {
+        let half = CheckedProbability::try_from_f64(0.5).unwrap();
+        assert_eq!(half.checked_add(half).unwrap(), CheckedProbability::one());
+        assert_eq!(half.checked_mul(half).unwrap().value(), 0.25);
+        assert_eq!(half.complement(), half);
+        assert_eq!(
+            half.checked_add(CheckedProbability::one()),
+            Err(ProbabilityError::OutOfRange { value: 1.5 })
+        );
+        assert_eq!(
+            CheckedProbability::try_from(f64::NAN),
+            Err(ProbabilityError::NotANumber)
+        );
+        assert_eq!(
+            CheckedProbability::try_from_f64(-0.1),
+            Err(ProbabilityError::Negative { value: -0.1 })
+        );
+
+        let weight = CheckedProbabilityWeight::try_from_f64(3.).unwrap();
+        assert_eq!(weight.checked_mul(weight).unwrap().value(), 9.);
+        assert!(CheckedProbabilityWeight::try_from_f64(f64::INFINITY).is_err());
+        assert!(CheckedProbabilityWeight::try_from_f64(f64::NAN).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid probability")]
+    fn nan_transition_probability() {
+        let state_transition_generator =
+            Arc::new(|state: i32| vec![(state + 1, "next", f64::NAN), (state, "stay", 1.)]);
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.next_step();
+    }
+
+    #[test]
+    fn invalid_weights() {
+        let rule: Rule<i32> = Rule::new(
+            "Forward".to_string(),
+            Arc::new(|_| true),
+            -0.5,
+            Arc::new(|state| state + 1),
+        );
+        let mut simulation =
+            Simulation::from_rules(0, HashMap::from([("forward".to_string(), rule)]));
+        assert!(matches!(
+            simulation.try_next_step(),
+            Err(SimulationError::InvalidProbability {
+                error: ProbabilityError::Negative { .. },
+                ..
+            })
+        ));
+        assert_eq!(simulation.time(), 0);
+
+        let provider = |_: &i32| vec![(1, f64::NAN, "next"), (2, 1., "jump")];
+        let mut simulation = Simulation::from_provider(0, Arc::new(provider));
+        assert!(matches!(
+            simulation.try_next_step(),
+            Err(SimulationError::InvalidProbability {
+                error: ProbabilityError::NotANumber,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn approximate_equality() {
+        assert!(0.1_f64.approx_eq(&0.1000001, Tolerance::Absolute(1e-6)));
+        assert!(!0.1_f64.approx_eq(&0.1001, Tolerance::Absolute(1e-6)));
+        assert!(1e6_f64.approx_eq(&1.000001e6, Tolerance::Relative(1e-6)));
+        assert!(!1e-6_f64.approx_eq(&2e-6, Tolerance::Relative(1e-6)));
+
+        let distribution = HashMap::from([(0, 0.1 + 0.2), (1, 0.7)]);
+        let expected = HashMap::from([(0, 0.3), (1, 0.7), (2, 0.)]);
+        assert_ne!(distribution, expected);
+        assert!(distribution.approx_eq(&expected, Tolerance::Absolute(1e-12)));
+        assert!(expected.approx_eq(&distribution, Tolerance::Absolute(1e-12)));
+        assert!(!distribution.approx_eq(
+            &HashMap::from([(0, 0.3), (2, 0.7)]),
+            Tolerance::Absolute(1e-12)
+        ));
+
+        let state_transition_generator =
+            Arc::new(|state: i32| vec![(state + 1, "next", 0.1), (state, "stay", 0.9)]);
+        let mut simulation = Simulation::new(0, state_transition_generator.clone());
+        let mut other = Simulation::new(0, state_transition_generator);
+        simulation.next_step();
+        other.next_step();
+        assert!(simulation.distribution_approx_eq(&other, 1, Tolerance::Relative(1e-12)));
+    }
+}
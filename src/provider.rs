@@ -0,0 +1,146 @@
+use std::{
+    fmt::Debug,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+use hashbrown::HashMap;
+
+use crate::models::rules::*;
+use crate::prelude::*;
+
+#[cfg(feature = "remote")]
+pub mod remote;
+
+pub type Successors<S, L> = Vec<(S, ProbabilityWeight, L)>;
+
+// Dynamics computed outside of entromatica, e.g. by another library or through FFI. The weights
+// of the successors are normalized, so they don't have to sum up to 1.
+pub trait SuccessorProvider<S, L>: Send + Sync {
+    fn successors(&self, state: &S) -> Successors<S, L>;
+
+    // Called with the whole frontier of a step, providers with a high overhead per call, e.g.
+    // remote ones, should answer all states at once
+    fn successors_batch(&self, states: &[S]) -> Vec<Successors<S, L>> {
+        states.iter().map(|state| self.successors(state)).collect()
+    }
+}
+
+impl<S, L, F> SuccessorProvider<S, L> for F
+where
+    F: Fn(&S) -> Successors<S, L> + Send + Sync,
+{
+    fn successors(&self, state: &S) -> Successors<S, L> {
+        self(state)
+    }
+}
+
+pub(crate) fn normalize_successors<S, L>(
+    state: &S,
+    successors: Successors<S, L>,
+) -> OutgoingTransitions<S, L>
+where
+    S: Debug,
+{
+    assert!(
+        !successors.is_empty(),
+        "Successor provider returned no successors for state {state:?}"
+    );
+    let weight_sum = successors
+        .iter()
+        .map(|(_, weight, _)| weight)
+        .sum::<ProbabilityWeight>();
+    successors
+        .into_iter()
+        .map(|(successor, weight, label)| (successor, label, weight / weight_sum))
+        .collect()
+}
+
+pub fn get_provider_state_transition_generator<S, L>(
+    provider: Arc<dyn SuccessorProvider<S, L>>,
+) -> StateTransitionGenerator<S, L>
+where
+    S: Debug + Clone + Send + Sync + 'static,
+    L: 'static,
+{
+    Arc::new(move |state: S| {
+        let successors = provider.successors(&state);
+        normalize_successors(&state, successors)
+    })
+}
+
+// Successors of the frontier are requested as one batch by the hook, the generator then only
+// looks them up. States the hook didn't see are requested one by one.
+pub(crate) fn batched_provider_state_transition_generator<S, L>(
+    provider: Arc<dyn SuccessorProvider<S, L>>,
+) -> (StateTransitionGenerator<S, L>, FrontierHook<S>)
+where
+    S: Debug + Clone + Send + Sync + Hash + 'static,
+    L: Send + 'static,
+{
+    let batch_results: Arc<Mutex<HashMap<u64, Successors<S, L>>>> = Arc::default();
+    let hook_provider = provider.clone();
+    let hook_results = batch_results.clone();
+    let frontier_hook = Arc::new(move |frontier: &[S]| {
+        let successors = hook_provider.successors_batch(frontier);
+        assert_eq!(
+            successors.len(),
+            frontier.len(),
+            "Successor provider returned a wrong number of results"
+        );
+        *hook_results.lock().unwrap() = frontier.iter().map(hash).zip(successors).collect();
+    }) as FrontierHook<S>;
+    let state_transition_generator = Arc::new(move |state: S| {
+        let batch_result = batch_results.lock().unwrap().remove(&hash(&state));
+        let successors = batch_result.unwrap_or_else(|| provider.successors(&state));
+        normalize_successors(&state, successors)
+    }) as StateTransitionGenerator<S, L>;
+    (state_transition_generator, frontier_hook)
+}
+
+impl<S, L> Simulation<S, L>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + 'static,
+    L: Hash + Clone + Send + Sync + PartialEq + Eq + Debug + 'static,
+{
+    pub fn from_provider(initial_state: S, provider: Arc<dyn SuccessorProvider<S, L>>) -> Self {
+        let (state_transition_generator, frontier_hook) =
+            batched_provider_state_transition_generator(provider);
+        let mut simulation = Simulation::new(initial_state, state_transition_generator);
+        simulation.set_frontier_hook(Some(frontier_hook));
+        simulation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Dice {
+        sides: u8,
+    }
+
+    impl SuccessorProvider<u8, String> for Dice {
+        fn successors(&self, _state: &u8) -> Successors<u8, String> {
+            (1..=self.sides)
+                .map(|side| (side, 2., format!("roll {side}")))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn provider() {
+        let mut simulation = Simulation::from_provider(0, Arc::new(Dice { sides: 4 }));
+        simulation.next_step();
+        assert_eq!(simulation.probability_of(&3, 1), 0.25);
+        simulation.explore();
+        assert_eq!(simulation.known_states().len(), 5);
+        assert_eq!(simulation.fixed_points(), Vec::<u8>::new());
+
+        let closure: Arc<dyn SuccessorProvider<i32, &str>> =
+            Arc::new(|state: &i32| vec![(state + 1, 1., "up"), (state - 1, 3., "down")]);
+        let mut simulation = Simulation::from_provider(0, closure);
+        simulation.next_step();
+        assert_eq!(simulation.probability_of(&-1, 1), 0.75);
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    hash::Hash,
+    io::{Read, Write},
+    marker::PhantomData,
+    net::TcpStream,
+    sync::Mutex,
+    time::Duration,
+};
+
+use hashbrown::HashMap;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::prelude::*;
+
+#[derive(Serialize)]
+struct SuccessorRequest<'a, S> {
+    states: &'a [S],
+}
+
+#[derive(Deserialize)]
+struct SuccessorResponse<S, L> {
+    successors: Vec<Successors<S, L>>,
+}
+
+// Fetches successors from a service speaking JSON over plain HTTP. A batch of states is posted as
+// {"states": [...]} and answered with {"successors": [[[state, weight, label], ...], ...]}.
+// Answers are cached locally, so simulations sharing the provider never ask twice.
+pub struct RemoteProvider<S, L> {
+    address: String,
+    host: String,
+    path: String,
+    batch_size: usize,
+    timeout: Option<Duration>,
+    cache: Mutex<HashMap<u64, Successors<S, L>>>,
+    requests: Mutex<usize>,
+    state_type: PhantomData<fn(S) -> L>,
+}
+
+impl<S, L> RemoteProvider<S, L>
+where
+    S: Hash + Clone + Send + Serialize + DeserializeOwned,
+    L: Clone + Send + DeserializeOwned,
+{
+    pub fn new(endpoint: &str) -> Result<Self, RemoteError> {
+        let without_scheme = endpoint
+            .strip_prefix("http://")
+            .ok_or_else(|| RemoteError::InvalidEndpoint(endpoint.to_string()))?;
+        let (host, path) = match without_scheme.find('/') {
+            Some(index) => without_scheme.split_at(index),
+            None => (without_scheme, "/"),
+        };
+        if host.is_empty() {
+            return Err(RemoteError::InvalidEndpoint(endpoint.to_string()));
+        }
+        let address = if host.contains(':') {
+            host.to_string()
+        } else {
+            format!("{host}:80")
+        };
+        Ok(Self {
+            address,
+            host: host.to_string(),
+            path: path.to_string(),
+            batch_size: 1024,
+            timeout: None,
+            cache: Mutex::new(HashMap::new()),
+            requests: Mutex::new(0),
+            state_type: PhantomData,
+        })
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "Batch size must be at least 1");
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    // Number of requests sent to the service so far
+    pub fn requests(&self) -> usize {
+        *self.requests.lock().unwrap()
+    }
+
+    pub fn cached_states(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    fn post(&self, body: &str) -> Result<String, RemoteError> {
+        let mut stream = TcpStream::connect(&self.address)?;
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            self.path,
+            self.host,
+            body.len()
+        )?;
+        *self.requests.lock().unwrap() += 1;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        let (head, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| RemoteError::Protocol("Response has no body".to_string()))?;
+        let status_line = head.lines().next().unwrap_or_default();
+        if status_line.split_whitespace().nth(1) != Some("200") {
+            return Err(RemoteError::Protocol(status_line.to_string()));
+        }
+        if head.to_lowercase().contains("transfer-encoding: chunked") {
+            return Err(RemoteError::Protocol(
+                "Chunked responses are not supported".to_string(),
+            ));
+        }
+        Ok(body.to_string())
+    }
+
+    pub fn try_successors_batch(&self, states: &[S]) -> Result<Vec<Successors<S, L>>, RemoteError> {
+        let uncached = {
+            let cache = self.cache.lock().unwrap();
+            states
+                .iter()
+                .filter(|state| !cache.contains_key(&hash(*state)))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        for batch in uncached.chunks(self.batch_size) {
+            let body = serde_json::to_string(&SuccessorRequest { states: batch }).unwrap();
+            let response = serde_json::from_str::<SuccessorResponse<S, L>>(&self.post(&body)?)
+                .map_err(|error| RemoteError::Malformed(error.to_string()))?;
+            if response.successors.len() != batch.len() {
+                return Err(RemoteError::Malformed(format!(
+                    "Expected successors of {} states but got {}",
+                    batch.len(),
+                    response.successors.len()
+                )));
+            }
+            let mut cache = self.cache.lock().unwrap();
+            batch
+                .iter()
+                .zip(response.successors)
+                .for_each(|(state, successors)| {
+                    cache.insert(hash(state), successors);
+                });
+        }
+        let cache = self.cache.lock().unwrap();
+        Ok(states
+            .iter()
+            .map(|state| cache[&hash(state)].clone())
+            .collect())
+    }
+}
+
+impl<S, L> SuccessorProvider<S, L> for RemoteProvider<S, L>
+where
+    S: Hash + Clone + Send + Serialize + DeserializeOwned,
+    L: Clone + Send + DeserializeOwned,
+{
+    fn successors(&self, state: &S) -> Successors<S, L> {
+        self.successors_batch(std::slice::from_ref(state))
+            .pop()
+            .unwrap()
+    }
+
+    fn successors_batch(&self, states: &[S]) -> Vec<Successors<S, L>> {
+        self.try_successors_batch(states)
+            .unwrap_or_else(|error| panic!("Remote successor provider failed: {error}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::BufRead, io::BufReader, net::TcpListener, sync::Arc, thread};
+
+    use super::*;
+
+    // Answers every request with a step up and a step down until the listener is dropped
+    fn serve(listener: TcpListener) {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else {
+                return;
+            };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(length) = line.to_lowercase().strip_prefix("content-length: ") {
+                    content_length = length.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            let successors = request["states"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|state| {
+                    let state = state.as_i64().unwrap();
+                    serde_json::json!([[state + 1, 1., "up"], [state - 1, 1., "down"]])
+                })
+                .collect::<Vec<_>>();
+            let body = serde_json::json!({ "successors": successors }).to_string();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn remote_provider() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/successors", listener.local_addr().unwrap());
+        thread::spawn(move || serve(listener));
+
+        let provider = Arc::new(
+            RemoteProvider::<i64, String>::new(&endpoint)
+                .unwrap()
+                .with_timeout(Duration::from_secs(10)),
+        );
+        let mut simulation = Simulation::from_provider(0, provider.clone());
+        for _ in 0..3 {
+            simulation.next_step();
+        }
+        assert_eq!(simulation.probability_of(&1, 3), 0.375);
+        // One batch per step
+        assert_eq!(provider.requests(), 3);
+        assert_eq!(provider.cached_states(), 5);
+
+        let mut other = Simulation::from_provider(0, provider.clone());
+        other.next_step();
+        assert_eq!(provider.requests(), 3);
+
+        assert!(matches!(
+            RemoteProvider::<i64, String>::new("https://example.com"),
+            Err(RemoteError::InvalidEndpoint(_))
+        ));
+    }
+}
@@ -0,0 +1,43 @@
+use crate::prelude::*;
+
+// SplitMix64, a small seedable generator. Sampling only needs reproducibility across platforms,
+// not cryptographic quality, so there is no need for an external dependency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        value ^ (value >> 31)
+    }
+
+    pub fn next_bool(&mut self) -> bool {
+        self.next_u64() >> 63 == 1
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_probability(&mut self) -> Probability {
+        (self.next_u64() >> 11) as Probability / (1u64 << 53) as Probability
+    }
+
+    // Index of the chosen weight, None if the random number falls beyond the total weight
+    pub fn choose(&mut self, weights: impl IntoIterator<Item = Probability>) -> Option<usize> {
+        let mut remaining = self.next_probability();
+        for (index, weight) in weights.into_iter().enumerate() {
+            if remaining < weight {
+                return Some(index);
+            }
+            remaining -= weight;
+        }
+        None
+    }
+}
@@ -0,0 +1,694 @@
+use std::{
+    fmt::Debug,
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+use hashbrown::HashMap;
+
+use crate::models::rules::*;
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MassBalance<T>
+where
+    T: Hash + Eq,
+{
+    time: Time,
+    retained: Probability,
+    moved: HashMap<T, Probability>,
+    unexplored: Probability,
+    killed: Probability,
+    // Removed from transitions which summed up to more than 1, so it is not part of the balance
+    pruned: Probability,
+    residual: Probability,
+}
+
+impl<T> MassBalance<T>
+where
+    T: Hash + Eq,
+{
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn retained(&self) -> Probability {
+        self.retained
+    }
+
+    pub fn moved(&self) -> &HashMap<T, Probability> {
+        &self.moved
+    }
+
+    pub fn moved_by(&self, transition: &T) -> Probability {
+        self.moved.get(transition).copied().unwrap_or(0.)
+    }
+
+    pub fn total_moved(&self) -> Probability {
+        self.moved.values().sum()
+    }
+
+    pub fn unexplored(&self) -> Probability {
+        self.unexplored
+    }
+
+    pub fn killed(&self) -> Probability {
+        self.killed
+    }
+
+    pub fn pruned(&self) -> Probability {
+        self.pruned
+    }
+
+    pub fn residual(&self) -> Probability {
+        self.residual
+    }
+
+    pub fn is_balanced(&self, tolerance: Probability) -> bool {
+        self.residual.abs() <= tolerance
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepReport<T>
+where
+    T: Hash + Eq,
+{
+    time: Time,
+    transition_mass: HashMap<T, Probability>,
+    rule_mass: HashMap<RuleName, Probability>,
+    rule_firings: HashMap<RuleName, usize>,
+    nothing_mass: Probability,
+    entropy_delta: Option<f64>,
+    metrics: Option<StepMetrics>,
+}
+
+// What only the step itself can observe, so reports created afterwards have none
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepMetrics {
+    discovered_states: usize,
+    explored_states: usize,
+    duration: Duration,
+}
+
+impl StepMetrics {
+    // States which were not known before the step
+    pub fn discovered_states(&self) -> usize {
+        self.discovered_states
+    }
+
+    // States whose transitions were generated instead of taken from the cache
+    pub fn explored_states(&self) -> usize {
+        self.explored_states
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+impl<T> StepReport<T>
+where
+    T: Hash + Eq,
+{
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn transition_mass(&self) -> &HashMap<T, Probability> {
+        &self.transition_mass
+    }
+
+    // Only available for simulations created from rules
+    pub fn rule_mass(&self) -> &HashMap<RuleName, Probability> {
+        &self.rule_mass
+    }
+
+    pub fn mass_of_rule(&self, rule_name: &str) -> Probability {
+        self.rule_mass.get(rule_name).copied().unwrap_or(0.)
+    }
+
+    // The number of states with mass in which the rule applies
+    pub fn rule_firings(&self) -> &HashMap<RuleName, usize> {
+        &self.rule_firings
+    }
+
+    pub fn nothing_mass(&self) -> Probability {
+        self.nothing_mass
+    }
+
+    // None if the step has not been taken yet
+    pub fn entropy_delta(&self) -> Option<f64> {
+        self.entropy_delta
+    }
+
+    pub fn metrics(&self) -> Option<&StepMetrics> {
+        self.metrics.as_ref()
+    }
+
+    pub fn dominant_rule(&self) -> Option<(&RuleName, Probability)> {
+        self.rule_mass
+            .iter()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(name, mass)| (name, *mass))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleChange {
+    name: RuleName,
+    description: Option<(String, String)>,
+    weight: Option<(ProbabilityWeight, ProbabilityWeight)>,
+}
+
+impl RuleChange {
+    pub fn name(&self) -> &RuleName {
+        &self.name
+    }
+
+    pub fn description(&self) -> Option<(&String, &String)> {
+        self.description.as_ref().map(|(old, new)| (old, new))
+    }
+
+    pub fn weight(&self) -> Option<(ProbabilityWeight, ProbabilityWeight)> {
+        self.weight
+    }
+}
+
+// Conditions and actions are closures and can't be compared, so rules only show up as changed
+// if their description or weight changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelDiff<S> {
+    added_rules: Vec<RuleName>,
+    removed_rules: Vec<RuleName>,
+    changed_rules: Vec<RuleChange>,
+    initial_distribution: Vec<(S, Probability, Probability)>,
+}
+
+impl<S> ModelDiff<S>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    pub fn between<T>(old: &Simulation<S, T>, new: &Simulation<S, T>) -> Self
+    where
+        T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    {
+        let empty = HashMap::new();
+        let old_rules = old.rules().unwrap_or(&empty);
+        let new_rules = new.rules().unwrap_or(&empty);
+        let mut added_rules = new_rules
+            .keys()
+            .filter(|name| !old_rules.contains_key(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+        added_rules.sort();
+        let mut removed_rules = old_rules
+            .keys()
+            .filter(|name| !new_rules.contains_key(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+        removed_rules.sort();
+        let mut changed_rules = old_rules
+            .iter()
+            .filter_map(|(name, old_rule)| {
+                let new_rule = new_rules.get(name)?;
+                let description = (old_rule.description() != new_rule.description()).then(|| {
+                    (
+                        old_rule.description().clone(),
+                        new_rule.description().clone(),
+                    )
+                });
+                let weight = (old_rule.weight() != new_rule.weight())
+                    .then(|| (old_rule.weight(), new_rule.weight()));
+                (description.is_some() || weight.is_some()).then(|| RuleChange {
+                    name: name.clone(),
+                    description,
+                    weight,
+                })
+            })
+            .collect::<Vec<_>>();
+        changed_rules.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let old_distribution = old.initial_distribution();
+        let new_distribution = new.initial_distribution();
+        let initial_distribution = old_distribution
+            .keys()
+            .chain(
+                new_distribution
+                    .keys()
+                    .filter(|state| !old_distribution.contains_key(*state)),
+            )
+            .filter_map(|state| {
+                let old_probability = old_distribution.get(state).copied().unwrap_or(0.);
+                let new_probability = new_distribution.get(state).copied().unwrap_or(0.);
+                (old_probability != new_probability)
+                    .then(|| (state.clone(), old_probability, new_probability))
+            })
+            .collect();
+        ModelDiff {
+            added_rules,
+            removed_rules,
+            changed_rules,
+            initial_distribution,
+        }
+    }
+
+    pub fn added_rules(&self) -> &Vec<RuleName> {
+        &self.added_rules
+    }
+
+    pub fn removed_rules(&self) -> &Vec<RuleName> {
+        &self.removed_rules
+    }
+
+    pub fn changed_rules(&self) -> &Vec<RuleChange> {
+        &self.changed_rules
+    }
+
+    pub fn initial_distribution(&self) -> &Vec<(S, Probability, Probability)> {
+        &self.initial_distribution
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added_rules.is_empty()
+            && self.removed_rules.is_empty()
+            && self.changed_rules.is_empty()
+            && self.initial_distribution.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepSummary<S>
+where
+    S: Hash + Eq,
+{
+    time: Time,
+    entropy: f64,
+    num_states: usize,
+    distribution: Option<StateProbabilityDistribution<S>>,
+}
+
+impl<S> StepSummary<S>
+where
+    S: Hash + Eq,
+{
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn entropy(&self) -> f64 {
+        self.entropy
+    }
+
+    pub fn num_states(&self) -> usize {
+        self.num_states
+    }
+
+    pub fn distribution(&self) -> Option<&StateProbabilityDistribution<S>> {
+        self.distribution.as_ref()
+    }
+
+    pub fn into_distribution(self) -> Option<StateProbabilityDistribution<S>> {
+        self.distribution
+    }
+}
+
+pub struct SimulationStream<'a, S, T> {
+    simulation: &'a mut Simulation<S, T>,
+    remaining: usize,
+    with_distributions: bool,
+    discard_history: bool,
+}
+
+impl<'a, S, T> SimulationStream<'a, S, T> {
+    pub fn with_distributions(mut self) -> Self {
+        self.with_distributions = true;
+        self
+    }
+
+    // Every distribution except the initial one is dropped as soon as the next step supersedes it
+    pub fn discarding_history(mut self) -> Self {
+        self.discard_history = true;
+        self
+    }
+}
+
+impl<'a, S, T> Iterator for SimulationStream<'a, S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    type Item = StepSummary<S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        // The distribution with its states is only built if it is returned
+        self.simulation.advance();
+        let time = self.simulation.time();
+        if self.discard_history && time > 1 {
+            self.simulation.forget_probability_distribution(time - 1);
+        }
+        Some(StepSummary {
+            time,
+            entropy: self.simulation.entropy(time),
+            num_states: self
+                .simulation
+                .iter_probability_distribution(time)
+                .expect("No probability distribution found for given time")
+                .count(),
+            distribution: self
+                .with_distributions
+                .then(|| self.simulation.probability_distribution(time)),
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    pub fn run_stream(&mut self, steps: usize) -> SimulationStream<'_, S, T> {
+        SimulationStream {
+            simulation: self,
+            remaining: steps,
+            with_distributions: false,
+            discard_history: false,
+        }
+    }
+
+    // Takes the next step and reports it, including what can't be derived afterwards
+    pub fn step(&mut self) -> StepReport<T> {
+        let time = self.time();
+        let known_states = self.num_known_states();
+        let explored_states = self
+            .iter_probability_distribution(time)
+            .expect("No probability distribution found for given time")
+            .filter(|(state, _)| self.cached_outgoing_transitions(state).is_none())
+            .count();
+        let start = Instant::now();
+        self.advance();
+        let duration = start.elapsed();
+        let mut report = self.step_report(time);
+        report.metrics = Some(StepMetrics {
+            discovered_states: self.num_known_states() - known_states,
+            explored_states,
+            duration,
+        });
+        report
+    }
+
+    pub fn run(&mut self, steps: usize) -> Vec<StepReport<T>> {
+        (0..steps).map(|_| self.step()).collect()
+    }
+
+    // Describes the step from the given time to the next one
+    pub fn step_report(&self, time: Time) -> StepReport<T> {
+        let mut transition_mass = HashMap::new();
+        for flow in self.probability_flows(time) {
+            *transition_mass
+                .entry(flow.transition().clone())
+                .or_insert(0.) += flow.mass();
+        }
+        let mut rule_mass = HashMap::new();
+        let mut rule_firings = HashMap::new();
+        let mut nothing_mass = 0.;
+        if let Some(rules) = self.rules() {
+            self.iter_probability_distribution(time)
+                .expect("No probability distribution found for given time")
+                .for_each(|(state, probability)| {
+                    let (rule_probabilities, nothing_probability) =
+                        rule_probabilities(rules, self.rule_options(), state);
+                    rule_probabilities
+                        .into_iter()
+                        .for_each(|(rule_name, rule_probability)| {
+                            if rule_probability > 0. {
+                                *rule_firings.entry(rule_name.clone()).or_insert(0) += 1;
+                            }
+                            *rule_mass.entry(rule_name).or_insert(0.) +=
+                                probability * rule_probability;
+                        });
+                    nothing_mass += probability * nothing_probability;
+                });
+        }
+        let entropy_delta = self
+            .try_probability_distribution(time + 1)
+            .is_ok()
+            .then(|| self.entropy(time + 1) - self.entropy(time));
+        StepReport {
+            time,
+            transition_mass,
+            rule_mass,
+            rule_firings,
+            nothing_mass,
+            entropy_delta,
+            metrics: None,
+        }
+    }
+
+    pub fn mass_balance(&self, time: Time) -> MassBalance<T> {
+        let unexplored = self
+            .iter_probability_distribution(time)
+            .expect("No probability distribution found for given time")
+            .filter(|(state, _)| self.cached_outgoing_transitions(state).is_none())
+            .map(|(_, probability)| probability)
+            .sum::<Probability>();
+        let mut retained = 0.;
+        let mut moved = HashMap::new();
+        for flow in self.probability_flows(time) {
+            if flow.source() == flow.target() {
+                retained += flow.mass();
+            } else {
+                *moved.entry(flow.transition().clone()).or_insert(0.) += flow.mass();
+            }
+        }
+        let killed = self.killed_mass(time);
+        let residual = self.probability_sum(time)
+            - retained
+            - moved.values().sum::<Probability>()
+            - unexplored
+            - killed;
+        MassBalance {
+            time,
+            retained,
+            moved,
+            unexplored,
+            killed,
+            pruned: self.overflow_mass(time),
+            residual,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn lazy_walk() {
+        let state_transition_generator = Arc::new(|state: i32| {
+            vec![
+                (state, "stay", 0.5),
+                (state + 1, "next", 0.25),
+                (state - 1, "previous", 0.25),
+            ]
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.next_step();
+
+        let balance = simulation.mass_balance(0);
+        assert_eq!(balance.time(), 0);
+        assert_eq!(balance.retained(), 0.5);
+        assert_eq!(balance.moved_by(&"next"), 0.25);
+        assert_eq!(balance.moved_by(&"previous"), 0.25);
+        assert_eq!(balance.moved_by(&"stay"), 0.);
+        assert_eq!(balance.unexplored(), 0.);
+        assert!(balance.is_balanced(1e-12));
+
+        let frontier = simulation.mass_balance(1);
+        assert_eq!(frontier.unexplored(), 0.5);
+        assert_eq!(frontier.retained(), 0.25);
+        assert_eq!(frontier.total_moved(), 0.25);
+        assert!(frontier.is_balanced(1e-12));
+        assert_eq!(simulation.probability_sum(1), 1.);
+
+        // Overflowing transitions are scaled down, the removed mass is not a residual
+        let state_transition_generator =
+            Arc::new(|state: i32| vec![(state, "stay", 0.75), (state + 1, "next", 0.75)]);
+        let mut overflowing = Simulation::new(0, state_transition_generator);
+        overflowing.set_overflow_policy(OverflowPolicy::Clamp);
+        overflowing.next_step();
+        let balance = overflowing.mass_balance(0);
+        assert_eq!(balance.retained(), 0.5);
+        assert_eq!(balance.moved_by(&"next"), 0.5);
+        assert_eq!(balance.pruned(), 0.5);
+        assert!(balance.is_balanced(1e-12));
+    }
+
+    #[test]
+    fn run_stream() {
+        let state_transition_generator =
+            Arc::new(|state: i32| vec![(state + 1, "next", 0.5), (state - 1, "previous", 0.5)]);
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        let summaries = simulation.run_stream(3).collect::<Vec<_>>();
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(summaries[2].time(), 3);
+        assert_eq!(summaries[2].num_states(), 4);
+        assert!(summaries[0].distribution().is_none());
+        assert_eq!(summaries[0].entropy(), 1.);
+
+        let last = simulation
+            .run_stream(2)
+            .with_distributions()
+            .discarding_history()
+            .last()
+            .unwrap();
+        assert_eq!(last.time(), 5);
+        assert_eq!(last.into_distribution().unwrap().len(), 6);
+        assert!(simulation.try_probability_distribution(4).is_err());
+        assert!(simulation.try_probability_distribution(3).is_err());
+        assert!(simulation.try_probability_distribution(2).is_ok());
+        assert!(simulation.try_probability_distribution(0).is_ok());
+    }
+
+    #[test]
+    fn model_diff() {
+        let rule = |description: &str, weight| -> Rule<i32> {
+            Rule::new(
+                description.to_string(),
+                Arc::new(|_| true),
+                weight,
+                Arc::new(|state| state + 1),
+            )
+        };
+        let old = Simulation::from_rules(
+            0,
+            HashMap::from([
+                ("forward".to_string(), rule("Forward", 0.5)),
+                ("jump".to_string(), rule("Jump", 0.1)),
+            ]),
+        );
+        let new = Simulation::from_rules(
+            1,
+            HashMap::from([
+                ("forward".to_string(), rule("Forward", 0.25)),
+                ("backward".to_string(), rule("Backward", 0.5)),
+            ]),
+        );
+        assert!(ModelDiff::between(&old, &old).is_empty());
+
+        let diff = ModelDiff::between(&old, &new);
+        assert_eq!(diff.added_rules(), &vec!["backward".to_string()]);
+        assert_eq!(diff.removed_rules(), &vec!["jump".to_string()]);
+        assert_eq!(diff.changed_rules().len(), 1);
+        assert_eq!(diff.changed_rules()[0].weight(), Some((0.5, 0.25)));
+        assert_eq!(diff.changed_rules()[0].description(), None);
+        assert_eq!(diff.initial_distribution().len(), 2);
+        assert!(diff.initial_distribution().contains(&(1, 0., 1.)));
+    }
+
+    #[test]
+    fn rule_mass() {
+        let rule = |description: &str, weight, step| step_rule(description, weight, step, 2);
+        let mut simulation = Simulation::from_rules(
+            0,
+            HashMap::from([
+                ("walk".to_string(), rule("Walk", 0.25, 1)),
+                ("stroll".to_string(), rule("Stroll", 0.25, 1)),
+                ("jump".to_string(), rule("Jump", 0.5, 2)),
+            ]),
+        );
+        simulation.next_step();
+
+        let report = simulation.step_report(0);
+        assert_eq!(report.time(), 0);
+        assert_eq!(report.mass_of_rule("walk"), 0.25 / 1.25);
+        assert_eq!(report.mass_of_rule("stroll"), 0.25 / 1.25);
+        assert_eq!(report.mass_of_rule("jump"), 0.5 / 1.25);
+        assert_eq!(report.nothing_mass(), 0.25 / 1.25);
+        assert_eq!(report.dominant_rule(), Some((&"jump".to_string(), 0.4)));
+        assert_eq!(
+            report.transition_mass()[&"Stroll | Walk".to_string()],
+            0.5 / 1.25
+        );
+
+        let generic = Simulation::new(0, Arc::new(|state: i32| vec![(state, "stay", 1.)]));
+        assert!(generic.step_report(0).rule_mass().is_empty());
+    }
+
+    #[test]
+    fn step() {
+        let rule = |description: &str, weight, step| step_rule(description, weight, step, 2);
+        let mut simulation = Simulation::from_rules(
+            0,
+            HashMap::from([
+                ("walk".to_string(), rule("Walk", 0.5, 1)),
+                ("jump".to_string(), rule("Jump", 0.5, 2)),
+            ]),
+        );
+        assert_eq!(simulation.step_report(0).entropy_delta(), None);
+        let reports = simulation.run(2);
+        assert_eq!(simulation.time(), 2);
+        let metrics = reports[0].metrics().unwrap();
+        assert_eq!(metrics.explored_states(), 1);
+        assert_eq!(metrics.discovered_states(), 2);
+        assert_eq!(reports[0].entropy_delta(), Some(simulation.entropy(1)));
+        assert_eq!(reports[0].rule_firings()["walk"], 1);
+
+        // Both rules apply in states 0 and 1, nothing does in state 2
+        let metrics = reports[1].metrics().unwrap();
+        assert_eq!(metrics.explored_states(), 2);
+        assert_eq!(metrics.discovered_states(), 1);
+        assert_eq!(reports[1].rule_firings()["jump"], 2);
+        assert!(simulation.step_report(1).metrics().is_none());
+        assert_eq!(
+            simulation.step_report(1).entropy_delta(),
+            reports[1].entropy_delta()
+        );
+    }
+
+    #[test]
+    fn step_without_frontier() {
+        // Half of the mass is killed in the first step and the rest in the second one
+        let state_transition_generator = Arc::new(|state: i32| match state {
+            0 => vec![(1, "next", 0.5)],
+            _ => Vec::new(),
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.set_sub_stochastic(true);
+        let reports = simulation.run(3);
+        assert_eq!(reports[0].transition_mass()[&"next"], 0.5);
+        assert!(reports[0].rule_mass().is_empty());
+        assert_eq!(reports[0].nothing_mass(), 0.);
+        assert_eq!(simulation.mass_balance(0).killed(), 0.5);
+        assert!(reports[1].transition_mass().is_empty());
+        assert_eq!(simulation.mass_balance(1).killed(), 0.5);
+
+        // Nothing is left to explore once all mass is killed
+        let metrics = reports[2].metrics().unwrap();
+        assert_eq!(metrics.explored_states(), 0);
+        assert_eq!(metrics.discovered_states(), 0);
+        assert!(reports[2].transition_mass().is_empty());
+        assert_eq!(reports[2].entropy_delta(), Some(0.));
+        assert_eq!(simulation.probability_sum(3), 0.);
+
+        // The explored states of a rule simulation don't need to be explored again
+        let mut simulation = Simulation::from_rules(
+            0,
+            HashMap::from([("walk".to_string(), step_rule("Walk", 0.5, 1, 2))]),
+        );
+        simulation.full_traversal(true);
+        let metrics = *simulation.step().metrics().unwrap();
+        assert_eq!(metrics.explored_states(), 0);
+        assert_eq!(metrics.discovered_states(), 0);
+    }
+}
@@ -0,0 +1,152 @@
+use std::{collections::BTreeSet, fmt::Debug, hash::Hash};
+
+use crate::prelude::*;
+
+pub type Recorder<'a, S, T> = &'a mut dyn FnMut(&Simulation<S, T>);
+
+// When a heavyweight recorder like a full snapshot or an export runs. Cheap observables are
+// tracked after every step instead.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    // Every period steps, starting at the offset
+    Every { period: Time, offset: Time },
+    // At the first time and then at times growing by the ratio, rounded up to whole steps
+    Geometric { first: Time, ratio: f64 },
+    At(BTreeSet<Time>),
+}
+
+impl Schedule {
+    pub fn every(period: Time) -> Self {
+        Self::every_from(period, 0)
+    }
+
+    pub fn every_from(period: Time, offset: Time) -> Self {
+        assert!(period > 0, "The period of a schedule must be positive");
+        Self::Every { period, offset }
+    }
+
+    pub fn geometric(first: Time, ratio: f64) -> Self {
+        assert!(
+            ratio > 1.,
+            "The ratio of a geometric schedule must exceed 1"
+        );
+        Self::Geometric { first, ratio }
+    }
+
+    pub fn at(times: impl IntoIterator<Item = Time>) -> Self {
+        Self::At(times.into_iter().collect())
+    }
+
+    pub fn contains(&self, time: Time) -> bool {
+        match self {
+            Self::Every { period, offset } => {
+                time >= *offset && (time - offset).is_multiple_of(*period)
+            }
+            Self::Geometric { .. } => {
+                self.geometric_times().find(|next| *next >= time) == Some(time)
+            }
+            Self::At(times) => times.contains(&time),
+        }
+    }
+
+    // The scheduled times up to and including the given time
+    pub fn times_until(&self, until: Time) -> Vec<Time> {
+        match self {
+            Self::Every { .. } | Self::At(_) => {
+                (0..=until).filter(|time| self.contains(*time)).collect()
+            }
+            Self::Geometric { .. } => self
+                .geometric_times()
+                .take_while(|time| *time <= until)
+                .collect(),
+        }
+    }
+
+    fn geometric_times(&self) -> impl Iterator<Item = Time> {
+        let (first, ratio) = match self {
+            Self::Geometric { first, ratio } => (*first, *ratio),
+            _ => unreachable!(),
+        };
+        std::iter::successors(Some(first), move |time| {
+            Some((*time + 1).max((*time as f64 * ratio).ceil() as Time))
+        })
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // Steps and calls every recorder after the steps its schedule contains. Tracked observables
+    // are still recorded after every step.
+    pub fn run_scheduled(
+        &mut self,
+        steps: usize,
+        recorders: &mut [(Schedule, Recorder<'_, S, T>)],
+    ) -> Time {
+        for _ in 0..steps {
+            self.advance();
+            let time = self.time();
+            recorders
+                .iter_mut()
+                .filter(|(schedule, _)| schedule.contains(time))
+                .for_each(|(_, recorder)| recorder(self));
+        }
+        self.time()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn scheduled_recorders() {
+        assert_eq!(
+            Schedule::every_from(3, 1).times_until(10),
+            vec![1, 4, 7, 10]
+        );
+        assert_eq!(
+            Schedule::geometric(1, 1.5).times_until(20),
+            vec![1, 2, 3, 5, 8, 12, 18]
+        );
+        assert!(!Schedule::geometric(1, 2.).contains(0));
+        assert!(Schedule::at([5, 7]).contains(7));
+
+        let state_transition_generator =
+            Arc::new(|state: i32| vec![(state + 1, "next", 0.5), (state - 1, "previous", 0.5)]);
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.track(Arc::new(|state: &i32| *state > 0), "positive");
+        let mut snapshots = Vec::new();
+        let mut exports = Vec::new();
+        let mut checks = Vec::new();
+        let time = simulation.run_scheduled(
+            10,
+            &mut [
+                (Schedule::every(3), &mut |simulation| {
+                    snapshots.push(simulation.snapshot())
+                }),
+                (Schedule::geometric(1, 2.), &mut |simulation| {
+                    exports.push(simulation.time())
+                }),
+                (Schedule::at([5, 7]), &mut |simulation| {
+                    checks.push(simulation.probability_sum(simulation.time()))
+                }),
+            ],
+        );
+        assert_eq!(time, 10);
+        assert_eq!(
+            snapshots
+                .iter()
+                .map(|snapshot| snapshot.time())
+                .collect::<Vec<_>>(),
+            vec![3, 6, 9]
+        );
+        assert_eq!(exports, vec![1, 2, 4, 8]);
+        assert_eq!(checks, vec![1., 1.]);
+        assert_eq!(simulation.time_series("positive").unwrap().len(), 11);
+    }
+}
@@ -0,0 +1,196 @@
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::{fmt::Debug, hash::Hash};
+
+use hashbrown::HashMap;
+
+use crate::models::rules::*;
+
+pub type Probability = f64;
+pub type Time = u64;
+
+pub type OutgoingTransitions<S, T> = Vec<(S, T, Probability)>;
+
+pub type StateTransitionGenerator<S, T> =
+    Arc<dyn Fn(S) -> OutgoingTransitions<S, T> + Send + Sync + 'static>;
+
+pub type StateProbabilityDistribution<S> = HashMap<S, Probability>;
+
+// The core of the engine, which also works without std: the current distribution, a cache of
+// generated transitions and sequential stepping. Unlike Simulation it keeps neither the history
+// nor a graph, so small models fit on embedded targets.
+#[derive(Clone)]
+pub struct SequentialSimulation<S, T> {
+    time: Time,
+    distribution: StateProbabilityDistribution<S>,
+    transitions: HashMap<S, OutgoingTransitions<S, T>>,
+    state_transition_generator: StateTransitionGenerator<S, T>,
+}
+
+impl<S, T> SequentialSimulation<S, T>
+where
+    S: Hash + Eq + Clone,
+    T: Clone,
+{
+    pub fn new(
+        initial_state: S,
+        state_transition_generator: StateTransitionGenerator<S, T>,
+    ) -> Self {
+        Self::new_with_distribution(
+            HashMap::from_iter([(initial_state, 1.)]),
+            state_transition_generator,
+        )
+    }
+
+    pub fn new_with_distribution(
+        initial_distribution: StateProbabilityDistribution<S>,
+        state_transition_generator: StateTransitionGenerator<S, T>,
+    ) -> Self {
+        Self {
+            time: 0,
+            distribution: initial_distribution,
+            transitions: HashMap::new(),
+            state_transition_generator,
+        }
+    }
+
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn distribution(&self) -> &StateProbabilityDistribution<S> {
+        &self.distribution
+    }
+
+    pub fn probability_of(&self, state: &S) -> Probability {
+        self.distribution.get(state).copied().unwrap_or(0.)
+    }
+
+    pub fn cached_states(&self) -> usize {
+        self.transitions.len()
+    }
+
+    // Frees the memory of the cache, transitions are generated again when they are needed
+    pub fn clear_cache(&mut self) {
+        self.transitions.clear();
+    }
+
+    pub fn outgoing_transitions(&mut self, state: &S) -> &OutgoingTransitions<S, T> {
+        if !self.transitions.contains_key(state) {
+            let transitions = (self.state_transition_generator)(state.clone());
+            let sum = transitions
+                .iter()
+                .map(|(_, _, probability)| probability)
+                .sum::<Probability>();
+            assert!(
+                (sum - 1.).abs() < 1e-10,
+                "Sum of probabilities of next states is not 1.0"
+            );
+            self.transitions.insert(state.clone(), transitions);
+        }
+        &self.transitions[state]
+    }
+
+    pub fn next_step(&mut self) -> &StateProbabilityDistribution<S> {
+        let distribution = core::mem::take(&mut self.distribution);
+        let mut next_distribution = HashMap::with_capacity(distribution.len());
+        distribution.into_iter().for_each(|(state, probability)| {
+            self.outgoing_transitions(&state).iter().for_each(
+                |(next_state, _, transition_probability)| {
+                    *next_distribution.entry(next_state.clone()).or_insert(0.) +=
+                        probability * transition_probability;
+                },
+            );
+        });
+        self.distribution = next_distribution;
+        self.time += 1;
+        &self.distribution
+    }
+
+    pub fn run(&mut self, steps: usize) -> &StateProbabilityDistribution<S> {
+        for _ in 0..steps {
+            self.next_step();
+        }
+        &self.distribution
+    }
+}
+
+impl<S> SequentialSimulation<S, String>
+where
+    S: Debug + Clone + Send + Sync + 'static + PartialEq + Eq + Hash,
+{
+    // Batch conditions are evaluated one state at a time, there is no frontier to batch over
+    pub fn from_rules(
+        initial_state: S,
+        rules: HashMap<RuleName, Rule<S>>,
+        options: RuleOptions,
+    ) -> Self {
+        let rules = options
+            .tie_breaking()
+            .order(&rules)
+            .into_iter()
+            .map(|(_, rule)| rule.clone())
+            .collect::<Vec<_>>();
+        Self::new(
+            initial_state,
+            Arc::new(move |state: S| {
+                let new_states = rules
+                    .iter()
+                    .filter(|rule| rule.applies(state.clone()))
+                    .map(|rule| {
+                        (
+                            rule.apply(state.clone()),
+                            rule.weight(),
+                            rule.description().clone(),
+                        )
+                    })
+                    .collect();
+                outgoing_transitions_with(state, new_states, options.nothing_happens())
+            }),
+        )
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn sequential_simulation() {
+        let rules = HashMap::from([
+            (
+                "forward".to_string(),
+                Rule::new(
+                    "Forward".to_string(),
+                    Arc::new(|state: i32| state < 3),
+                    0.5,
+                    Arc::new(|state| state + 1),
+                ),
+            ),
+            (
+                "reset".to_string(),
+                Rule::new(
+                    "Reset".to_string(),
+                    Arc::new(|state: i32| state > 1),
+                    0.2,
+                    Arc::new(|_| 0),
+                ),
+            ),
+        ]);
+        let mut sequential =
+            SequentialSimulation::from_rules(0, rules.clone(), RuleOptions::default());
+        let mut simulation = Simulation::from_rules(0, rules);
+        sequential.run(6);
+        for _ in 0..6 {
+            simulation.next_step();
+        }
+        assert_eq!(sequential.time(), 6);
+        assert_eq!(sequential.cached_states(), 4);
+        for state in 0..4 {
+            assert!(sequential
+                .probability_of(&state)
+                .approx_eq(&simulation.probability_of(&state, 6), Tolerance::stored()));
+        }
+    }
+}
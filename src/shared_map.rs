@@ -0,0 +1,319 @@
+use std::{fmt::Debug, hash::Hash, sync::Arc};
+
+use hashbrown::{HashMap, HashSet};
+
+// Lookups walk all layers, so once there are more, all but the oldest layer, which usually holds
+// the bulk of the map, are merged
+const MAX_LAYERS: usize = 8;
+
+#[derive(Clone)]
+struct Layer<K, V> {
+    entries: HashMap<K, V>,
+    // Keys of older layers which are hidden by this layer
+    removed: HashSet<K>,
+}
+
+impl<K, V> Layer<K, V>
+where
+    K: Hash + Eq,
+{
+    fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            removed: HashSet::new(),
+        }
+    }
+
+    fn hides(&self, key: &K) -> bool {
+        self.entries.contains_key(key) || self.removed.contains(key)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.removed.is_empty()
+    }
+}
+
+// A map whose bulk is shared between forks. A fork shares all layers including the one written
+// to, which is frozen on the next write, so neither forking nor writing afterwards copies entries
+// written before the fork. A plain clone only copies the layer written to.
+pub(crate) struct SharedMap<K, V> {
+    // Oldest layer first, the last one is the only one written to
+    layers: Vec<Arc<Layer<K, V>>>,
+    // Counting the visible entries would walk all layers
+    len: usize,
+}
+
+impl<K, V> SharedMap<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    pub fn new() -> Self {
+        Self {
+            layers: vec![Arc::new(Layer::new())],
+            len: 0,
+        }
+    }
+
+    // Freezes the last layer if a fork shares it
+    fn local(&mut self) -> &mut Layer<K, V> {
+        if Arc::get_mut(self.layers.last_mut().unwrap()).is_none() {
+            self.push_layer();
+        }
+        Arc::get_mut(self.layers.last_mut().unwrap()).unwrap()
+    }
+
+    fn push_layer(&mut self) {
+        if self.layers.last().is_some_and(|layer| layer.is_empty()) {
+            self.layers.pop();
+        }
+        if self.layers.len() >= MAX_LAYERS {
+            self.merge_layers();
+        }
+        self.layers.push(Arc::new(Layer::new()));
+    }
+
+    fn merge_layers(&mut self) {
+        let newer = self.layers.split_off(1);
+        let mut merged = Layer::new();
+        newer.iter().for_each(|layer| {
+            merged.entries.retain(|key, _| !layer.removed.contains(key));
+            merged
+                .removed
+                .retain(|key| !layer.entries.contains_key(key));
+            merged.removed.extend(layer.removed.iter().cloned());
+            merged.entries.extend(
+                layer
+                    .entries
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone())),
+            );
+        });
+        self.layers.push(Arc::new(merged));
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        for layer in self.layers.iter().rev() {
+            if let Some(value) = layer.entries.get(key) {
+                return Some(value);
+            }
+            if layer.removed.contains(key) {
+                return None;
+            }
+        }
+        None
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        if !self.contains_key(&key) {
+            self.len += 1;
+        }
+        let local = self.local();
+        local.removed.remove(&key);
+        local.entries.insert(key, value);
+    }
+
+    // Values of shared layers are cloned, as they stay visible to other forks
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let value = self.get(key).cloned()?;
+        // The last layer has to be writable before it is known whether older layers exist
+        self.local();
+        let is_layered = self.layers.len() > 1;
+        let local = self.local();
+        local.entries.remove(key);
+        if is_layered {
+            local.removed.insert(key.clone());
+        }
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.local().entries.reserve(additional);
+    }
+
+    pub fn get_or_insert_with(&mut self, key: K, value: impl FnOnce() -> V) -> &V {
+        if !self.contains_key(&key) {
+            self.insert(key.clone(), value());
+        }
+        self.get(&key).unwrap()
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&K, &V) -> bool) {
+        let removed = self
+            .iter()
+            .filter(|(key, value)| !keep(key, value))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        removed.iter().for_each(|key| {
+            self.remove(key);
+        });
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.layers
+            .iter()
+            .enumerate()
+            .flat_map(move |(index, layer)| {
+                layer.entries.iter().filter(move |(key, _)| {
+                    !self.layers[index + 1..]
+                        .iter()
+                        .any(|newer| newer.hides(key))
+                })
+            })
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn fork(&self) -> Self {
+        let mut fork = Self {
+            layers: self.layers.clone(),
+            len: self.len,
+        };
+        fork.push_layer();
+        fork
+    }
+}
+
+impl<K, V> Clone for SharedMap<K, V>
+where
+    K: Clone,
+    V: Clone,
+{
+    fn clone(&self) -> Self {
+        let mut layers = self.layers.clone();
+        if let Some(last) = layers.last_mut() {
+            *last = Arc::new((**last).clone());
+        }
+        Self {
+            layers,
+            len: self.len,
+        }
+    }
+}
+
+impl<K, V> Default for SharedMap<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Extend<(K, V)> for SharedMap<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        iter.for_each(|(key, value)| self.insert(key, value));
+    }
+}
+
+// Shared layers are only cloned if a fork still references them
+impl<K, V> IntoIterator for SharedMap<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    type Item = (K, V);
+    type IntoIter = hashbrown::hash_map::IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let mut map = HashMap::new();
+        self.layers.into_iter().for_each(|layer| {
+            let layer = Arc::try_unwrap(layer).unwrap_or_else(|layer| (*layer).clone());
+            map.retain(|key, _| !layer.removed.contains(key));
+            map.extend(layer.entries);
+        });
+        map.into_iter()
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for SharedMap<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V> Debug for SharedMap<K, V>
+where
+    K: Hash + Eq + Clone + Debug,
+    V: Clone + Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fork() {
+        let mut original = SharedMap::from_iter([(1, "a"), (2, "b")]);
+        let mut fork = original.fork();
+        assert!(Arc::ptr_eq(&original.layers[0], &fork.layers[0]));
+
+        fork.insert(3, "c");
+        fork.retain(|key, _| *key != 1);
+        original.insert(2, "B");
+        assert_eq!(fork.get(&1), None);
+        assert_eq!(fork.get(&2), Some(&"b"));
+        assert_eq!(fork.len(), 2);
+        assert_eq!(original.get(&1), Some(&"a"));
+        assert_eq!(original.get(&2), Some(&"B"));
+        assert_eq!(original.get(&3), None);
+        assert_eq!(original.len(), 2);
+        assert_eq!(*original.get_or_insert_with(4, || "d"), "d");
+        assert_eq!(*original.get_or_insert_with(4, || "e"), "d");
+
+        let second_fork = original.fork();
+        assert_eq!(second_fork.len(), 3);
+        assert_eq!(fork.len(), 2);
+        assert!(Arc::ptr_eq(&fork.layers[0], &second_fork.layers[0]));
+        assert_eq!(second_fork.layers.len(), 3);
+        assert_eq!(fork.remove(&2), Some("b"));
+        assert_eq!(fork.get(&2), None);
+        assert_eq!(second_fork.get(&2), Some(&"B"));
+
+        let mut deep_fork = second_fork;
+        for key in 10..20 {
+            deep_fork.insert(key, "x");
+            deep_fork = deep_fork.fork();
+        }
+        assert!(deep_fork.layers.len() <= MAX_LAYERS);
+        assert_eq!(deep_fork.len(), 13);
+        assert_eq!(deep_fork.get(&2), Some(&"B"));
+        assert_eq!(deep_fork.iter().count(), 13);
+    }
+}
@@ -1,41 +1,194 @@
 use std::{
+    collections::BTreeMap,
     fmt::Debug,
     hash::Hash,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, RwLock},
 };
 
+use crate::models::rules::*;
 use crate::prelude::*;
-use hashbrown::HashMap;
-use petgraph::{graph::Graph, visit::EdgeRef};
+use hashbrown::{HashMap, HashSet};
+use petgraph::graph::{Graph, NodeIndex};
 use rayon::prelude::*;
 
+pub use crate::sequential::{
+    OutgoingTransitions, Probability, StateProbabilityDistribution, StateTransitionGenerator, Time,
+};
+
 type StateHash = u64;
-type KnownStates<S> = HashMap<StateHash, S>;
+type KnownStates<S> = SharedMap<StateHash, S>;
 
 type TransitionHash = u64;
-type KnownTransitions<T> = HashMap<TransitionHash, T>;
+type KnownTransitions<T> = SharedMap<TransitionHash, T>;
 
+// States and transitions are only stored once, in the known states and transitions
 type StateTransitionGraph = Graph<StateHash, (TransitionHash, Probability)>;
 
-pub type StateTransitionGenerator<S, T> =
-    Arc<dyn Fn(S) -> OutgoingTransitions<S, T> + Send + Sync + 'static>;
+// Called with all states whose transitions are about to be generated, before the generator is
+// called for each of them
+pub type FrontierHook<S> = Arc<dyn Fn(&[S]) + Send + Sync + 'static>;
+
+// Builds the generator of a simulation from its rules, kept so edited rules are installed the same
+// way as the original ones
+pub(crate) type RuleGenerator<S, T> = Arc<
+    dyn Fn(
+            HashMap<RuleName, Rule<S>>,
+            RuleOptions,
+        ) -> (StateTransitionGenerator<S, T>, Option<FrontierHook<S>>)
+        + Send
+        + Sync
+        + 'static,
+>;
+
+pub type StateValidator<S> = Arc<dyn Fn(&S) -> Result<(), SchemaError> + Send + Sync + 'static>;
+
+type HashedStateProbabilityDistribution = HashedDistribution;
+
+// How transitions between the same two states are represented in a weighted graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParallelEdges {
+    // One edge per transition
+    #[default]
+    Keep,
+    // One edge per pair of states carrying the summed probability
+    Merge,
+}
 
-pub type StateProbabilityDistribution<S> = HashMap<S, Probability>;
+// What happens if the probabilities of the next states of a state do not sum up to 1, or exceed 1
+// in a sub-stochastic model, e.g. from float drift in long runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OverflowPolicy {
+    #[default]
+    Error,
+    // Transitions summing up to more than 1 are scaled down, the removed mass can be read with
+    // Simulation::overflow_mass
+    Clamp,
+    // Transitions are scaled to sum up to 1, or to at most 1 in a sub-stochastic model
+    Renormalize,
+}
 
-pub type OutgoingTransitions<S, T> = Vec<(S, T, Probability)>;
+impl OverflowPolicy {
+    // The factor the transitions of a state are scaled with, given the sum of their probabilities
+    pub(crate) fn scale(self, sum: Probability, sub_stochastic: bool) -> Probability {
+        match self {
+            OverflowPolicy::Clamp if sum > 1. => 1. / sum,
+            OverflowPolicy::Renormalize if sum > 1. || (!sub_stochastic && sum > 0.) => 1. / sum,
+            _ => 1.,
+        }
+    }
+}
 
-type HashedStateProbabilityDistribution = HashMap<StateHash, Probability>;
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbabilityFlow<S, T> {
+    source: S,
+    target: S,
+    transition: T,
+    mass: Probability,
+}
 
-pub type Probability = f64;
-pub type Time = u64;
+impl<S, T> ProbabilityFlow<S, T> {
+    pub fn new(source: S, target: S, transition: T, mass: Probability) -> Self {
+        Self {
+            source,
+            target,
+            transition,
+            mass,
+        }
+    }
+
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    pub fn target(&self) -> &S {
+        &self.target
+    }
+
+    pub fn transition(&self) -> &T {
+        &self.transition
+    }
+
+    pub fn mass(&self) -> Probability {
+        self.mass
+    }
+}
+
+// Transitions only depend on the state and the model, so simulations of the same model with
+// different initial distributions can share one cache.
+#[derive(Clone)]
+pub struct TransitionCache<S, T> {
+    transitions: Arc<RwLock<HashMap<S, OutgoingTransitions<S, T>>>>,
+}
+
+impl<S, T> TransitionCache<S, T>
+where
+    S: Hash + Eq,
+{
+    pub fn new() -> Self {
+        Self {
+            transitions: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.transitions.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, state: &S) -> bool {
+        self.transitions.read().unwrap().contains_key(state)
+    }
+
+    pub fn clear(&self) {
+        self.transitions.write().unwrap().clear();
+    }
+}
+
+impl<S, T> Default for TransitionCache<S, T>
+where
+    S: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S, T> Debug for TransitionCache<S, T>
+where
+    S: Hash + Eq,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransitionCache")
+            .field("len", &self.len())
+            .finish()
+    }
+}
 
 #[derive(Clone)]
 pub struct Simulation<S, T> {
-    state_transition_graph: StateTransitionGraph,
-    probability_distributions: HashMap<Time, HashedStateProbabilityDistribution>,
+    // Copied on the first write after a fork, it only holds hashes
+    state_transition_graph: Arc<StateTransitionGraph>,
+    node_indices: SharedMap<StateHash, NodeIndex>,
+    probability_distributions: SharedMap<Time, HashedStateProbabilityDistribution>,
     known_states: KnownStates<S>,
     known_transitions: KnownTransitions<T>,
+    // States added by extend_known_states, which are kept when the history is replayed
+    added_states: SharedMap<StateHash, ()>,
     state_transition_generator: CachedFunction<S, OutgoingTransitions<S, T>>,
+    state_validator: Option<StateValidator<S>>,
+    rules: Option<HashMap<RuleName, Rule<S>>>,
+    rule_options: RuleOptions,
+    rule_generator: Option<RuleGenerator<S, T>>,
+    sub_stochastic: bool,
+    killed_mass: HashMap<Time, Probability>,
+    tracked: Arc<HashMap<String, TrackedObservable<S>>>,
+    overflow_policy: OverflowPolicy,
+    overflow_mass: HashMap<Time, Probability>,
+    labelings: BTreeMap<LabelName, Labeling<S>>,
+    frontier_hook: Option<FrontierHook<S>>,
 }
 
 impl<S, T> Debug for Simulation<S, T>
@@ -53,6 +206,55 @@ where
     }
 }
 
+type Explored<S, T> = Mutex<Vec<(S, OutgoingTransitions<S, T>)>>;
+
+// Every newly discovered state is explored in its own task, so rayon's work stealing keeps all
+// threads busy even if a few states have many more successors than the rest. The frontier hook
+// sees the states discovered by a state before their tasks are spawned.
+fn explore_state<'scope, S, T>(
+    scope: &rayon::Scope<'scope>,
+    state: S,
+    generator: &'scope CachedFunction<S, OutgoingTransitions<S, T>>,
+    frontier_hook: Option<&'scope FrontierHook<S>>,
+    seen: &'scope Mutex<HashSet<StateHash>>,
+    explored: &'scope Explored<S, T>,
+) where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq,
+{
+    let transitions = generator.compute(state.clone());
+    let discovered = transitions
+        .iter()
+        .filter(|(new_state, _, _)| seen.lock().unwrap().insert(hash(new_state)))
+        .map(|(new_state, _, _)| new_state.clone())
+        .collect::<Vec<_>>();
+    spawn_explore_states(scope, discovered, generator, frontier_hook, seen, explored);
+    explored.lock().unwrap().push((state, transitions));
+}
+
+fn spawn_explore_states<'scope, S, T>(
+    scope: &rayon::Scope<'scope>,
+    states: Vec<S>,
+    generator: &'scope CachedFunction<S, OutgoingTransitions<S, T>>,
+    frontier_hook: Option<&'scope FrontierHook<S>>,
+    seen: &'scope Mutex<HashSet<StateHash>>,
+    explored: &'scope Explored<S, T>,
+) where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq,
+{
+    if let Some(frontier_hook) = frontier_hook {
+        if !states.is_empty() {
+            frontier_hook(&states);
+        }
+    }
+    states.into_iter().for_each(|state| {
+        scope.spawn(move |scope| {
+            explore_state(scope, state, generator, frontier_hook, seen, explored)
+        });
+    });
+}
+
 impl<S, T> Simulation<S, T>
 where
     S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
@@ -65,20 +267,39 @@ where
         let initial_state_hash = hash(&initial_state);
 
         let mut state_transition_graph = Graph::new();
-        state_transition_graph.add_node(initial_state_hash);
+        let node_indices = SharedMap::from_iter([(
+            initial_state_hash,
+            state_transition_graph.add_node(initial_state_hash),
+        )]);
 
-        let probabilities = HashMap::from([(0, HashMap::from([(initial_state_hash, 1.0)]))]);
+        let probabilities = SharedMap::from_iter([(
+            0,
+            HashedDistribution::from_iter([(initial_state_hash, 1.0)]),
+        )]);
 
-        let known_states = HashMap::from([(initial_state_hash, initial_state)]);
+        let known_states = SharedMap::from_iter([(initial_state_hash, initial_state)]);
 
-        let known_transitions = HashMap::new();
+        let known_transitions = SharedMap::new();
 
         Self {
-            state_transition_graph,
+            state_transition_graph: Arc::new(state_transition_graph),
+            node_indices,
             probability_distributions: probabilities,
             known_states,
             known_transitions,
+            added_states: SharedMap::new(),
             state_transition_generator: CachedFunction::new(state_transition_generator),
+            state_validator: None,
+            rules: None,
+            rule_options: RuleOptions::default(),
+            rule_generator: None,
+            sub_stochastic: false,
+            killed_mass: HashMap::new(),
+            tracked: Arc::new(HashMap::new()),
+            overflow_policy: OverflowPolicy::default(),
+            overflow_mass: HashMap::new(),
+            labelings: BTreeMap::new(),
+            frontier_hook: None,
         }
     }
 
@@ -92,9 +313,9 @@ where
                 let state_hash = hash(state);
                 (state_hash, state.clone())
             })
-            .collect::<HashMap<_, _>>();
+            .collect::<SharedMap<_, _>>();
 
-        let known_transitions = HashMap::new();
+        let known_transitions = SharedMap::new();
 
         let hashed_probabilities = probabilities
             .iter()
@@ -102,20 +323,298 @@ where
                 let state_hash = hash(state);
                 (state_hash, *probability)
             })
-            .collect::<HashMap<_, _>>();
+            .collect::<HashedDistribution>();
 
         let mut graph: StateTransitionGraph = Graph::new();
-        probabilities.iter().for_each(|(state, _)| {
-            let state_hash = hash(state);
-            graph.add_node(state_hash);
-        });
+        let node_indices = probabilities
+            .keys()
+            .map(|state| {
+                let state_hash = hash(state);
+                (state_hash, graph.add_node(state_hash))
+            })
+            .collect();
 
         Self {
-            state_transition_graph: graph,
-            probability_distributions: HashMap::from([(0, hashed_probabilities)]),
+            state_transition_graph: Arc::new(graph),
+            node_indices,
+            probability_distributions: SharedMap::from_iter([(0, hashed_probabilities)]),
             known_states,
             known_transitions,
+            added_states: SharedMap::new(),
             state_transition_generator: CachedFunction::new(state_transition_generator),
+            state_validator: None,
+            rules: None,
+            rule_options: RuleOptions::default(),
+            rule_generator: None,
+            sub_stochastic: false,
+            killed_mass: HashMap::new(),
+            tracked: Arc::new(HashMap::new()),
+            overflow_policy: OverflowPolicy::default(),
+            overflow_mass: HashMap::new(),
+            labelings: BTreeMap::new(),
+            frontier_hook: None,
+        }
+    }
+
+    pub fn set_state_validator(&mut self, state_validator: StateValidator<S>) {
+        self.state_validator = Some(state_validator);
+    }
+
+    pub(crate) fn set_frontier_hook(&mut self, frontier_hook: Option<FrontierHook<S>>) {
+        self.frontier_hook = frontier_hook;
+    }
+
+    fn call_frontier_hook<'a>(&self, states: impl Iterator<Item = &'a S>)
+    where
+        S: 'a,
+    {
+        if let Some(frontier_hook) = &self.frontier_hook {
+            let frontier = states
+                .filter(|state| self.cached_outgoing_transitions(state).is_none())
+                .cloned()
+                .collect::<Vec<_>>();
+            if !frontier.is_empty() {
+                frontier_hook(&frontier);
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn set_transition_observer(
+        &mut self,
+        transition_observer: Option<InsertObserver<S, OutgoingTransitions<S, T>>>,
+    ) {
+        self.state_transition_generator
+            .set_insert_observer(transition_observer);
+    }
+
+    // The cache must only be shared between simulations with the same state transition generator
+    pub fn share_cache(&mut self, cache: &TransitionCache<S, T>) {
+        self.state_transition_generator
+            .set_shared_cache(cache.transitions.clone());
+    }
+
+    pub fn validate_known_states(&self) -> Result<(), SchemaError> {
+        match &self.state_validator {
+            Some(validator) => self
+                .known_states
+                .values()
+                .try_for_each(|state| validator(state)),
+            None => Ok(()),
+        }
+    }
+
+    // Adds many states at once, e.g. from an enumeration of the state space. They are validated as
+    // a batch, so nothing is added if any of them is invalid, and become nodes of the graph
+    // without transitions.
+    pub fn extend_known_states(
+        &mut self,
+        states: impl IntoIterator<Item = S>,
+    ) -> Result<(), SchemaError> {
+        let mut seen = HashSet::new();
+        let new_states = states
+            .into_iter()
+            .map(|state| (hash(&state), state))
+            .filter(|(state_hash, _)| {
+                !self.known_states.contains_key(state_hash) && seen.insert(*state_hash)
+            })
+            .collect::<Vec<_>>();
+        if let Some(validator) = &self.state_validator {
+            new_states
+                .iter()
+                .try_for_each(|(_, state)| validator(state))?;
+        }
+        self.known_states.reserve(new_states.len());
+        self.node_indices.reserve(new_states.len());
+        Arc::make_mut(&mut self.state_transition_graph).reserve_nodes(new_states.len());
+        new_states.into_iter().for_each(|(state_hash, state)| {
+            self.graph_node(state_hash);
+            self.record_labels(state_hash, &state);
+            self.known_states.insert(state_hash, state);
+            self.added_states.insert(state_hash, ());
+        });
+        Ok(())
+    }
+
+    // The fork shares everything explored so far with this simulation and only stores what it
+    // explores on its own. The graph is copied by whichever side explores new transitions first.
+    pub fn fork(&self) -> Self {
+        Self {
+            state_transition_graph: self.state_transition_graph.clone(),
+            node_indices: self.node_indices.fork(),
+            probability_distributions: self.probability_distributions.fork(),
+            known_states: self.known_states.fork(),
+            known_transitions: self.known_transitions.fork(),
+            added_states: self.added_states.fork(),
+            state_transition_generator: self.state_transition_generator.fork(),
+            state_validator: self.state_validator.clone(),
+            rules: self.rules.clone(),
+            rule_options: self.rule_options,
+            rule_generator: self.rule_generator.clone(),
+            sub_stochastic: self.sub_stochastic,
+            killed_mass: self.killed_mass.clone(),
+            overflow_policy: self.overflow_policy,
+            overflow_mass: self.overflow_mass.clone(),
+            tracked: self.tracked.clone(),
+            labelings: self.labelings.clone(),
+            frontier_hook: self.frontier_hook.clone(),
+        }
+    }
+
+    // In a sub-stochastic model the probabilities of the next states may sum up to less than 1,
+    // the missing mass is absorbed into an implicit dead state and recorded per step.
+    pub fn set_sub_stochastic(&mut self, sub_stochastic: bool) {
+        self.sub_stochastic = sub_stochastic;
+    }
+
+    pub fn is_sub_stochastic(&self) -> bool {
+        self.sub_stochastic
+    }
+
+    pub fn killed_mass(&self, time: Time) -> Probability {
+        self.killed_mass.get(&time).copied().unwrap_or(0.)
+    }
+
+    pub fn total_killed_mass(&self) -> Probability {
+        self.killed_mass.values().sum()
+    }
+
+    pub fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
+    }
+
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+
+    // The mass removed by OverflowPolicy::Clamp in the step from the given time
+    pub fn overflow_mass(&self, time: Time) -> Probability {
+        self.overflow_mass.get(&time).copied().unwrap_or(0.)
+    }
+
+    pub fn total_overflow_mass(&self) -> Probability {
+        self.overflow_mass.values().sum()
+    }
+
+    pub fn rules(&self) -> Option<&HashMap<RuleName, Rule<S>>> {
+        self.rules.as_ref()
+    }
+
+    pub(crate) fn set_rules(&mut self, rules: HashMap<RuleName, Rule<S>>) {
+        self.rules = Some(rules);
+    }
+
+    pub fn rule_options(&self) -> RuleOptions {
+        self.rule_options
+    }
+
+    pub(crate) fn set_rule_options(&mut self, rule_options: RuleOptions) {
+        self.rule_options = rule_options;
+    }
+
+    pub(crate) fn rule_generator(&self) -> Option<RuleGenerator<S, T>> {
+        self.rule_generator.clone()
+    }
+
+    pub(crate) fn set_rule_generator(&mut self, rule_generator: RuleGenerator<S, T>) {
+        self.rule_generator = Some(rule_generator);
+    }
+
+    pub(crate) fn tracked(&self) -> &HashMap<String, TrackedObservable<S>> {
+        &self.tracked
+    }
+
+    pub(crate) fn tracked_mut(&mut self) -> &mut HashMap<String, TrackedObservable<S>> {
+        Arc::make_mut(&mut self.tracked)
+    }
+
+    pub(crate) fn labelings(&self) -> &BTreeMap<LabelName, Labeling<S>> {
+        &self.labelings
+    }
+
+    pub(crate) fn labelings_mut(&mut self) -> &mut BTreeMap<LabelName, Labeling<S>> {
+        &mut self.labelings
+    }
+
+    // Has to be called before a state is inserted into the known states
+    fn record_labels(&mut self, state_hash: StateHash, state: &S) {
+        if !self.known_states.contains_key(&state_hash) {
+            self.labelings
+                .values_mut()
+                .for_each(|labeling| labeling.record(state_hash, state));
+        }
+    }
+
+    // Only the cached transitions of affected states are dropped, all other states keep their
+    // cached transitions while the steps up to the current time are replayed.
+    pub fn replace_state_transition_generator(
+        &mut self,
+        state_transition_generator: StateTransitionGenerator<S, T>,
+        is_affected: impl Fn(&S) -> bool,
+    ) {
+        self.swap_state_transition_generator(state_transition_generator, is_affected);
+        self.replay();
+    }
+
+    // Keeps the history, only the cached transitions and the outgoing edges of affected states
+    // are dropped so they are explored again with the new generator
+    pub(crate) fn swap_state_transition_generator(
+        &mut self,
+        state_transition_generator: StateTransitionGenerator<S, T>,
+        is_affected: impl Fn(&S) -> bool,
+    ) {
+        self.state_transition_generator
+            .set_function(state_transition_generator);
+        self.state_transition_generator
+            .retain(|state, _| !is_affected(state));
+        let affected = self
+            .state_transition_graph
+            .node_indices()
+            .filter(|index| {
+                let state_hash = self.state_transition_graph[*index];
+                is_affected(self.state(state_hash).unwrap())
+            })
+            .collect::<HashSet<_>>();
+        Arc::make_mut(&mut self.state_transition_graph).retain_edges(|graph, edge| {
+            let (source, _) = graph.edge_endpoints(edge).unwrap();
+            !affected.contains(&source)
+        });
+    }
+
+    // Recomputes the distribution at the current time from the previous one
+    pub(crate) fn redo_last_step(&mut self) {
+        let time = self.time();
+        if time > 0 {
+            self.forget_probability_distribution(time);
+            self.advance();
+        }
+    }
+
+    fn replay(&mut self) {
+        let time = self.time();
+        let initial_distribution = self.probability_distributions.remove(&0).unwrap();
+        let added_states = &self.added_states;
+        let is_kept = |state_hash: &StateHash| {
+            initial_distribution.get(state_hash).is_some() || added_states.contains_key(state_hash)
+        };
+        self.known_states
+            .retain(|state_hash, _| is_kept(state_hash));
+        self.labelings
+            .values_mut()
+            .for_each(|labeling| labeling.forget(is_kept));
+        self.known_transitions.clear();
+        self.state_transition_graph = Arc::new(Graph::new());
+        self.node_indices.clear();
+        let kept_states = self.known_states.keys().copied().collect::<Vec<_>>();
+        kept_states.into_iter().for_each(|state_hash| {
+            self.graph_node(state_hash);
+        });
+        self.probability_distributions = SharedMap::from_iter([(0, initial_distribution)]);
+        self.killed_mass.clear();
+        self.overflow_mass.clear();
+        self.reset_tracked();
+        for _ in 0..time {
+            self.advance();
         }
     }
 
@@ -123,52 +622,79 @@ where
         self.known_states.get(&state_hash)
     }
 
-    fn transition(&self, transition_hash: TransitionHash) -> Option<&T> {
-        self.known_transitions.get(&transition_hash)
+    pub(crate) fn states_in_graph_order(&self) -> Vec<&S> {
+        self.state_transition_graph
+            .node_weights()
+            .map(|state_hash| self.state(*state_hash).unwrap())
+            .collect()
+    }
+
+    pub(crate) fn cached_outgoing_transitions(
+        &self,
+        state: &S,
+    ) -> Option<&OutgoingTransitions<S, T>> {
+        self.state_transition_generator.get(state)
+    }
+
+    // The graph is kept up to date while states are explored, so this is cheap to call. Nodes
+    // and edges carry hashes, which are resolved with known_state and known_transition.
+    pub fn graph(&self) -> &Graph<StateHash, (TransitionHash, Probability)> {
+        &self.state_transition_graph
     }
 
     pub fn state_transition_graph(&self) -> Graph<S, (T, Probability)> {
+        self.state_transition_graph.map(
+            |_, state_hash| self.state(*state_hash).unwrap().clone(),
+            |_, (transition_hash, probability)| {
+                (
+                    self.known_transitions.get(transition_hash).unwrap().clone(),
+                    *probability,
+                )
+            },
+        )
+    }
+
+    // Edges carry the normalized transition probabilities, built from the cached transitions so
+    // that transitions to the same successor are not lost
+    pub fn weighted_graph(&self, parallel_edges: ParallelEdges) -> Graph<S, Probability> {
         let mut graph = Graph::new();
-        self.state_transition_graph
-            .node_indices()
-            .map(|node| {
-                let state_hash = *self.state_transition_graph.node_weight(node).unwrap();
-                self.state(state_hash).unwrap().clone()
+        let states = self.states_in_graph_order();
+        let indices = states
+            .iter()
+            .map(|state| (hash(*state), graph.add_node((*state).clone())))
+            .collect::<HashMap<_, _>>();
+        let mut merged: HashMap<(NodeIndex, NodeIndex), Probability> = HashMap::new();
+        states
+            .into_iter()
+            .filter_map(|state| {
+                Some((
+                    indices[&hash(state)],
+                    self.cached_outgoing_transitions(state)?,
+                ))
             })
-            .for_each(|state| {
-                graph.add_node(state);
+            .for_each(|(source, transitions)| {
+                transitions.iter().for_each(|(target, _, probability)| {
+                    let target = indices[&hash(target)];
+                    match parallel_edges {
+                        ParallelEdges::Keep => {
+                            graph.add_edge(source, target, *probability);
+                        }
+                        ParallelEdges::Merge => match merged.get_mut(&(source, target)) {
+                            Some(edge) => *edge += probability,
+                            None => {
+                                merged.insert((source, target), *probability);
+                            }
+                        },
+                    }
+                });
+            });
+        let mut merged = merged.into_iter().collect::<Vec<_>>();
+        merged.sort_by_key(|(edge, _)| *edge);
+        merged
+            .into_iter()
+            .for_each(|((source, target), probability)| {
+                graph.add_edge(source, target, probability);
             });
-        let edges = self
-            .state_transition_graph
-            .edge_references()
-            .map(|edge| {
-                let source_hash = *self
-                    .state_transition_graph
-                    .node_weight(edge.source())
-                    .unwrap();
-                let target_hash = *self
-                    .state_transition_graph
-                    .node_weight(edge.target())
-                    .unwrap();
-                let (transition_hash, probability) = edge.weight();
-                let source = self.state(source_hash).unwrap().clone();
-                let target = self.state(target_hash).unwrap().clone();
-                let transition = self.transition(*transition_hash).unwrap().clone();
-                (source, target, transition, *probability)
-            })
-            .collect::<Vec<(S, S, T, Probability)>>();
-        for (source_state, target_state, transition, probability) in edges {
-            // check if nodes already exist
-            let source = graph
-                .node_indices()
-                .find(|node| graph.node_weight(*node).unwrap() == &source_state)
-                .unwrap_or_else(|| graph.add_node(source_state.clone()));
-            let target = graph
-                .node_indices()
-                .find(|node| graph.node_weight(*node).unwrap() == &target_state)
-                .unwrap_or_else(|| graph.add_node(target_state.clone()));
-            graph.update_edge(source, target, (transition, probability));
-        }
         graph
     }
 
@@ -180,7 +706,7 @@ where
                     .iter()
                     .map(|(state_hash, probability)| {
                         let state = self.state(*state_hash).unwrap().clone();
-                        (state, *probability)
+                        (state, probability)
                     })
                     .collect::<HashMap<_, _>>();
 
@@ -190,13 +716,7 @@ where
     }
 
     pub fn state_probability(&self, state: S, time: Time) -> f64 {
-        self.probability_distributions
-            .get(&time)
-            .and_then(|state_probability_distribution| {
-                state_probability_distribution.get(&hash(&state))
-            })
-            .copied()
-            .unwrap_or(0.0)
+        self.probability_of(&state, time)
     }
 
     pub fn initial_distribution(&self) -> StateProbabilityDistribution<S> {
@@ -204,17 +724,121 @@ where
     }
 
     pub fn probability_distribution(&self, time: Time) -> StateProbabilityDistribution<S> {
+        self.try_probability_distribution(time)
+            .expect("No probability distribution found for given time")
+    }
+
+    pub fn try_probability_distribution(
+        &self,
+        time: Time,
+    ) -> Result<StateProbabilityDistribution<S>, SimulationError> {
+        Ok(self
+            .iter_probability_distribution(time)?
+            .map(|(state, probability)| (state.clone(), probability))
+            .collect())
+    }
+
+    pub fn iter_probability_distribution(
+        &self,
+        time: Time,
+    ) -> Result<impl Iterator<Item = (&S, Probability)>, SimulationError> {
+        let distribution = self
+            .probability_distributions
+            .get(&time)
+            .ok_or(SimulationError::NoProbabilityDistribution { time })?;
+        Ok(distribution
+            .iter()
+            .map(|(state_hash, probability)| (self.state(*state_hash).unwrap(), probability)))
+    }
+
+    // Moves the states out of the simulation instead of cloning them
+    pub fn into_probability_distribution(
+        self,
+        time: Time,
+    ) -> Result<StateProbabilityDistribution<S>, SimulationError> {
+        let distribution = self
+            .probability_distributions
+            .get(&time)
+            .ok_or(SimulationError::NoProbabilityDistribution { time })?;
+        let mut states = self.known_states.into_iter().collect::<HashMap<_, _>>();
+        Ok(distribution
+            .iter()
+            .map(|(state_hash, probability)| (states.remove(state_hash).unwrap(), probability))
+            .collect())
+    }
+
+    pub fn par_iter_probability_distribution(
+        &self,
+        time: Time,
+    ) -> Result<impl IndexedParallelIterator<Item = (&S, Probability)>, SimulationError> {
+        let distribution = self
+            .probability_distributions
+            .get(&time)
+            .ok_or(SimulationError::NoProbabilityDistribution { time })?;
+        let known_states = &self.known_states;
+        Ok(distribution
+            .par_iter()
+            .map(|(state_hash, probability)| (known_states.get(state_hash).unwrap(), probability)))
+    }
+
+    pub fn probability_of(&self, state: &S, time: Time) -> Probability {
+        self.probability_distributions
+            .get(&time)
+            .and_then(|distribution| distribution.get(&hash(state)))
+            .unwrap_or(0.0)
+    }
+
+    pub fn known_state(&self, state_hash: u64) -> Result<&S, SimulationError> {
+        self.state(state_hash)
+            .ok_or(SimulationError::UnknownState { state_hash })
+    }
+
+    pub fn known_transition(&self, transition_hash: u64) -> Result<&T, SimulationError> {
+        self.known_transitions
+            .get(&transition_hash)
+            .ok_or(SimulationError::UnknownTransition { transition_hash })
+    }
+
+    pub fn probability_sum(&self, time: Time) -> Probability {
+        self.hashed_distribution(time).sum()
+    }
+
+    pub(crate) fn stored_times(&self) -> Vec<Time> {
+        let mut times = self
+            .probability_distributions
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        times.sort();
+        times
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn insert_probability_distribution(
+        &mut self,
+        time: Time,
+        distribution: StateProbabilityDistribution<S>,
+    ) {
+        let hashed_distribution = HashedDistribution::from_unique(distribution.into_iter().map(
+            |(state, probability)| {
+                let state_hash = hash(&state);
+                self.graph_node(state_hash);
+                self.record_labels(state_hash, &state);
+                self.known_states.get_or_insert_with(state_hash, || state);
+                (state_hash, probability)
+            },
+        ));
+        self.probability_distributions
+            .insert(time, hashed_distribution);
+    }
+
+    pub(crate) fn forget_probability_distribution(&mut self, time: Time) {
+        self.probability_distributions.remove(&time);
+    }
+
+    fn hashed_distribution(&self, time: Time) -> &HashedStateProbabilityDistribution {
         self.probability_distributions
             .get(&time)
-            .map(|state_probability_distribution| {
-                state_probability_distribution
-                    .iter()
-                    .map(|(state_hash, probability)| {
-                        let state = self.state(*state_hash).unwrap().clone();
-                        (state, *probability)
-                    })
-                    .collect::<HashMap<_, _>>()
-            })
             .expect("No probability distribution found for given time")
     }
 
@@ -222,18 +846,79 @@ where
         self.known_states.values().cloned().collect()
     }
 
+    pub fn into_known_states(self) -> impl Iterator<Item = S> {
+        self.known_states.into_iter().map(|(_, state)| state)
+    }
+
     pub fn known_transitions(&self) -> Vec<T> {
         self.known_transitions.values().cloned().collect()
     }
 
+    pub fn iter_known_states(&self) -> impl Iterator<Item = &S> {
+        self.known_states.values()
+    }
+
+    pub fn num_known_states(&self) -> usize {
+        self.known_states.len()
+    }
+
+    // The states are collected into a vector first, as the shared map can not be split
+    pub fn par_iter_known_states(&self) -> impl IndexedParallelIterator<Item = &S> {
+        self.iter_known_states().collect::<Vec<_>>().into_par_iter()
+    }
+
+    pub fn iter_known_transitions(&self) -> impl Iterator<Item = &T> {
+        self.known_transitions.values()
+    }
+
+    pub fn probability_flows(&self, time: Time) -> Vec<ProbabilityFlow<S, T>> {
+        self.hashed_distribution(time)
+            .iter()
+            .filter_map(|(state_hash, probability)| {
+                let source = self.state(*state_hash).unwrap();
+                self.cached_outgoing_transitions(source)
+                    .map(|transitions| (source, probability, transitions))
+            })
+            .flat_map(|(source, probability, transitions)| {
+                // The same scale as in the step, so the flows sum up to the next distribution
+                let sum = transitions
+                    .iter()
+                    .map(|(_, _, probability)| probability)
+                    .sum::<Probability>();
+                let probability =
+                    probability * self.overflow_policy.scale(sum, self.sub_stochastic);
+                transitions
+                    .iter()
+                    .map(move |(target, transition, transition_probability)| {
+                        ProbabilityFlow::new(
+                            source.clone(),
+                            target.clone(),
+                            transition.clone(),
+                            probability * transition_probability,
+                        )
+                    })
+            })
+            .collect()
+    }
+
     pub fn entropy(&self, time: Time) -> f64 {
-        let state_probability_distribution = self.probability_distribution(time);
-        let entropy = state_probability_distribution
-            .values()
-            .map(|probability| probability * probability.log2())
-            .sum::<f64>()
-            .abs();
-        entropy
+        self.hashed_distribution(time).entropy()
+    }
+
+    // Expected value of a function of the state, e.g. a reward, a parameter or an indicator
+    pub fn expectation(&self, time: Time, function: impl Fn(&S) -> f64 + Sync) -> f64 {
+        self.par_iter_probability_distribution(time)
+            .expect("No probability distribution found for given time")
+            .map(|(state, probability)| function(state) * probability)
+            .sum()
+    }
+
+    pub fn probability_where(
+        &self,
+        time: Time,
+        predicate: impl Fn(&S) -> bool + Sync,
+    ) -> Probability {
+        self.expectation(time, |state| if predicate(state) { 1. } else { 0. })
     }
 
     pub fn time(&self) -> Time {
@@ -245,131 +930,348 @@ where
     }
 
     pub fn next_step(&mut self) -> StateProbabilityDistribution<S> {
+        self.try_next_step()
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    // Nothing is changed if a new state is invalid
+    pub fn try_next_step(&mut self) -> Result<StateProbabilityDistribution<S>, SimulationError> {
+        self.try_advance(None)?;
+        Ok(self.probability_distribution(self.time()))
+    }
+
+    // Takes the next step without building the new distribution with its states
+    pub(crate) fn advance(&mut self) {
+        if let Err(error) = self.try_advance(None) {
+            panic!("{error}");
+        }
+    }
+
+    // Nothing is changed if the step would make more states known than the maximum
+    pub(crate) fn try_advance(&mut self, max_states: Option<usize>) -> Result<(), SimulationError> {
         let initial_time = self.time();
-        let state_probability_distribution: Vec<(S, Probability)> = self
-            .probability_distribution(initial_time)
-            .into_par_iter()
+        // Only the hashes are copied, the states stay borrowed from the known states
+        let state_probability_distribution: Vec<(StateHash, Probability)> = self
+            .hashed_distribution(initial_time)
+            .iter()
+            .map(|(state_hash, probability)| (*state_hash, probability))
             .collect();
 
+        self.call_frontier_hook(
+            state_probability_distribution
+                .iter()
+                .map(|(state_hash, _)| self.state(*state_hash).unwrap()),
+        );
+        let known_states = &self.known_states;
         let state_transition_probabilities = self.state_transition_generator.call_many_parallel(
             state_probability_distribution
                 .par_iter()
-                .map(|(state, _)| state.clone()),
+                .map(|(state_hash, _)| known_states.get(state_hash).unwrap()),
         );
+        self.validate_new_states(state_transition_probabilities.iter().map(Arc::as_ref))?;
+        self.check_state_limit(
+            state_transition_probabilities.iter().map(Arc::as_ref),
+            max_states,
+        )?;
 
-        // Check if probabilities sum up to 1.0
-        state_transition_probabilities
+        // Check if probabilities are valid and sum up to 1.0, or at most 1.0 if sub-stochastic.
+        // Every state gets the killed mass, the overflowing mass and the factor its transitions
+        // are scaled with.
+        let sub_stochastic = self.sub_stochastic;
+        let overflow_policy = self.overflow_policy;
+        let no_rule_is_error =
+            self.rules.is_some() && self.rule_options.nothing_happens() == NothingHappens::Error;
+        let checks = state_transition_probabilities
             .par_iter()
-            .for_each(|next_states| {
-                assert_eq!(
-                    (next_states
-                        .iter()
-                        .map(|(_, _, probability)| probability)
-                        .sum::<Probability>()
-                        * 10_i64.pow(10) as f64)
-                        .round()
-                        / 10_i64.pow(10) as f64,
-                    1.0,
-                    "Sum of probabilities of next states is not 1.0"
-                );
-            });
+            .zip_eq(state_probability_distribution.par_iter())
+            .map(|(next_states, (state_hash, current_state_probability))| {
+                if no_rule_is_error && next_states.is_empty() {
+                    return Err(SimulationError::NoRuleApplies {
+                        state_hash: *state_hash,
+                    });
+                }
+                // Invalid weights of rules and successor providers end up here as invalid
+                // probabilities. Values slightly above 1 from float drift are left to the sum check
+                // below.
+                next_states
+                    .iter()
+                    .try_for_each(|(_, _, probability)| {
+                        CheckedProbabilityWeight::try_from_f64(*probability).map(|_| ())
+                    })
+                    .map_err(|error| SimulationError::InvalidProbability {
+                        state_hash: *state_hash,
+                        error,
+                    })?;
+                let sum = next_states
+                    .iter()
+                    .map(|(_, _, probability)| probability)
+                    .sum::<Probability>();
+                let rounded_sum = (sum * 10_i64.pow(10) as f64).round() / 10_i64.pow(10) as f64;
+                let scale = overflow_policy.scale(sum, sub_stochastic);
+                match overflow_policy {
+                    OverflowPolicy::Clamp if sum > 1. => {
+                        Ok((0., current_state_probability * (sum - 1.), scale))
+                    }
+                    OverflowPolicy::Renormalize if scale != 1. => Ok((0., 0., scale)),
+                    _ if sub_stochastic && rounded_sum <= 1.0 => {
+                        Ok((current_state_probability * (1. - sum).max(0.), 0., scale))
+                    }
+                    _ if !sub_stochastic && rounded_sum == 1.0 => Ok((0., 0., scale)),
+                    // Also a state without successors, as there is nothing to renormalize
+                    _ => Err(SimulationError::ProbabilitySum {
+                        state_hash: *state_hash,
+                        sum,
+                    }),
+                }
+            })
+            .collect::<Result<Vec<_>, SimulationError>>()?;
+        if sub_stochastic {
+            let killed_mass = checks.iter().map(|(killed, _, _)| killed).sum();
+            self.killed_mass.insert(initial_time, killed_mass);
+        }
+        if overflow_policy == OverflowPolicy::Clamp {
+            let overflow_mass = checks.iter().map(|(_, overflow, _)| overflow).sum();
+            self.overflow_mass.insert(initial_time, overflow_mass);
+        }
 
         // Calculate new state probability distribution
-        let new_hashed_state_probability_distribution_mutex = Mutex::new(HashMap::new());
-        state_transition_probabilities
+        let new_hashed_state_probability_distribution = state_transition_probabilities
             .par_iter()
             .zip_eq(state_probability_distribution.par_iter())
-            .for_each(|(next_states, (_, current_state_probability))| {
-                next_states.iter().for_each(|(new_state, _, probability)| {
-                    new_hashed_state_probability_distribution_mutex
-                        .lock()
-                        .unwrap()
-                        .entry(hash(new_state))
-                        .and_modify(|state_probability| {
-                            *state_probability += current_state_probability * probability;
-                        })
-                        .or_insert(current_state_probability * probability);
-                });
-            });
+            .zip_eq(checks.par_iter())
+            .flat_map_iter(
+                |((next_states, (_, current_state_probability)), (_, _, scale))| {
+                    next_states.iter().map(move |(new_state, _, probability)| {
+                        (
+                            hash(new_state),
+                            current_state_probability * probability * scale,
+                        )
+                    })
+                },
+            )
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect::<HashedDistribution>();
         // Add new state probability distribution to list of all state probability distributions
-        self.probability_distributions.insert(
-            initial_time + 1,
-            new_hashed_state_probability_distribution_mutex
-                .into_inner()
-                .unwrap(),
-        );
+        self.probability_distributions
+            .insert(initial_time + 1, new_hashed_state_probability_distribution);
 
-        // Add new states and transitions to known states and transitions
+        // Add new states and transitions to known states, transitions and the state transition graph
         state_transition_probabilities
             .iter()
-            .for_each(|next_states| {
-                next_states.iter().for_each(|(new_state, transition, _)| {
-                    self.known_states.insert(hash(new_state), new_state.clone());
-                    self.known_transitions
-                        .insert(hash(transition), transition.clone());
-                });
+            .zip(state_probability_distribution.iter())
+            .for_each(|(next_states, (state_hash, _))| {
+                self.add_outgoing_edges(*state_hash, next_states);
             });
+        self.record_tracked(initial_time + 1);
+        Ok(())
+    }
 
-        // Add new state transitions to state transition graph
-        state_transition_probabilities
+    // Checks the states which are not known yet, before anything is added
+    fn validate_new_states<'a>(
+        &self,
+        transitions: impl Iterator<Item = &'a OutgoingTransitions<S, T>>,
+    ) -> Result<(), SimulationError>
+    where
+        S: 'a,
+        T: 'a,
+    {
+        let Some(validator) = &self.state_validator else {
+            return Ok(());
+        };
+        transitions
+            .flatten()
+            .map(|(new_state, _, _)| (hash(new_state), new_state))
+            .filter(|(state_hash, _)| !self.known_states.contains_key(state_hash))
+            .try_for_each(|(state_hash, new_state)| {
+                validator(new_state)
+                    .map_err(|error| SimulationError::InvalidState { state_hash, error })
+            })
+    }
+
+    fn check_state_limit<'a>(
+        &self,
+        transitions: impl Iterator<Item = &'a OutgoingTransitions<S, T>>,
+        max_states: Option<usize>,
+    ) -> Result<(), SimulationError>
+    where
+        S: 'a,
+        T: 'a,
+    {
+        let Some(max_states) = max_states else {
+            return Ok(());
+        };
+        let new_states = transitions
+            .flatten()
+            .map(|(new_state, _, _)| hash(new_state))
+            .filter(|state_hash| !self.known_states.contains_key(state_hash))
+            .collect::<HashSet<_>>();
+        let num_states = self.known_states.len() + new_states.len();
+        if num_states > max_states {
+            Err(SimulationError::TooManyStates {
+                num_states,
+                max_states,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn insert_transitions(&mut self, state: S, transitions: OutgoingTransitions<S, T>) {
+        if let Err(error) = self.validate_new_states([&transitions].into_iter()) {
+            panic!("{error}");
+        }
+        self.add_outgoing_transitions(&state, &transitions);
+        self.state_transition_generator.insert(state, transitions);
+    }
+
+    // The new states have to be validated before
+    fn add_outgoing_transitions(&mut self, source: &S, transitions: &OutgoingTransitions<S, T>) {
+        let source_hash = hash(source);
+        self.record_labels(source_hash, source);
+        self.known_states
+            .get_or_insert_with(source_hash, || source.clone());
+        self.add_outgoing_edges(source_hash, transitions);
+    }
+
+    // The source has to be known already
+    fn add_outgoing_edges(
+        &mut self,
+        source_hash: StateHash,
+        transitions: &OutgoingTransitions<S, T>,
+    ) {
+        let source_index = self.graph_node(source_hash);
+        transitions
             .iter()
-            .zip(state_probability_distribution.iter())
-            .for_each(|(next_states, (old_state, _))| {
-                next_states
-                    .iter()
-                    .for_each(|(new_state, transition, probability)| {
-                        let source = self
-                            .state_transition_graph
-                            .node_indices()
-                            .find(|node_index| {
-                                self.state_transition_graph
-                                    .node_weight(*node_index)
-                                    .unwrap()
-                                    == &hash(old_state)
-                            })
-                            .unwrap();
-                        let target = self
-                            .state_transition_graph
-                            .node_indices()
-                            .find(|node_index| {
-                                self.state_transition_graph
-                                    .node_weight(*node_index)
-                                    .unwrap()
-                                    == &hash(new_state)
-                            })
-                            .unwrap_or_else(|| {
-                                self.state_transition_graph.add_node(hash(new_state))
-                            });
-                        self.state_transition_graph.update_edge(
-                            source,
-                            target,
-                            (hash(transition), *probability),
-                        );
-                    });
+            .for_each(|(new_state, transition, probability)| {
+                // Hash first and only clone states and transitions which are not known yet
+                let new_state_hash = hash(new_state);
+                let transition_hash = hash(transition);
+                self.record_labels(new_state_hash, new_state);
+                self.known_states
+                    .get_or_insert_with(new_state_hash, || new_state.clone());
+                self.known_transitions
+                    .get_or_insert_with(transition_hash, || transition.clone());
+                let target_index = self.graph_node(new_state_hash);
+                Arc::make_mut(&mut self.state_transition_graph).update_edge(
+                    source_index,
+                    target_index,
+                    (transition_hash, *probability),
+                );
+            });
+    }
+
+    fn graph_node(&mut self, state_hash: StateHash) -> NodeIndex {
+        if let Some(index) = self.node_indices.get(&state_hash) {
+            return *index;
+        }
+        let index = Arc::make_mut(&mut self.state_transition_graph).add_node(state_hash);
+        self.node_indices.insert(state_hash, index);
+        index
+    }
+
+    pub fn explore(&mut self) {
+        self.try_explore().unwrap_or_else(|error| panic!("{error}"));
+    }
+
+    // Nothing is changed if a new state is invalid
+    pub fn try_explore(&mut self) -> Result<(), SimulationError> {
+        let generator = &self.state_transition_generator;
+        let frontier_hook = self.frontier_hook.as_ref();
+        let seen = Mutex::new(self.known_states.keys().copied().collect::<HashSet<_>>());
+        let explored = Mutex::new(Vec::new());
+        let unexplored = self
+            .known_states
+            .values()
+            .filter(|state| generator.get(state).is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        rayon::scope(|scope| {
+            spawn_explore_states(
+                scope,
+                unexplored,
+                generator,
+                frontier_hook,
+                &seen,
+                &explored,
+            );
+        });
+        let explored = explored.into_inner().unwrap();
+        self.validate_new_states(explored.iter().map(|(_, transitions)| transitions))?;
+        for (state, transitions) in explored {
+            self.add_outgoing_transitions(&state, &transitions);
+            self.state_transition_generator.insert(state, transitions);
+        }
+        Ok(())
+    }
+
+    // Explores the given states in parallel and returns the states which were discovered
+    pub(crate) fn explore_frontier(&mut self, frontier: Vec<S>) -> Vec<S> {
+        self.try_explore_frontier(frontier, None)
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    pub(crate) fn try_explore_frontier(
+        &mut self,
+        frontier: Vec<S>,
+        max_states: Option<usize>,
+    ) -> Result<Vec<S>, SimulationError> {
+        self.call_frontier_hook(frontier.iter());
+        let transitions = self
+            .state_transition_generator
+            .call_many_parallel(frontier.par_iter());
+        self.validate_new_states(transitions.iter().map(Arc::as_ref))?;
+        self.check_state_limit(transitions.iter().map(Arc::as_ref), max_states)?;
+        let mut discovered = Vec::new();
+        frontier
+            .iter()
+            .zip(transitions.iter())
+            .for_each(|(state, transitions)| {
+                transitions.iter().for_each(|(new_state, _, _)| {
+                    if !self.known_states.contains_key(&hash(new_state)) {
+                        discovered.push(new_state.clone());
+                    }
+                });
+                self.add_outgoing_transitions(state, transitions);
             });
+        Ok(discovered)
+    }
 
-        // Return the new state probability distribution
-        self.probability_distribution(initial_time + 1)
+    // One step of an arbitrary distribution, outside of the stored distributions. Transitions are
+    // generated and cached as needed.
+    pub(crate) fn propagate(
+        &mut self,
+        distribution: &StateProbabilityDistribution<S>,
+    ) -> StateProbabilityDistribution<S> {
+        let unexplored = distribution
+            .keys()
+            .filter(|state| self.cached_outgoing_transitions(state).is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        if !unexplored.is_empty() {
+            self.explore_frontier(unexplored);
+        }
+        let mut next_distribution = StateProbabilityDistribution::new();
+        distribution.iter().for_each(|(state, probability)| {
+            self.cached_outgoing_transitions(state)
+                .unwrap()
+                .iter()
+                .for_each(|(next_state, _, transition_probability)| {
+                    *next_distribution.entry(next_state.clone()).or_insert(0.) +=
+                        probability * transition_probability;
+                });
+        });
+        next_distribution
     }
 
     pub fn full_traversal(&mut self, modify_cache_only: bool) {
         if modify_cache_only {
-            let mut simulation_clone = self.clone();
-            let mut num_current_known_states = 0;
-            while num_current_known_states != simulation_clone.known_states.len() {
-                num_current_known_states = simulation_clone.known_states.len();
-                simulation_clone.next_step();
-                self.known_states = simulation_clone.known_states.clone();
-                self.known_transitions = simulation_clone.known_transitions.clone();
-                self.state_transition_graph = simulation_clone.state_transition_graph.clone();
-                self.state_transition_generator =
-                    simulation_clone.state_transition_generator.clone();
-            }
+            self.explore();
         } else {
             let mut num_current_known_states = 0;
             while num_current_known_states != self.known_states.len() {
                 num_current_known_states = self.known_states.len();
-                self.next_step();
+                self.advance();
             }
         }
     }
@@ -378,11 +1280,11 @@ where
         self.full_traversal(true);
         let mut simulation_clone = self.clone();
         let uniform_probability = 1.0 / self.known_states.len() as Probability;
-        let uniform_state_probability_distribution = self
-            .known_states
-            .iter()
-            .map(|(state_hash, _)| (*state_hash, uniform_probability))
-            .collect::<HashMap<_, _>>();
+        let uniform_state_probability_distribution = HashedDistribution::from_unique(
+            self.known_states
+                .iter()
+                .map(|(state_hash, _)| (*state_hash, uniform_probability)),
+        );
         let next_time = simulation_clone.time() + 1;
         simulation_clone
             .probability_distributions
@@ -396,6 +1298,8 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     use super::*;
 
     #[test]
@@ -430,6 +1334,12 @@ mod tests {
         assert_eq!(simulation.state_probability(0, 1), 0.);
         assert_eq!(simulation.state_probability(1, 1), 0.5);
         assert_eq!(simulation.state_probability(-1, 1), 0.5);
+        assert_eq!(simulation.expectation(1, |state| *state as f64), 0.);
+        assert_eq!(
+            simulation.expectation(1, |state| (state * state) as f64),
+            1.
+        );
+        assert_eq!(simulation.probability_where(1, |state| *state > 0), 0.5);
 
         assert_eq!(simulation.initial_distribution(), HashMap::from([(0, 1.0)]));
 
@@ -459,6 +1369,28 @@ mod tests {
         assert_eq!(simulation.state_transition_graph().node_count(), 4);
         assert_eq!(simulation.state_transition_graph().edge_count(), 4);
         assert_eq!(simulation.entropy(1), 2.0);
+        // The graph is maintained while exploring, so it can be inspected without rebuilding it
+        let graph = simulation.graph();
+        assert_eq!(graph.node_count(), 4);
+        assert!(graph.edge_indices().all(|edge| {
+            let (source, target) = graph.edge_endpoints(edge).unwrap();
+            let source = simulation.known_state(graph[source]).unwrap();
+            let target = simulation.known_state(graph[target]).unwrap();
+            let (transition_hash, probability) = graph[edge];
+            match (
+                *simulation.known_transition(transition_hash).unwrap(),
+                probability,
+            ) {
+                ("next", 0.5) => source + 1 == *target,
+                ("previous", 0.5) => source - 1 == *target,
+                _ => false,
+            }
+        }));
+        let weighted = simulation.weighted_graph(ParallelEdges::Keep);
+        assert_eq!(weighted.edge_count(), 4);
+        assert!(weighted
+            .edge_weights()
+            .all(|probability| *probability == 0.5));
         assert_eq!(
             simulation.probability_distributions(),
             HashMap::from([
@@ -525,6 +1457,62 @@ mod tests {
         );
     }
 
+    #[test]
+    fn borrowed_accessors() {
+        let state_transition_generator =
+            Arc::new(|state: i32| vec![(state + 1, "next", 0.5), (state - 1, "previous", 0.5)]);
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.next_step();
+        assert_eq!(simulation.iter_known_states().count(), 3);
+        assert_eq!(simulation.iter_known_transitions().count(), 2);
+        assert_eq!(
+            simulation
+                .iter_probability_distribution(1)
+                .unwrap()
+                .map(|(_, probability)| probability)
+                .sum::<Probability>(),
+            1.
+        );
+        assert_eq!(simulation.probability_of(&1, 1), 0.5);
+        assert_eq!(simulation.known_state(hash(&-1)), Ok(&-1));
+        assert_eq!(
+            simulation.known_state(hash(&5)),
+            Err(SimulationError::UnknownState {
+                state_hash: hash(&5)
+            })
+        );
+        assert_eq!(
+            simulation.try_probability_distribution(2),
+            Err(SimulationError::NoProbabilityDistribution { time: 2 })
+        );
+    }
+
+    #[test]
+    fn explore() {
+        // A skewed frontier: state 0 has many successors, all others only one.
+        let state_transition_generator = Arc::new(|state: i32| -> OutgoingTransitions<i32, i32> {
+            if state == 0 {
+                (1..=100).map(|next| (next, next, 0.01)).collect()
+            } else if state < 1000 {
+                vec![(state + 100, 0, 1.)]
+            } else {
+                vec![(state, 0, 1.)]
+            }
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.explore();
+        assert_eq!(simulation.known_states().len(), 1 + 1099);
+        assert_eq!(simulation.known_transitions().len(), 101);
+        assert_eq!(simulation.state_transition_graph().node_count(), 1100);
+        assert_eq!(simulation.state_transition_graph().edge_count(), 100 + 1099);
+        assert_eq!(simulation.time(), 0);
+        assert_eq!(simulation.probability_distributions().len(), 1);
+
+        let mut stepped = simulation.clone();
+        stepped.next_step();
+        assert_eq!(stepped.known_states().len(), 1100);
+    }
+
     #[test]
     fn uniform_distribution_is_steady() {
         {
@@ -584,4 +1572,252 @@ mod tests {
             assert!(!simulation.uniform_distribution_is_steady());
         }
     }
+
+    #[test]
+    fn fork() {
+        let state_transition_generator =
+            Arc::new(|state: i32| vec![(state + 1, "next", 0.5), (state - 1, "previous", 0.5)]);
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.next_step();
+        simulation.next_step();
+
+        let mut fork = simulation.fork();
+        assert_eq!(fork.known_states().len(), 5);
+        assert!(fork.cached_outgoing_transitions(&1).is_some());
+        fork.next_step();
+        assert_eq!(fork.time(), 3);
+        assert_eq!(fork.known_states().len(), 7);
+        assert_eq!(simulation.time(), 2);
+        assert_eq!(simulation.known_states().len(), 5);
+        assert!(simulation.cached_outgoing_transitions(&2).is_none());
+
+        simulation.next_step();
+        assert_eq!(
+            simulation.probability_distribution(3),
+            fork.probability_distribution(3)
+        );
+    }
+
+    #[test]
+    fn shared_cache() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted_calls = calls.clone();
+        let state_transition_generator = Arc::new(move |state: i32| {
+            counted_calls.fetch_add(1, Ordering::SeqCst);
+            vec![((state + 1) % 4, "next", 0.5), (state, "stay", 0.5)]
+        });
+        let cache = TransitionCache::new();
+        let mut first = Simulation::new(0, state_transition_generator.clone());
+        first.share_cache(&cache);
+        first.explore();
+        assert_eq!(cache.len(), 4);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        let mut second = Simulation::new(2, state_transition_generator);
+        second.share_cache(&cache);
+        second.next_step();
+        second.explore();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert_eq!(second.known_states().len(), 4);
+        assert_eq!(second.probability_of(&3, 1), 0.5);
+    }
+
+    #[test]
+    fn sub_stochastic() {
+        let state_transition_generator =
+            Arc::new(|state: i32| vec![(state + 1, "next", 0.5), (state, "stay", 0.25)]);
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.set_sub_stochastic(true);
+        simulation.next_step();
+        simulation.next_step();
+        assert_eq!(simulation.killed_mass(0), 0.25);
+        assert_eq!(simulation.killed_mass(1), 0.1875);
+        assert_eq!(simulation.probability_sum(2), 0.5625);
+        assert_eq!(simulation.mass_balance(1).killed(), 0.1875);
+        assert!(simulation.mass_balance(1).is_balanced(1e-12));
+        assert_eq!(
+            simulation.probability_sum(2) + simulation.total_killed_mass(),
+            1.
+        );
+    }
+
+    #[test]
+    fn weighted_graph() {
+        let state_transition_generator = Arc::new(|state: u8| {
+            vec![
+                (1 - state, "flip", 0.25),
+                (1 - state, "toss", 0.25),
+                (state, "stay", 0.5),
+            ]
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.next_step();
+        simulation.next_step();
+        let kept = simulation.weighted_graph(ParallelEdges::Keep);
+        assert_eq!(kept.node_count(), 2);
+        assert_eq!(kept.edge_count(), 6);
+        let merged = simulation.weighted_graph(ParallelEdges::Merge);
+        assert_eq!(merged.edge_count(), 4);
+        let flip = merged
+            .find_edge(
+                merged.node_indices().next().unwrap(),
+                merged.node_indices().nth(1).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(merged[flip], 0.5);
+    }
+
+    #[test]
+    fn extend_known_states() {
+        let state_transition_generator = Arc::new(|state: u8| vec![(state / 2, "halve", 1.)]);
+        let mut simulation = Simulation::new(4, state_transition_generator);
+        simulation.label("even", |state: &u8| state.is_multiple_of(2));
+        simulation.set_state_validator(Arc::new(|state: &u8| {
+            if *state < 10 {
+                Ok(())
+            } else {
+                Err(SchemaError::UnknownEntity {
+                    entity: state.to_string(),
+                })
+            }
+        }));
+        assert!(simulation.extend_known_states([1, 2, 12]).is_err());
+        assert_eq!(simulation.known_states().len(), 1);
+
+        simulation.extend_known_states([0, 1, 2, 2, 4]).unwrap();
+        assert_eq!(simulation.known_states().len(), 4);
+        assert_eq!(simulation.graph().node_count(), 4);
+        assert_eq!(simulation.graph().edge_count(), 0);
+        assert_eq!(simulation.labeled_states("even").len(), 3);
+        // Added states are explored like any other state once they are reached
+        simulation.next_step();
+        assert_eq!(simulation.probability_of(&2, 1), 1.);
+        assert_eq!(simulation.graph().node_count(), 4);
+    }
+
+    #[test]
+    fn invalid_new_state() {
+        let state_transition_generator =
+            Arc::new(|state: u8| vec![(state + 1, "up", 0.5), (state, "stay", 0.5)]);
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.set_state_validator(Arc::new(|state: &u8| {
+            if *state < 2 {
+                Ok(())
+            } else {
+                Err(SchemaError::UnknownEntity {
+                    entity: state.to_string(),
+                })
+            }
+        }));
+        simulation.try_next_step().unwrap();
+        assert!(matches!(
+            simulation.try_next_step(),
+            Err(SimulationError::InvalidState { state_hash, .. }) if state_hash == hash(&2u8)
+        ));
+        // Nothing of the failed step is kept
+        assert_eq!(simulation.time(), 1);
+        assert_eq!(simulation.known_states().len(), 2);
+        assert_eq!(simulation.graph().node_count(), 2);
+        assert_eq!(simulation.graph().edge_count(), 2);
+    }
+
+    #[test]
+    fn into_states() {
+        let state_transition_generator = Arc::new(|state: String| {
+            vec![
+                (format!("{state}a"), "a", 0.5),
+                (format!("{state}b"), "b", 0.5),
+            ]
+        });
+        let mut simulation = Simulation::new(String::new(), state_transition_generator);
+        simulation.next_step();
+        simulation.next_step();
+        let fork = simulation.fork();
+        let expected = simulation.probability_distribution(2);
+        assert_eq!(
+            simulation.clone().into_probability_distribution(2),
+            Ok(expected)
+        );
+        assert!(simulation.clone().into_probability_distribution(3).is_err());
+        let mut states = simulation.into_known_states().collect::<Vec<_>>();
+        states.sort();
+        assert_eq!(states, vec!["", "a", "aa", "ab", "b", "ba", "bb"]);
+        assert_eq!(fork.known_states().len(), 7);
+    }
+
+    #[test]
+    fn overflow_policy() {
+        // Drift of 1e-6 above 1, which is more than the rounding tolerates
+        let state_transition_generator =
+            Arc::new(|state: u8| vec![((state + 1) % 2, "flip", 0.5 + 1e-6), (state, "stay", 0.5)]);
+        let mut clamped = Simulation::new(0, state_transition_generator);
+        let mut renormalized = clamped.clone();
+        let mut strict = clamped.clone();
+        clamped.set_overflow_policy(OverflowPolicy::Clamp);
+        renormalized.set_overflow_policy(OverflowPolicy::Renormalize);
+        for _ in 0..100 {
+            clamped.next_step();
+            renormalized.next_step();
+        }
+        assert!(clamped
+            .probability_sum(100)
+            .approx_eq(&1., Tolerance::stored()));
+        assert!(renormalized
+            .probability_sum(100)
+            .approx_eq(&1., Tolerance::stored()));
+        assert!((clamped.overflow_mass(0) - 1e-6).abs() < 1e-12);
+        assert!((clamped.total_overflow_mass() - 100e-6).abs() < 1e-9);
+        assert_eq!(renormalized.total_overflow_mass(), 0.);
+        assert!(matches!(
+            strict.try_next_step(),
+            Err(SimulationError::ProbabilitySum { .. })
+        ));
+        assert_eq!(strict.time(), 0);
+
+        // Missing mass is renormalized unless the model is sub-stochastic
+        let state_transition_generator = Arc::new(|state: u8| vec![(state, "stay", 0.5)]);
+        let mut leaking = Simulation::new(0, state_transition_generator);
+        leaking.set_overflow_policy(OverflowPolicy::Renormalize);
+        let mut killing = leaking.clone();
+        killing.set_sub_stochastic(true);
+        leaking.next_step();
+        killing.next_step();
+        assert_eq!(leaking.probability_of(&0, 1), 1.);
+        assert_eq!(killing.probability_of(&0, 1), 0.5);
+
+        let state_transition_generator = Arc::new(|_: u8| Vec::<(u8, &str, f64)>::new());
+        let mut dead_end = Simulation::new(0, state_transition_generator);
+        dead_end.set_overflow_policy(OverflowPolicy::Renormalize);
+        assert_eq!(
+            dead_end.try_next_step(),
+            Err(SimulationError::ProbabilitySum {
+                state_hash: hash(&0_u8),
+                sum: 0.
+            })
+        );
+    }
+
+    #[test]
+    fn parallel_iterators() {
+        let state_transition_generator =
+            Arc::new(|state: i32| vec![(state - 1, "down", 0.5), (state + 1, "up", 0.5)]);
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        for _ in 0..4 {
+            simulation.next_step();
+        }
+        let second_moment = simulation
+            .par_iter_probability_distribution(4)
+            .unwrap()
+            .map(|(state, probability)| (state * state) as f64 * probability)
+            .sum::<f64>();
+        assert_eq!(second_moment, 4.);
+        assert!(simulation.par_iter_probability_distribution(5).is_err());
+        assert_eq!(
+            simulation
+                .par_iter_known_states()
+                .filter(|state| **state > 0)
+                .count(),
+            4
+        );
+    }
 }
@@ -0,0 +1,146 @@
+use std::{
+    fmt::Debug,
+    hash::Hash,
+    sync::{Arc, RwLock},
+};
+
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot<S>
+where
+    S: Hash + Eq,
+{
+    time: Time,
+    distribution: StateProbabilityDistribution<S>,
+    num_known_states: usize,
+}
+
+impl<S> Snapshot<S>
+where
+    S: Hash + Eq,
+{
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn distribution(&self) -> &StateProbabilityDistribution<S> {
+        &self.distribution
+    }
+
+    pub fn probability_of(&self, state: &S) -> Probability {
+        self.distribution.get(state).copied().unwrap_or(0.)
+    }
+
+    pub fn num_known_states(&self) -> usize {
+        self.num_known_states
+    }
+}
+
+// Snapshots are immutable, publishing one only swaps the pointer. Readers on other threads keep
+// the snapshot they loaded for as long as they need it without blocking the simulation.
+#[derive(Debug)]
+pub struct SharedSnapshot<S>
+where
+    S: Hash + Eq,
+{
+    current: Arc<RwLock<Arc<Snapshot<S>>>>,
+}
+
+impl<S> Clone for SharedSnapshot<S>
+where
+    S: Hash + Eq,
+{
+    fn clone(&self) -> Self {
+        Self {
+            current: self.current.clone(),
+        }
+    }
+}
+
+impl<S> SharedSnapshot<S>
+where
+    S: Hash + Eq,
+{
+    pub fn new(snapshot: Snapshot<S>) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(snapshot))),
+        }
+    }
+
+    pub fn load(&self) -> Arc<Snapshot<S>> {
+        self.current.read().unwrap().clone()
+    }
+
+    pub fn publish(&self, snapshot: Snapshot<S>) {
+        let snapshot = Arc::new(snapshot);
+        *self.current.write().unwrap() = snapshot;
+    }
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    pub fn snapshot(&self) -> Snapshot<S> {
+        let time = self.time();
+        Snapshot {
+            time,
+            distribution: self.probability_distribution(time),
+            num_known_states: self.num_known_states(),
+        }
+    }
+
+    pub fn shared_snapshot(&self) -> SharedSnapshot<S> {
+        SharedSnapshot::new(self.snapshot())
+    }
+
+    // Publishes a snapshot after every step
+    pub fn run_with_snapshots(
+        &mut self,
+        steps: usize,
+        shared_snapshot: &SharedSnapshot<S>,
+    ) -> Time {
+        for _ in 0..steps {
+            self.advance();
+            shared_snapshot.publish(self.snapshot());
+        }
+        self.time()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn concurrent_snapshots() {
+        let state_transition_generator =
+            Arc::new(|state: i32| vec![(state + 1, "next", 0.5), (state - 1, "previous", 0.5)]);
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        let shared_snapshot = simulation.shared_snapshot();
+        assert_eq!(shared_snapshot.load().time(), 0);
+
+        let reader_snapshot = shared_snapshot.clone();
+        let reader = thread::spawn(move || {
+            let mut times = Vec::new();
+            while times.last() != Some(&50) {
+                let snapshot = reader_snapshot.load();
+                let sum = snapshot.distribution().values().sum::<Probability>();
+                assert!(sum.approx_eq(&1., Tolerance::stored()));
+                times.push(snapshot.time());
+            }
+            times
+        });
+        assert_eq!(simulation.run_with_snapshots(50, &shared_snapshot), 50);
+
+        let times = reader.join().unwrap();
+        assert!(times.windows(2).all(|window| window[0] <= window[1]));
+        let snapshot = shared_snapshot.load();
+        assert_eq!(snapshot.num_known_states(), 101);
+        assert_eq!(snapshot.probability_of(&50), 0.5_f64.powi(50));
+    }
+}
@@ -0,0 +1,269 @@
+use std::{collections::BTreeMap, fmt::Debug, hash::Hash, sync::Arc};
+
+use crate::prelude::*;
+
+pub type Observable<S> = Arc<dyn Fn(&S) -> bool + Send + Sync + 'static>;
+
+pub type TimeSeries = BTreeMap<Time, Probability>;
+
+pub type SteadyStateCallback = Arc<dyn Fn(&str, Time, Probability) + Send + Sync + 'static>;
+
+// An observable is steady once its probability stayed within the tolerance over the last steps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SteadyStateCriterion {
+    tolerance: Probability,
+    steps: usize,
+}
+
+impl SteadyStateCriterion {
+    pub fn new(tolerance: Probability, steps: usize) -> Self {
+        assert!(tolerance >= 0., "Tolerance must not be negative");
+        assert!(steps > 0, "Steady state criterion needs at least one step");
+        Self { tolerance, steps }
+    }
+
+    pub fn tolerance(&self) -> Probability {
+        self.tolerance
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn is_satisfied_by(&self, series: &TimeSeries) -> bool {
+        if series.len() <= self.steps {
+            return false;
+        }
+        let (minimum, maximum) = series.values().rev().take(self.steps + 1).fold(
+            (Probability::INFINITY, Probability::NEG_INFINITY),
+            |(minimum, maximum), probability| {
+                (minimum.min(*probability), maximum.max(*probability))
+            },
+        );
+        maximum - minimum <= self.tolerance
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct TrackedObservable<S> {
+    observable: Observable<S>,
+    series: TimeSeries,
+    criterion: Option<SteadyStateCriterion>,
+    callback: Option<SteadyStateCallback>,
+    steady_since: Option<Time>,
+}
+
+impl<S, T> Simulation<S, T>
+where
+    S: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+    T: Hash + Clone + Send + Sync + PartialEq + Eq + Debug,
+{
+    // The probability of the observable is recorded for all distributions still stored and for
+    // every following step. Tracking an already tracked label replaces its observable.
+    pub fn track(&mut self, observable: Observable<S>, label: &str) {
+        let mut tracked = TrackedObservable {
+            observable,
+            series: TimeSeries::new(),
+            criterion: None,
+            callback: None,
+            steady_since: None,
+        };
+        for time in self.stored_times() {
+            let probability = self.observed_probability(&tracked.observable, time);
+            tracked.series.insert(time, probability);
+        }
+        self.tracked_mut().insert(label.to_string(), tracked);
+    }
+
+    pub fn untrack(&mut self, label: &str) -> Option<TimeSeries> {
+        self.tracked_mut()
+            .remove(label)
+            .map(|tracked| tracked.series)
+    }
+
+    pub fn tracked_labels(&self) -> Vec<&String> {
+        let mut labels = self.tracked().keys().collect::<Vec<_>>();
+        labels.sort();
+        labels
+    }
+
+    pub fn time_series(&self, label: &str) -> Option<&TimeSeries> {
+        self.tracked().get(label).map(|tracked| &tracked.series)
+    }
+
+    // The callback is called every time the observable becomes steady
+    pub fn detect_steady_state(
+        &mut self,
+        label: &str,
+        criterion: SteadyStateCriterion,
+        callback: Option<SteadyStateCallback>,
+    ) {
+        let tracked = self
+            .tracked_mut()
+            .get_mut(label)
+            .unwrap_or_else(|| panic!("Observable {label} is not tracked"));
+        tracked.criterion = Some(criterion);
+        tracked.callback = callback;
+        tracked.steady_since = criterion
+            .is_satisfied_by(&tracked.series)
+            .then(|| *tracked.series.keys().next_back().unwrap());
+    }
+
+    pub fn is_steady(&self, label: &str) -> bool {
+        self.steady_since(label).is_some()
+    }
+
+    pub fn steady_since(&self, label: &str) -> Option<Time> {
+        self.tracked()
+            .get(label)
+            .and_then(|tracked| tracked.steady_since)
+    }
+
+    pub fn all_steady(&self) -> bool {
+        self.tracked()
+            .values()
+            .filter(|tracked| tracked.criterion.is_some())
+            .all(|tracked| tracked.steady_since.is_some())
+    }
+
+    // Steps until every observable with a steady state criterion is steady. Returns None if that
+    // didn't happen within the given number of steps.
+    pub fn run_until_steady(&mut self, max_steps: usize) -> Option<Time> {
+        for _ in 0..max_steps {
+            if self.all_steady() {
+                return Some(self.time());
+            }
+            self.advance();
+        }
+        self.all_steady().then(|| self.time())
+    }
+
+    fn observed_probability(&self, observable: &Observable<S>, time: Time) -> Probability {
+        self.iter_probability_distribution(time)
+            .expect("No probability distribution found for given time")
+            .filter(|(state, _)| observable(state))
+            .map(|(_, probability)| probability)
+            .sum()
+    }
+
+    // Only the unchanged initial distribution is kept, the following steps are recorded again
+    pub(crate) fn reset_tracked(&mut self) {
+        self.tracked_mut().values_mut().for_each(|tracked| {
+            tracked.series.retain(|time, _| *time == 0);
+            tracked.steady_since = None;
+        });
+    }
+
+    pub(crate) fn record_tracked(&mut self, time: Time) {
+        if self.tracked().is_empty() {
+            return;
+        }
+        let probabilities = self
+            .tracked()
+            .iter()
+            .map(|(label, tracked)| {
+                (
+                    label.clone(),
+                    self.observed_probability(&tracked.observable, time),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut callbacks = Vec::new();
+        probabilities.into_iter().for_each(|(label, probability)| {
+            if let Some(tracked) = self.tracked_mut().get_mut(&label) {
+                tracked.series.insert(time, probability);
+                let Some(criterion) = tracked.criterion else {
+                    return;
+                };
+                if !criterion.is_satisfied_by(&tracked.series) {
+                    tracked.steady_since = None;
+                } else if tracked.steady_since.is_none() {
+                    tracked.steady_since = Some(time);
+                    if let Some(callback) = &tracked.callback {
+                        callbacks.push((callback.clone(), label, probability));
+                    }
+                }
+            }
+        });
+        callbacks
+            .into_iter()
+            .for_each(|(callback, label, probability)| callback(&label, time, probability));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn tracking() {
+        let state_transition_generator = Arc::new(|state: i32| {
+            if state < 2 {
+                vec![(state + 1, "next", 0.5), (state, "stay", 0.5)]
+            } else {
+                vec![(state, "stay", 1.)]
+            }
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.next_step();
+        simulation.track(Arc::new(|state| *state == 2), "done");
+        simulation.track(Arc::new(|state| *state > 0), "started");
+        simulation.next_step();
+        simulation.next_step();
+
+        assert_eq!(simulation.tracked_labels(), vec!["done", "started"]);
+        let done = simulation.time_series("done").unwrap();
+        assert_eq!(
+            done.iter().map(|(time, p)| (*time, *p)).collect::<Vec<_>>(),
+            vec![(0, 0.), (1, 0.), (2, 0.25), (3, 0.5)]
+        );
+        assert_eq!(simulation.time_series("started").unwrap()[&3], 0.875);
+        assert!(simulation.time_series("unknown").is_none());
+
+        let started = simulation.untrack("started").unwrap();
+        assert_eq!(started.len(), 4);
+        simulation.next_step();
+        assert_eq!(simulation.tracked_labels(), vec!["done"]);
+        assert_eq!(simulation.time_series("done").unwrap().len(), 5);
+    }
+
+    #[test]
+    fn steady_state_detection() {
+        let state_transition_generator = Arc::new(|state: i32| match state {
+            0 => vec![(1, "fast", 0.9), (2, "slow", 0.1)],
+            2 => vec![(3, "next", 0.5), (2, "stay", 0.5)],
+            _ => vec![(state, "stay", 1.)],
+        });
+        let mut simulation = Simulation::new(0, state_transition_generator);
+        simulation.track(Arc::new(|state| *state == 1), "fast");
+        simulation.track(Arc::new(|state| *state == 3), "slow");
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let callback_calls = calls.clone();
+        let callback: SteadyStateCallback = Arc::new(move |label, time, _| {
+            callback_calls
+                .lock()
+                .unwrap()
+                .push((label.to_string(), time));
+        });
+        let criterion = SteadyStateCriterion::new(1e-3, 2);
+        simulation.detect_steady_state("fast", criterion, Some(callback.clone()));
+        simulation.detect_steady_state("slow", criterion, Some(callback));
+        assert!(!simulation.all_steady());
+
+        assert_eq!(simulation.run_until_steady(3), None);
+        assert!(simulation.is_steady("fast"));
+        assert_eq!(simulation.steady_since("fast"), Some(3));
+        assert!(!simulation.is_steady("slow"));
+
+        let time = simulation.run_until_steady(100).unwrap();
+        assert_eq!(simulation.steady_since("slow"), Some(time));
+        assert!(simulation.time_series("slow").unwrap()[&time] > 0.099);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![("fast".to_string(), 3), ("slow".to_string(), time)]
+        );
+    }
+}